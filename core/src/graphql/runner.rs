@@ -1,4 +1,5 @@
 use futures::future;
+use std::collections::HashMap;
 use std::env;
 use std::str::FromStr;
 use std::time::{Duration, Instant};
@@ -12,6 +13,7 @@ use lazy_static::lazy_static;
 pub struct GraphQlRunner<S> {
     logger: Logger,
     store: Arc<S>,
+    query_execution_time: Box<HistogramVec>,
 }
 
 lazy_static! {
@@ -42,12 +44,31 @@ where
     S: Store,
 {
     /// Creates a new query runner.
-    pub fn new(logger: &Logger, store: Arc<S>) -> Self {
+    pub fn new(logger: &Logger, store: Arc<S>, metrics_registry: Arc<dyn MetricsRegistry>) -> Self {
+        let query_execution_time = metrics_registry
+            .new_histogram_vec(
+                "query_execution_time_ms",
+                "Time taken to execute GraphQL queries, by subgraph deployment",
+                HashMap::new(),
+                &["deployment"],
+                vec![
+                    5.0, 10.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 5000.0, 30000.0,
+                ],
+            )
+            .expect("failed to create `query_execution_time_ms` histogram");
+
         GraphQlRunner {
             logger: logger.new(o!("component" => "GraphQlRunner")),
             store,
+            query_execution_time,
         }
     }
+
+    fn observe_query_execution_time(&self, query: &Query, start: Instant) {
+        self.query_execution_time
+            .with_label_values(&[query.schema.id.as_str()])
+            .observe(start.elapsed().as_millis() as f64);
+    }
 }
 
 impl<S> GraphQlRunnerTrait for GraphQlRunner<S>
@@ -55,6 +76,7 @@ where
     S: Store,
 {
     fn run_query(&self, query: Query) -> QueryResultFuture {
+        let start = Instant::now();
         let result = execute_query(
             &query,
             QueryExecutionOptions {
@@ -66,6 +88,7 @@ where
                 max_first: *GRAPHQL_MAX_FIRST,
             },
         );
+        self.observe_query_execution_time(&query, start);
         Box::new(future::ok(result))
     }
 
@@ -76,6 +99,7 @@ where
         max_depth: Option<u8>,
         max_first: Option<u32>,
     ) -> QueryResultFuture {
+        let start = Instant::now();
         let result = execute_query(
             &query,
             QueryExecutionOptions {
@@ -87,6 +111,7 @@ where
                 max_first: max_first.unwrap_or(*GRAPHQL_MAX_FIRST),
             },
         );
+        self.observe_query_execution_time(&query, start);
         Box::new(future::ok(result))
     }
 
@@ -13,10 +13,12 @@ extern crate uuid;
 
 mod graphql;
 mod link_resolver;
+mod metrics;
 mod subgraph;
 
 pub use crate::graphql::GraphQlRunner;
 pub use crate::link_resolver::LinkResolver;
+pub use crate::metrics::MetricsRegistry;
 pub use crate::subgraph::{
     DataSourceLoader, SubgraphAssignmentProvider, SubgraphInstanceManager, SubgraphRegistrar,
 };
@@ -3,12 +3,14 @@ use futures::{stream::poll_fn, try_ready};
 use ipfs_api;
 use lazy_static::lazy_static;
 use lru_time_cache::LruCache;
+use std::collections::HashSet;
 use std::env;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use graph::prelude::{LinkResolver as LinkResolverTrait, *};
+use graph::tokio::timer::timeout;
 use serde_json::Value;
 
 // Environment variable for limiting the `ipfs.map` file size limit.
@@ -39,6 +41,30 @@ fn ipfs_timeout() -> Duration {
     Duration::from_secs(timeout.unwrap_or(60))
 }
 
+/// Messages of IPFS API errors that say that a path does not refer to a
+/// file. These errors do not depend on the IPFS node that reports them.
+const INVALID_FILE_MESSAGES: &[&str] = &["invalid", "is a directory", "not a file"];
+
+/// Convert the error of an IPFS request that was run with a timeout, keeping
+/// timeouts recognizable as `LinkResolverTimeout` and paths that are not
+/// files as `LinkResolverInvalidFile`.
+fn ipfs_error(path: &str, e: timeout::Error<ipfs_api::response::Error>) -> failure::Error {
+    if e.is_elapsed() {
+        return LinkResolverTimeout(path.to_owned()).into();
+    }
+    let msg = e.to_string();
+    match e.into_inner() {
+        Some(ipfs_api::response::Error::Api(ref api))
+            if INVALID_FILE_MESSAGES
+                .iter()
+                .any(|invalid| api.message.contains(invalid)) =>
+        {
+            LinkResolverInvalidFile(path.to_owned(), msg).into()
+        }
+        _ => failure::err_msg(msg),
+    }
+}
+
 fn read_u64_from_env(name: &str) -> Option<u64> {
     env::var(name).ok().map(|s| {
         u64::from_str(&s).unwrap_or_else(|_| {
@@ -67,7 +93,10 @@ where
             client
                 .object_stat(&path)
                 .timeout(ipfs_timeout())
-                .map_err(|e| failure::err_msg(e.to_string()))
+                .map_err({
+                    let path = path.clone();
+                    move |e| ipfs_error(&path, e)
+                })
                 .and_then(move |stat| match stat.cumulative_size > max_bytes {
                     false => Ok(()),
                     true => Err(format_err!(
@@ -123,7 +152,10 @@ impl LinkResolverTrait for LinkResolver {
             .concat2()
             .timeout(ipfs_timeout)
             .map(|x| x.to_vec())
-            .map_err(|e| failure::err_msg(e.to_string()));
+            .map_err({
+                let path = path.clone();
+                move |e| ipfs_error(&path, e)
+            });
 
         let cache_for_writing = self.cache.clone();
 
@@ -148,64 +180,17 @@ impl LinkResolverTrait for LinkResolver {
     fn json_stream(
         &self,
         link: &Link,
+        format: JsonStreamFormat,
     ) -> Box<dyn Future<Item = JsonValueStream, Error = failure::Error> + Send + 'static> {
         // Discard the `/ipfs/` prefix (if present) to get the hash.
         let path = link.link.trim_start_matches("/ipfs/").to_owned();
-        let mut stream = self.client.cat(&path).fuse();
-        let mut buf = BytesMut::with_capacity(1024);
-        // Count the number of lines we've already successfully deserialized.
-        // We need that to adjust the line number in error messages from serde_json
-        // to translate from line numbers in the snippet we are deserializing
-        // to the line number in the overall file
-        let mut count = 0;
-
-        let stream: JsonValueStream = Box::new(poll_fn(
-            move || -> Poll<Option<JsonStreamValue>, failure::Error> {
-                loop {
-                    if let Some(offset) = buf.iter().position(|b| *b == b'\n') {
-                        let line_bytes = buf.split_to(offset + 1);
-                        count += 1;
-                        if line_bytes.len() > 1 {
-                            let line = std::str::from_utf8(&line_bytes)?;
-                            let res = match serde_json::from_str::<Value>(line) {
-                                Ok(v) => Ok(Async::Ready(Some(JsonStreamValue {
-                                    value: v,
-                                    line: count,
-                                }))),
-                                Err(e) => {
-                                    // Adjust the line number in the serde error. This
-                                    // is fun because we can only get at the full error
-                                    // message, and not the error message without line number
-                                    let msg = e.to_string();
-                                    let msg = msg.split(" at line ").next().unwrap();
-                                    Err(format_err!(
-                                        "{} at line {} column {}: '{}'",
-                                        msg,
-                                        e.line() + count - 1,
-                                        e.column(),
-                                        line
-                                    ))
-                                }
-                            };
-                            return res;
-                        }
-                    } else {
-                        // We only get here if there is no complete line in buf, and
-                        // it is therefore ok to immediately pass an Async::NotReady
-                        // from stream through.
-                        // If we get a None from poll, but still have something in buf,
-                        // that means the input was not terminated with a newline. We
-                        // add that so that the last line gets picked up in the next
-                        // run through the loop.
-                        match try_ready!(stream.poll()) {
-                            Some(b) => buf.extend_from_slice(&b),
-                            None if buf.len() > 0 => buf.extend_from_slice(&[b'\n']),
-                            None => return Ok(Async::Ready(None)),
-                        }
-                    }
-                }
-            },
-        ));
+        let bytes = self.client.cat(&path);
+
+        let stream = match format {
+            JsonStreamFormat::JsonLines => split_stream(bytes, JsonLines::default()),
+            JsonStreamFormat::JsonArray => split_stream(bytes, JsonArray::default()),
+            JsonStreamFormat::Csv => split_stream(bytes, Csv::default()),
+        };
 
         let max_file_size =
             read_u64_from_env(MAX_IPFS_MAP_FILE_SIZE_VAR).unwrap_or(DEFAULT_MAX_IPFS_MAP_FILE_SIZE);
@@ -219,6 +204,363 @@ impl LinkResolverTrait for LinkResolver {
     }
 }
 
+/// Splits the contents of a file into JSON values as the file is read, so
+/// that only the part of the file that has not been turned into values yet
+/// needs to be kept in memory.
+trait ValueSplitter: Send + 'static {
+    /// Remove the next complete value from the front of `buf` and return it,
+    /// or return `None` if `buf` does not contain a complete value yet. `eof`
+    /// is `true` once the entire file has been read into `buf`; returning
+    /// `None` then ends the stream.
+    fn next_value(
+        &mut self,
+        buf: &mut BytesMut,
+        eof: bool,
+    ) -> Result<Option<JsonStreamValue>, failure::Error>;
+}
+
+fn split_stream<S, V>(stream: S, mut splitter: V) -> JsonValueStream
+where
+    S: Stream + Send + 'static,
+    S::Item: AsRef<[u8]>,
+    failure::Error: From<S::Error>,
+    V: ValueSplitter,
+{
+    let mut stream = stream.fuse();
+    let mut buf = BytesMut::with_capacity(1024);
+    let mut eof = false;
+
+    Box::new(poll_fn(
+        move || -> Poll<Option<JsonStreamValue>, failure::Error> {
+            loop {
+                if let Some(value) = splitter.next_value(&mut buf, eof)? {
+                    return Ok(Async::Ready(Some(value)));
+                }
+                if eof {
+                    return Ok(Async::Ready(None));
+                }
+                // We only get here if there is no complete value in buf, and
+                // it is therefore ok to immediately pass an Async::NotReady
+                // from stream through.
+                match try_ready!(stream.poll()) {
+                    Some(b) => buf.extend_from_slice(b.as_ref()),
+                    None => eof = true,
+                }
+            }
+        },
+    ))
+}
+
+/// Newline-delimited JSON, where every line that is not empty holds one
+/// JSON value.
+#[derive(Default)]
+struct JsonLines {
+    // Count the number of lines we've already successfully deserialized.
+    // We need that to adjust the line number in error messages from serde_json
+    // to translate from line numbers in the snippet we are deserializing
+    // to the line number in the overall file
+    count: usize,
+}
+
+impl ValueSplitter for JsonLines {
+    fn next_value(
+        &mut self,
+        buf: &mut BytesMut,
+        eof: bool,
+    ) -> Result<Option<JsonStreamValue>, failure::Error> {
+        // If the input was not terminated with a newline, we add one so that
+        // the last line gets picked up.
+        if eof && !buf.is_empty() && !buf.ends_with(b"\n") {
+            buf.extend_from_slice(b"\n");
+        }
+
+        while let Some(offset) = buf.iter().position(|b| *b == b'\n') {
+            let line_bytes = buf.split_to(offset + 1);
+            self.count += 1;
+            if line_bytes.len() > 1 {
+                let line = std::str::from_utf8(&line_bytes)?;
+                return match serde_json::from_str::<Value>(line) {
+                    Ok(v) => Ok(Some(JsonStreamValue {
+                        value: v,
+                        line: self.count,
+                    })),
+                    Err(e) => {
+                        // Adjust the line number in the serde error. This
+                        // is fun because we can only get at the full error
+                        // message, and not the error message without line number
+                        let msg = e.to_string();
+                        let msg = msg.split(" at line ").next().unwrap();
+                        Err(format_err!(
+                            "{} at line {} column {}: '{}'",
+                            msg,
+                            e.line() + self.count - 1,
+                            e.column(),
+                            line
+                        ))
+                    }
+                };
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// A file that consists of one JSON array, whose elements are the values.
+/// Elements are found by scanning for the commas that separate them, which
+/// requires keeping track of nesting and of strings so that commas inside
+/// of elements are skipped.
+#[derive(Default)]
+struct JsonArray {
+    // Whether we have seen the opening and the closing bracket of the array
+    started: bool,
+    finished: bool,
+    // How far we have scanned into `buf` looking for the end of the
+    // current element, and the state of the scan at that point
+    pos: usize,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    // The number of elements we've already returned
+    count: usize,
+}
+
+impl JsonArray {
+    fn element(&mut self, bytes: &[u8]) -> Result<JsonStreamValue, failure::Error> {
+        self.count += 1;
+        let text = std::str::from_utf8(bytes)?;
+        serde_json::from_str::<Value>(text)
+            .map(|value| JsonStreamValue {
+                value,
+                line: self.count,
+            })
+            .map_err(|e| format_err!("{} in element {} of the JSON array", e, self.count))
+    }
+}
+
+impl ValueSplitter for JsonArray {
+    fn next_value(
+        &mut self,
+        buf: &mut BytesMut,
+        eof: bool,
+    ) -> Result<Option<JsonStreamValue>, failure::Error> {
+        if !self.started {
+            match buf.iter().position(|b| !b.is_ascii_whitespace()) {
+                Some(start) if buf[start] == b'[' => {
+                    buf.split_to(start + 1);
+                    self.started = true;
+                }
+                Some(start) => {
+                    return Err(format_err!(
+                        "expected a JSON array but found '{}'",
+                        buf[start] as char
+                    ))
+                }
+                None if eof => {
+                    return Err(format_err!("expected a JSON array but the file is empty"))
+                }
+                None => {
+                    buf.clear();
+                    return Ok(None);
+                }
+            }
+        }
+
+        if self.finished {
+            // Only whitespace may follow the end of the array
+            return match buf.iter().find(|b| !b.is_ascii_whitespace()) {
+                Some(b) => Err(format_err!(
+                    "unexpected '{}' after the end of the JSON array",
+                    *b as char
+                )),
+                None => {
+                    buf.clear();
+                    Ok(None)
+                }
+            };
+        }
+
+        while self.pos < buf.len() {
+            let b = buf[self.pos];
+            self.pos += 1;
+
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if b == b'\\' {
+                    self.escaped = true;
+                } else if b == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+
+            match b {
+                b'"' => self.in_string = true,
+                b'[' | b'{' => self.depth += 1,
+                b']' | b'}' if self.depth > 0 => self.depth -= 1,
+                b',' | b']' if self.depth == 0 => {
+                    let bytes = buf.split_to(self.pos);
+                    let bytes = &bytes[..bytes.len() - 1];
+                    self.pos = 0;
+
+                    if b == b']' {
+                        self.finished = true;
+                        // The closing bracket of an empty array
+                        if self.count == 0 && bytes.iter().all(u8::is_ascii_whitespace) {
+                            return self.next_value(buf, eof);
+                        }
+                    }
+                    return self.element(bytes).map(Some);
+                }
+                _ => (),
+            }
+        }
+
+        if eof {
+            return Err(format_err!("EOF while parsing the JSON array"));
+        }
+        Ok(None)
+    }
+}
+
+/// A CSV file whose first record is a header row with the names of the
+/// columns. Every other record becomes a JSON object that maps the column
+/// names to the fields of the record, which are all strings. Fields may be
+/// quoted with `"`, in which case they can contain commas, newlines, and
+/// `""` for a literal `"`.
+#[derive(Default)]
+struct Csv {
+    header: Option<Vec<String>>,
+    // How far we have scanned into `buf` looking for the end of the
+    // current record, and whether that position is inside of quotes
+    pos: usize,
+    in_quotes: bool,
+    // The number of lines we've already consumed, so that we can report
+    // on which line a record starts
+    lines: usize,
+}
+
+impl ValueSplitter for Csv {
+    fn next_value(
+        &mut self,
+        buf: &mut BytesMut,
+        eof: bool,
+    ) -> Result<Option<JsonStreamValue>, failure::Error> {
+        // If the input was not terminated with a newline, we add one so that
+        // the last record gets picked up.
+        if eof && !buf.is_empty() && !buf.ends_with(b"\n") {
+            buf.extend_from_slice(b"\n");
+        }
+
+        loop {
+            let mut end = None;
+            while self.pos < buf.len() {
+                let b = buf[self.pos];
+                self.pos += 1;
+                match b {
+                    b'"' => self.in_quotes = !self.in_quotes,
+                    b'\n' if !self.in_quotes => {
+                        end = Some(self.pos);
+                        break;
+                    }
+                    _ => (),
+                }
+            }
+            let end = match end {
+                Some(end) => end,
+                None if eof && self.in_quotes => {
+                    return Err(format_err!(
+                        "unterminated quoted field in the CSV record on line {}",
+                        self.lines + 1
+                    ))
+                }
+                None => return Ok(None),
+            };
+
+            let record_bytes = buf.split_to(end);
+            self.pos = 0;
+            let line = self.lines + 1;
+            self.lines += record_bytes.iter().filter(|b| **b == b'\n').count();
+
+            let record = std::str::from_utf8(&record_bytes)?.trim_end_matches(&['\r', '\n'][..]);
+            if record.is_empty() {
+                continue;
+            }
+            let fields = csv_fields(record)
+                .map_err(|e| format_err!("{} in the CSV record on line {}", e, line))?;
+
+            match self.header {
+                Some(ref header) => {
+                    if fields.len() != header.len() {
+                        return Err(format_err!(
+                            "the CSV record on line {} has {} fields, but the header has {}",
+                            line,
+                            fields.len(),
+                            header.len()
+                        ));
+                    }
+                    let value = Value::Object(
+                        header
+                            .iter()
+                            .cloned()
+                            .zip(fields.into_iter().map(Value::String))
+                            .collect(),
+                    );
+                    return Ok(Some(JsonStreamValue { value, line }));
+                }
+                None => {
+                    let mut names = HashSet::new();
+                    if let Some(name) = fields.iter().find(|name| !names.insert(*name)) {
+                        return Err(format_err!(
+                            "the CSV header contains the column `{}` more than once",
+                            name
+                        ));
+                    }
+                    self.header = Some(fields);
+                }
+            }
+        }
+    }
+}
+
+/// Split one CSV record into its fields.
+fn csv_fields(record: &str) -> Result<Vec<String>, failure::Error> {
+    let mut fields = vec![];
+    let mut chars = record.chars().peekable();
+    loop {
+        let mut field = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => return Err(format_err!("unterminated quoted field")),
+                }
+            }
+        } else {
+            while let Some(c) = chars.peek().cloned() {
+                if c == ',' {
+                    break;
+                }
+                field.push(c);
+                chars.next();
+            }
+        }
+        fields.push(field);
+
+        match chars.next() {
+            None => return Ok(fields),
+            Some(',') => (),
+            Some(c) => return Err(format_err!("unexpected '{}' after a quoted field", c)),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -251,17 +593,24 @@ mod tests {
         );
     }
 
-    fn json_round_trip(text: &'static str) -> Result<Vec<Value>, failure::Error> {
+    fn stream_round_trip(
+        text: &'static str,
+        format: JsonStreamFormat,
+    ) -> Result<Vec<Value>, failure::Error> {
         let client = ipfs_api::IpfsClient::default();
 
         let mut runtime = tokio::runtime::Runtime::new().unwrap();
         let link = runtime.block_on(client.add(text.as_bytes())).unwrap().hash;
         runtime.block_on(
-            LinkResolver::json_stream(&client.into(), &Link { link: link.clone() })
+            LinkResolver::json_stream(&client.into(), &Link { link: link.clone() }, format)
                 .and_then(|stream| stream.map(|sv| sv.value).collect()),
         )
     }
 
+    fn json_round_trip(text: &'static str) -> Result<Vec<Value>, failure::Error> {
+        stream_round_trip(text, JsonStreamFormat::JsonLines)
+    }
+
     #[test]
     fn read_json_stream() {
         let values = json_round_trip("\"with newline\"\n");
@@ -280,6 +629,74 @@ mod tests {
         );
     }
 
+    #[test]
+    fn read_json_array_stream() {
+        let read = |text| stream_round_trip(text, JsonStreamFormat::JsonArray);
+
+        let values = read(" [ {\"a\": [1, 2], \"b\": \"x,]\\\"\"},\n 3, \"s\" ]\n");
+        assert_eq!(
+            vec![json!({"a": [1, 2], "b": "x,]\""}), json!(3), json!("s")],
+            values.unwrap()
+        );
+
+        let values = read("[]");
+        assert_eq!(Vec::<Value>::new(), values.unwrap());
+
+        let values = read("[1, 2");
+        assert_eq!(
+            "EOF while parsing the JSON array",
+            values.unwrap_err().to_string()
+        );
+
+        let values = read("[1] 2");
+        assert_eq!(
+            "unexpected '2' after the end of the JSON array",
+            values.unwrap_err().to_string()
+        );
+
+        let values = read("{\"not\": \"an array\"}");
+        assert_eq!(
+            "expected a JSON array but found '{'",
+            values.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn read_csv_stream() {
+        let read = |text| stream_round_trip(text, JsonStreamFormat::Csv);
+
+        let values = read("id,value\r\none,eins\r\n\n\"t,w\"\"o\",\"zw\nei\"\nthree,drei");
+        assert_eq!(
+            vec![
+                json!({"id": "one", "value": "eins"}),
+                json!({"id": "t,w\"o", "value": "zw\nei"}),
+                json!({"id": "three", "value": "drei"}),
+            ],
+            values.unwrap()
+        );
+
+        let values = read("id,value\n");
+        assert_eq!(Vec::<Value>::new(), values.unwrap());
+
+        let values = read("id,value\none,eins\ntwo\n");
+        assert_eq!(
+            "the CSV record on line 3 has 1 fields, but the header has 2",
+            values.unwrap_err().to_string()
+        );
+
+        let values = read("id,value\none,\"eins\n");
+        assert_eq!(
+            "unterminated quoted field in the CSV record on line 2",
+            values.unwrap_err().to_string()
+        );
+
+        let values = read("id,id\none,two\n");
+        assert_eq!(
+            "the CSV header contains the column `id` more than once",
+            values.unwrap_err().to_string()
+        );
+    }
+
     #[test]
     fn ipfs_map_file_size() {
         let file = "\"small test string that trips the size restriction\"";
@@ -0,0 +1,107 @@
+use graph::prelude::{MetricsRegistry as MetricsRegistryTrait, *};
+
+/// Metrics registry backed by a Prometheus `Registry`.
+#[derive(Clone)]
+pub struct MetricsRegistry {
+    logger: Logger,
+    registry: Arc<Registry>,
+}
+
+impl MetricsRegistry {
+    pub fn new(logger: Logger, registry: Arc<Registry>) -> Self {
+        MetricsRegistry {
+            logger: logger.new(o!("component" => "MetricsRegistry")),
+            registry,
+        }
+    }
+}
+
+impl MetricsRegistryTrait for MetricsRegistry {
+    fn register(&self, name: &str, collector: Box<dyn Collector>) {
+        match self.registry.register(collector) {
+            Ok(()) => (),
+            Err(PrometheusError::AlreadyReg) => warn!(
+                self.logger,
+                "Metric already registered, it will not be exported again";
+                "metric_name" => name
+            ),
+            Err(e) => error!(
+                self.logger,
+                "Failed to register metric";
+                "metric_name" => name,
+                "error" => e.to_string()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn registry() -> (Arc<Registry>, MetricsRegistry) {
+        let logger = Logger::root(slog::Discard, o!());
+        let prometheus_registry = Arc::new(Registry::new());
+        let registry = MetricsRegistry::new(logger, prometheus_registry.clone());
+        (prometheus_registry, registry)
+    }
+
+    fn const_labels(network: &str) -> HashMap<String, String> {
+        let mut labels = HashMap::new();
+        labels.insert(String::from("network"), network.to_owned());
+        labels
+    }
+
+    #[test]
+    fn registered_metrics_are_gathered() {
+        let (prometheus_registry, registry) = registry();
+
+        let counter = registry
+            .new_counter("test_counter", "test", HashMap::new())
+            .unwrap();
+        counter.inc_by(3.0);
+
+        let families = prometheus_registry.gather();
+        assert_eq!(families.len(), 1);
+        assert_eq!(families[0].get_name(), "test_counter");
+        assert_eq!(families[0].get_metric()[0].get_counter().get_value(), 3.0);
+    }
+
+    #[test]
+    fn metrics_with_different_const_labels_are_gathered() {
+        let (prometheus_registry, registry) = registry();
+
+        registry
+            .new_gauge("test_gauge", "test", const_labels("mainnet"))
+            .unwrap()
+            .set(1.0);
+        registry
+            .new_gauge("test_gauge", "test", const_labels("ropsten"))
+            .unwrap()
+            .set(2.0);
+
+        let families = prometheus_registry.gather();
+        assert_eq!(families.len(), 1);
+        assert_eq!(families[0].get_metric().len(), 2);
+    }
+
+    #[test]
+    fn duplicate_metrics_are_not_fatal() {
+        let (prometheus_registry, registry) = registry();
+
+        let first = registry
+            .new_gauge("test_gauge", "test", HashMap::new())
+            .unwrap();
+        let second = registry
+            .new_gauge("test_gauge", "test", HashMap::new())
+            .unwrap();
+        first.set(1.0);
+        second.set(2.0);
+
+        // Only the first gauge is exported
+        let families = prometheus_registry.gather();
+        assert_eq!(families.len(), 1);
+        assert_eq!(families[0].get_metric()[0].get_gauge().get_value(), 1.0);
+    }
+}
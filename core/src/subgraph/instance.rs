@@ -36,6 +36,7 @@ where
         let manifest_id = manifest.id.clone();
         let network_name = manifest.network_name()?;
         let templates = manifest.templates;
+        let schema = Arc::new(manifest.schema);
 
         // Create a new runtime host for each data source in the subgraph manifest;
         // we use the same order here as in the subgraph manifest to make the
@@ -48,6 +49,7 @@ where
                     &logger,
                     network_name.clone(),
                     manifest_id.clone(),
+                    schema.clone(),
                     d,
                     templates.clone(),
                 )
@@ -1,8 +1,14 @@
 use futures::future::{loop_fn, Loop};
 use futures::sync::mpsc::{channel, Receiver, Sender};
+use lazy_static::lazy_static;
 use std::collections::HashMap;
+use std::convert::TryInto;
+use std::env;
 use std::ops::Deref;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::RwLock;
+use std::time::Instant;
 use uuid::Uuid;
 
 use graph::data::subgraph::schema::{
@@ -12,16 +18,83 @@ use graph::prelude::{SubgraphInstance as SubgraphInstanceTrait, *};
 
 use super::SubgraphInstance;
 
+lazy_static! {
+    /// How often, in blocks, to prune the entity history of subgraphs that
+    /// have pruning enabled
+    static ref PRUNING_INTERVAL: u64 = env::var("GRAPH_PRUNING_INTERVAL")
+        .ok()
+        .map(|s| match u64::from_str(&s) {
+            Ok(interval) if interval > 0 => interval,
+            _ => panic!(
+                "env var GRAPH_PRUNING_INTERVAL must be a positive number of blocks, not `{}`",
+                s
+            ),
+        })
+        .unwrap_or(1000);
+}
+
 type SharedInstanceKeepAliveMap = Arc<RwLock<HashMap<SubgraphDeploymentId, CancelGuard>>>;
 
+/// Metrics collected while indexing subgraphs, labeled by deployment.
+struct SubgraphInstanceMetrics {
+    block_processing_duration: Box<HistogramVec>,
+    block_trigger_count: Box<HistogramVec>,
+    entity_cache_flush_size: Box<HistogramVec>,
+}
+
+impl SubgraphInstanceMetrics {
+    fn new(registry: Arc<dyn MetricsRegistry>) -> Self {
+        let block_processing_duration = registry
+            .new_histogram_vec(
+                "subgraph_block_processing_duration_ms",
+                "Time taken to process a block, including writing its changes to the store",
+                HashMap::new(),
+                &["deployment"],
+                vec![
+                    5.0, 10.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 5000.0, 30000.0,
+                ],
+            )
+            .expect("failed to create `subgraph_block_processing_duration_ms` histogram");
+        let block_trigger_count = registry
+            .new_histogram_vec(
+                "subgraph_block_trigger_count",
+                "Number of triggers found in a block",
+                HashMap::new(),
+                &["deployment"],
+                vec![1.0, 5.0, 10.0, 20.0, 50.0, 100.0, 500.0, 1000.0],
+            )
+            .expect("failed to create `subgraph_block_trigger_count` histogram");
+        let entity_cache_flush_size = registry
+            .new_histogram_vec(
+                "subgraph_entity_cache_flush_size",
+                "Number of entity modifications written to the store for a block",
+                HashMap::new(),
+                &["deployment"],
+                vec![1.0, 5.0, 10.0, 50.0, 100.0, 500.0, 1000.0, 5000.0, 10000.0],
+            )
+            .expect("failed to create `subgraph_entity_cache_flush_size` histogram");
+
+        SubgraphInstanceMetrics {
+            block_processing_duration,
+            block_trigger_count,
+            entity_cache_flush_size,
+        }
+    }
+}
+
 struct IndexingInputs<B, S, T> {
     deployment_id: SubgraphDeploymentId,
     network_name: String,
+    schema: Arc<Schema>,
     store: Arc<S>,
     stream_builder: B,
     host_builder: T,
     include_calls_in_blocks: bool,
     top_level_templates: Vec<DataSourceTemplate>,
+    metrics: Arc<SubgraphInstanceMetrics>,
+    reorg_threshold: u64,
+    /// Set while the entity history of the subgraph is being pruned
+    pruning: Arc<AtomicBool>,
 }
 
 struct IndexingState<T>
@@ -35,6 +108,8 @@ where
     call_filter: EthereumCallFilter,
     block_filter: EthereumBlockFilter,
     restarts: u64,
+    /// The block at which the entity history was last pruned
+    last_pruned: u64,
 }
 
 struct IndexingContext<B, S, T>
@@ -62,6 +137,8 @@ impl SubgraphInstanceManager {
         stores: HashMap<String, Arc<S>>,
         host_builder: T,
         block_stream_builder: B,
+        metrics_registry: Arc<dyn MetricsRegistry>,
+        reorg_threshold: u64,
     ) -> Self
     where
         S: Store + ChainStore,
@@ -74,6 +151,8 @@ impl SubgraphInstanceManager {
         // Create channel for receiving subgraph provider events.
         let (subgraph_sender, subgraph_receiver) = channel(100);
 
+        let metrics = Arc::new(SubgraphInstanceMetrics::new(metrics_registry));
+
         // Handle incoming events from the subgraph provider.
         Self::handle_subgraph_events(
             logger_factory,
@@ -81,6 +160,8 @@ impl SubgraphInstanceManager {
             stores,
             host_builder,
             block_stream_builder,
+            metrics,
+            reorg_threshold,
         );
 
         SubgraphInstanceManager {
@@ -96,6 +177,8 @@ impl SubgraphInstanceManager {
         stores: HashMap<String, Arc<S>>,
         host_builder: T,
         block_stream_builder: B,
+        metrics: Arc<SubgraphInstanceMetrics>,
+        reorg_threshold: u64,
     ) where
         S: Store + ChainStore,
         T: RuntimeHostBuilder,
@@ -132,6 +215,8 @@ impl SubgraphInstanceManager {
                                     ))
                                     .clone(),
                                 manifest,
+                                metrics.clone(),
+                                reorg_threshold,
                             )
                             .map_err(|err| {
                                 error!(
@@ -170,6 +255,8 @@ impl SubgraphInstanceManager {
         stream_builder: B,
         store: Arc<S>,
         manifest: SubgraphManifest,
+        metrics: Arc<SubgraphInstanceMetrics>,
+        reorg_threshold: u64,
     ) -> Result<(), Error>
     where
         T: RuntimeHostBuilder,
@@ -193,6 +280,7 @@ impl SubgraphInstanceManager {
         // Clone the deployment ID for later
         let deployment_id = manifest.id.clone();
         let network_name = manifest.network_name()?;
+        let schema = Arc::new(manifest.schema.clone());
 
         // Obtain filters from the manifest
         let log_filter = EthereumLogFilter::from_data_sources(&manifest.data_sources);
@@ -221,11 +309,15 @@ impl SubgraphInstanceManager {
             inputs: IndexingInputs {
                 deployment_id,
                 network_name,
+                schema,
                 store,
                 stream_builder,
                 host_builder,
                 include_calls_in_blocks,
                 top_level_templates,
+                metrics,
+                reorg_threshold,
+                pruning: Arc::new(AtomicBool::new(false)),
             },
             state: IndexingState {
                 logger,
@@ -235,6 +327,7 @@ impl SubgraphInstanceManager {
                 call_filter,
                 block_filter,
                 restarts: 0,
+                last_pruned: 0,
             },
         };
 
@@ -416,10 +509,19 @@ where
     S: ChainStore + Store,
     T: RuntimeHostBuilder,
 {
+    let start_time = Instant::now();
+
     let calls = block.calls;
     let triggers = block.triggers;
     let block = block.ethereum_block;
 
+    let metrics = ctx.inputs.metrics.clone();
+    let deployment_label = ctx.inputs.deployment_id.to_string();
+    metrics
+        .block_trigger_count
+        .with_label_values(&[&deployment_label])
+        .observe(triggers.len() as f64);
+
     let logger = logger.new(o!(
         "block_number" => format!("{:?}", block.block.number.unwrap()),
         "block_hash" => format!("{:?}", block.block.hash.unwrap())
@@ -440,6 +542,7 @@ where
     let block_ptr_now = EthereumBlockPointer::to_parent(&block);
     let block_ptr_after = EthereumBlockPointer::from(&*block);
     let block_ptr_for_new_data_sources = block_ptr_after.clone();
+    let block_number = block_ptr_after.number;
 
     // Clone a few things to pass into futures
     let logger1 = logger.clone();
@@ -456,9 +559,9 @@ where
         block.clone(),
         triggers,
     )
-    .and_then(|(ctx, block_state)| {
+    .and_then(move |(ctx, block_state)| {
         // Instantiate dynamic data sources
-        create_dynamic_data_sources(logger1, ctx, block_state).from_err()
+        create_dynamic_data_sources(logger1, ctx, block_state, block_number).from_err()
     })
     .and_then(move |(ctx, block_state, data_sources, runtime_hosts)| {
         // Reprocess the triggers from this block that match the new data sources
@@ -535,7 +638,7 @@ where
         .from_err()
     })
     // Apply entity operations and advance the stream
-    .and_then(move |(ctx, block_state, needs_restart)| {
+    .and_then(move |(mut ctx, block_state, needs_restart)| {
         // Avoid writing to store if block stream has been canceled
         if block_stream_cancel_handle.is_canceled() {
             return Err(CancelableError::Cancel);
@@ -553,6 +656,10 @@ where
         if !mods.is_empty() {
             info!(logger4, "Applying {} entity operation(s)", mods.len());
         }
+        metrics
+            .entity_cache_flush_size
+            .with_label_values(&[&deployment_label])
+            .observe(mods.len() as f64);
 
         // Transact entity operations into the store and update the
         // subgraph's block stream pointer
@@ -572,6 +679,17 @@ where
                         &block_ptr_after,
                     );
                 }
+                // Block numbers are not always consecutive, and go back
+                // when blocks are reverted
+                if block_ptr_after.number >= ctx.state.last_pruned + *PRUNING_INTERVAL
+                    && prune_in_background(&logger4, &ctx.inputs, &block_ptr_after)
+                {
+                    ctx.state.last_pruned = block_ptr_after.number;
+                }
+                metrics
+                    .block_processing_duration
+                    .with_label_values(&[&deployment_label])
+                    .observe(start_time.elapsed().as_millis() as f64);
                 (ctx, needs_restart)
             })
             .map_err(|e| {
@@ -580,6 +698,38 @@ where
     })
 }
 
+/// Prune the entity history of the subgraph on a separate thread so that
+/// indexing does not have to wait for it. Only one pruning run per subgraph
+/// is in flight at any time. Returns `true` if a pruning run was started
+fn prune_in_background<B, S, T>(
+    logger: &Logger,
+    inputs: &IndexingInputs<B, S, T>,
+    block_ptr: &EthereumBlockPointer,
+) -> bool
+where
+    S: Store,
+{
+    // The oldest block that a reorg could revert the subgraph to
+    let reorg_block: BlockNumber = match block_ptr.number.checked_sub(inputs.reorg_threshold + 1) {
+        Some(number) => number.try_into().unwrap(),
+        None => return false,
+    };
+    let pruning = inputs.pruning.clone();
+    if pruning.swap(true, Ordering::SeqCst) {
+        return false;
+    }
+
+    let logger = logger.clone();
+    let store = inputs.store.clone();
+    let deployment_id = inputs.deployment_id.clone();
+    tokio::spawn(graph::util::futures::blocking(future::lazy(move || {
+        store.prune_subgraph_deployment(&logger, &deployment_id, reorg_block);
+        pruning.store(false, Ordering::SeqCst);
+        Ok(())
+    })));
+    true
+}
+
 fn process_triggers<B, S, T>(
     logger: Logger,
     ctx: IndexingContext<B, S, T>,
@@ -639,6 +789,7 @@ fn create_dynamic_data_sources<B, S, T>(
     logger: Logger,
     ctx: IndexingContext<B, S, T>,
     block_state: BlockState,
+    block_number: u64,
 ) -> impl Future<
     Item = (
         IndexingContext<B, S, T>,
@@ -674,8 +825,14 @@ where
 
     stream::iter_ok(initial_state.block_state.created_data_sources.clone())
         .fold(initial_state, move |mut state, info| {
-            // Try to instantiate a data source from the template
-            let data_source = match DataSource::try_from_template(info.template, &info.params) {
+            // Try to instantiate a data source from the template; the
+            // data source starts at the block in which it is created
+            let data_source = match DataSource::try_from_template(
+                info.template,
+                &info.params,
+                info.context,
+                block_number,
+            ) {
                 Ok(data_source) => data_source,
                 Err(e) => return future::err(e),
             };
@@ -685,6 +842,7 @@ where
                 &logger,
                 state.ctx.inputs.network_name.clone(),
                 state.ctx.inputs.deployment_id.clone(),
+                state.ctx.inputs.schema.clone(),
                 data_source.clone(),
                 state.ctx.inputs.top_level_templates.clone(),
             ) {
@@ -47,7 +47,7 @@ where
                       kind
                       network
                       name
-                      source { address abi }
+                      source { address abi startBlock }
                       mapping {
                         kind
                         apiVersion
@@ -55,9 +55,9 @@ where
                         file
                         entities
                         abis { name file }
-                        blockHandlers { handler filter}
+                        blockHandlers { handler filter { kind every } }
                         callHandlers {  function handler}
-                        eventHandlers { event handler }
+                        eventHandlers { event handler receipt topic1 topic2 topic3 anonymous }
                       }
                       templates {
                         kind
@@ -71,11 +71,12 @@ where
                           file
                           entities
                           abis { name file }
-                          blockHandlers { handler filter}
+                          blockHandlers { handler filter { kind every } }
                           callHandlers { function handler}
-                          eventHandlers { event handler }
+                          eventHandlers { event handler receipt topic1 topic2 topic3 anonymous }
                         }
                       }
+                      context
                     }
                   }
                 }
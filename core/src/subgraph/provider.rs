@@ -93,12 +93,25 @@ where
         let logger_for_resolve = logger.clone();
         let logger_for_err = logger.clone();
         let logger_for_data_sources = logger.clone();
+        let logger_for_graft = logger.clone();
+        let store_for_graft = store.clone();
 
         info!(logger, "Resolve subgraph files using IPFS");
 
         Box::new(
             SubgraphManifest::resolve(Link { link }, self.resolver.clone(), logger_for_resolve)
                 .map_err(SubgraphAssignmentProviderError::ResolveError)
+                .and_then(move |manifest| {
+                    // The dynamic data sources of a graft include those of
+                    // its base, and must therefore be loaded after copying
+                    // the data of the base. Copying can take a long time
+                    graph::util::futures::blocking(future::lazy(move || {
+                        store_for_graft
+                            .copy_graft_base(&logger_for_graft, &manifest.id)
+                            .map(|()| manifest)
+                            .map_err(|e| SubgraphAssignmentProviderError::GraftError(e.into()))
+                    }))
+                })
                 .and_then(move |manifest| {
                     (
                         future::ok(manifest),
@@ -1,11 +1,12 @@
 use std::collections::{HashMap, HashSet};
 use std::iter;
+use std::path::PathBuf;
 use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 use super::validation;
 use graph::data::subgraph::schema::{
     generate_entity_id, SubgraphDeploymentAssignmentEntity, SubgraphDeploymentEntity,
-    SubgraphEntity, SubgraphVersionEntity, TypedEntity,
+    SubgraphEntity, SubgraphManifestEntity, SubgraphVersionEntity, TypedEntity, SUBGRAPHS_ID,
 };
 use graph::prelude::{
     CreateSubgraphResult, SubgraphAssignmentProvider as SubgraphAssignmentProviderTrait,
@@ -21,6 +22,7 @@ pub struct SubgraphRegistrar<L, P, S, CS> {
     chain_stores: HashMap<String, Arc<CS>>,
     node_id: NodeId,
     version_switching_mode: SubgraphVersionSwitchingMode,
+    reorg_threshold: u64,
     assignment_event_stream_cancel_guard: CancelGuard, // cancels on drop
 }
 
@@ -39,6 +41,7 @@ where
         chain_stores: HashMap<String, Arc<CS>>,
         node_id: NodeId,
         version_switching_mode: SubgraphVersionSwitchingMode,
+        reorg_threshold: u64,
     ) -> Self {
         let logger = logger_factory.component_logger("SubgraphRegistrar", None);
         let logger_factory = logger_factory.with_parent(logger.clone());
@@ -52,6 +55,7 @@ where
             chain_stores,
             node_id,
             version_switching_mode,
+            reorg_threshold,
             assignment_event_stream_cancel_guard: CancelGuard::new(),
         }
     }
@@ -279,6 +283,7 @@ where
         let store = self.store.clone();
         let chain_stores = self.chain_stores.clone();
         let version_switching_mode = self.version_switching_mode;
+        let reorg_threshold = self.reorg_threshold;
 
         let logger = self.logger_factory.subgraph_logger(&hash);
 
@@ -299,6 +304,7 @@ where
                         manifest,
                         node_id,
                         version_switching_mode,
+                        reorg_threshold,
                     )
                 }),
         )
@@ -319,13 +325,57 @@ where
         &self,
         hash: SubgraphDeploymentId,
         node_id: NodeId,
+        shard: Option<String>,
     ) -> Box<dyn Future<Item = (), Error = SubgraphRegistrarError> + Send + 'static> {
-        Box::new(future::result(reassign_subgraph(
+        let logger = self.logger.clone();
+        let store = self.store.clone();
+
+        // Moving the deployment to another shard copies all its data
+        Box::new(graph::util::futures::blocking(future::lazy(move || {
+            reassign_subgraph(&logger, store, hash, node_id, shard)
+        })))
+    }
+
+    fn set_subgraph_pruning(
+        &self,
+        hash: SubgraphDeploymentId,
+        pruning: Option<Pruning>,
+    ) -> Box<dyn Future<Item = (), Error = SubgraphRegistrarError> + Send + 'static> {
+        Box::new(future::result(set_subgraph_pruning(
             self.store.clone(),
             hash,
-            node_id,
+            pruning,
         )))
     }
+
+    fn export_subgraph(
+        &self,
+        hash: SubgraphDeploymentId,
+        directory: PathBuf,
+    ) -> Box<dyn Future<Item = (), Error = SubgraphRegistrarError> + Send + 'static> {
+        let logger = self.logger.clone();
+        let store = self.store.clone();
+
+        // Exporting reads all data of the deployment
+        Box::new(graph::util::futures::blocking(future::lazy(move || {
+            export_subgraph(&logger, store, hash, directory)
+        })))
+    }
+
+    fn import_subgraph(
+        &self,
+        directory: PathBuf,
+    ) -> Box<dyn Future<Item = SubgraphDeploymentId, Error = SubgraphRegistrarError> + Send + 'static>
+    {
+        let logger = self.logger.clone();
+        let store = self.store.clone();
+
+        Box::new(graph::util::futures::blocking(future::lazy(move || {
+            store
+                .import_subgraph_deployment(&logger, &directory)
+                .map_err(SubgraphRegistrarError::from)
+        })))
+    }
 }
 
 fn handle_assignment_event<P>(
@@ -455,6 +505,7 @@ fn create_subgraph_version(
     manifest: SubgraphManifest,
     node_id: NodeId,
     version_switching_mode: SubgraphVersionSwitchingMode,
+    reorg_threshold: u64,
 ) -> Result<(), SubgraphRegistrarError> {
     let mut ops = vec![];
 
@@ -654,12 +705,32 @@ fn create_subgraph_version(
 
     // Create deployment only if it does not exist already
     if !deployment_exists {
-        let chain_head_block = chain_store.chain_head_ptr()?;
         let genesis_block = chain_store.genesis_block_ptr()?;
-        ops.extend(
-            SubgraphDeploymentEntity::new(&manifest, false, false, genesis_block, chain_head_block)
-                .create_operations(&manifest.id),
-        );
+        let deployment = match &manifest.graft {
+            // A graft starts out at the genesis block until the data of its
+            // base has been copied
+            Some(graft) => {
+                let graft_block = validate_graft(
+                    store.clone(),
+                    chain_store.clone(),
+                    &manifest,
+                    reorg_threshold,
+                )?;
+                SubgraphDeploymentEntity::new(&manifest, false, false, genesis_block, None)
+                    .with_graft(graft.base.clone(), graft_block)
+            }
+            None => {
+                let chain_head_block = chain_store.chain_head_ptr()?;
+                SubgraphDeploymentEntity::new(
+                    &manifest,
+                    false,
+                    false,
+                    genesis_block,
+                    chain_head_block,
+                )
+            }
+        };
+        ops.extend(deployment.create_operations(&manifest.id));
     }
 
     // Possibly add assignment for new deployment hash, and possibly remove assignments for old
@@ -714,7 +785,7 @@ fn create_subgraph_version(
     if deployment_exists {
         store.apply_metadata_operations(ops)?
     } else {
-        store.create_subgraph_deployment(logger, &manifest.schema, ops)?;
+        store.create_subgraph_deployment(logger, &name, &manifest.schema, ops)?;
     }
 
     debug!(
@@ -727,6 +798,109 @@ fn create_subgraph_version(
     Ok(())
 }
 
+/// Check that the deployment described by `manifest` can be grafted onto
+/// its graft base: the base must exist and still have its data for the
+/// graft block, and its schema must be compatible with the new schema.
+/// The graft block must also be at least `reorg_threshold` blocks behind
+/// the head of the base so that it can not be reverted anymore. A base
+/// with JSONB storage only has the latest version of its entities; it must
+/// be stopped and can only be grafted at its head, which must be at least
+/// `reorg_threshold` blocks behind the head of the chain.
+/// Returns the pointer to the graft block
+fn validate_graft(
+    store: Arc<impl Store>,
+    chain_store: Arc<impl ChainStore>,
+    manifest: &SubgraphManifest,
+    reorg_threshold: u64,
+) -> Result<EthereumBlockPointer, SubgraphRegistrarError> {
+    use SubgraphManifestValidationError::*;
+
+    let graft = match &manifest.graft {
+        Some(graft) => graft,
+        None => unreachable!("validate_graft is only called for grafts"),
+    };
+    let base = graft.base.to_string();
+
+    if graft.base == *SUBGRAPHS_ID || !store.is_deployed(&graft.base)? {
+        return Err(GraftBaseNotFound(base).into());
+    }
+
+    let base_ptr = store.block_ptr(graft.base.clone())?;
+    if base_ptr.number < graft.block {
+        return Err(GraftBlockNotIndexed(base, graft.block, base_ptr.number).into());
+    }
+    let earliest_block = store.earliest_block(&graft.base)? as u64;
+    if graft.block < earliest_block {
+        return Err(GraftBlockPruned(base, graft.block, earliest_block).into());
+    }
+
+    let head = if store.uses_relational_schema(&graft.base)? {
+        base_ptr.number
+    } else {
+        let assignment = store.get(SubgraphDeploymentAssignmentEntity::key(graft.base.clone()))?;
+        if assignment.is_some() {
+            return Err(GraftBaseNotStopped(base).into());
+        }
+        if graft.block != base_ptr.number {
+            return Err(GraftBlockNotLatest(base, graft.block, base_ptr.number).into());
+        }
+        chain_store
+            .chain_head_ptr()?
+            .map(|ptr| ptr.number)
+            .unwrap_or(0)
+    };
+    if graft.block + reorg_threshold > head {
+        return Err(GraftBlockNotFinal(base, graft.block, reorg_threshold).into());
+    }
+
+    let base_manifest = store
+        .get(SubgraphManifestEntity::key(SubgraphManifestEntity::id(
+            &graft.base,
+        )))?
+        .ok_or_else(|| GraftBaseNotFound(base.clone()))?;
+    let base_schema = match base_manifest.get("schema") {
+        Some(Value::String(raw)) => Schema::parse(raw, graft.base.clone())?,
+        _ => return Err(format_err!("graft base `{}` has no schema", base).into()),
+    };
+    let incompatibilities = manifest.schema.graft_incompatibilities(&base_schema);
+    if !incompatibilities.is_empty() {
+        let reasons = incompatibilities
+            .iter()
+            .map(|incompatibility| incompatibility.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Err(GraftSchemaIncompatible(base, reasons).into());
+    }
+
+    chain_store
+        .ancestor_block(base_ptr, base_ptr.number - graft.block)?
+        .map(EthereumBlockPointer::from)
+        .ok_or_else(|| GraftBlockNotFound(graft.block, base).into())
+}
+
+fn export_subgraph(
+    logger: &Logger,
+    store: Arc<impl Store>,
+    hash: SubgraphDeploymentId,
+    directory: PathBuf,
+) -> Result<(), SubgraphRegistrarError> {
+    if hash == *SUBGRAPHS_ID || !store.is_deployed(&hash)? {
+        return Err(SubgraphRegistrarError::DeploymentNotFound(hash.to_string()));
+    }
+
+    let start = Instant::now();
+    store.export_subgraph_deployment(logger, &hash, &directory)?;
+
+    info!(
+        logger,
+        "Exported subgraph deployment";
+        "subgraph_hash" => hash.to_string(),
+        "directory" => directory.display().to_string(),
+        "time_ms" => start.elapsed().as_millis()
+    );
+    Ok(())
+}
+
 fn get_subgraph_version_deployment_id(
     store: Arc<impl Store>,
     version_id: String,
@@ -882,14 +1056,17 @@ fn remove_subgraph_versions(
     Ok(ops)
 }
 
-/// Reassign a subgraph deployment to a different node.
+/// Reassign a subgraph deployment to a different node, and, if `shard` is
+/// given, move its entities to that database shard.
 ///
 /// Reassigning to a nodeId that does not match any reachable graph-nodes will effectively pause the
 /// subgraph syncing process.
 fn reassign_subgraph(
+    logger: &Logger,
     store: Arc<impl Store>,
     hash: SubgraphDeploymentId,
     node_id: NodeId,
+    shard: Option<String>,
 ) -> Result<(), SubgraphRegistrarError> {
     let mut ops = vec![];
 
@@ -903,6 +1080,16 @@ fn reassign_subgraph(
         .and_then(|d| d.get("nodeId"))
         .ok_or_else(|| SubgraphRegistrarError::DeploymentNotFound(hash.clone().to_string()))?;
 
+    let current_shard = store
+        .get(SubgraphDeploymentEntity::key(hash.clone()))?
+        .map(|deployment| SubgraphDeploymentEntity::shard(&deployment))
+        .ok_or_else(|| SubgraphRegistrarError::DeploymentNotFound(hash.clone().to_string()))?;
+    let shard = shard.filter(|shard| *shard != current_shard);
+
+    if let Some(shard) = shard {
+        return move_subgraph(logger, store, hash, current_node_id, node_id, &shard);
+    }
+
     if current_node_id.to_string() == node_id.to_string() {
         return Err(SubgraphRegistrarError::DeploymentAssignmentUnchanged(
             hash.clone().to_string(),
@@ -931,3 +1118,67 @@ fn reassign_subgraph(
 
     Ok(())
 }
+
+/// Move the entities of a subgraph deployment to the database shard `shard`
+/// and assign the deployment to `node_id`. The deployment is unassigned
+/// while it is being moved so that nothing writes to it; if the move fails,
+/// it is assigned to its old node again
+fn move_subgraph(
+    logger: &Logger,
+    store: Arc<impl Store>,
+    hash: SubgraphDeploymentId,
+    current_node_id: &Value,
+    node_id: NodeId,
+    shard: &str,
+) -> Result<(), SubgraphRegistrarError> {
+    let current_node_id = NodeId::new(current_node_id.to_string())
+        .map_err(|()| format_err!("invalid node id `{}`", current_node_id))?;
+
+    store.apply_metadata_operations(vec![
+        MetadataOperation::AbortUnless {
+            description: "Deployment assignment is unchanged".to_owned(),
+            query: SubgraphDeploymentAssignmentEntity::query().filter(EntityFilter::And(vec![
+                EntityFilter::new_equal("nodeId", current_node_id.to_string()),
+                EntityFilter::new_equal("id", hash.to_string()),
+            ])),
+            entity_ids: vec![hash.to_string()],
+        },
+        MetadataOperation::Remove {
+            entity: SubgraphDeploymentAssignmentEntity::TYPENAME.to_owned(),
+            id: hash.to_string(),
+        },
+    ])?;
+
+    let (node_id, result) = match store.move_subgraph_deployment(logger, &hash, shard) {
+        Ok(()) => (node_id, Ok(())),
+        Err(e) => (current_node_id, Err(e)),
+    };
+    store.apply_metadata_operations(
+        SubgraphDeploymentAssignmentEntity::new(node_id).write_operations(&hash),
+    )?;
+    result.map_err(SubgraphRegistrarError::from)
+}
+
+fn set_subgraph_pruning(
+    store: Arc<impl Store>,
+    hash: SubgraphDeploymentId,
+    pruning: Option<Pruning>,
+) -> Result<(), SubgraphRegistrarError> {
+    if !store.is_deployed(&hash)? {
+        return Err(SubgraphRegistrarError::DeploymentNotFound(hash.to_string()));
+    }
+
+    let mut ops = vec![MetadataOperation::AbortUnless {
+        description: "Deployment must exist to change its pruning settings".to_owned(),
+        query: SubgraphDeploymentEntity::query()
+            .filter(EntityFilter::new_equal("id", hash.to_string())),
+        entity_ids: vec![hash.to_string()],
+    }];
+    ops.extend(SubgraphDeploymentEntity::update_pruning_operations(
+        &hash, pruning,
+    ));
+
+    store.apply_metadata_operations(ops)?;
+
+    Ok(())
+}
@@ -1,4 +1,6 @@
+use graph::data::subgraph::Mapping;
 use graph::prelude::*;
+use graph::util::ethereum::contract_event_with_signature;
 
 pub fn validate_manifest(
     manifest: SubgraphManifest,
@@ -28,27 +30,156 @@ pub fn validate_manifest(
 
         let mut non_filtered_block_handler_count = 0;
         let mut call_filtered_block_handler_count = 0;
+        let mut polling_filtered_block_handler_count = 0;
+        let mut once_filtered_block_handler_count = 0;
         data_source
             .mapping
             .block_handlers
             .iter()
-            .for_each(|block_handler| {
-                if block_handler.filter.is_none() {
-                    non_filtered_block_handler_count += 1
-                } else {
-                    call_filtered_block_handler_count += 1
+            .for_each(|block_handler| match block_handler.filter {
+                None => non_filtered_block_handler_count += 1,
+                Some(BlockHandlerFilter::Call) => call_filtered_block_handler_count += 1,
+                Some(BlockHandlerFilter::Polling { .. }) => {
+                    polling_filtered_block_handler_count += 1
                 }
+                Some(BlockHandlerFilter::Once) => once_filtered_block_handler_count += 1,
             });
-        return non_filtered_block_handler_count > 1 || call_filtered_block_handler_count > 1;
+        return non_filtered_block_handler_count > 1
+            || call_filtered_block_handler_count > 1
+            || polling_filtered_block_handler_count > 1
+            || once_filtered_block_handler_count > 1;
     });
 
     if has_too_many_block_handlers {
         errors.push(SubgraphManifestValidationError::DataSourceBlockHandlerLimitExceeded)
     }
 
+    // Validate that polling block handlers have a non-zero interval
+    let has_invalid_polling_interval = manifest.data_sources.iter().any(|data_source| {
+        data_source
+            .mapping
+            .block_handlers
+            .iter()
+            .any(|block_handler| match block_handler.filter {
+                Some(BlockHandlerFilter::Polling { every }) => every == 0,
+                _ => false,
+            })
+    });
+
+    if has_invalid_polling_interval {
+        errors.push(SubgraphManifestValidationError::InvalidPollingInterval)
+    }
+
+    // Validate that anonymous events are only indexed for specific contracts;
+    // without an address, they could match the logs of any contract
+    let has_anonymous_events_without_address = manifest.data_sources.iter().any(|data_source| {
+        data_source.source.address.is_none()
+            && data_source
+                .mapping
+                .event_handlers
+                .iter()
+                .any(|event_handler| event_handler.anonymous)
+    });
+
+    if has_anonymous_events_without_address {
+        errors.push(SubgraphManifestValidationError::AnonymousEventSourceAddressRequired)
+    }
+
+    // Validate that the anonymous events of every data source and template
+    // can be identified by their number of topics
+    for data_source in manifest.data_sources.iter() {
+        validate_anonymous_events(&data_source.source.abi, &data_source.mapping, &mut errors);
+        for template in data_source.templates.iter() {
+            validate_anonymous_events(&template.source.abi, &template.mapping, &mut errors);
+        }
+    }
+    for template in manifest.templates.iter() {
+        validate_anonymous_events(&template.source.abi, &template.mapping, &mut errors);
+    }
+
     if errors.is_empty() {
         return Ok(manifest);
     }
 
     return Err(SubgraphRegistrarError::ManifestValidationError(errors));
 }
+
+/// Anonymous events are matched by contract address and number of topics, so
+/// each of them has to be declared anonymous in the contract ABI, with the
+/// same indexed parameters as in the manifest, and no two anonymous events of
+/// the same mapping may have the same number of indexed parameters. Logs of
+/// the named events in the ABI are never matched as anonymous events, but
+/// a named event handler whose signature is not in the ABI must not produce
+/// as many topics as an anonymous one. Topic filters refer to the topics
+/// after the event signature, which anonymous events don't have, so they
+/// are not allowed on anonymous events.
+fn validate_anonymous_events(
+    abi_name: &str,
+    mapping: &Mapping,
+    errors: &mut Vec<SubgraphManifestValidationError>,
+) {
+    let contract = mapping
+        .abis
+        .iter()
+        .find(|abi| abi.name == abi_name)
+        .map(|abi| &abi.contract);
+
+    let anonymous_handlers = mapping
+        .event_handlers
+        .iter()
+        .filter(|event_handler| event_handler.anonymous)
+        .collect::<Vec<_>>();
+
+    for event_handler in anonymous_handlers.iter() {
+        let is_valid = contract
+            .and_then(|contract| contract_event_with_signature(contract, &event_handler.event))
+            .map_or(false, |event| {
+                event.anonymous
+                    && event.inputs.iter().filter(|input| input.indexed).count()
+                        == event_handler.indexed_param_count()
+            });
+
+        if !is_valid {
+            errors.push(SubgraphManifestValidationError::InvalidAnonymousEvent(
+                event_handler.event.clone(),
+            ));
+        }
+
+        if event_handler.topic1.is_some()
+            || event_handler.topic2.is_some()
+            || event_handler.topic3.is_some()
+        {
+            errors.push(SubgraphManifestValidationError::AnonymousEventTopicFilter(
+                event_handler.event.clone(),
+            ));
+        }
+    }
+
+    for (i, a) in anonymous_handlers.iter().enumerate() {
+        if let Some(b) = anonymous_handlers[i + 1..]
+            .iter()
+            .find(|b| b.event != a.event && b.indexed_param_count() == a.indexed_param_count())
+        {
+            errors.push(SubgraphManifestValidationError::AmbiguousAnonymousEvents(
+                a.event.clone(),
+                b.event.clone(),
+            ));
+        }
+    }
+
+    let named_signatures = mapping.named_event_signatures(abi_name);
+    for a in anonymous_handlers.iter() {
+        if let Some(b) = mapping.event_handlers.iter().find(|b| {
+            !b.anonymous
+                && b.indexed_param_count() + 1 == a.indexed_param_count()
+                && !named_signatures.contains(&b.topic0())
+        }) {
+            errors.push(
+                SubgraphManifestValidationError::AmbiguousAnonymousAndNamedEvents(
+                    a.event.clone(),
+                    b.event.clone(),
+                ),
+            );
+        }
+    }
+}
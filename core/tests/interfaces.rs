@@ -23,6 +23,8 @@ fn insert_and_query(
         schema: schema.clone(),
         data_sources: vec![],
         templates: vec![],
+        pruning: None,
+        graft: None,
     };
 
     let logger = Logger::root(slog::Discard, o!());
@@ -33,7 +35,12 @@ fn insert_and_query(
         .map(|op| op.into())
         .collect();
     STORE
-        .create_subgraph_deployment(&logger, &schema, ops)
+        .create_subgraph_deployment(
+            &logger,
+            &SubgraphName::new("interfaces").unwrap(),
+            &schema,
+            ops,
+        )
         .unwrap();
 
     let insert_ops = entities
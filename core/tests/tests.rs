@@ -20,7 +20,7 @@ use std::time::Instant;
 use graph::components::ethereum::*;
 use graph::prelude::*;
 use graph_core::{LinkResolver, SubgraphInstanceManager};
-use graph_mock::{FakeStore, MockBlockStreamBuilder, MockStore};
+use graph_mock::{FakeStore, MockBlockStreamBuilder, MockMetricsRegistry, MockStore};
 use web3::types::*;
 
 use crate::tokio::timer::Delay;
@@ -146,6 +146,7 @@ fn multiple_data_sources_per_subgraph() {
             _: &Logger,
             _: String,
             _: SubgraphDeploymentId,
+            _: Arc<Schema>,
             data_source: DataSource,
             _: Vec<DataSourceTemplate>,
         ) -> Result<Self::Host, Error> {
@@ -178,6 +179,8 @@ fn multiple_data_sources_per_subgraph() {
                 stores,
                 host_builder.clone(),
                 block_stream_builder.clone(),
+                Arc::new(MockMetricsRegistry::new()),
+                50,
             );
 
             // Load a subgraph with two data sources
@@ -251,7 +254,11 @@ fn subgraph_provider_events() {
                 .into_iter()
                 .map(|s| ("mainnet".to_string(), s))
                 .collect();
-            let graphql_runner = Arc::new(graph_core::GraphQlRunner::new(&logger, store.clone()));
+            let graphql_runner = Arc::new(graph_core::GraphQlRunner::new(
+                &logger,
+                store.clone(),
+                Arc::new(MockMetricsRegistry::new()),
+            ));
             let mut provider = graph_core::SubgraphAssignmentProvider::new(
                 &logger_factory,
                 resolver.clone(),
@@ -269,6 +276,7 @@ fn subgraph_provider_events() {
                 stores,
                 node_id.clone(),
                 SubgraphVersionSwitchingMode::Instant,
+                50,
             );
             registrar
                 .start()
@@ -900,10 +900,8 @@ where
                         Ok(None) | Err(_) => {
                             let logger = ctx.logger.clone();
                             let ctx_1 = ctx.clone();
-                            let ctx_2 = ctx_1.clone();
                             Box::new(
-                                ctx_1
-                                    .eth_adapter
+                                ctx.eth_adapter
                                     .block_by_hash(&logger, block_hash)
                                     .and_then(move |block_opt| {
                                         block_opt.ok_or_else(move || {
@@ -914,16 +912,7 @@ where
                                         })
                                     })
                                     .and_then(move |block| {
-                                        ctx_1.eth_adapter.load_full_block(&logger, block).map_err(
-                                            |e| format_err!("Error loading full block: {}", e),
-                                        )
-                                    })
-                                    .and_then(move |block| {
-                                        // Cache in store for later
-                                        ctx_2
-                                            .chain_store
-                                            .upsert_blocks(stream::once(Ok(block.clone())))
-                                            .map(move |()| block)
+                                        ctx_1.load_receipts(block, include_calls_in_block)
                                     }),
                             )
                         }
@@ -954,6 +943,41 @@ where
         Box::new(block)
     }
 
+    /// Load the receipts of `block` and cache the full block in the chain
+    /// store. The receipts are only needed for logs, for event handlers
+    /// that take the receipt of their transaction, and to find the
+    /// transactions of calls; without those handlers, they are skipped if
+    /// the block's log bloom rules out matching logs. Such a block is
+    /// incomplete and not cached.
+    fn load_receipts(
+        &self,
+        block: Block<Transaction>,
+        include_calls_in_block: bool,
+    ) -> Box<dyn Future<Item = EthereumBlock, Error = Error> + Send> {
+        if !include_calls_in_block
+            && !self.log_filter.receipts
+            && !self.log_filter.check_bloom(block.logs_bloom)
+        {
+            return Box::new(future::ok(EthereumBlock {
+                block,
+                transaction_receipts: Vec::new(),
+            }));
+        }
+
+        let chain_store = self.chain_store.clone();
+        Box::new(
+            self.eth_adapter
+                .load_full_block(&self.logger, block)
+                .map_err(|e| format_err!("Error loading full block: {}", e))
+                .and_then(move |block| {
+                    // Cache in store for later
+                    chain_store
+                        .upsert_blocks(stream::once(Ok(block.clone())))
+                        .map(move |()| block)
+                }),
+        )
+    }
+
     pub fn parse_triggers(
         log_filter: EthereumLogFilter,
         call_filter: EthereumCallFilter,
@@ -1003,8 +1027,22 @@ where
             }
         }
 
-        // Sort the triggers
+        // Sort the triggers; `once` block handlers initialize their data
+        // sources and therefore run before everything else in the block
         triggers.sort_by(|a, b| {
+            match (a, b) {
+                (
+                    EthereumTrigger::Block(EthereumBlockTriggerType::Once(_)),
+                    EthereumTrigger::Block(EthereumBlockTriggerType::Once(_)),
+                ) => return Ordering::Equal,
+                (EthereumTrigger::Block(EthereumBlockTriggerType::Once(_)), _) => {
+                    return Ordering::Less
+                }
+                (_, EthereumTrigger::Block(EthereumBlockTriggerType::Once(_))) => {
+                    return Ordering::Greater
+                }
+                _ => {}
+            }
             let a_tx_index = a.transaction_index(&tx_hash_indexes).unwrap();
             let b_tx_index = b.transaction_index(&tx_hash_indexes).unwrap();
             if a_tx_index.is_none() && b_tx_index.is_none() {
@@ -1367,6 +1405,9 @@ fn parse_block_triggers(
     block: &EthereumBlockWithCalls,
 ) -> Vec<EthereumTrigger> {
     let trigger_every_block = block_filter.trigger_every_block;
+    let block_number = block.ethereum_block.block.number.unwrap().as_u64();
+    let trigger_polling = block_filter.matches_polling(block_number);
+    let trigger_once = block_filter.matches_once(block_number);
     let call_filter = EthereumCallFilter::from(block_filter);
     let mut triggers = block.calls.as_ref().map_or(vec![], |calls| {
         calls
@@ -1378,5 +1419,232 @@ fn parse_block_triggers(
     if trigger_every_block {
         triggers.push(EthereumTrigger::Block(EthereumBlockTriggerType::Every));
     }
+    if trigger_polling {
+        triggers.push(EthereumTrigger::Block(EthereumBlockTriggerType::Polling(
+            block_number,
+        )));
+    }
+    if trigger_once {
+        triggers.push(EthereumTrigger::Block(EthereumBlockTriggerType::Once(
+            block_number,
+        )));
+    }
     triggers
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graph_mock::MockStore;
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+    /// An Ethereum adapter that only knows how to load the receipts of a
+    /// block, and counts how often it was asked to.
+    #[derive(Default)]
+    struct ReceiptsAdapter {
+        full_blocks_loaded: AtomicUsize,
+    }
+
+    impl ReceiptsAdapter {
+        fn full_blocks_loaded(&self) -> usize {
+            self.full_blocks_loaded.load(AtomicOrdering::SeqCst)
+        }
+    }
+
+    impl EthereumAdapter for ReceiptsAdapter {
+        fn net_identifiers(
+            &self,
+            _: &Logger,
+        ) -> Box<dyn Future<Item = EthereumNetworkIdentifier, Error = Error> + Send> {
+            unimplemented!()
+        }
+
+        fn latest_block(
+            &self,
+            _: &Logger,
+        ) -> Box<dyn Future<Item = Block<Transaction>, Error = EthereumAdapterError> + Send>
+        {
+            unimplemented!()
+        }
+
+        fn block_by_hash(
+            &self,
+            _: &Logger,
+            _: H256,
+        ) -> Box<dyn Future<Item = Option<Block<Transaction>>, Error = Error> + Send> {
+            unimplemented!()
+        }
+
+        fn load_full_block(
+            &self,
+            _: &Logger,
+            block: Block<Transaction>,
+        ) -> Box<dyn Future<Item = EthereumBlock, Error = EthereumAdapterError> + Send> {
+            self.full_blocks_loaded.fetch_add(1, AtomicOrdering::SeqCst);
+            Box::new(future::ok(EthereumBlock {
+                block,
+                transaction_receipts: vec![],
+            }))
+        }
+
+        fn block_parent_hash(
+            &self,
+            _: &Logger,
+            _: H256,
+        ) -> Box<dyn Future<Item = Option<H256>, Error = Error> + Send> {
+            unimplemented!()
+        }
+
+        fn block_hash_by_block_number(
+            &self,
+            _: &Logger,
+            _: u64,
+        ) -> Box<dyn Future<Item = Option<H256>, Error = Error> + Send> {
+            unimplemented!()
+        }
+
+        fn is_on_main_chain(
+            &self,
+            _: &Logger,
+            _: EthereumBlockPointer,
+        ) -> Box<dyn Future<Item = bool, Error = Error> + Send> {
+            unimplemented!()
+        }
+
+        fn calls_in_block(
+            &self,
+            _: &Logger,
+            _: u64,
+            _: H256,
+        ) -> Box<dyn Future<Item = Vec<EthereumCall>, Error = Error> + Send> {
+            unimplemented!()
+        }
+
+        fn blocks_with_triggers(
+            &self,
+            _: &Logger,
+            _: u64,
+            _: u64,
+            _: EthereumLogFilter,
+            _: EthereumCallFilter,
+            _: EthereumBlockFilter,
+        ) -> Box<dyn Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
+            unimplemented!()
+        }
+
+        fn blocks_with_logs(
+            &self,
+            _: &Logger,
+            _: u64,
+            _: u64,
+            _: EthereumLogFilter,
+        ) -> Box<dyn Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
+            unimplemented!()
+        }
+
+        fn blocks_with_calls(
+            &self,
+            _: &Logger,
+            _: u64,
+            _: u64,
+            _: EthereumCallFilter,
+        ) -> Box<dyn Future<Item = HashSet<EthereumBlockPointer>, Error = Error> + Send> {
+            unimplemented!()
+        }
+
+        fn blocks(
+            &self,
+            _: &Logger,
+            _: u64,
+            _: u64,
+        ) -> Box<dyn Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
+            unimplemented!()
+        }
+
+        fn contract_call(
+            &self,
+            _: &Logger,
+            _: EthereumContractCall,
+            _: Arc<dyn EthereumCallCache>,
+        ) -> Box<dyn Future<Item = Vec<ethabi::Token>, Error = EthereumContractCallError> + Send>
+        {
+            unimplemented!()
+        }
+    }
+
+    fn context(
+        log_filter: EthereumLogFilter,
+    ) -> BlockStreamContext<MockStore, MockStore, ReceiptsAdapter> {
+        let store = Arc::new(MockStore::new(vec![]));
+        BlockStreamContext {
+            subgraph_store: store.clone(),
+            chain_store: store,
+            eth_adapter: Arc::new(ReceiptsAdapter::default()),
+            node_id: NodeId::new("test").unwrap(),
+            subgraph_id: SubgraphDeploymentId::new("testsubgraph").unwrap(),
+            reorg_threshold: 50,
+            log_filter,
+            call_filter: EthereumCallFilter::from_data_sources(vec![]),
+            block_filter: EthereumBlockFilter::from_data_sources(vec![]),
+            include_calls_in_blocks: false,
+            logger: Logger::root(slog::Discard, o!()),
+        }
+    }
+
+    fn transfer_filter() -> EthereumLogFilter {
+        EthereumLogFilter::from_iter(vec![(Some(Address::from([1; 20])), H256::from([2; 32]))])
+    }
+
+    fn block(logs_bloom: H2048) -> Block<Transaction> {
+        Block {
+            logs_bloom,
+            ..EthereumBlock::default().block
+        }
+    }
+
+    #[test]
+    fn load_receipts_skips_blocks_whose_bloom_does_not_match() {
+        let ctx = context(transfer_filter());
+
+        ctx.load_receipts(block(H2048::zero()), false)
+            .wait()
+            .unwrap();
+
+        assert_eq!(ctx.eth_adapter.full_blocks_loaded(), 0);
+    }
+
+    #[test]
+    fn load_receipts_loads_blocks_whose_bloom_matches() {
+        let ctx = context(transfer_filter());
+
+        ctx.load_receipts(block(H2048::from([0xff; 256])), false)
+            .wait()
+            .unwrap();
+
+        assert_eq!(ctx.eth_adapter.full_blocks_loaded(), 1);
+    }
+
+    #[test]
+    fn load_receipts_loads_blocks_for_receipt_handlers() {
+        let mut filter = transfer_filter();
+        filter.receipts = true;
+        let ctx = context(filter);
+
+        ctx.load_receipts(block(H2048::zero()), false)
+            .wait()
+            .unwrap();
+
+        assert_eq!(ctx.eth_adapter.full_blocks_loaded(), 1);
+    }
+
+    #[test]
+    fn load_receipts_loads_blocks_with_calls() {
+        let ctx = context(transfer_filter());
+
+        ctx.load_receipts(block(H2048::zero()), true)
+            .wait()
+            .unwrap();
+
+        assert_eq!(ctx.eth_adapter.full_blocks_loaded(), 1);
+    }
+}
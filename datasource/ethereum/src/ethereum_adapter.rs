@@ -2,7 +2,7 @@ use ethabi::Token;
 use futures::future;
 use futures::prelude::*;
 use lazy_static::lazy_static;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use ethabi::ParamType;
@@ -10,12 +10,16 @@ use graph::components::ethereum::{EthereumAdapter as EthereumAdapterTrait, *};
 use graph::prelude::*;
 use web3;
 use web3::api::Web3;
+use web3::helpers::CallFuture;
 use web3::transports::batch::Batch;
 use web3::types::{Filter, *};
+use web3::Transport as _;
+
+use crate::transport::MeteredTransport;
 
 #[derive(Clone)]
 pub struct EthereumAdapter<T: web3::Transport> {
-    web3: Arc<Web3<T>>,
+    web3: Arc<Web3<MeteredTransport<T>>>,
     start_block: u64,
 }
 
@@ -41,14 +45,115 @@ lazy_static! {
         .expect("invalid number of parallel Ethereum block ranges to scan");
 }
 
+/// The parameters of an `eth_getLogs` request.
+#[derive(Clone, Debug)]
+struct LogsRequest {
+    addresses: Vec<H160>,
+    event_signatures: Option<Vec<H256>>,
+    topics: [Option<Vec<H256>>; 3],
+}
+
+impl LogsRequest {
+    /// Build a request that returns at least all logs matching any of the
+    /// given event filters.
+    ///
+    /// A single request can only filter on contract addresses, event
+    /// signatures or the values of topics 1-3 if every event filter
+    /// restricts them. For example, if we have a data source without a
+    /// contract address, we can't add addresses to the request because it
+    /// would only match the contracts for which we _have_ addresses;
+    /// therefore we perform a broader logs scan and filter out irrelevant
+    /// events ourselves.
+    ///
+    /// Our own filtering is performed later when the events are passed to
+    /// subgraphs and runtime hosts for processing:
+    /// - At the top level in `BlockStreamContext::do_step`
+    /// - At the subgraph level in `SubgraphInstance::matches_log`
+    /// - At the data source level in `RuntimeHost::matches_log`
+    fn from_event_filters(filters: Vec<&EthereumEventFilter>) -> Self {
+        let addresses = if filters
+            .iter()
+            .any(|filter| filter.contract_address.is_none())
+        {
+            vec![]
+        } else {
+            filters
+                .iter()
+                .filter_map(|filter| filter.contract_address)
+                .collect::<HashSet<H160>>()
+                .into_iter()
+                .collect()
+        };
+
+        let event_signatures = if filters
+            .iter()
+            .any(|filter| filter.event_signature.is_none())
+        {
+            None
+        } else {
+            Some(
+                filters
+                    .iter()
+                    .filter_map(|filter| filter.event_signature)
+                    .collect::<HashSet<H256>>()
+                    .into_iter()
+                    .collect(),
+            )
+        };
+
+        let mut topics: [Option<Vec<H256>>; 3] = [None, None, None];
+        for (i, topic) in topics.iter_mut().enumerate() {
+            if filters.iter().all(|filter| filter.topics()[i].is_some()) {
+                *topic = Some(
+                    filters
+                        .iter()
+                        .filter_map(|filter| filter.topics()[i].clone())
+                        .flatten()
+                        .collect::<HashSet<H256>>()
+                        .into_iter()
+                        .collect(),
+                );
+            }
+        }
+
+        LogsRequest {
+            addresses,
+            event_signatures,
+            topics,
+        }
+    }
+}
+
 impl<T> EthereumAdapter<T>
 where
     T: web3::BatchTransport + Send + Sync + 'static,
     T::Out: Send,
 {
-    pub fn new(transport: T, start_block: u64) -> Self {
+    pub fn new(
+        transport: T,
+        start_block: u64,
+        network_name: &str,
+        metrics_registry: Arc<dyn MetricsRegistry>,
+    ) -> Self {
+        let mut const_labels = HashMap::new();
+        const_labels.insert(String::from("network"), network_name.to_owned());
+        let request_duration = metrics_registry
+            .new_histogram_vec(
+                "ethereum_rpc_request_duration_ms",
+                "Latency of Ethereum JSON-RPC requests, by method",
+                const_labels,
+                &["method"],
+                vec![
+                    5.0, 10.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 5000.0, 30000.0,
+                ],
+            )
+            .expect("failed to create `ethereum_rpc_request_duration_ms` histogram");
+
         EthereumAdapter {
-            web3: Arc::new(Web3::new(transport)),
+            web3: Arc::new(Web3::new(MeteredTransport::new(
+                transport,
+                request_duration,
+            ))),
             start_block,
         }
     }
@@ -136,8 +241,7 @@ where
         logger: &Logger,
         from: u64,
         to: u64,
-        addresses: Vec<H160>,
-        event_signatures: Vec<H256>,
+        request: LogsRequest,
         too_many_logs_fingerprint: &'static str,
     ) -> impl Future<Item = Vec<Log>, Error = tokio_timer::timeout::Error<web3::error::Error>> {
         let eth_adapter = self.clone();
@@ -154,8 +258,13 @@ where
                 let log_filter: Filter = FilterBuilder::default()
                     .from_block(from.into())
                     .to_block(to.into())
-                    .address(addresses.clone())
-                    .topics(Some(event_signatures.clone()), None, None, None)
+                    .address(request.addresses.clone())
+                    .topics(
+                        request.event_signatures.clone(),
+                        request.topics[0].clone(),
+                        request.topics[1].clone(),
+                        request.topics[2].clone(),
+                    )
                     .build();
 
                 // Request logs from client
@@ -199,6 +308,90 @@ where
         .flatten()
     }
 
+    /// Find all blocks in the range `[from, to]` with logs matching the
+    /// filter, using `eth_getLogs`.
+    fn blocks_with_logs_in_range(
+        &self,
+        logger: &Logger,
+        from: u64,
+        to: u64,
+        log_filter: EthereumLogFilter,
+    ) -> Box<dyn Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
+        Box::new(
+            // Get a stream of all relevant logs in range
+            self.log_stream(&logger, from, to, log_filter).map(|logs| {
+                let mut block_ptrs = vec![];
+                for log in logs.iter() {
+                    let hash = log
+                        .block_hash
+                        .expect("log from Eth node is missing block hash");
+                    let number = log
+                        .block_number
+                        .expect("log from Eth node is missing block number")
+                        .as_u64();
+                    let block_ptr = EthereumBlockPointer::from((hash, number));
+                    if !block_ptrs.contains(&block_ptr) {
+                        if let Some(prev) = block_ptrs.last() {
+                            assert!(prev.number < number);
+                        }
+                        block_ptrs.push(block_ptr);
+                    }
+                }
+                block_ptrs
+            }),
+        )
+    }
+
+    /// Find the pointers of the blocks with the given numbers.
+    fn blocks_by_numbers(
+        &self,
+        logger: &Logger,
+        block_numbers: Vec<u64>,
+    ) -> impl Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send {
+        let eth = self.clone();
+        let logger = logger.clone();
+
+        stream::iter_ok(block_numbers)
+            .map(move |number| {
+                eth.block_hash_by_block_number(&logger, number)
+                    .and_then(move |hash_opt| {
+                        hash_opt.ok_or_else(|| {
+                            format_err!("Ethereum node could not find block with number {}", number)
+                        })
+                    })
+                    .map(move |hash| EthereumBlockPointer::from((hash, number)))
+            })
+            .buffered(*LOG_STREAM_PARALLEL_CHUNKS as usize)
+            .collect()
+    }
+
+    /// Get the log bloom of the block with the given number.
+    fn logs_bloom_by_block_number(
+        &self,
+        logger: &Logger,
+        block_number: u64,
+    ) -> impl Future<Item = Option<H2048>, Error = Error> + Send {
+        let web3 = self.web3.clone();
+
+        retry("eth_getBlockByNumber RPC call", &logger)
+            .no_limit()
+            .timeout_secs(60)
+            .run(move || {
+                web3.eth()
+                    .block(BlockId::Number(block_number.into()))
+                    .from_err()
+                    .map(|block_opt| block_opt.map(|block| block.logs_bloom))
+            })
+            .map_err(move |e| {
+                e.into_inner().unwrap_or_else(move || {
+                    format_err!(
+                        "Ethereum node took too long to return data for block #{}",
+                        block_number
+                    )
+                })
+            })
+    }
+
     fn log_stream(
         &self,
         logger: &Logger,
@@ -217,53 +410,27 @@ where
             );
         }
 
-        // Collect all event sigs
         let eth = self.clone();
         let logger = logger.to_owned();
 
-        let event_sigs = log_filter
-            .contract_address_and_event_sig_pairs
+        // Events of specific contracts, events of any contract and anonymous
+        // events are requested separately, so that each request can filter
+        // on as much as possible: contract addresses and event signatures,
+        // event signatures only and contract addresses only, respectively.
+        let (anonymous, named): (Vec<_>, Vec<_>) = log_filter
+            .event_filters
             .iter()
-            .map(|(_addr, sig)| *sig)
-            .collect::<HashSet<H256>>()
+            .partition(|filter| filter.event_signature.is_none());
+        let (with_address, without_address): (Vec<_>, Vec<_>) = named
             .into_iter()
-            .collect::<Vec<H256>>();
-
-        // Collect all contract addresses; if we have a data source without a contract
-        // address, we can't add addresses to the filter because it would only match
-        // the contracts for which we _have_ addresses; therefore if we have a data source
-        // without a contract address, we perform a broader logs scan and filter out
-        // irrelevant events ourselves.
-        //
-        // Our own filtering is performed later when the events are passed to
-        // subgraphs and runtime hosts for processing:
-        // - At the top level in `BlockStreamContext::do_step`
-        // - At the subgraph level in `SubgraphInstance::matches_log`
-        // - At the data source level in `RuntimeHost::matches_log`
-        let addresses = if log_filter
-            .contract_address_and_event_sig_pairs
-            .iter()
-            .any(|(addr, _)| addr.is_none())
-        {
-            vec![]
-        } else {
-            log_filter
-                .contract_address_and_event_sig_pairs
-                .iter()
-                .map(|(addr, _sig)| match addr {
-                    None => unreachable!(
-                        "shouldn't include addresses in Ethereum logs filter \
-                         if there are data sources without a contract address"
-                    ),
-                    Some(addr) => *addr,
-                })
-                .collect::<HashSet<H160>>()
-                .into_iter()
-                .collect::<Vec<H160>>()
-        };
+            .partition(|filter| filter.contract_address.is_some());
+        let requests = vec![with_address, without_address, anonymous]
+            .into_iter()
+            .filter(|filters| !filters.is_empty())
+            .map(LogsRequest::from_event_filters)
+            .collect::<Vec<_>>();
 
-        let logger = logger.to_owned();
-        let step = match addresses.is_empty() {
+        let step = match requests.iter().any(|request| request.addresses.is_empty()) {
             // `to - from` is the size of the full range.
             false => to - from,
             true => (to - from).min(*MAX_EVENT_ONLY_RANGE),
@@ -286,18 +453,32 @@ where
                 let high = (low + step).min(to);
                 debug!(logger, "Requesting logs for blocks [{}, {}]", low, high);
                 chunk_futures.push(
-                    eth.logs_with_sigs(
-                        &logger,
-                        low,
-                        high,
-                        addresses.clone(),
-                        event_sigs.clone(),
-                        TOO_MANY_LOGS_FINGERPRINT,
+                    future::join_all(
+                        requests
+                            .iter()
+                            .map(|request| {
+                                eth.logs_with_sigs(
+                                    &logger,
+                                    low,
+                                    high,
+                                    request.clone(),
+                                    TOO_MANY_LOGS_FINGERPRINT,
+                                )
+                            })
+                            .collect::<Vec<_>>(),
                     )
                     .map(move |logs| {
-                        logs.into_iter()
+                        let mut logs = logs
+                            .into_iter()
+                            .flatten()
                             .filter(|log| log_filter.matches(log))
-                            .collect::<Vec<Log>>()
+                            .collect::<Vec<Log>>();
+
+                        // Restore the chain order of logs from different
+                        // requests and drop those returned more than once
+                        logs.sort_by_key(|log| (log.block_number, log.log_index));
+                        logs.dedup_by_key(|log| (log.block_number, log.log_index));
+                        logs
                     }),
                 );
                 low = high + 1;
@@ -339,7 +520,7 @@ where
         logger: &Logger,
         contract_address: Address,
         call_data: Bytes,
-        block_number_opt: Option<BlockNumber>,
+        block_hash: H256,
     ) -> impl Future<Item = Bytes, Error = EthereumContractCallError> + Send {
         let web3 = self.web3.clone();
         let logger = logger.clone();
@@ -381,7 +562,17 @@ where
                             value: None,
                             data: Some(call_data.clone()),
                         };
-                        web3.eth().call(req, block_number_opt).then(|result| {
+                        // Identify the block by its hash (EIP-1898) rather
+                        // than its number so that the call is never made
+                        // against a block that has since been reorged into
+                        // the chain at the same height
+                        let params = vec![
+                            web3::helpers::serialize(&req),
+                            serde_json::json!({ "blockHash": block_hash }),
+                        ];
+                        let call: CallFuture<Bytes, _> =
+                            CallFuture::new(web3.transport().execute("eth_call", params));
+                        call.then(|result| {
                             // Try to check if the call was reverted. The JSON-RPC response for
                             // reverts is not standardized, the current situation for the tested
                             // clients is:
@@ -863,6 +1054,15 @@ where
                 )));
             }
 
+            // Polling and `once` block handlers run on blocks known by number
+            let block_numbers = block_filter.block_numbers_in_range(from, to);
+            if !block_numbers.is_empty() {
+                block_futs.push(Box::new(
+                    eth.blocks_by_numbers(&logger, block_numbers)
+                        .map(|block_ptrs| block_ptrs.into_iter().collect()),
+                ));
+            }
+
             match block_filter.contract_addresses.len() {
                 0 => (),
                 _ => {
@@ -935,30 +1135,25 @@ where
         to: u64,
         log_filter: EthereumLogFilter,
     ) -> Box<dyn Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send> {
-        let eth = self.clone();
-        Box::new(
-            // Get a stream of all relevant logs in range
-            eth.log_stream(&logger, from, to, log_filter).map(|logs| {
-                let mut block_ptrs = vec![];
-                for log in logs.iter() {
-                    let hash = log
-                        .block_hash
-                        .expect("log from Eth node is missing block hash");
-                    let number = log
-                        .block_number
-                        .expect("log from Eth node is missing block number")
-                        .as_u64();
-                    let block_ptr = EthereumBlockPointer::from((hash, number));
-                    if !block_ptrs.contains(&block_ptr) {
-                        if let Some(prev) = block_ptrs.last() {
-                            assert!(prev.number < number);
+        // When scanning a single block, which is what happens once a subgraph
+        // has caught up with the chain head, check the block's log bloom
+        // first and skip `eth_getLogs` if no matching log can be in it
+        if from == to {
+            let eth = self.clone();
+            let logger = logger.clone();
+            return Box::new(self.logs_bloom_by_block_number(&logger, from).and_then(
+                move |bloom_opt| -> Box<dyn Future<Item = _, Error = _> + Send> {
+                    match bloom_opt {
+                        Some(bloom) if !log_filter.check_bloom(bloom) => {
+                            Box::new(future::ok(vec![]))
                         }
-                        block_ptrs.push(block_ptr);
+                        _ => eth.blocks_with_logs_in_range(&logger, from, to, log_filter),
                     }
-                }
-                block_ptrs
-            }),
-        )
+                },
+            ));
+        }
+
+        self.blocks_with_logs_in_range(&logger, from, to, log_filter)
     }
 
     fn blocks_with_calls(
@@ -1001,6 +1196,7 @@ where
         &self,
         logger: &Logger,
         call: EthereumContractCall,
+        cache: Arc<dyn EthereumCallCache>,
     ) -> Box<dyn Future<Item = Vec<Token>, Error = EthereumContractCallError> + Send> {
         // Emit custom error for type mismatches.
         for (token, kind) in call
@@ -1019,20 +1215,44 @@ where
         // Encode the call parameters according to the ABI
         let call_data = call.function.encode_input(&call.args).unwrap();
 
-        Box::new(
-            // Make the actual function call
-            self.call(
-                logger,
-                call.address,
-                Bytes(call_data),
-                Some(call.block_ptr.number.into()),
-            )
-            .and_then(move |output| {
-                // Decode the return values according to the ABI
-                call.function
-                    .decode_output(&output.0)
-                    .map_err(EthereumContractCallError::from)
-            }),
-        )
+        // Failing to read from or write to the cache is not fatal, we just
+        // fall back to making the call
+        let cached_output = cache
+            .get_call(call.address, &call_data, call.block_ptr)
+            .unwrap_or_else(|e| {
+                error!(logger, "Failed to read eth_call cache"; "error" => e.to_string());
+                None
+            });
+
+        let output: Box<dyn Future<Item = Vec<u8>, Error = EthereumContractCallError> + Send> =
+            match cached_output {
+                Some(output) => Box::new(future::ok(output)),
+                None => {
+                    let logger = logger.clone();
+                    let address = call.address;
+                    let block_ptr = call.block_ptr;
+
+                    Box::new(
+                        // Make the actual function call
+                        self.call(&logger, address, Bytes(call_data.clone()), block_ptr.hash)
+                            .map(move |output| {
+                                if let Err(e) =
+                                    cache.set_call(address, &call_data, block_ptr, &output.0)
+                                {
+                                    error!(logger, "Failed to write eth_call cache";
+                                       "error" => e.to_string());
+                                }
+                                output.0
+                            }),
+                    )
+                }
+            };
+
+        Box::new(output.and_then(move |output| {
+            // Decode the return values according to the ABI
+            call.function
+                .decode_output(&output)
+                .map_err(EthereumContractCallError::from)
+        }))
     }
 }
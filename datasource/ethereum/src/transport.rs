@@ -2,6 +2,8 @@ use graph::prelude::*;
 use jsonrpc_core::types::Call;
 use serde_json::Value;
 use std::env;
+use std::fmt;
+use std::time::Instant;
 
 use web3::transports::{http, ipc, ws};
 use web3::RequestId;
@@ -83,3 +85,173 @@ impl web3::BatchTransport for Transport {
         }
     }
 }
+
+/// Wrapper around a web3 transport that records the latency of every
+/// JSON-RPC request, labeled by the request method. Each request in a
+/// batch is recorded with the latency of the whole batch.
+#[derive(Clone)]
+pub(crate) struct MeteredTransport<T> {
+    inner: T,
+    request_duration: Box<HistogramVec>,
+}
+
+impl<T> MeteredTransport<T> {
+    pub fn new(inner: T, request_duration: Box<HistogramVec>) -> Self {
+        MeteredTransport {
+            inner,
+            request_duration,
+        }
+    }
+
+    fn histogram(&self, request: &Call) -> Histogram {
+        match request {
+            Call::MethodCall(call) => self.request_duration.with_label_values(&[&call.method]),
+            _ => self.request_duration.with_label_values(&["unknown"]),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MeteredTransport<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl<T: web3::Transport> web3::Transport for MeteredTransport<T> {
+    type Out = MeteredRequest<T::Out>;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        self.inner.prepare(method, params)
+    }
+
+    fn send(&self, id: RequestId, request: Call) -> Self::Out {
+        let histograms = vec![self.histogram(&request)];
+        MeteredRequest {
+            inner: self.inner.send(id, request),
+            histograms,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl<T: web3::BatchTransport> web3::BatchTransport for MeteredTransport<T> {
+    type Batch = MeteredRequest<T::Batch>;
+
+    fn send_batch<I>(&self, requests: I) -> Self::Batch
+    where
+        I: IntoIterator<Item = (RequestId, Call)>,
+    {
+        let requests: Vec<_> = requests.into_iter().collect();
+        let histograms = requests
+            .iter()
+            .map(|(_, request)| self.histogram(request))
+            .collect();
+        MeteredRequest {
+            inner: self.inner.send_batch(requests),
+            histograms,
+            start: Instant::now(),
+        }
+    }
+}
+
+/// A JSON-RPC request, or batch of requests, that records its duration
+/// once it completes.
+pub(crate) struct MeteredRequest<F> {
+    inner: F,
+    histograms: Vec<Histogram>,
+    start: Instant,
+}
+
+impl<F: Future> Future for MeteredRequest<F> {
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let result = self.inner.poll();
+        match result {
+            Ok(Async::NotReady) => (),
+            _ => {
+                let elapsed = self.start.elapsed().as_millis() as f64;
+                for histogram in &self.histograms {
+                    histogram.observe(elapsed);
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use web3::helpers::build_request;
+    use web3::{BatchTransport, Transport as _};
+
+    /// A transport that answers every request with `null`.
+    #[derive(Clone, Debug)]
+    struct NullTransport;
+
+    impl web3::Transport for NullTransport {
+        type Out = Box<dyn Future<Item = Value, Error = web3::error::Error> + Send>;
+
+        fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+            (1, build_request(1, method, params))
+        }
+
+        fn send(&self, _: RequestId, _: Call) -> Self::Out {
+            Box::new(future::ok(Value::Null))
+        }
+    }
+
+    impl web3::BatchTransport for NullTransport {
+        type Batch = Box<
+            dyn Future<Item = Vec<Result<Value, web3::error::Error>>, Error = web3::error::Error>
+                + Send,
+        >;
+
+        fn send_batch<T>(&self, requests: T) -> Self::Batch
+        where
+            T: IntoIterator<Item = (RequestId, Call)>,
+        {
+            let responses = requests.into_iter().map(|_| Ok(Value::Null)).collect();
+            Box::new(future::ok(responses))
+        }
+    }
+
+    fn metered_transport() -> MeteredTransport<NullTransport> {
+        let opts = HistogramOpts::new("test_request_duration_ms", "test");
+        let request_duration = HistogramVec::new(opts, &["method"]).unwrap();
+        MeteredTransport::new(NullTransport, Box::new(request_duration))
+    }
+
+    fn sample_count(transport: &MeteredTransport<NullTransport>, method: &str) -> u64 {
+        transport
+            .request_duration
+            .with_label_values(&[method])
+            .get_sample_count()
+    }
+
+    #[test]
+    fn send_records_request_duration() {
+        let transport = metered_transport();
+        let (id, request) = transport.prepare("eth_blockNumber", vec![]);
+        transport.send(id, request).wait().unwrap();
+
+        assert_eq!(sample_count(&transport, "eth_blockNumber"), 1);
+    }
+
+    #[test]
+    fn send_batch_records_request_duration() {
+        let transport = metered_transport();
+        let requests = vec![
+            transport.prepare("eth_getBlockByHash", vec![]),
+            transport.prepare("eth_getBlockByHash", vec![]),
+            transport.prepare("eth_getTransactionReceipt", vec![]),
+        ];
+        let responses = transport.send_batch(requests).wait().unwrap();
+
+        assert_eq!(responses.len(), 3);
+        assert_eq!(sample_count(&transport, "eth_getBlockByHash"), 2);
+        assert_eq!(sample_count(&transport, "eth_getTransactionReceipt"), 1);
+    }
+}
@@ -10,6 +10,7 @@ use graph::components::ethereum::EthereumContractCall;
 use graph::prelude::EthereumAdapter as EthereumAdapterTrait;
 use graph::prelude::*;
 use graph_datasource_ethereum::EthereumAdapter;
+use graph_mock::{FakeStore, MockMetricsRegistry};
 use web3::error::Error;
 use web3::helpers::*;
 use web3::types::*;
@@ -142,7 +143,12 @@ fn contract_call() {
     )));
 
     let logger = Logger::root(slog::Discard, o!());
-    let adapter = EthereumAdapter::new(transport, 0u64);
+    let adapter = EthereumAdapter::new(
+        transport,
+        0u64,
+        "fake_network",
+        Arc::new(MockMetricsRegistry::new()),
+    );
     let balance_of = Function {
         name: "balanceOf".to_owned(),
         inputs: vec![Param {
@@ -164,7 +170,10 @@ fn contract_call() {
         function: function,
         args: vec![Token::Address(holder_addr)],
     };
-    let call_result = adapter.contract_call(&logger, call).wait().unwrap();
+    let call_result = adapter
+        .contract_call(&logger, call, Arc::new(FakeStore))
+        .wait()
+        .unwrap();
 
     assert_eq!(call_result[0], Token::Uint(U256::from(100000)));
 }
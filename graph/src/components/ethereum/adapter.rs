@@ -2,7 +2,7 @@ use ethabi::{Bytes, Error as ABIError, Function, ParamType, Token};
 use failure::{Error, SyncFailure};
 use futures::Future;
 use slog::Logger;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::iter::FromIterator;
 use tiny_keccak::keccak256;
 use web3::types::*;
@@ -84,82 +84,197 @@ impl From<Error> for EthereumAdapterError {
     }
 }
 
+/// Matches the logs of a single event, optionally restricted to one contract
+/// address and to lists of accepted values for the indexed topics 1-3.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EthereumEventFilter {
+    pub contract_address: Option<Address>,
+    /// The event signature, which is the first topic of the event's logs.
+    /// Anonymous events have no signature and are matched by their
+    /// `topic_count` instead.
+    pub event_signature: Option<H256>,
+    /// If set, only logs with exactly this many topics match.
+    pub topic_count: Option<usize>,
+    /// Logs whose first topic is one of these signatures don't match. This
+    /// keeps the logs of the named events of a contract from matching a
+    /// filter for one of its anonymous events.
+    pub excluded_signatures: Vec<H256>,
+    pub topic1: Option<Vec<H256>>,
+    pub topic2: Option<Vec<H256>>,
+    pub topic3: Option<Vec<H256>>,
+}
+
+impl EthereumEventFilter {
+    /// The accepted values for topics 1-3; `None` accepts any value.
+    pub fn topics(&self) -> [&Option<Vec<H256>>; 3] {
+        [&self.topic1, &self.topic2, &self.topic3]
+    }
+
+    /// Check whether topics 1-3 of a log are among the accepted `values`
+    /// for each of them. Topics without a restriction match any value.
+    pub fn topics_match(values: [&Option<Vec<H256>>; 3], topics: &[H256]) -> bool {
+        values.iter().enumerate().all(|(i, values)| match values {
+            None => true,
+            Some(values) => topics
+                .get(i + 1)
+                .map_or(false, |topic| values.contains(topic)),
+        })
+    }
+
+    /// Check if log bloom filter indicates a possible match for this filter.
+    fn check_bloom(&self, bloom: &H2048) -> bool {
+        // Every log sets the bloom bits for its contract address and
+        // for each of its topics, the first of which is the event
+        // signature
+        self.event_signature
+            .map_or(true, |sig| bloom_contains(bloom, &sig.0))
+            && self
+                .contract_address
+                .map_or(true, |addr| bloom_contains(bloom, &addr.0))
+            && self.topics().iter().all(|values| match values {
+                None => true,
+                Some(values) => values.iter().any(|value| bloom_contains(bloom, &value.0)),
+            })
+    }
+
+    /// Check if this filter matches the specified `Log`.
+    pub fn matches(&self, log: &Log) -> bool {
+        // First topic should be event sig
+        self.event_signature
+            .map_or(true, |sig| log.topics.first() == Some(&sig))
+            && self
+                .topic_count
+                .map_or(true, |count| log.topics.len() == count)
+            && log
+                .topics
+                .first()
+                .map_or(true, |topic0| !self.excluded_signatures.contains(topic0))
+            && self
+                .contract_address
+                .map_or(true, |addr| addr == log.address)
+            && Self::topics_match(self.topics(), &log.topics)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct EthereumLogFilter {
-    pub contract_address_and_event_sig_pairs: HashSet<(Option<Address>, H256)>,
+    pub event_filters: HashSet<EthereumEventFilter>,
+    /// Whether some event handler needs the receipt of the transaction
+    /// that emitted its log; blocks then always need their receipts
+    pub receipts: bool,
 }
 
 impl EthereumLogFilter {
     /// Check if log bloom filter indicates a possible match for this log filter.
     /// Returns `true` to indicate that a matching `Log` _might_ be contained.
     /// Returns `false` to indicate that a matching `Log` _is not_ contained.
-    pub fn check_bloom(&self, _bloom: H2048) -> bool {
-        // TODO issue #352: implement bloom filter check
-        true // not even wrong
+    pub fn check_bloom(&self, bloom: H2048) -> bool {
+        self.event_filters
+            .iter()
+            .any(|filter| filter.check_bloom(&bloom))
     }
 
     /// Check if this filter matches the specified `Log`.
     pub fn matches(&self, log: &Log) -> bool {
-        // First topic should be event sig
-        match log.topics.first() {
-            None => false,
-            Some(sig) => self
-                .contract_address_and_event_sig_pairs
-                .iter()
-                .any(|pair| match pair {
-                    // The `Log` matches the filter either if the filter contains
-                    // a (contract address, event signature) pair that matches the
-                    // `Log`...
-                    (Some(addr), s) => addr == &log.address && s == sig,
-
-                    // ...or if the filter contains a pair with no contract address
-                    // but an event signature that matches the event
-                    (None, s) => s == sig,
-                }),
-        }
+        self.event_filters.iter().any(|filter| filter.matches(log))
     }
 
     pub fn from_data_sources<'a>(iter: impl IntoIterator<Item = &'a DataSource>) -> Self {
-        iter.into_iter()
-            .map(|data_source| {
-                let contract_addr = data_source.source.address;
-
+        let data_sources: Vec<_> = iter.into_iter().collect();
+        EthereumLogFilter {
+            receipts: data_sources.iter().any(|data_source| {
                 data_source
                     .mapping
                     .event_handlers
                     .iter()
-                    .map(move |event_handler| {
-                        let event_sig = event_handler.topic0();
-                        (contract_addr, event_sig)
-                    })
-            })
-            .flatten()
-            .collect()
+                    .any(|event_handler| event_handler.receipt)
+            }),
+            event_filters: data_sources
+                .into_iter()
+                .map(|data_source| {
+                    let contract_addr = data_source.source.address;
+                    let named_signatures = data_source
+                        .mapping
+                        .named_event_signatures(&data_source.source.abi);
+
+                    data_source
+                        .mapping
+                        .event_handlers
+                        .iter()
+                        .map(move |event_handler| EthereumEventFilter {
+                            contract_address: contract_addr,
+                            event_signature: if event_handler.anonymous {
+                                None
+                            } else {
+                                Some(event_handler.topic0())
+                            },
+                            topic_count: if event_handler.anonymous {
+                                Some(event_handler.indexed_param_count())
+                            } else {
+                                None
+                            },
+                            excluded_signatures: if event_handler.anonymous {
+                                named_signatures.clone()
+                            } else {
+                                vec![]
+                            },
+                            topic1: event_handler.topic1.clone(),
+                            topic2: event_handler.topic2.clone(),
+                            topic3: event_handler.topic3.clone(),
+                        })
+                })
+                .flatten()
+                .collect(),
+        }
     }
 
     /// Extends this log filter with another one.
     pub fn extend(&mut self, other: EthereumLogFilter) {
-        self.contract_address_and_event_sig_pairs
-            .extend(other.contract_address_and_event_sig_pairs.iter());
+        self.event_filters.extend(other.event_filters.into_iter());
+        self.receipts |= other.receipts;
     }
 
     /// An empty filter is one that never matches.
     pub fn is_empty(&self) -> bool {
         // Destructure to make sure we're checking all fields.
         let EthereumLogFilter {
-            contract_address_and_event_sig_pairs,
+            event_filters,
+            receipts: _,
         } = self;
-        contract_address_and_event_sig_pairs.is_empty()
+        event_filters.is_empty()
     }
 }
 
+/// Check whether the bits for `input` are set in a log bloom filter. Each
+/// input sets three bits, taken from the low 11 bits of the first three
+/// pairs of bytes of its Keccak-256 hash.
+fn bloom_contains(bloom: &H2048, input: &[u8]) -> bool {
+    let hash = keccak256(input);
+    (0..3).all(|i| {
+        let bit = (((hash[2 * i] as usize) << 8) | hash[2 * i + 1] as usize) & 2047;
+        bloom.0[255 - bit / 8] & (1 << (bit % 8)) != 0
+    })
+}
+
 impl FromIterator<(Option<Address>, H256)> for EthereumLogFilter {
     fn from_iter<I>(iter: I) -> Self
     where
         I: IntoIterator<Item = (Option<Address>, H256)>,
     {
         EthereumLogFilter {
-            contract_address_and_event_sig_pairs: iter.into_iter().collect(),
+            event_filters: iter
+                .into_iter()
+                .map(|(contract_address, event_signature)| EthereumEventFilter {
+                    contract_address,
+                    event_signature: Some(event_signature),
+                    topic_count: None,
+                    excluded_signatures: vec![],
+                    topic1: None,
+                    topic2: None,
+                    topic3: None,
+                })
+                .collect(),
+            receipts: false,
         }
     }
 }
@@ -267,6 +382,10 @@ impl From<EthereumBlockFilter> for EthereumCallFilter {
 pub struct EthereumBlockFilter {
     pub contract_addresses: HashSet<Address>,
     pub trigger_every_block: bool,
+    /// `(start block, interval)` pairs of data sources with polling block handlers.
+    pub polling_intervals: HashSet<(u64, u64)>,
+    /// Start blocks of data sources with `once` block handlers.
+    pub once_blocks: HashSet<u64>,
 }
 
 impl EthereumBlockFilter {
@@ -291,8 +410,31 @@ impl EthereumBlockFilter {
                     .into_iter()
                     .any(|block_handler| block_handler.filter.is_none());
 
+                let start_block = data_source.source.start_block;
+                let polling_intervals = data_source
+                    .mapping
+                    .block_handlers
+                    .iter()
+                    .filter_map(|block_handler| match block_handler.filter {
+                        Some(BlockHandlerFilter::Polling { every }) => Some((start_block, every)),
+                        _ => None,
+                    })
+                    .collect();
+
+                let has_block_handler_with_once_filter = data_source
+                    .mapping
+                    .block_handlers
+                    .iter()
+                    .any(|block_handler| block_handler.filter == Some(BlockHandlerFilter::Once));
+
                 filter_opt.extend(Self {
                     trigger_every_block: has_block_handler_without_filter,
+                    polling_intervals,
+                    once_blocks: if has_block_handler_with_once_filter {
+                        vec![start_block].into_iter().collect()
+                    } else {
+                        HashSet::default()
+                    },
                     contract_addresses: if has_block_handler_with_call_filter {
                         vec![data_source.source.address.unwrap().to_owned()]
                             .into_iter()
@@ -308,6 +450,48 @@ impl EthereumBlockFilter {
     pub fn extend(&mut self, other: EthereumBlockFilter) {
         self.trigger_every_block = self.trigger_every_block || other.trigger_every_block;
         self.contract_addresses.extend(other.contract_addresses);
+        self.polling_intervals.extend(other.polling_intervals);
+        self.once_blocks.extend(other.once_blocks);
+    }
+
+    /// Check if a polling block handler is due on the block with the given number.
+    pub fn matches_polling(&self, block_number: u64) -> bool {
+        self.polling_intervals.iter().any(|(start_block, every)| {
+            BlockHandlerFilter::Polling { every: *every }
+                .matches_block_number(*start_block, block_number)
+        })
+    }
+
+    /// Check if a `once` block handler runs on the block with the given number.
+    pub fn matches_once(&self, block_number: u64) -> bool {
+        self.once_blocks.contains(&block_number)
+    }
+
+    /// Returns the numbers of all blocks in `[from, to]` on which polling or
+    /// `once` block handlers run, in ascending order.
+    pub fn block_numbers_in_range(&self, from: u64, to: u64) -> Vec<u64> {
+        let mut numbers = BTreeSet::new();
+        for (start_block, every) in self.polling_intervals.iter() {
+            if *every == 0 {
+                continue;
+            }
+            // First block at or after `from` on which the handler is due
+            let mut number = if from <= *start_block {
+                *start_block
+            } else {
+                start_block + (from - start_block + every - 1) / every * every
+            };
+            while number <= to {
+                numbers.insert(number);
+                number += every;
+            }
+        }
+        numbers.extend(
+            self.once_blocks
+                .iter()
+                .filter(|number| from <= **number && **number <= to),
+        );
+        numbers.into_iter().collect()
     }
 }
 
@@ -433,10 +617,180 @@ pub trait EthereumAdapter: Send + Sync + 'static {
         to: u64,
     ) -> Box<dyn Future<Item = Vec<EthereumBlockPointer>, Error = Error> + Send>;
 
-    /// Call the function of a smart contract.
+    /// Call the function of a smart contract. Return values are looked up
+    /// in and written to `cache`.
     fn contract_call(
         &self,
         logger: &Logger,
         call: EthereumContractCall,
+        cache: Arc<dyn EthereumCallCache>,
     ) -> Box<dyn Future<Item = Vec<Token>, Error = EthereumContractCallError> + Send>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{EthereumBlockFilter, EthereumEventFilter, EthereumLogFilter};
+    use serde_json::json;
+    use std::str::FromStr;
+    use web3::types::{Address, Log, H2048, H256};
+
+    /// Bloom for a single log emitted by
+    /// `0xef2d6d194084c2de36e0dabfce45d046b37d1106` with topic
+    /// `0x02c69be41d0b7e40352fc85be1cd65eb03d40ef8427a0ca4596b1ead9a00e9fc`.
+    fn bloom() -> H2048 {
+        let mut bloom = [0u8; 256];
+        for (byte, mask) in &[(155, 16), (123, 8), (20, 16), (128, 16), (100, 2), (99, 2)] {
+            bloom[*byte] |= mask;
+        }
+        H2048::from(bloom)
+    }
+
+    fn address() -> Address {
+        Address::from_str("ef2d6d194084c2de36e0dabfce45d046b37d1106").unwrap()
+    }
+
+    fn event_sig() -> H256 {
+        H256::from_str("02c69be41d0b7e40352fc85be1cd65eb03d40ef8427a0ca4596b1ead9a00e9fc").unwrap()
+    }
+
+    #[test]
+    fn check_bloom_matches_address_and_event_sig() {
+        let filter: EthereumLogFilter = vec![(Some(address()), event_sig())].into_iter().collect();
+        assert!(filter.check_bloom(bloom()));
+    }
+
+    #[test]
+    fn check_bloom_matches_event_sig_without_address() {
+        let filter: EthereumLogFilter = vec![(None, event_sig())].into_iter().collect();
+        assert!(filter.check_bloom(bloom()));
+    }
+
+    #[test]
+    fn check_bloom_rejects_other_address() {
+        let other = Address::from_str("0000000000000000000000000000000000000001").unwrap();
+        let filter: EthereumLogFilter = vec![(Some(other), event_sig())].into_iter().collect();
+        assert!(!filter.check_bloom(bloom()));
+    }
+
+    #[test]
+    fn check_bloom_rejects_empty_bloom_and_empty_filter() {
+        let filter: EthereumLogFilter = vec![(None, event_sig())].into_iter().collect();
+        assert!(!filter.check_bloom(H2048::zero()));
+
+        let empty: EthereumLogFilter = vec![].into_iter().collect();
+        assert!(!empty.check_bloom(bloom()));
+    }
+
+    fn topic(n: u8) -> H256 {
+        H256::from([n; 32])
+    }
+
+    fn event_filter(topic1: Option<Vec<H256>>, topic2: Option<Vec<H256>>) -> EthereumEventFilter {
+        EthereumEventFilter {
+            contract_address: Some(address()),
+            event_signature: Some(event_sig()),
+            topic_count: None,
+            excluded_signatures: vec![],
+            topic1,
+            topic2,
+            topic3: None,
+        }
+    }
+
+    fn log(topics: Vec<H256>) -> Log {
+        serde_json::from_value(json!({
+            "address": address(),
+            "topics": topics,
+            "data": "0x",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn check_bloom_rejects_unset_topic_values() {
+        // The bloom only contains the address and the event signature
+        let filter = EthereumLogFilter {
+            event_filters: vec![event_filter(Some(vec![topic(1)]), None)]
+                .into_iter()
+                .collect(),
+            receipts: false,
+        };
+        assert!(!filter.check_bloom(bloom()));
+
+        let filter = EthereumLogFilter {
+            event_filters: vec![event_filter(None, None)].into_iter().collect(),
+            receipts: false,
+        };
+        assert!(filter.check_bloom(bloom()));
+    }
+
+    #[test]
+    fn matches_restricts_indexed_topics() {
+        let filter = event_filter(Some(vec![topic(1), topic(2)]), Some(vec![topic(3)]));
+
+        assert!(filter.matches(&log(vec![event_sig(), topic(1), topic(3)])));
+        assert!(filter.matches(&log(vec![event_sig(), topic(2), topic(3)])));
+        assert!(!filter.matches(&log(vec![event_sig(), topic(3), topic(3)])));
+        assert!(!filter.matches(&log(vec![event_sig(), topic(1), topic(4)])));
+
+        // Logs without the restricted topics don't match
+        assert!(!filter.matches(&log(vec![event_sig(), topic(1)])));
+
+        // Unrestricted topics match any value
+        let filter = event_filter(None, Some(vec![topic(3)]));
+        assert!(filter.matches(&log(vec![event_sig(), topic(5), topic(3)])));
+        assert!(!filter.matches(&log(vec![topic(1), topic(5), topic(3)])));
+    }
+
+    #[test]
+    fn matches_anonymous_events_by_topic_count() {
+        let filter = EthereumEventFilter {
+            contract_address: Some(address()),
+            event_signature: None,
+            topic_count: Some(2),
+            excluded_signatures: vec![event_sig()],
+            topic1: None,
+            topic2: None,
+            topic3: None,
+        };
+
+        assert!(filter.matches(&log(vec![topic(1), topic(2)])));
+        assert!(!filter.matches(&log(vec![topic(1)])));
+        assert!(!filter.matches(&log(vec![event_sig(), topic(1), topic(2)])));
+        // Logs of a named event with one indexed parameter
+        assert!(!filter.matches(&log(vec![event_sig(), topic(1)])));
+
+        // Only the contract address is checked against the bloom
+        let filter = EthereumLogFilter {
+            event_filters: vec![filter].into_iter().collect(),
+            receipts: false,
+        };
+        assert!(filter.check_bloom(bloom()));
+    }
+
+    #[test]
+    fn block_filter_polling_and_once() {
+        let filter = EthereumBlockFilter {
+            polling_intervals: vec![(100, 10), (105, 25)].into_iter().collect(),
+            once_blocks: vec![103].into_iter().collect(),
+            ..EthereumBlockFilter::default()
+        };
+
+        assert!(!filter.matches_polling(90));
+        assert!(filter.matches_polling(100));
+        assert!(!filter.matches_polling(101));
+        assert!(filter.matches_polling(130));
+        assert!(filter.matches_polling(105));
+        assert!(filter.matches_polling(155));
+
+        assert!(filter.matches_once(103));
+        assert!(!filter.matches_once(100));
+
+        assert_eq!(filter.block_numbers_in_range(0, 99), Vec::<u64>::new());
+        assert_eq!(
+            filter.block_numbers_in_range(101, 135),
+            vec![103, 105, 110, 120, 130]
+        );
+        assert_eq!(filter.block_numbers_in_range(150, 160), vec![150, 155, 160]);
+    }
+}
@@ -6,13 +6,13 @@ mod types;
 pub use self::adapter::{
     EthereumAdapter, EthereumAdapterError, EthereumBlockFilter, EthereumCallFilter,
     EthereumContractCall, EthereumContractCallError, EthereumContractState,
-    EthereumContractStateError, EthereumContractStateRequest, EthereumLogFilter,
-    EthereumNetworkIdentifier,
+    EthereumContractStateError, EthereumContractStateRequest, EthereumEventFilter,
+    EthereumLogFilter, EthereumNetworkIdentifier,
 };
 pub use self::listener::{ChainHeadUpdate, ChainHeadUpdateListener, ChainHeadUpdateStream};
 pub use self::stream::{BlockStream, BlockStreamBuilder};
 pub use self::types::{
     EthereumBlock, EthereumBlockData, EthereumBlockPointer, EthereumBlockTriggerType,
     EthereumBlockWithCalls, EthereumBlockWithTriggers, EthereumCall, EthereumCallData,
-    EthereumEventData, EthereumTransactionData, EthereumTrigger,
+    EthereumEventData, EthereumTransactionData, EthereumTransactionReceiptData, EthereumTrigger,
 };
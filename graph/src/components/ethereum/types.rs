@@ -125,6 +125,11 @@ pub enum EthereumTrigger {
 pub enum EthereumBlockTriggerType {
     Every,
     WithCallTo(Address),
+    /// Polling block handlers may be due on the block with this number.
+    Polling(u64),
+    /// Data sources with `once` block handlers may start at the block with
+    /// this number.
+    Once(u64),
 }
 
 impl EthereumTrigger {
@@ -218,6 +223,36 @@ impl<'a> From<&'a Transaction> for EthereumTransactionData {
     }
 }
 
+/// Ethereum transaction receipt data.
+#[derive(Clone, Debug)]
+pub struct EthereumTransactionReceiptData {
+    pub transaction_hash: H256,
+    pub transaction_index: U128,
+    pub block_hash: Option<H256>,
+    pub block_number: Option<U256>,
+    pub cumulative_gas_used: U256,
+    pub gas_used: Option<U256>,
+    pub contract_address: Option<H160>,
+    pub status: Option<u64>,
+    pub logs: Vec<Log>,
+}
+
+impl<'a> From<&'a TransactionReceipt> for EthereumTransactionReceiptData {
+    fn from(receipt: &'a TransactionReceipt) -> EthereumTransactionReceiptData {
+        EthereumTransactionReceiptData {
+            transaction_hash: receipt.transaction_hash,
+            transaction_index: receipt.transaction_index,
+            block_hash: receipt.block_hash,
+            block_number: receipt.block_number,
+            cumulative_gas_used: receipt.cumulative_gas_used,
+            gas_used: receipt.gas_used,
+            contract_address: receipt.contract_address,
+            status: receipt.status.map(|status| status.as_u64()),
+            logs: receipt.logs.clone(),
+        }
+    }
+}
+
 /// An Ethereum event logged from a specific contract address and block.
 #[derive(Debug)]
 pub struct EthereumEventData {
@@ -228,6 +263,8 @@ pub struct EthereumEventData {
     pub block: EthereumBlockData,
     pub transaction: EthereumTransactionData,
     pub params: Vec<LogParam>,
+    /// Only set for handlers that ask for receipts in the manifest.
+    pub receipt: Option<EthereumTransactionReceiptData>,
 }
 
 impl Clone for EthereumEventData {
@@ -247,6 +284,7 @@ impl Clone for EthereumEventData {
                     value: log_param.value.clone(),
                 })
                 .collect(),
+            receipt: self.receipt.clone(),
         }
     }
 }
@@ -1,13 +1,42 @@
-use failure;
+use failure::{self, Fail};
 use serde_json::Value;
 use slog::Logger;
 use tokio::prelude::*;
 
 use crate::data::subgraph::Link;
 
+/// The error that a `LinkResolver` returns when a request times out. Whether
+/// a request times out depends on the IPFS node and the network rather than
+/// on the file that was requested, so a timeout must never be treated as a
+/// property of the file.
+#[derive(Fail, Debug)]
+#[fail(display = "IPFS request for `{}` timed out", _0)]
+pub struct LinkResolverTimeout(pub String);
+
+/// The error that a `LinkResolver` returns when a link does not refer to a
+/// file, for example because it is not a valid IPFS path or because it is a
+/// directory. Unlike all other errors, this only depends on the link, and
+/// every IPFS node reports it in the same way.
+#[derive(Fail, Debug)]
+#[fail(display = "`{}` is not an IPFS file: {}", _0, _1)]
+pub struct LinkResolverInvalidFile(pub String, pub String);
+
+/// The formats of files that `json_stream` can read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JsonStreamFormat {
+    /// One JSON value per line.
+    JsonLines,
+    /// A single JSON array; each of its elements is one value.
+    JsonArray,
+    /// CSV with a header row; each record becomes a JSON object that maps
+    /// the column names from the header row to the record's fields.
+    Csv,
+}
+
 /// The values that `json_stream` returns. The struct contains the deserialized
 /// JSON value from the input stream, together with the line number from which
-/// the value was read.
+/// the value was read. For JSON arrays, `line` is the position of the element
+/// in the array instead.
 pub struct JsonStreamValue {
     pub value: Value,
     pub line: usize,
@@ -26,11 +55,13 @@ pub trait LinkResolver: Send + Sync + 'static + Sized {
     ) -> Box<dyn Future<Item = Vec<u8>, Error = failure::Error> + Send>;
 
     /// Read the contents of `link` and deserialize them into a stream of JSON
-    /// values. The values must each be on a single line; newlines are significant
-    /// as they are used to split the file contents and each line is deserialized
-    /// separately.
+    /// values. How the file contents are split into values depends on
+    /// `format`. For `JsonLines`, the values must each be on a single line;
+    /// newlines are significant as they are used to split the file contents
+    /// and each line is deserialized separately.
     fn json_stream(
         &self,
         link: &Link,
+        format: JsonStreamFormat,
     ) -> Box<dyn Future<Item = JsonValueStream, Error = failure::Error> + Send + 'static>;
 }
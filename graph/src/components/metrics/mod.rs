@@ -0,0 +1,72 @@
+pub use prometheus::core::Collector;
+pub use prometheus::{
+    Counter, Gauge, Histogram, HistogramOpts, HistogramVec, Opts, PrometheusError, Registry,
+};
+use std::collections::HashMap;
+
+/// A registry that metrics are added to so they can be exported, e.g. by a
+/// `MetricsServer`.
+///
+/// Metrics with the same name may be registered more than once as long as
+/// their constant labels differ; this is how e.g. RPC latency is tracked
+/// separately for each Ethereum network.
+pub trait MetricsRegistry: Send + Sync + 'static {
+    /// Adds a collector to the registry. Failures to register a collector
+    /// are not fatal; the metric is still usable but will not be exported.
+    fn register(&self, name: &str, collector: Box<dyn Collector>);
+
+    fn new_gauge(
+        &self,
+        name: &str,
+        help: &str,
+        const_labels: HashMap<String, String>,
+    ) -> Result<Box<Gauge>, PrometheusError> {
+        let opts = Opts::new(name, help).const_labels(const_labels);
+        let gauge = Gauge::with_opts(opts)?;
+        self.register(name, Box::new(gauge.clone()));
+        Ok(Box::new(gauge))
+    }
+
+    fn new_counter(
+        &self,
+        name: &str,
+        help: &str,
+        const_labels: HashMap<String, String>,
+    ) -> Result<Box<Counter>, PrometheusError> {
+        let opts = Opts::new(name, help).const_labels(const_labels);
+        let counter = Counter::with_opts(opts)?;
+        self.register(name, Box::new(counter.clone()));
+        Ok(Box::new(counter))
+    }
+
+    fn new_histogram(
+        &self,
+        name: &str,
+        help: &str,
+        const_labels: HashMap<String, String>,
+        buckets: Vec<f64>,
+    ) -> Result<Box<Histogram>, PrometheusError> {
+        let opts = HistogramOpts::new(name, help)
+            .const_labels(const_labels)
+            .buckets(buckets);
+        let histogram = Histogram::with_opts(opts)?;
+        self.register(name, Box::new(histogram.clone()));
+        Ok(Box::new(histogram))
+    }
+
+    fn new_histogram_vec(
+        &self,
+        name: &str,
+        help: &str,
+        const_labels: HashMap<String, String>,
+        variable_labels: &[&str],
+        buckets: Vec<f64>,
+    ) -> Result<Box<HistogramVec>, PrometheusError> {
+        let opts = HistogramOpts::new(name, help)
+            .const_labels(const_labels)
+            .buckets(buckets);
+        let histograms = HistogramVec::new(opts, variable_labels)?;
+        self.register(name, Box::new(histograms.clone()));
+        Ok(Box::new(histograms))
+    }
+}
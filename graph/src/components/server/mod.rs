@@ -9,3 +9,6 @@ pub mod admin;
 
 /// Component for the index node server.
 pub mod index_node;
+
+/// Component for the Prometheus metrics server.
+pub mod metrics;
@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fmt;
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
@@ -26,6 +27,14 @@ lazy_static! {
             .unwrap_or(Duration::from_millis(1000));
 }
 
+/// The type we use for block numbers. This has to be a signed integer type
+/// since Postgres does not support unsigned integer types. But 2G ought to
+/// be enough for everybody
+pub type BlockNumber = i32;
+
+/// The block number to use when we want the latest version of entities
+pub const BLOCK_NUMBER_MAX: BlockNumber = std::i32::MAX;
+
 /// Key by which an individual entity in the store can be accessed.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct EntityKey {
@@ -58,6 +67,20 @@ pub enum EntityFilter {
     NotStartsWith(Attribute, Value),
     EndsWith(Attribute, Value),
     NotEndsWith(Attribute, Value),
+    /// Full-text search; the attribute is the name of a search declared
+    /// with a `@fulltext` directive and the value the search query
+    Fulltext(Attribute, Value),
+    /// Matches entities that are linked to at least one entity of one of
+    /// the `entity_types` that matches `filter`. Unless the link is
+    /// `derived`, `attribute` is the field of the entity that holds the
+    /// id(s) of the linked entities; for derived links, it is the field of
+    /// the linked entities that holds the id(s) of the entity
+    Child {
+        attribute: Attribute,
+        entity_types: Vec<String>,
+        filter: Box<EntityFilter>,
+        derived: bool,
+    },
 }
 
 // Define some convenience methods
@@ -78,6 +101,18 @@ impl EntityFilter {
             attribute_values.into_iter().map(Into::into).collect(),
         )
     }
+
+    /// Combine this filter with `filter` so that only entities that match
+    /// both pass.
+    pub fn and(self, filter: EntityFilter) -> Self {
+        match self {
+            EntityFilter::And(mut filters) => {
+                filters.push(filter);
+                EntityFilter::And(filters)
+            }
+            other => EntityFilter::And(vec![other, filter]),
+        }
+    }
 }
 
 /// The order in which entities should be restored from a store.
@@ -128,6 +163,18 @@ pub struct EntityQuery {
 
     /// A range to limit the size of the result.
     pub range: EntityRange,
+
+    /// The block at which to run the query. Entities are returned as they
+    /// were after that block was processed; `BLOCK_NUMBER_MAX` returns the
+    /// latest version of each entity.
+    pub block: BlockNumber,
+
+    /// The id of an entity that the query should continue after. Only
+    /// entities that come after this entity in the order of the query, i.e.
+    /// by the `order_by` attribute and then by `id`, are returned. Unlike
+    /// `range.skip`, this does not get slower the further a query pages
+    /// into the result.
+    pub after: Option<String>,
 }
 
 impl EntityQuery {
@@ -143,6 +190,8 @@ impl EntityQuery {
             order_by: None,
             order_direction: None,
             range,
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         }
     }
 
@@ -161,6 +210,199 @@ impl EntityQuery {
         self.range = range;
         self
     }
+
+    pub fn at_block(mut self, block: BlockNumber) -> Self {
+        self.block = block;
+        self
+    }
+
+    pub fn after(mut self, id: String) -> Self {
+        self.after = Some(id);
+        self
+    }
+}
+
+/// How the entities returned by `Store::find_children` are linked to their
+/// parents.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EntityLink {
+    /// The children hold the id of their parent in `attribute`; this is the
+    /// case for fields with a `@derivedFrom` directive. If `list` is set,
+    /// the attribute holds a list of parent ids.
+    Derived { attribute: Attribute, list: bool },
+    /// The parents hold the ids of their children; `child_ids` contains
+    /// the ids referenced by each parent, in the same order as the parents.
+    Direct { child_ids: Vec<Vec<String>> },
+}
+
+impl EntityLink {
+    /// The filter that matches the children of the parent with id
+    /// `parent_id`, which is the `index`-th parent of a batch. Returns
+    /// `None` if the parent has no children.
+    pub fn filter(&self, index: usize, parent_id: &str) -> Option<EntityFilter> {
+        match self {
+            EntityLink::Derived { attribute, list } => {
+                let parent_id = Value::from(parent_id);
+                Some(if *list {
+                    EntityFilter::Contains(attribute.clone(), Value::List(vec![parent_id]))
+                } else {
+                    EntityFilter::Equal(attribute.clone(), parent_id)
+                })
+            }
+            EntityLink::Direct { child_ids } => {
+                let child_ids = &child_ids[index];
+                if child_ids.is_empty() {
+                    None
+                } else {
+                    Some(EntityFilter::Or(
+                        child_ids
+                            .iter()
+                            .map(|id| EntityFilter::Equal("id".to_owned(), Value::from(id)))
+                            .collect(),
+                    ))
+                }
+            }
+        }
+    }
+}
+
+/// An aggregate over all entities that match an `EntityQuery`; see
+/// `Store::aggregate`. Like their SQL counterparts, all aggregates except
+/// `Count` ignore entities that do not have a value for the attribute, and
+/// are `Value::Null` if no entity has one.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EntityAggregate {
+    /// The number of entities, as a `BigInt`.
+    Count,
+    /// The sum of the attribute; a `BigInt` for `Int` and `BigInt`
+    /// attributes, and a `BigDecimal` for `BigDecimal` attributes.
+    Sum(Attribute),
+    /// The smallest value of the attribute.
+    Min(Attribute),
+    /// The largest value of the attribute.
+    Max(Attribute),
+    /// The average of the attribute, as a `BigDecimal`.
+    Avg(Attribute),
+}
+
+impl EntityAggregate {
+    /// The attribute that is aggregated, or `None` for `Count`.
+    pub fn attribute(&self) -> Option<&Attribute> {
+        match self {
+            EntityAggregate::Count => None,
+            EntityAggregate::Sum(attribute)
+            | EntityAggregate::Min(attribute)
+            | EntityAggregate::Max(attribute)
+            | EntityAggregate::Avg(attribute) => Some(attribute),
+        }
+    }
+
+    /// Compute the aggregate over `entities` in memory.
+    pub fn compute(&self, entities: &[Entity]) -> Result<Value, QueryExecutionError> {
+        let attribute = match self.attribute() {
+            Some(attribute) => attribute,
+            None => return Ok(Value::BigInt(BigInt::from(entities.len() as u64))),
+        };
+
+        let values = entities
+            .iter()
+            .filter_map(|entity| entity.get(attribute))
+            .filter(|value| **value != Value::Null)
+            .map(|value| Self::to_big_decimal(attribute, value).map(|decimal| (value, decimal)))
+            .collect::<Result<Vec<_>, _>>()?;
+        if values.is_empty() {
+            return Ok(Value::Null);
+        }
+
+        Ok(match self {
+            EntityAggregate::Count => unreachable!("count does not have an attribute"),
+            EntityAggregate::Sum(_) => match values[0].0 {
+                Value::BigDecimal(_) => Value::BigDecimal(values.into_iter().fold(
+                    BigInt::from(0).to_big_decimal(0.into()),
+                    |sum, (_, decimal)| sum + decimal,
+                )),
+                _ => Value::BigInt(values.into_iter().fold(BigInt::from(0), |sum, (value, _)| {
+                    match value {
+                        Value::Int(i) => sum + BigInt::from(*i),
+                        Value::BigInt(i) => sum + i.clone(),
+                        _ => sum,
+                    }
+                })),
+            },
+            EntityAggregate::Min(_) => values
+                .into_iter()
+                .min_by(|(_, a), (_, b)| a.cmp(b))
+                .map(|(value, _)| value.clone())
+                .unwrap(),
+            EntityAggregate::Max(_) => values
+                .into_iter()
+                .max_by(|(_, a), (_, b)| a.cmp(b))
+                .map(|(value, _)| value.clone())
+                .unwrap(),
+            EntityAggregate::Avg(_) => {
+                let count = BigInt::from(values.len() as u64).to_big_decimal(0.into());
+                let sum = values.into_iter().fold(
+                    BigInt::from(0).to_big_decimal(0.into()),
+                    |sum, (_, decimal)| sum + decimal,
+                );
+                Value::BigDecimal(sum / count)
+            }
+        })
+    }
+
+    fn to_big_decimal(attribute: &str, value: &Value) -> Result<BigDecimal, QueryExecutionError> {
+        match value {
+            Value::Int(i) => Ok(BigInt::from(*i).to_big_decimal(0.into())),
+            Value::BigInt(i) => Ok(i.clone().to_big_decimal(0.into())),
+            Value::BigDecimal(d) => Ok(d.clone()),
+            _ => Err(QueryExecutionError::AttributeTypeError(
+                attribute.to_owned(),
+                "numeric".to_owned(),
+            )),
+        }
+    }
+
+    /// Convert the text form of an aggregate that the database computed
+    /// into a `Value` of the type promised above. `value_type` is the type
+    /// of the aggregated attribute, and `None` for `Count`.
+    pub fn value_from_text(
+        &self,
+        text: &str,
+        value_type: Option<ValueType>,
+    ) -> Result<Value, Error> {
+        // Postgres computes averages with a fixed number of decimal
+        // places; drop the trailing zeros that leaves behind
+        let text = match self {
+            EntityAggregate::Avg(_) if text.contains('.') => {
+                text.trim_end_matches('0').trim_end_matches('.')
+            }
+            _ => text,
+        };
+        let value_type = match (self, value_type) {
+            (EntityAggregate::Count, _) => ValueType::BigInt,
+            (EntityAggregate::Avg(_), _)
+            | (EntityAggregate::Sum(_), Some(ValueType::BigDecimal)) => ValueType::BigDecimal,
+            (EntityAggregate::Sum(_), _) => ValueType::BigInt,
+            (_, Some(value_type)) => value_type,
+            (_, None) => return Err(format_err!("the type of {:?} is not known", self)),
+        };
+        match value_type {
+            ValueType::Int => i32::from_str(text)
+                .map(Value::Int)
+                .map_err(|e| format_err!("{}", e)),
+            ValueType::BigInt => BigInt::from_str(text)
+                .map(Value::BigInt)
+                .map_err(|e| format_err!("{}", e)),
+            ValueType::BigDecimal => BigDecimal::from_str(text)
+                .map(Value::BigDecimal)
+                .map_err(|e| format_err!("{}", e)),
+            _ => Err(format_err!(
+                "{:?} attributes can not be aggregated",
+                value_type
+            )),
+        }
+        .map_err(|e| format_err!("invalid value {} for aggregate {:?}: {}", text, self, e))
+    }
 }
 
 /// Operation types that lead to entity changes.
@@ -633,10 +875,92 @@ pub trait Store: Send + Sync + 'static {
     /// Queries the store for a single entity matching the store query.
     fn find_one(&self, query: EntityQuery) -> Result<Option<Entity>, QueryExecutionError>;
 
+    /// Queries the store for the children of all `parent_ids` at once. The
+    /// filter, order, range and cursor of `query` apply to the children of
+    /// each parent separately, as if `find` had been called once per parent.
+    /// Returns the children of each parent that has any, keyed by the id of
+    /// the parent and in the order requested by the query.
+    ///
+    /// The default implementation does exactly that; stores should override
+    /// it with something that needs fewer round trips.
+    fn find_children(
+        &self,
+        query: EntityQuery,
+        parent_ids: Vec<String>,
+        link: EntityLink,
+    ) -> Result<HashMap<String, Vec<Entity>>, QueryExecutionError> {
+        let mut children = HashMap::new();
+        for (index, parent_id) in parent_ids.into_iter().enumerate() {
+            let filter = match link.filter(index, &parent_id) {
+                Some(filter) => filter,
+                None => continue,
+            };
+            let mut query = query.clone();
+            query.filter = Some(match query.filter.take() {
+                Some(query_filter) => query_filter.and(filter),
+                None => filter,
+            });
+            let entities = self.find(query)?;
+            if !entities.is_empty() {
+                children.insert(parent_id, entities);
+            }
+        }
+        Ok(children)
+    }
+
+    /// Computes `aggregates` over all entities that match the filter of
+    /// `query`; the order and range of `query` are ignored. Returns one value
+    /// for each aggregate, in the same order.
+    ///
+    /// The default implementation loads all matching entities and computes
+    /// the aggregates in memory; stores should override it with something
+    /// that does not need to load the entities.
+    fn aggregate(
+        &self,
+        mut query: EntityQuery,
+        aggregates: Vec<EntityAggregate>,
+    ) -> Result<Vec<Value>, QueryExecutionError> {
+        query.order_by = None;
+        query.order_direction = None;
+        query.range = EntityRange {
+            first: None,
+            skip: 0,
+        };
+        query.after = None;
+        let entities = self.find(query)?;
+        aggregates
+            .iter()
+            .map(|aggregate| aggregate.compute(&entities))
+            .collect()
+    }
+
     /// Find the reverse of keccak256 for `hash` through looking it up in the
     /// rainbow table.
     fn find_ens_name(&self, _hash: &str) -> Result<Option<String>, QueryExecutionError>;
 
+    /// Look up the number of the block with the given hash. Return `None` if
+    /// the block is unknown or not on the main chain of the subgraph's
+    /// network.
+    fn block_number(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        block_hash: H256,
+    ) -> Result<Option<BlockNumber>, StoreError>;
+
+    /// Return the earliest block for which queries against the subgraph can
+    /// be answered. This is only different from 0 if the entity history of
+    /// the subgraph has been pruned.
+    fn earliest_block(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<BlockNumber, StoreError> {
+        let entity = self.get(SubgraphDeploymentEntity::key(subgraph_id.clone()))?;
+        Ok(entity
+            .and_then(|entity| entity.get("prunedBlockNumber").cloned())
+            .and_then(|value| value.as_bigint())
+            .map_or(0, |number| number.to_u64() as BlockNumber))
+    }
+
     /// Updates the block pointer.  Careful: this is only safe to use if it is known that no store
     /// changes are needed to go from `block_ptr_from` to `block_ptr_to`.
     ///
@@ -973,13 +1297,19 @@ pub trait Store: Send + Sync + 'static {
             .unwrap_or(Ok(false))
     }
 
+    /// Return true if the entities of the deployment are stored in a
+    /// relational schema, and false if they are stored as JSONB
+    fn uses_relational_schema(&self, id: &SubgraphDeploymentId) -> Result<bool, StoreError>;
+
     /// Create a new subgraph deployment. The deployment must not exist yet. `ops`
     /// needs to contain all the operations on subgraphs and subgraph deployments to
     /// create the deployment, including any assignments as a current or pending
-    /// version
+    /// version. The store may use the `name` of the subgraph to decide where
+    /// to put the entities of the deployment
     fn create_subgraph_deployment(
         &self,
         subgraph_logger: &Logger,
+        name: &SubgraphName,
         schema: &Schema,
         ops: Vec<MetadataOperation>,
     ) -> Result<(), StoreError>;
@@ -1007,14 +1337,91 @@ pub trait Store: Send + Sync + 'static {
         subgraph_id: &SubgraphDeploymentId,
         block_ptr: &EthereumBlockPointer,
     );
+
+    /// If the subgraph is a graft whose base has not been copied yet, copy
+    /// the entities and dynamic data sources of the base as of the graft
+    /// block into the subgraph and move its block pointer to the graft
+    /// block. This must happen before the dynamic data sources of the
+    /// subgraph are loaded when it is started
+    fn copy_graft_base(
+        &self,
+        logger: &Logger,
+        subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<(), StoreError>;
+
+    /// Remove the entity versions of the subgraph that are no longer visible
+    /// at `reorg_block` or any later block, but only if pruning is enabled
+    /// for the subgraph. If its pruning settings name a retention block
+    /// before `reorg_block`, versions visible at that block are kept, too.
+    /// Blocks before the cutoff can not be queried after pruning.
+    ///
+    /// Any errors happening during pruning will be logged as warnings
+    /// on `logger`, but otherwise ignored
+    fn prune_subgraph_deployment(
+        &self,
+        logger: &Logger,
+        subgraph_id: &SubgraphDeploymentId,
+        reorg_block: BlockNumber,
+    );
+
+    /// Write the schema, the metadata and all entity versions of the
+    /// subgraph into the directory `dir`, which must not exist yet. The
+    /// subgraph can keep indexing while it is being exported
+    fn export_subgraph_deployment(
+        &self,
+        logger: &Logger,
+        subgraph_id: &SubgraphDeploymentId,
+        dir: &Path,
+    ) -> Result<(), StoreError>;
+
+    /// Recreate a subgraph deployment from a directory written by
+    /// `export_subgraph_deployment`. The deployment must not exist yet, and
+    /// is not assigned to any node. Returns the id of the deployment
+    fn import_subgraph_deployment(
+        &self,
+        logger: &Logger,
+        dir: &Path,
+    ) -> Result<SubgraphDeploymentId, StoreError>;
+
+    /// Move the entities of the subgraph into the database shard `shard`.
+    /// The subgraph must not be indexed while it is being moved
+    fn move_subgraph_deployment(
+        &self,
+        logger: &Logger,
+        subgraph_id: &SubgraphDeploymentId,
+        shard: &str,
+    ) -> Result<(), StoreError>;
 }
 
 pub trait SubgraphDeploymentStore: Send + Sync + 'static {
     fn subgraph_schema(&self, subgraph_id: &SubgraphDeploymentId) -> Result<Arc<Schema>, Error>;
 }
 
+/// Cache for the return values of `eth_call`s. Calls are identified by the
+/// contract address, the ABI-encoded call data and the hash of the block
+/// they were made against, so cached values never become stale.
+pub trait EthereumCallCache: Send + Sync + 'static {
+    /// Get the cached return value of a call, or `None` if the call is not
+    /// in the cache.
+    fn get_call(
+        &self,
+        contract_address: ethabi::Address,
+        encoded_call: &[u8],
+        block: EthereumBlockPointer,
+    ) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Store the return value of a call.
+    fn set_call(
+        &self,
+        contract_address: ethabi::Address,
+        encoded_call: &[u8],
+        block: EthereumBlockPointer,
+        return_value: &[u8],
+    ) -> Result<(), Error>;
+}
+
 /// Common trait for blockchain store implementations.
-pub trait ChainStore: Send + Sync + 'static {
+pub trait ChainStore: EthereumCallCache {
     type ChainHeadUpdateListener: ChainHeadUpdateListener;
 
     /// Get a pointer to this blockchain's genesis block.
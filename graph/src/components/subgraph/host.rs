@@ -49,12 +49,14 @@ pub trait RuntimeHost: Send + Sync + Debug {
 pub trait RuntimeHostBuilder: Clone + Send + Sync + 'static {
     type Host: RuntimeHost;
 
-    /// Build a new runtime host for a subgraph data source.
+    /// Build a new runtime host for a subgraph data source. Entities that
+    /// the data source's mappings store are checked against `schema`.
     fn build(
         &self,
         logger: &Logger,
         network_name: String,
         subgraph_id: SubgraphDeploymentId,
+        schema: Arc<Schema>,
         data_source: DataSource,
         top_level_templates: Vec<DataSourceTemplate>,
     ) -> Result<Self::Host, Error>;
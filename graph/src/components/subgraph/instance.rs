@@ -6,6 +6,7 @@ pub struct DataSourceTemplateInfo {
     pub data_source: String,
     pub template: DataSourceTemplate,
     pub params: Vec<String>,
+    pub context: Option<DataSourceContext>,
 }
 
 #[derive(Debug, Default)]
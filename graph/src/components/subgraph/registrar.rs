@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use crate::prelude::*;
 
 #[derive(Clone, Copy, Debug)]
@@ -39,5 +41,23 @@ pub trait SubgraphRegistrar: Send + Sync + 'static {
         &self,
         hash: SubgraphDeploymentId,
         node_id: NodeId,
+        shard: Option<String>,
+    ) -> Box<dyn Future<Item = (), Error = SubgraphRegistrarError> + Send + 'static>;
+
+    fn set_subgraph_pruning(
+        &self,
+        hash: SubgraphDeploymentId,
+        pruning: Option<Pruning>,
     ) -> Box<dyn Future<Item = (), Error = SubgraphRegistrarError> + Send + 'static>;
+
+    fn export_subgraph(
+        &self,
+        hash: SubgraphDeploymentId,
+        directory: PathBuf,
+    ) -> Box<dyn Future<Item = (), Error = SubgraphRegistrarError> + Send + 'static>;
+
+    fn import_subgraph(
+        &self,
+        directory: PathBuf,
+    ) -> Box<dyn Future<Item = SubgraphDeploymentId, Error = SubgraphRegistrarError> + Send + 'static>;
 }
@@ -1,3 +1,4 @@
+use crate::data::schema::SCHEMA_TYPE_NAME;
 use crate::prelude::Fail;
 use graphql_parser::schema::*;
 use serde::{Deserialize, Serialize};
@@ -27,19 +28,59 @@ pub enum SchemaValidationError {
         _0, _1, _2
     )]
     CannotImplement(String, String, Strings), // (type, interface, missing_fields)
+
+    #[fail(display = "Invalid @fulltext directive: {}", _0)]
+    InvalidFulltextDirective(String),
+
+    #[fail(
+        display = "Type `{}` can not be queried since its query field has the same name \
+                   as the aggregate query field of type `{}`",
+        _0, _1
+    )]
+    AggregateNameConflict(String, String), // (type, aggregated type)
 }
 
 /// Validates whether a GraphQL schema is compatible with The Graph.
 pub(crate) fn validate_schema(schema: &Document) -> Result<(), SchemaValidationError> {
-    validate_schema_types(schema)
+    validate_schema_types(schema)?;
+    validate_aggregate_names(schema)
+}
+
+/// Validates that no type is named like the aggregate of another type.
+/// The query field for a type `TokenAggregate` would be `tokenAggregate`,
+/// which is also the name of the field that aggregates `Token`s.
+fn validate_aggregate_names(schema: &Document) -> Result<(), SchemaValidationError> {
+    let names = schema
+        .definitions
+        .iter()
+        .filter_map(|d| match d {
+            Definition::TypeDefinition(TypeDefinition::Object(t)) => Some(&t.name),
+            Definition::TypeDefinition(TypeDefinition::Interface(t)) => Some(&t.name),
+            _ => None,
+        })
+        .filter(|name| name.as_str() != SCHEMA_TYPE_NAME)
+        .collect::<Vec<_>>();
+
+    for name in names.iter().filter(|name| name.ends_with("Aggregate")) {
+        let aggregated = &name[..name.len() - "Aggregate".len()];
+        if names.iter().any(|other| other.as_str() == aggregated) {
+            return Err(SchemaValidationError::AggregateNameConflict(
+                name.to_string(),
+                aggregated.to_owned(),
+            ));
+        }
+    }
+    Ok(())
 }
 
 /// Validates whether all object types in the schema are declared with an @entity directive.
+/// The `_Schema_` type only holds schema-level directives and is exempt from this.
 fn validate_schema_types(schema: &Document) -> Result<(), SchemaValidationError> {
     use self::SchemaValidationError::*;
 
     let types_without_entity_directive = get_object_type_definitions(schema)
         .iter()
+        .filter(|t| t.name != SCHEMA_TYPE_NAME)
         .filter(|t| get_object_type_directive(t, String::from("entity")).is_none())
         .map(|t| t.name.to_owned())
         .collect::<Vec<_>>();
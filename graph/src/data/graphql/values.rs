@@ -38,6 +38,22 @@ impl TryFromValue for String {
     }
 }
 
+impl TryFromValue for u64 {
+    fn try_from_value(value: &Value) -> Result<Self, Error> {
+        match value {
+            Value::Int(n) => n
+                .as_i64()
+                .filter(|n| *n >= 0)
+                .map(|n| n as u64)
+                .ok_or_else(|| format_err!("Cannot parse value into a u64: {:?}", value)),
+            // `BigInt` values are represented as strings
+            Value::String(s) => u64::from_str(s)
+                .map_err(|e| format_err!("Cannot parse u64 value from string `{}`: {}", s, e)),
+            _ => Err(format_err!("Cannot parse value into a u64: {:?}", value)),
+        }
+    }
+}
+
 impl TryFromValue for H160 {
     fn try_from_value(value: &Value) -> Result<Self, Error> {
         match value {
@@ -8,7 +8,7 @@ use std::error::Error;
 use std::fmt;
 use std::string::FromUtf8Error;
 
-use crate::components::store::StoreError;
+use crate::components::store::{BlockNumber, StoreError};
 use crate::data::subgraph::*;
 
 /// Error caused while executing a [Query](struct.Query.html).
@@ -51,8 +51,11 @@ pub enum QueryExecutionError {
     Unimplemented(String),
     EnumCoercionError(Pos, String, q::Value, String, Vec<String>),
     ScalarCoercionError(Pos, String, q::Value, String),
-    TooComplex(u64, u64), // (complexity, max_complexity)
-    TooDeep(u8),          // max_depth
+    TooComplex(u64, u64),              // (complexity, max_complexity)
+    TooDeep(u8),                       // max_depth
+    BlockNotIndexed(BlockNumber, u64), // (requested block, latest indexed block)
+    BlockNotFound(String),
+    BlockPruned(BlockNumber, BlockNumber), // (requested block, earliest available block)
 }
 
 impl Error for QueryExecutionError {
@@ -191,7 +194,21 @@ impl fmt::Display for QueryExecutionError {
                            of the query, querying fewer relationships or using `first` to \
                            return smaller collections", complexity, max_complexity)
             }
-            TooDeep(max_depth) => write!(f, "query has a depth that exceeds the limit of `{}`", max_depth)
+            TooDeep(max_depth) => write!(f, "query has a depth that exceeds the limit of `{}`", max_depth),
+            BlockNotIndexed(block, latest) => {
+                write!(f, "subgraph has only indexed up to block number {} \
+                           and data for block number {} is therefore not yet available",
+                       latest, block)
+            }
+            BlockNotFound(hash) => {
+                write!(f, "block with hash `{}` is not on the main chain", hash)
+            }
+            BlockPruned(block, earliest) => {
+                write!(f, "the history of this subgraph has been pruned and data for \
+                           block number {} is therefore no longer available; the earliest \
+                           block that can be queried is {}",
+                       block, earliest)
+            }
         }
     }
 }
@@ -1,8 +1,11 @@
+use crate::components::store::EntityKey;
 use crate::data::graphql::validation::{
     get_object_type_definitions, validate_interface_implementation, validate_schema,
     SchemaValidationError,
 };
+use crate::data::store::{Entity, Value, ValueType};
 use crate::data::subgraph::SubgraphDeploymentId;
+use crate::prelude::Fail;
 use failure::Error;
 use graphql_parser;
 use graphql_parser::{
@@ -12,6 +15,328 @@ use graphql_parser::{
 };
 use std::collections::BTreeMap;
 use std::iter::FromIterator;
+use std::str::FromStr;
+
+/// The name of the type that holds schema-level directives like
+/// `@fulltext`. It is not an entity type and is not part of the API schema.
+pub const SCHEMA_TYPE_NAME: &str = "_Schema_";
+
+/// The languages a full-text search can use, mapped to the name of the
+/// corresponding Postgres text search configuration.
+const FULLTEXT_LANGUAGES: &[(&str, &str)] = &[
+    ("simple", "simple"),
+    ("da", "danish"),
+    ("nl", "dutch"),
+    ("en", "english"),
+    ("fi", "finnish"),
+    ("fr", "french"),
+    ("de", "german"),
+    ("hu", "hungarian"),
+    ("it", "italian"),
+    ("no", "norwegian"),
+    ("pt", "portuguese"),
+    ("ro", "romanian"),
+    ("ru", "russian"),
+    ("es", "spanish"),
+    ("sv", "swedish"),
+    ("tr", "turkish"),
+];
+
+/// How the matches of a full-text search are ranked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FulltextAlgorithm {
+    /// Rank by how often the search terms occur
+    Rank,
+    /// Rank by how often and how close together the search terms occur
+    ProximityRank,
+}
+
+/// A full-text search over `String` fields of one or more entity types. It
+/// is declared with a directive on the `_Schema_` type:
+///
+/// ```graphql
+/// type _Schema_
+///   @fulltext(
+///     name: "bandSearch"
+///     language: en
+///     algorithm: rank
+///     include: [{ entity: "Band", fields: [{ name: "name" }, { name: "bio" }] }]
+///   )
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct FulltextDefinition {
+    /// The name of the search, which is also the name of its query field
+    pub name: String,
+    /// The Postgres text search configuration, e.g. `english`
+    pub language: String,
+    pub algorithm: FulltextAlgorithm,
+    /// The searched fields, grouped by entity type
+    pub included_fields: Vec<(String, Vec<String>)>,
+}
+
+impl FulltextDefinition {
+    /// Extracts all full-text searches declared in `document`, checking that
+    /// they are well-formed and only search `String` fields of entity types.
+    pub fn from_document(
+        document: &schema::Document,
+    ) -> Result<Vec<FulltextDefinition>, SchemaValidationError> {
+        let schema_type = match get_object_type_definitions(document)
+            .into_iter()
+            .find(|object_type| object_type.name == SCHEMA_TYPE_NAME)
+        {
+            Some(schema_type) => schema_type,
+            None => return Ok(vec![]),
+        };
+
+        let mut definitions: Vec<FulltextDefinition> = vec![];
+        for directive in schema_type
+            .directives
+            .iter()
+            .filter(|directive| directive.name == "fulltext")
+        {
+            let definition = Self::from_directive(document, directive)?;
+            if definitions.iter().any(|d| d.name == definition.name) {
+                return Err(invalid_fulltext(
+                    &definition.name,
+                    "the search is declared more than once",
+                ));
+            }
+            definitions.push(definition);
+        }
+        Ok(definitions)
+    }
+
+    fn from_directive(
+        document: &schema::Document,
+        directive: &schema::Directive,
+    ) -> Result<FulltextDefinition, SchemaValidationError> {
+        let argument = |name: &str| {
+            directive
+                .arguments
+                .iter()
+                .find(|(arg_name, _)| arg_name == name)
+                .map(|(_, value)| value)
+        };
+
+        let name = match argument("name") {
+            Some(schema::Value::String(name)) if is_valid_name(name) => name.clone(),
+            _ => {
+                return Err(SchemaValidationError::InvalidFulltextDirective(
+                    "`name` must be a string that is a valid GraphQL name".to_owned(),
+                ))
+            }
+        };
+
+        let language = match argument("language") {
+            Some(schema::Value::Enum(language)) => FULLTEXT_LANGUAGES
+                .iter()
+                .find(|(code, _)| code == language)
+                .map(|(_, config)| config.to_string())
+                .ok_or_else(|| {
+                    invalid_fulltext(&name, &format!("unsupported language `{}`", language))
+                })?,
+            _ => return Err(invalid_fulltext(&name, "`language` must be an enum value")),
+        };
+
+        let algorithm = match argument("algorithm") {
+            Some(schema::Value::Enum(algorithm)) if algorithm == "rank" => FulltextAlgorithm::Rank,
+            Some(schema::Value::Enum(algorithm)) if algorithm == "proximityRank" => {
+                FulltextAlgorithm::ProximityRank
+            }
+            _ => {
+                return Err(invalid_fulltext(
+                    &name,
+                    "`algorithm` must be one of `rank` or `proximityRank`",
+                ))
+            }
+        };
+
+        let includes = match argument("include") {
+            Some(schema::Value::List(includes)) if !includes.is_empty() => includes,
+            _ => {
+                return Err(invalid_fulltext(
+                    &name,
+                    "`include` must be a non-empty list of entity types and their fields",
+                ))
+            }
+        };
+
+        let object_types = get_object_type_definitions(document);
+        let mut included_fields: Vec<(String, Vec<String>)> = vec![];
+        for include in includes {
+            let (entity, fields) = match include {
+                schema::Value::Object(include) => match (include.get("entity"), include.get("fields")) {
+                    (Some(schema::Value::String(entity)), Some(schema::Value::List(fields)))
+                        if !fields.is_empty() =>
+                    {
+                        (entity, fields)
+                    }
+                    _ => {
+                        return Err(invalid_fulltext(
+                            &name,
+                            "each entry in `include` must have an `entity` and a non-empty list of `fields`",
+                        ))
+                    }
+                },
+                _ => return Err(invalid_fulltext(&name, "`include` must be a list of objects")),
+            };
+
+            let object_type = object_types
+                .iter()
+                .find(|object_type| {
+                    &object_type.name == entity && object_type.name != SCHEMA_TYPE_NAME
+                })
+                .ok_or_else(|| {
+                    invalid_fulltext(&name, &format!("entity type `{}` does not exist", entity))
+                })?;
+            if included_fields.iter().any(|(e, _)| e == entity) {
+                return Err(invalid_fulltext(
+                    &name,
+                    &format!("entity type `{}` is included more than once", entity),
+                ));
+            }
+
+            let mut field_names = vec![];
+            for field in fields {
+                let field_name = match field {
+                    schema::Value::Object(field) => match field.get("name") {
+                        Some(schema::Value::String(field_name)) => field_name,
+                        _ => return Err(invalid_fulltext(&name, "each field must have a `name`")),
+                    },
+                    _ => {
+                        return Err(invalid_fulltext(
+                            &name,
+                            "`fields` must be a list of objects",
+                        ))
+                    }
+                };
+                match object_type.fields.iter().find(|f| &f.name == field_name) {
+                    Some(field) if is_string_type(&field.field_type) => (),
+                    Some(_) => {
+                        return Err(invalid_fulltext(
+                            &name,
+                            &format!("field `{}.{}` is not a String", entity, field_name),
+                        ))
+                    }
+                    None => {
+                        return Err(invalid_fulltext(
+                            &name,
+                            &format!("field `{}.{}` does not exist", entity, field_name),
+                        ))
+                    }
+                }
+                field_names.push(field_name.clone());
+            }
+            included_fields.push((entity.clone(), field_names));
+        }
+
+        let definition = FulltextDefinition {
+            name,
+            language,
+            algorithm,
+            included_fields,
+        };
+        if definition.result_type(document).is_none() {
+            return Err(invalid_fulltext(
+                &definition.name,
+                "the included entity types must implement a common interface",
+            ));
+        }
+        Ok(definition)
+    }
+
+    /// The type of the entities the search returns. That is the entity type
+    /// if only one is included, and otherwise the first interface that all
+    /// included entity types implement.
+    pub fn result_type(&self, document: &schema::Document) -> Option<Name> {
+        let object_types = get_object_type_definitions(document);
+        let interfaces_for = |entity: &String| {
+            object_types
+                .iter()
+                .find(|object_type| &object_type.name == entity)
+                .map(|object_type| object_type.implements_interfaces.clone())
+                .unwrap_or_default()
+        };
+
+        let mut entities = self.included_fields.iter().map(|(entity, _)| entity);
+        let first = entities.next()?;
+        if self.included_fields.len() == 1 {
+            return Some(first.clone());
+        }
+        let others = entities.map(interfaces_for).collect::<Vec<_>>();
+        interfaces_for(first).into_iter().find(|interface| {
+            others
+                .iter()
+                .all(|interfaces| interfaces.contains(interface))
+        })
+    }
+}
+
+fn invalid_fulltext(name: &str, reason: &str) -> SchemaValidationError {
+    SchemaValidationError::InvalidFulltextDirective(format!("search `{}`: {}", name, reason))
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_string_type(field_type: &schema::Type) -> bool {
+    match field_type {
+        schema::Type::NamedType(name) => name == "String",
+        schema::Type::NonNullType(inner) => is_string_type(inner),
+        schema::Type::ListType(_) => false,
+    }
+}
+
+/// An entity that a mapping tried to store does not match the schema of
+/// the subgraph.
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum EntityValidationError {
+    #[fail(display = "Entity {}[{}]: unknown entity type `{}`", _0, _1, _0)]
+    UnknownEntityType(String, String), // (type, id)
+
+    #[fail(display = "Entity {}[{}]: unknown field `{}`", _0, _1, _2)]
+    UnknownField(String, String, String), // (type, id, field)
+
+    #[fail(
+        display = "Entity {}[{}]: field `{}` is derived and can not be set",
+        _0, _1, _2
+    )]
+    DerivedField(String, String, String), // (type, id, field)
+
+    #[fail(
+        display = "Entity {}[{}]: missing value for non-nullable field `{}` of type `{}`",
+        _0, _1, _2, _3
+    )]
+    MissingField(String, String, String, String), // (type, id, field, expected type)
+
+    #[fail(
+        display = "Entity {}[{}]: field `{}` has type `{}` but got a value of type `{}`",
+        _0, _1, _2, _3, _4
+    )]
+    TypeMismatch(String, String, String, String, String), // (type, id, field, expected, actual)
+}
+
+/// A difference between the schema of a graft and the schema of its base
+/// that prevents copying the entities of the base into the graft.
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum GraftIncompatibility {
+    #[fail(
+        display = "field `{}.{}` has type `{}` but has type `{}` in the base",
+        _0, _1, _2, _3
+    )]
+    TypeChanged(String, String, String, String), // (type, field, graft type, base type)
+
+    #[fail(
+        display = "field `{}.{}` of type `{}` is not in the base and must be nullable",
+        _0, _1, _2
+    )]
+    NewNonNullableField(String, String, String), // (type, field, type)
+}
 
 /// A validated and preprocessed GraphQL schema for a subgraph.
 #[derive(Clone, Debug, PartialEq)]
@@ -95,6 +420,7 @@ impl Schema {
     pub fn parse(raw: &str, id: SubgraphDeploymentId) -> Result<Self, Error> {
         let document = graphql_parser::parse_schema(&raw)?;
         validate_schema(&document)?;
+        FulltextDefinition::from_document(&document)?;
 
         let (interfaces_for_type, types_for_interface) = Self::collect_interfaces(&document)?;
 
@@ -109,6 +435,149 @@ impl Schema {
         Ok(schema)
     }
 
+    /// Checks that `entity` can be stored under `key`: the entity type must
+    /// be defined, every attribute must be a field of that type that is not
+    /// derived, every value must match the type of its field, and every
+    /// non-nullable field must have a value. Fields are checked in the order
+    /// in which the schema declares them, and missing fields are reported
+    /// last, so that the error for an entity is always the same.
+    pub fn validate_entity(
+        &self,
+        key: &EntityKey,
+        entity: &Entity,
+    ) -> Result<(), EntityValidationError> {
+        let entity_type = &key.entity_type;
+        let id = &key.entity_id;
+
+        let object_type = get_object_type_definitions(&self.document)
+            .into_iter()
+            .find(|object_type| &object_type.name == entity_type)
+            .ok_or_else(|| {
+                EntityValidationError::UnknownEntityType(entity_type.clone(), id.clone())
+            })?;
+
+        let mut missing_field = None;
+        for field in &object_type.fields {
+            let is_derived = field
+                .directives
+                .iter()
+                .any(|directive| directive.name == "derivedFrom");
+
+            match entity.get(&field.name) {
+                Some(_) if is_derived => {
+                    return Err(EntityValidationError::DerivedField(
+                        entity_type.clone(),
+                        id.clone(),
+                        field.name.clone(),
+                    ));
+                }
+                Some(Value::Null) | None => {
+                    if let schema::Type::NonNullType(_) = field.field_type {
+                        if !is_derived && missing_field.is_none() {
+                            missing_field = Some(field);
+                        }
+                    }
+                }
+                Some(value) => {
+                    if let Some(mismatch) = mismatched_value(&field.field_type, value) {
+                        return Err(EntityValidationError::TypeMismatch(
+                            entity_type.clone(),
+                            id.clone(),
+                            field.name.clone(),
+                            field.field_type.to_string(),
+                            mismatch.type_name().to_owned(),
+                        ));
+                    }
+                }
+            }
+        }
+
+        let mut unknown_fields: Vec<_> = entity
+            .keys()
+            .filter(|attr| !object_type.fields.iter().any(|field| &field.name == *attr))
+            .collect();
+        unknown_fields.sort();
+        if let Some(attr) = unknown_fields.first() {
+            return Err(EntityValidationError::UnknownField(
+                entity_type.clone(),
+                id.clone(),
+                attr.to_string(),
+            ));
+        }
+
+        match missing_field {
+            Some(field) => Err(EntityValidationError::MissingField(
+                entity_type.clone(),
+                id.clone(),
+                field.name.clone(),
+                field.field_type.to_string(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks whether the entities stored for the schema `base` can be used
+    /// as entities for this schema. Every field the two schemas have in
+    /// common must have the same type, except that a non-nullable field can
+    /// become nullable; fields that only this schema has must be nullable.
+    /// Types and fields that only the base has are dropped when copying, and
+    /// types that only this schema has start out empty.
+    pub fn graft_incompatibilities(&self, base: &Schema) -> Vec<GraftIncompatibility> {
+        fn is_derived(field: &schema::Field) -> bool {
+            field
+                .directives
+                .iter()
+                .any(|directive| directive.name == "derivedFrom")
+        }
+
+        let base_types = get_object_type_definitions(&base.document);
+        let mut incompatibilities = vec![];
+        for object_type in get_object_type_definitions(&self.document) {
+            let base_type = match base_types
+                .iter()
+                .find(|base_type| base_type.name == object_type.name)
+            {
+                Some(base_type) => base_type,
+                None => continue,
+            };
+
+            for field in object_type.fields.iter().filter(|field| !is_derived(field)) {
+                let base_field = base_type
+                    .fields
+                    .iter()
+                    .find(|base_field| base_field.name == field.name && !is_derived(base_field));
+
+                match (base_field, &field.field_type) {
+                    (None, schema::Type::NonNullType(_)) => {
+                        incompatibilities.push(GraftIncompatibility::NewNonNullableField(
+                            object_type.name.clone(),
+                            field.name.clone(),
+                            field.field_type.to_string(),
+                        ))
+                    }
+                    (None, _) => {}
+                    (Some(base_field), field_type) => {
+                        let compatible = match &base_field.field_type {
+                            schema::Type::NonNullType(inner) => {
+                                field_type == &base_field.field_type || field_type == &**inner
+                            }
+                            nullable => field_type == nullable,
+                        };
+                        if !compatible {
+                            incompatibilities.push(GraftIncompatibility::TypeChanged(
+                                object_type.name.clone(),
+                                field.name.clone(),
+                                field.field_type.to_string(),
+                                base_field.field_type.to_string(),
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+        incompatibilities
+    }
+
     /// Returned map has one an entry for each interface in the schema.
     pub fn types_for_interface(&self) -> &BTreeMap<Name, Vec<ObjectType>> {
         &self.types_for_interface
@@ -155,6 +624,56 @@ impl Schema {
             };
         }
     }
+
+    /// Removes the `@fulltext` directives from the `_Schema_` type, and with
+    /// them all full-text searches that the schema declares.
+    pub fn remove_fulltext_directives(&mut self) {
+        for definition in self.document.definitions.iter_mut() {
+            if let schema::Definition::TypeDefinition(TypeDefinition::Object(object_type)) =
+                definition
+            {
+                if object_type.name == SCHEMA_TYPE_NAME {
+                    object_type
+                        .directives
+                        .retain(|directive| directive.name != "fulltext");
+                }
+            }
+        }
+    }
+}
+
+/// Returns the first value in `value` that does not match `field_type`, if
+/// any. Enums and references to other entities are stored as strings.
+fn mismatched_value<'a>(field_type: &schema::Type, value: &'a Value) -> Option<&'a Value> {
+    use graphql_parser::schema::Type::*;
+
+    match (field_type, value) {
+        (NonNullType(_), Value::Null) => Some(value),
+        (NonNullType(inner), _) => mismatched_value(inner, value),
+        (_, Value::Null) => None,
+        (ListType(inner), Value::List(values)) => values
+            .iter()
+            .find_map(|value| mismatched_value(inner, value)),
+        (ListType(_), _) => Some(value),
+        (NamedType(name), _) => {
+            let matches = match (ValueType::from_str(name), value) {
+                (Ok(ValueType::Boolean), Value::Bool(_))
+                | (Ok(ValueType::BigInt), Value::BigInt(_))
+                | (Ok(ValueType::Bytes), Value::Bytes(_))
+                | (Ok(ValueType::BigDecimal), Value::BigDecimal(_))
+                | (Ok(ValueType::Int), Value::Int(_))
+                | (Ok(ValueType::ID), Value::String(_))
+                | (Ok(ValueType::String), Value::String(_))
+                | (Err(_), Value::String(_)) => true,
+                _ => false,
+            };
+            if matches {
+                None
+            } else {
+                Some(value)
+            }
+        }
+    }
 }
 
 #[test]
@@ -190,3 +709,291 @@ fn invalid_interface_implementation() {
          required fields: x: Int, y: Int"
     );
 }
+
+#[test]
+fn aggregate_name_conflict() {
+    let schema = "
+        type Token @entity { id: ID! }
+        type TokenAggregate @entity { id: ID! }
+    ";
+    let res = Schema::parse(schema, SubgraphDeploymentId::new("dummy").unwrap());
+    let error = res
+        .unwrap_err()
+        .downcast::<SchemaValidationError>()
+        .unwrap();
+    assert_eq!(
+        error,
+        SchemaValidationError::AggregateNameConflict(
+            "TokenAggregate".to_owned(),
+            "Token".to_owned()
+        )
+    );
+}
+
+#[test]
+fn fulltext_definition() {
+    let schema = r#"
+        type _Schema_
+          @fulltext(
+            name: "bandSearch"
+            language: en
+            algorithm: rank
+            include: [{ entity: "Band", fields: [{ name: "name" }, { name: "bio" }] }]
+          )
+
+        type Band @entity {
+            id: ID!
+            name: String!
+            bio: String
+            members: [String!]!
+        }
+    "#;
+    let schema = Schema::parse(schema, SubgraphDeploymentId::new("dummy").unwrap()).unwrap();
+    let definitions = FulltextDefinition::from_document(&schema.document).unwrap();
+    assert_eq!(
+        definitions,
+        vec![FulltextDefinition {
+            name: "bandSearch".to_owned(),
+            language: "english".to_owned(),
+            algorithm: FulltextAlgorithm::Rank,
+            included_fields: vec![("Band".to_owned(), vec!["name".to_owned(), "bio".to_owned()])],
+        }]
+    );
+
+    let mut schema = schema;
+    schema.remove_fulltext_directives();
+    assert_eq!(
+        FulltextDefinition::from_document(&schema.document).unwrap(),
+        vec![]
+    );
+}
+
+#[test]
+fn fulltext_definition_only_includes_string_fields() {
+    let schema = r#"
+        type _Schema_
+          @fulltext(
+            name: "bandSearch"
+            language: en
+            algorithm: rank
+            include: [{ entity: "Band", fields: [{ name: "members" }] }]
+          )
+
+        type Band @entity {
+            id: ID!
+            members: [String!]!
+        }
+    "#;
+    let res = Schema::parse(schema, SubgraphDeploymentId::new("dummy").unwrap());
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Invalid @fulltext directive: search `bandSearch`: field `Band.members` is not a String"
+    );
+}
+
+#[test]
+fn fulltext_definition_with_multiple_entities_needs_common_interface() {
+    let schema = r#"
+        type _Schema_
+          @fulltext(
+            name: "search"
+            language: simple
+            algorithm: proximityRank
+            include: [
+              { entity: "Band", fields: [{ name: "name" }] },
+              { entity: "Musician", fields: [{ name: "name" }] }
+            ]
+          )
+
+        type Band @entity {
+            id: ID!
+            name: String!
+        }
+
+        type Musician @entity {
+            id: ID!
+            name: String!
+        }
+    "#;
+    let res = Schema::parse(schema, SubgraphDeploymentId::new("dummy").unwrap());
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Invalid @fulltext directive: search `search`: the included entity types must \
+         implement a common interface"
+    );
+}
+
+#[test]
+fn validate_entity() {
+    use crate::data::store::scalar::BigInt;
+
+    let schema = "
+        enum Color { red, green }
+
+        type Band @entity {
+            id: ID!
+            name: String!
+            color: Color
+            members: [BigInt!]
+            songs: [Song!]! @derivedFrom(field: \"band\")
+        }
+
+        type Song @entity {
+            id: ID!
+            band: Band!
+        }
+    ";
+    let schema = Schema::parse(schema, SubgraphDeploymentId::new("dummy").unwrap()).unwrap();
+    let key = |entity_type: &str| EntityKey {
+        subgraph_id: SubgraphDeploymentId::new("dummy").unwrap(),
+        entity_type: entity_type.to_owned(),
+        entity_id: "1".to_owned(),
+    };
+    let band = |fields: Vec<(&str, Value)>| {
+        let mut entity = Entity::new();
+        entity.set("id", "1");
+        for (name, value) in fields {
+            entity.set(name, value);
+        }
+        entity
+    };
+    let check = |fields| schema.validate_entity(&key("Band"), &band(fields));
+
+    assert_eq!(Ok(()), check(vec![("name", Value::from("Queen"))]));
+    assert_eq!(
+        Ok(()),
+        check(vec![
+            ("name", Value::from("Queen")),
+            ("color", Value::from("red")),
+            ("members", Value::List(vec![Value::BigInt(BigInt::from(4))])),
+        ])
+    );
+    assert_eq!(
+        Ok(()),
+        schema.validate_entity(&key("Song"), &band(vec![("band", Value::from("1"))]))
+    );
+
+    assert_eq!(
+        Err(EntityValidationError::UnknownEntityType(
+            "Album".to_owned(),
+            "1".to_owned()
+        )),
+        schema.validate_entity(&key("Album"), &band(vec![]))
+    );
+    assert_eq!(
+        Err(EntityValidationError::MissingField(
+            "Band".to_owned(),
+            "1".to_owned(),
+            "name".to_owned(),
+            "String!".to_owned()
+        )),
+        check(vec![("name", Value::Null)])
+    );
+    assert_eq!(
+        Err(EntityValidationError::UnknownField(
+            "Band".to_owned(),
+            "1".to_owned(),
+            "genre".to_owned()
+        )),
+        check(vec![("genre", Value::from("rock"))])
+    );
+    assert_eq!(
+        Err(EntityValidationError::DerivedField(
+            "Band".to_owned(),
+            "1".to_owned(),
+            "songs".to_owned()
+        )),
+        check(vec![
+            ("name", Value::from("Queen")),
+            ("songs", Value::List(vec![]))
+        ])
+    );
+    assert_eq!(
+        "Entity Band[1]: field `members` has type `[BigInt!]` but got a value of type `Int`",
+        check(vec![
+            ("name", Value::from("Queen")),
+            ("members", Value::List(vec![Value::Int(4)]))
+        ])
+        .unwrap_err()
+        .to_string()
+    );
+}
+
+#[test]
+fn graft_incompatibilities() {
+    let base = "
+        type Band @entity {
+            id: ID!
+            name: String!
+            rating: Int
+            genre: String
+            songs: [Song!]! @derivedFrom(field: \"band\")
+        }
+
+        type Song @entity {
+            id: ID!
+            band: Band!
+        }
+    ";
+    let base = Schema::parse(base, SubgraphDeploymentId::new("base").unwrap()).unwrap();
+    let graft = |schema: &str| {
+        Schema::parse(schema, SubgraphDeploymentId::new("graft").unwrap())
+            .unwrap()
+            .graft_incompatibilities(&base)
+    };
+
+    // Dropping types and fields, relaxing nullability and adding nullable
+    // fields or new types is fine
+    assert_eq!(
+        Vec::<GraftIncompatibility>::new(),
+        graft(
+            "
+            type Band @entity {
+                id: ID!
+                name: String
+                rating: Int
+                songs: [String!]
+                albums: [Album!]! @derivedFrom(field: \"band\")
+            }
+
+            type Album @entity {
+                id: ID!
+                band: Band!
+            }
+            "
+        )
+    );
+
+    assert_eq!(
+        vec![
+            GraftIncompatibility::TypeChanged(
+                "Band".to_owned(),
+                "rating".to_owned(),
+                "Int!".to_owned(),
+                "Int".to_owned()
+            ),
+            GraftIncompatibility::TypeChanged(
+                "Band".to_owned(),
+                "genre".to_owned(),
+                "[String]".to_owned(),
+                "String".to_owned()
+            ),
+            GraftIncompatibility::NewNonNullableField(
+                "Band".to_owned(),
+                "founded".to_owned(),
+                "Int!".to_owned()
+            ),
+        ],
+        graft(
+            "
+            type Band @entity {
+                id: ID!
+                name: String!
+                rating: Int!
+                genre: [String]
+                founded: Int!
+            }
+            "
+        )
+    );
+}
@@ -250,6 +250,20 @@ impl Value {
             None
         }
     }
+
+    /// The name of the variant of this value, for use in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "String",
+            Value::Int(_) => "Int",
+            Value::BigDecimal(_) => "BigDecimal",
+            Value::Bool(_) => "Bool",
+            Value::List(_) => "List",
+            Value::Null => "Null",
+            Value::Bytes(_) => "Bytes",
+            Value::BigInt(_) => "BigInt",
+        }
+    }
 }
 
 impl fmt::Display for Value {
@@ -4,7 +4,7 @@ use serde::{self, Deserialize, Serialize};
 use web3::types::*;
 
 use std::fmt::{self, Display, Formatter};
-use std::ops::{Add, Div, Mul, Rem, Sub};
+use std::ops::{Add, BitAnd, BitOr, Div, Mul, Rem, Shl, Shr, Sub};
 use std::str::FromStr;
 
 pub use num_bigint::Sign as BigIntSign;
@@ -98,6 +98,29 @@ impl BigInt {
         U256::from_little_endian(&bytes)
     }
 
+    /// Like `to_signed_u256`, but returns `None` if the value does not fit
+    /// into a signed 256-bit integer.
+    pub fn checked_to_signed_u256(&self) -> Option<U256> {
+        let bytes = self.to_signed_bytes_le();
+        if bytes.len() > 32 {
+            return None;
+        }
+        let fill = if self < &BigInt::from(0) { 255 } else { 0 };
+        let mut i_bytes: [u8; 32] = [fill; 32];
+        i_bytes[..bytes.len()].copy_from_slice(&bytes);
+        Some(U256::from_little_endian(&i_bytes))
+    }
+
+    /// Like `to_unsigned_u256`, but returns `None` if the value is negative
+    /// or does not fit into an unsigned 256-bit integer.
+    pub fn checked_to_unsigned_u256(&self) -> Option<U256> {
+        let (sign, bytes) = self.to_bytes_le();
+        if sign == BigIntSign::Minus || bytes.len() > 32 {
+            return None;
+        }
+        Some(U256::from_little_endian(&bytes))
+    }
+
     pub fn to_big_decimal(self, exp: BigInt) -> BigDecimal {
         let bytes = exp.to_signed_bytes_le();
 
@@ -116,6 +139,15 @@ impl BigInt {
 
         BigInt(self.0.pow(&exponent))
     }
+
+    /// The square root, rounded down.
+    pub fn sqrt(&self) -> Self {
+        if self < &BigInt::from(0) {
+            panic!("Cannot take the square root of a negative `BigInt`!")
+        }
+
+        BigInt(self.0.sqrt())
+    }
 }
 
 impl Display for BigInt {
@@ -228,6 +260,41 @@ impl Rem for BigInt {
     }
 }
 
+/// Bitwise operations treat negative numbers as if they were in two's
+/// complement representation with infinite sign extension.
+impl BitOr for BigInt {
+    type Output = BigInt;
+
+    fn bitor(self, other: BigInt) -> BigInt {
+        BigInt(self.0.bitor(other.0))
+    }
+}
+
+impl BitAnd for BigInt {
+    type Output = BigInt;
+
+    fn bitand(self, other: BigInt) -> BigInt {
+        BigInt(self.0.bitand(other.0))
+    }
+}
+
+impl Shl<u8> for BigInt {
+    type Output = BigInt;
+
+    fn shl(self, bits: u8) -> BigInt {
+        BigInt(self.0.shl(bits as usize))
+    }
+}
+
+/// Rounds towards negative infinity, like an arithmetic shift.
+impl Shr<u8> for BigInt {
+    type Output = BigInt;
+
+    fn shr(self, bits: u8) -> BigInt {
+        BigInt(self.0.shr(bits as usize))
+    }
+}
+
 /// A byte array that's serialized as a hex string prefixed by `0x`.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Bytes(Box<[u8]>);
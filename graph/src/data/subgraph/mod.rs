@@ -1,4 +1,4 @@
-use ethabi::Contract;
+use ethabi::{Contract, RawLog};
 use failure;
 use failure::{Error, SyncFailure};
 use futures::stream;
@@ -13,12 +13,14 @@ use std::ops::Deref;
 use std::str::FromStr;
 use std::sync::Arc;
 use tokio::prelude::*;
-use web3::types::{Address, H256};
+use web3::types::{Address, Log, H256};
 
+use crate::components::ethereum::EthereumEventFilter;
 use crate::components::link_resolver::LinkResolver;
 use crate::components::store::StoreError;
 use crate::data::query::QueryExecutionError;
 use crate::data::schema::Schema;
+use crate::data::store::Entity;
 use crate::data::subgraph::schema::{
     EthereumBlockHandlerEntity, EthereumCallHandlerEntity, EthereumContractAbiEntity,
     EthereumContractDataSourceEntity, EthereumContractDataSourceTemplateEntity,
@@ -26,7 +28,9 @@ use crate::data::subgraph::schema::{
     EthereumContractMappingEntity, EthereumContractSourceEntity, SUBGRAPHS_ID,
 };
 use crate::prelude::{format_err, Deserialize, Fail, Serialize};
-use crate::util::ethereum::string_to_h256;
+use crate::util::ethereum::{
+    contract_event_with_signature, named_event_signatures, string_to_h256,
+};
 
 /// Rust representation of the GraphQL schema for a `SubgraphManifest`.
 pub mod schema;
@@ -279,6 +283,8 @@ pub enum SubgraphAssignmentProviderError {
     ResolveError(SubgraphManifestResolveError),
     #[fail(display = "Failed to load dynamic data sources: {}", _0)]
     DynamicDataSourcesError(failure::Error),
+    #[fail(display = "Failed to copy the data of the graft base: {}", _0)]
+    GraftError(failure::Error),
     /// Occurs when attempting to remove a subgraph that's not hosted.
     #[fail(display = "Subgraph with ID {} already running", _0)]
     AlreadyRunning(SubgraphDeploymentId),
@@ -327,6 +333,72 @@ pub enum SubgraphManifestValidationError {
     EthereumNetworkRequired,
     #[fail(display = "subgraph data source has too many similar block handlers")]
     DataSourceBlockHandlerLimitExceeded,
+    #[fail(display = "subgraph block handler polling interval must be greater than zero")]
+    InvalidPollingInterval,
+    #[fail(
+        display = "subgraph data sources with anonymous event handlers require a source address"
+    )]
+    AnonymousEventSourceAddressRequired,
+    #[fail(
+        display = "subgraph anonymous event `{}` must be declared anonymous in the ABI, \
+                   with all of its indexed parameters marked as `indexed`",
+        _0
+    )]
+    InvalidAnonymousEvent(String),
+    #[fail(
+        display = "subgraph anonymous events `{}` and `{}` have the same number of \
+                   indexed parameters and cannot be told apart",
+        _0, _1
+    )]
+    AmbiguousAnonymousEvents(String, String),
+    #[fail(
+        display = "subgraph anonymous event `{}` and event `{}` have the same number of \
+                   topics and cannot be told apart",
+        _0, _1
+    )]
+    AmbiguousAnonymousAndNamedEvents(String, String),
+    #[fail(
+        display = "subgraph anonymous event `{}` cannot filter on topic1, topic2 or topic3",
+        _0
+    )]
+    AnonymousEventTopicFilter(String),
+    #[fail(display = "graft base deployment `{}` does not exist", _0)]
+    GraftBaseNotFound(String),
+    #[fail(
+        display = "graft base `{}` has only indexed up to block {}, it can not be grafted at block {}",
+        _0, _2, _1
+    )]
+    GraftBlockNotIndexed(String, u64, u64),
+    #[fail(
+        display = "graft base `{}` has pruned its history before block {}, it can not be grafted at block {}",
+        _0, _2, _1
+    )]
+    GraftBlockPruned(String, u64, u64),
+    #[fail(
+        display = "block {} of graft base `{}` is not in the block cache",
+        _0, _1
+    )]
+    GraftBlockNotFound(u64, String),
+    #[fail(
+        display = "graft block {} is one of the last {} blocks of graft base `{}` and can still be reverted",
+        _1, _2, _0
+    )]
+    GraftBlockNotFinal(String, u64, u64),
+    #[fail(
+        display = "graft base `{}` uses JSONB storage and must be stopped before it can be grafted",
+        _0
+    )]
+    GraftBaseNotStopped(String),
+    #[fail(
+        display = "graft base `{}` uses JSONB storage and can only be grafted at its latest block {}, not at block {}",
+        _0, _2, _1
+    )]
+    GraftBlockNotLatest(String, u64, u64),
+    #[fail(
+        display = "subgraph schema is not compatible with the schema of graft base `{}`: {}",
+        _0, _1
+    )]
+    GraftSchemaIncompatible(String, String),
 }
 
 #[derive(Fail, Debug)]
@@ -385,6 +457,8 @@ pub struct Source {
     #[serde(default, deserialize_with = "deserialize_address")]
     pub address: Option<Address>,
     pub abi: String,
+    #[serde(default, rename = "startBlock")]
+    pub start_block: u64,
 }
 
 impl From<EthereumContractSourceEntity> for Source {
@@ -392,6 +466,7 @@ impl From<EthereumContractSourceEntity> for Source {
         Self {
             address: entity.address,
             abi: entity.abi,
+            start_block: entity.start_block,
         }
     }
 }
@@ -467,13 +542,44 @@ pub enum BlockHandlerFilter {
     // Call filter will trigger on all blocks where the data source contract
     // address has been called
     Call,
+    // Polling filter will trigger on every `every`th block, counting from
+    // the data source's start block
+    Polling { every: u64 },
+    // Once filter will trigger only on the data source's start block
+    Once,
+}
+
+impl BlockHandlerFilter {
+    /// Check whether a block handler with this filter should run on the
+    /// block with number `block_number` of a data source that starts at
+    /// `start_block`. Always `false` for call filters, since those depend
+    /// on the calls in the block rather than its number.
+    pub fn matches_block_number(&self, start_block: u64, block_number: u64) -> bool {
+        match self {
+            BlockHandlerFilter::Call => false,
+            BlockHandlerFilter::Polling { every } => {
+                *every > 0
+                    && block_number >= start_block
+                    && (block_number - start_block) % every == 0
+            }
+            BlockHandlerFilter::Once => block_number == start_block,
+        }
+    }
 }
 
 impl From<EthereumBlockHandlerEntity> for MappingBlockHandler {
     fn from(entity: EthereumBlockHandlerEntity) -> Self {
+        let filter = entity.filter.and_then(|filter| {
+            match (filter.kind.as_ref().map(String::as_str), filter.every) {
+                (Some("call"), _) => Some(BlockHandlerFilter::Call),
+                (Some("polling"), Some(every)) => Some(BlockHandlerFilter::Polling { every }),
+                (Some("once"), _) => Some(BlockHandlerFilter::Once),
+                _ => None,
+            }
+        });
         Self {
             handler: entity.handler,
-            filter: None,
+            filter,
         }
     }
 }
@@ -498,6 +604,16 @@ pub struct MappingEventHandler {
     pub event: String,
     pub topic0: Option<H256>,
     pub handler: String,
+    #[serde(default)]
+    pub receipt: bool,
+    #[serde(default)]
+    pub topic1: Option<Vec<H256>>,
+    #[serde(default)]
+    pub topic2: Option<Vec<H256>>,
+    #[serde(default)]
+    pub topic3: Option<Vec<H256>>,
+    #[serde(default)]
+    pub anonymous: bool,
 }
 
 impl MappingEventHandler {
@@ -505,6 +621,49 @@ impl MappingEventHandler {
         self.topic0
             .unwrap_or_else(|| string_to_h256(&self.event.replace("indexed ", "")))
     }
+
+    /// The number of parameters marked as `indexed` in the event signature.
+    pub fn indexed_param_count(&self) -> usize {
+        self.event.matches("indexed ").count()
+    }
+
+    /// Check whether a log is an instance of this handler's event.
+    /// Anonymous events don't include their signature in the topics, so
+    /// they are identified by the number of topics and by whether the log
+    /// can be decoded with the event's ABI in `contract` instead; logs whose
+    /// first topic is one of the `named_signatures` of the other events of
+    /// the contract are never instances of an anonymous event.
+    pub fn matches_signature(
+        &self,
+        log: &Log,
+        contract: &Contract,
+        named_signatures: &[H256],
+    ) -> bool {
+        if self.anonymous {
+            log.topics.len() == self.indexed_param_count()
+                && log
+                    .topics
+                    .first()
+                    .map_or(true, |topic0| !named_signatures.contains(topic0))
+                && contract_event_with_signature(contract, &self.event).map_or(false, |event| {
+                    event
+                        .parse_log(RawLog {
+                            topics: log.topics.clone(),
+                            data: log.data.0.clone(),
+                        })
+                        .is_ok()
+                })
+        } else {
+            log.topics.first() == Some(&self.topic0())
+        }
+    }
+
+    /// Check whether the indexed topics 1-3 of a log are among the values
+    /// this handler is restricted to. Topics without a restriction match
+    /// any value.
+    pub fn matches_topics(&self, topics: &[H256]) -> bool {
+        EthereumEventFilter::topics_match([&self.topic1, &self.topic2, &self.topic3], topics)
+    }
 }
 
 impl From<EthereumContractEventHandlerEntity> for MappingEventHandler {
@@ -513,6 +672,11 @@ impl From<EthereumContractEventHandlerEntity> for MappingEventHandler {
             event: entity.event,
             topic0: entity.topic0,
             handler: entity.handler,
+            receipt: entity.receipt,
+            topic1: entity.topic1,
+            topic2: entity.topic2,
+            topic3: entity.topic3,
+            anonymous: entity.anonymous,
         }
     }
 }
@@ -548,6 +712,17 @@ pub struct Mapping {
     pub link: Link,
 }
 
+impl Mapping {
+    /// The signatures of the named events in the ABI `abi_name`, see
+    /// `graph::util::ethereum::named_event_signatures`
+    pub fn named_event_signatures(&self, abi_name: &str) -> Vec<H256> {
+        self.abis
+            .iter()
+            .find(|abi| abi.name == abi_name)
+            .map_or(vec![], |abi| named_event_signatures(&abi.contract))
+    }
+}
+
 impl UnresolvedMapping {
     pub fn resolve(
         self,
@@ -610,7 +785,11 @@ impl From<EthereumContractMappingEntity> for UnresolvedMapping {
     }
 }
 
-#[derive(Clone, Debug, Hash, Eq, PartialEq, Deserialize)]
+/// Typed key/value data that is passed to a data source when it is created
+/// from a template with `dataSource.createWithContext`.
+pub type DataSourceContext = Entity;
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
 pub struct BaseDataSource<M, T> {
     pub kind: String,
     pub network: Option<String>,
@@ -619,6 +798,8 @@ pub struct BaseDataSource<M, T> {
     pub mapping: M,
     #[serde(default)]
     pub templates: Vec<T>, // Deprecated in manifest spec version 0.0.2
+    #[serde(skip)]
+    pub context: Option<DataSourceContext>,
 }
 
 pub type UnresolvedDataSource = BaseDataSource<UnresolvedMapping, UnresolvedDataSourceTemplate>;
@@ -637,6 +818,7 @@ impl UnresolvedDataSource {
             source,
             mapping,
             templates,
+            context,
         } = self;
 
         info!(logger, "Resolve data source"; "name" => &name);
@@ -658,6 +840,7 @@ impl UnresolvedDataSource {
                 source,
                 mapping,
                 templates,
+                context,
             })
     }
 }
@@ -666,6 +849,8 @@ impl DataSource {
     pub fn try_from_template(
         template: DataSourceTemplate,
         params: &Vec<String>,
+        context: Option<DataSourceContext>,
+        start_block: u64,
     ) -> Result<Self, failure::Error> {
         // Obtain the address from the parameters
         let string = params
@@ -693,9 +878,11 @@ impl DataSource {
             source: Source {
                 address: Some(address),
                 abi: template.source.abi,
+                start_block,
             },
             mapping: template.mapping,
             templates: Vec::new(),
+            context,
         })
     }
 }
@@ -709,6 +896,7 @@ impl From<EthereumContractDataSourceEntity> for UnresolvedDataSource {
             source: entity.source.into(),
             mapping: entity.mapping.into(),
             templates: entity.templates.into_iter().map(Into::into).collect(),
+            context: entity.context,
         }
     }
 }
@@ -782,6 +970,28 @@ impl DataSourceTemplate {
     }
 }
 
+/// How much entity history a deployment keeps. Versions of entities that
+/// stopped being current before the cutoff are removed; history inside the
+/// reorg window is always kept so that reverting blocks keeps working
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Pruning {
+    /// Keep every version that is visible at this block or later. Without
+    /// it, only the versions inside the reorg window are kept
+    pub retention_block: Option<u64>,
+}
+
+/// Start a new deployment from the data of an existing one. All entities
+/// and dynamic data sources of `base` as of `block` are copied into the new
+/// deployment, which then continues indexing with its own mappings from the
+/// block after `block`
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Graft {
+    pub base: SubgraphDeploymentId,
+    pub block: u64,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct BaseSubgraphManifest<S, D, T> {
@@ -794,6 +1004,8 @@ pub struct BaseSubgraphManifest<S, D, T> {
     pub data_sources: Vec<D>,
     #[serde(default)]
     pub templates: Vec<T>,
+    pub pruning: Option<Pruning>,
+    pub graft: Option<Graft>,
 }
 
 /// Consider two subgraphs to be equal if they come from the same IPLD link.
@@ -891,6 +1103,8 @@ impl UnresolvedSubgraphManifest {
             schema,
             data_sources,
             templates,
+            pruning,
+            graft,
         } = self;
 
         match semver::Version::parse(&spec_version) {
@@ -938,6 +1152,8 @@ impl UnresolvedSubgraphManifest {
                     schema,
                     data_sources,
                     templates,
+                    pruning,
+                    graft,
                 }),
         )
     }
@@ -29,7 +29,7 @@ use crate::components::store::{
 };
 use crate::data::graphql::{TryFromValue, ValueMap};
 use crate::data::store::{Entity, NodeId, SubgraphEntityPair, Value, ValueType};
-use crate::data::subgraph::{SubgraphManifest, SubgraphName};
+use crate::data::subgraph::{DataSourceContext, Pruning, SubgraphManifest, SubgraphName};
 use crate::prelude::*;
 
 lazy_static! {
@@ -38,6 +38,10 @@ lazy_static! {
         SubgraphDeploymentId::new("subgraphs").unwrap();
 }
 
+/// Name of the shard that holds the metadata and the chain data, and the
+/// entities of all deployments that are not placed in another shard
+pub const PRIMARY_SHARD: &str = "primary";
+
 /// Generic type for the entity types defined below.
 pub trait TypedEntity {
     const TYPENAME: &'static str;
@@ -214,6 +218,8 @@ pub struct SubgraphDeploymentEntity {
     ethereum_head_block_hash: Option<H256>,
     ethereum_head_block_number: Option<u64>,
     total_ethereum_blocks_count: u64,
+    pruning: Option<Pruning>,
+    graft: Option<(SubgraphDeploymentId, EthereumBlockPointer)>,
 }
 
 impl TypedEntity for SubgraphDeploymentEntity {
@@ -243,6 +249,17 @@ impl SubgraphDeploymentEntity {
             ethereum_head_block_hash: Some(latest_ethereum_block.hash),
             ethereum_head_block_number: Some(latest_ethereum_block.number),
             total_ethereum_blocks_count: latest_ethereum_block.number,
+            pruning: source_manifest.pruning.clone(),
+            graft: None,
+        }
+    }
+
+    /// Make the deployment a graft on `base`; the data of `base` as of
+    /// `block` is copied into the deployment when it is first started
+    pub fn with_graft(self, base: SubgraphDeploymentId, block: EthereumBlockPointer) -> Self {
+        Self {
+            graft: Some((base, block)),
+            ..self
         }
     }
 
@@ -307,6 +324,12 @@ impl SubgraphDeploymentEntity {
         );
         entity.set("totalEthereumBlocksCount", self.total_ethereum_blocks_count);
         entity.set("entityCount", 0 as u64);
+        Self::set_pruning(&mut entity, self.pruning);
+        if let Some((base, block)) = self.graft {
+            entity.set("graftBase", base.to_string());
+            entity.set("graftBlockHash", block.hash_hex());
+            entity.set("graftBlockNumber", block.number);
+        }
         ops.push(set_metadata_operation(
             Self::TYPENAME,
             id.to_string(),
@@ -383,6 +406,111 @@ impl SubgraphDeploymentEntity {
             None,
         )]
     }
+
+    /// Change the pruning settings of the deployment; `None` turns pruning
+    /// off. History that has already been pruned stays pruned
+    pub fn update_pruning_operations(
+        id: &SubgraphDeploymentId,
+        pruning: Option<Pruning>,
+    ) -> Vec<MetadataOperation> {
+        let mut entity = Entity::new();
+        Self::set_pruning(&mut entity, pruning);
+
+        vec![update_metadata_operation(
+            Self::TYPENAME,
+            id.as_str(),
+            entity,
+            None,
+        )]
+    }
+
+    pub fn update_pruned_block_operations(
+        id: &SubgraphDeploymentId,
+        block: BlockNumber,
+    ) -> Vec<MetadataOperation> {
+        let mut entity = Entity::new();
+        entity.set("prunedBlockNumber", block as u64);
+
+        vec![update_metadata_operation(
+            Self::TYPENAME,
+            id.as_str(),
+            entity,
+            None,
+        )]
+    }
+
+    /// Record that the entities of the deployment are stored in `shard`
+    pub fn update_shard_operations(
+        id: &SubgraphDeploymentId,
+        shard: &str,
+    ) -> Vec<MetadataOperation> {
+        let mut entity = Entity::new();
+        entity.set("shard", shard);
+
+        vec![update_metadata_operation(
+            Self::TYPENAME,
+            id.as_str(),
+            entity,
+            None,
+        )]
+    }
+
+    /// Read the pruning settings from a `SubgraphDeployment` entity
+    pub fn pruning(entity: &Entity) -> Option<Pruning> {
+        match entity.get("pruning") {
+            Some(Value::Bool(true)) => Some(Pruning {
+                retention_block: entity
+                    .get("pruningRetentionBlock")
+                    .cloned()
+                    .and_then(|value| value.as_bigint())
+                    .map(|number| number.to_u64()),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Read the graft base and block from a `SubgraphDeployment` entity.
+    /// Returns `None` if the deployment is not a graft
+    pub fn graft(
+        entity: &Entity,
+    ) -> Result<Option<(SubgraphDeploymentId, EthereumBlockPointer)>, Error> {
+        let base = match entity.get("graftBase") {
+            Some(Value::String(base)) => SubgraphDeploymentId::new(base.as_str())
+                .map_err(|()| format_err!("invalid graft base `{}`", base))?,
+            _ => return Ok(None),
+        };
+        let hash = entity
+            .get("graftBlockHash")
+            .and_then(|hash| hash.clone().as_string())
+            .ok_or_else(|| format_err!("SubgraphDeployment is missing graftBlockHash"))?
+            .parse::<H256>()
+            .map_err(|e| format_err!("graftBlockHash: {}", e))?;
+        let number = entity
+            .get("graftBlockNumber")
+            .and_then(|number| number.clone().as_bigint())
+            .ok_or_else(|| format_err!("SubgraphDeployment is missing graftBlockNumber"))?
+            .to_u64();
+        Ok(Some((base, EthereumBlockPointer { hash, number })))
+    }
+
+    /// Read the name of the shard that stores the entities of the
+    /// deployment from a `SubgraphDeployment` entity
+    pub fn shard(entity: &Entity) -> String {
+        match entity.get("shard") {
+            Some(Value::String(shard)) => shard.clone(),
+            _ => PRIMARY_SHARD.to_owned(),
+        }
+    }
+
+    fn set_pruning(entity: &mut Entity, pruning: Option<Pruning>) {
+        entity.set("pruning", pruning.is_some());
+        entity.set(
+            "pruningRetentionBlock",
+            pruning
+                .and_then(|pruning| pruning.retention_block)
+                .map_or(Value::Null, Value::from),
+        );
+    }
 }
 
 #[derive(Debug)]
@@ -491,6 +619,9 @@ pub struct EthereumContractDataSourceEntity {
     pub source: EthereumContractSourceEntity,
     pub mapping: EthereumContractMappingEntity,
     pub templates: Vec<EthereumContractDataSourceTemplateEntity>,
+    /// Only dynamic data sources have a context; it is never written for
+    /// the data sources of a manifest.
+    pub context: Option<DataSourceContext>,
 }
 
 impl TypedEntity for EthereumContractDataSourceEntity {
@@ -547,6 +678,7 @@ impl<'a> From<&'a super::DataSource> for EthereumContractDataSourceEntity {
                 .iter()
                 .map(|template| EthereumContractDataSourceTemplateEntity::from(template))
                 .collect(),
+            context: data_source.context.clone(),
         }
     }
 }
@@ -568,6 +700,10 @@ impl TryFromValue for EthereumContractDataSourceEntity {
             source: map.get_required("source")?,
             mapping: map.get_required("mapping")?,
             templates: map.get_optional("templates")?.unwrap_or_default(),
+            context: map
+                .get_optional::<String>("context")?
+                .map(|context| serde_json::from_str(&context))
+                .transpose()?,
         })
     }
 }
@@ -583,12 +719,39 @@ pub struct DynamicEthereumContractDataSourceEntity {
     source: EthereumContractSourceEntity,
     mapping: EthereumContractMappingEntity,
     templates: Vec<EthereumContractDataSourceTemplateEntity>,
+    /// The context of the data source, serialized as JSON.
+    context: Option<String>,
 }
 
 impl DynamicEthereumContractDataSourceEntity {
     pub fn write_entity_operations(self, id: &str) -> Vec<EntityOperation> {
         WriteOperations::write_entity_operations(self, id)
     }
+
+    /// Query for the dynamic data sources of `deployment` that were created
+    /// at or before `block`
+    pub fn query_up_to(deployment: &SubgraphDeploymentId, block: u64) -> EntityQuery {
+        Self::query().filter(EntityFilter::And(vec![
+            EntityFilter::new_equal("deployment", deployment.to_string()),
+            EntityFilter::LessOrEqual("ethereumBlockNumber".to_owned(), block.into()),
+        ]))
+    }
+
+    /// Copy the dynamic data source `entity` to `deployment`. The copy
+    /// shares the source, mapping and template entities of the original
+    /// since they are never changed once they have been written
+    pub fn copy_operations(
+        mut entity: Entity,
+        deployment: &SubgraphDeploymentId,
+    ) -> Result<Vec<MetadataOperation>, Error> {
+        // Prefixing the original id keeps the copies in the same order
+        // as the originals
+        let id = format!("{}-{}", deployment, entity.id()?);
+        entity.remove("__typename");
+        entity.set("id", id.clone());
+        entity.set("deployment", deployment.to_string());
+        Ok(vec![set_metadata_operation(Self::TYPENAME, id, entity)])
+    }
 }
 
 impl TypedEntity for DynamicEthereumContractDataSourceEntity {
@@ -626,6 +789,7 @@ impl WriteOperations for DynamicEthereumContractDataSourceEntity {
         entity.set("deployment", self.deployment);
         entity.set("ethereumBlockHash", self.ethereum_block_hash);
         entity.set("ethereumBlockNumber", self.ethereum_block_number);
+        entity.set("context", self.context);
         ops.add(Self::TYPENAME, id.to_owned(), entity);
     }
 }
@@ -660,6 +824,9 @@ impl<'a, 'b, 'c>
                 .iter()
                 .map(|template| EthereumContractDataSourceTemplateEntity::from(template))
                 .collect(),
+            context: data_source.context.as_ref().map(|context| {
+                serde_json::to_string(context).expect("failed to serialize data source context")
+            }),
         }
     }
 }
@@ -668,6 +835,7 @@ impl<'a, 'b, 'c>
 pub struct EthereumContractSourceEntity {
     pub address: Option<super::Address>,
     pub abi: String,
+    pub start_block: u64,
 }
 
 impl TypedEntity for EthereumContractSourceEntity {
@@ -681,6 +849,7 @@ impl WriteOperations for EthereumContractSourceEntity {
         entity.set("id", id);
         entity.set("address", self.address);
         entity.set("abi", self.abi);
+        entity.set("startBlock", self.start_block);
         ops.add(Self::TYPENAME, id.to_owned(), entity);
     }
 }
@@ -690,6 +859,7 @@ impl From<super::Source> for EthereumContractSourceEntity {
         Self {
             address: source.address,
             abi: source.abi,
+            start_block: source.start_block,
         }
     }
 }
@@ -707,6 +877,7 @@ impl TryFromValue for EthereumContractSourceEntity {
         Ok(Self {
             address: map.get_optional("address")?,
             abi: map.get_required("abi")?,
+            start_block: map.get_optional("startBlock")?.unwrap_or(0),
         })
     }
 }
@@ -936,6 +1107,17 @@ impl From<super::MappingBlockHandler> for EthereumBlockHandlerEntity {
                 // TODO: Figure out how to use serde to get lowercase spelling here
                 super::BlockHandlerFilter::Call => Some(EthereumBlockHandlerFilterEntity {
                     kind: Some("call".to_string()),
+                    every: None,
+                }),
+                super::BlockHandlerFilter::Polling { every } => {
+                    Some(EthereumBlockHandlerFilterEntity {
+                        kind: Some("polling".to_string()),
+                        every: Some(every),
+                    })
+                }
+                super::BlockHandlerFilter::Once => Some(EthereumBlockHandlerFilterEntity {
+                    kind: Some("once".to_string()),
+                    every: None,
                 }),
             },
             None => None,
@@ -967,6 +1149,7 @@ impl TryFromValue for EthereumBlockHandlerEntity {
 #[derive(Debug)]
 pub struct EthereumBlockHandlerFilterEntity {
     pub kind: Option<String>,
+    pub every: Option<u64>,
 }
 
 impl TypedEntity for EthereumBlockHandlerFilterEntity {
@@ -979,6 +1162,7 @@ impl WriteOperations for EthereumBlockHandlerFilterEntity {
         let mut entity = Entity::new();
         entity.set("id", id);
         entity.set("kind", self.kind);
+        entity.set("every", self.every);
         ops.add(Self::TYPENAME, id.to_owned(), entity)
     }
 }
@@ -997,6 +1181,7 @@ impl TryFromValue for EthereumBlockHandlerFilterEntity {
 
         Ok(Self {
             kind: map.get_optional("kind")?,
+            every: map.get_optional("every")?,
         })
     }
 }
@@ -1053,6 +1238,11 @@ pub struct EthereumContractEventHandlerEntity {
     pub event: String,
     pub topic0: Option<H256>,
     pub handler: String,
+    pub receipt: bool,
+    pub topic1: Option<Vec<H256>>,
+    pub topic2: Option<Vec<H256>>,
+    pub topic3: Option<Vec<H256>>,
+    pub anonymous: bool,
 }
 
 impl TypedEntity for EthereumContractEventHandlerEntity {
@@ -1067,6 +1257,11 @@ impl WriteOperations for EthereumContractEventHandlerEntity {
         entity.set("event", self.event);
         entity.set("topic0", self.topic0.map_or(Value::Null, Value::from));
         entity.set("handler", self.handler);
+        entity.set("receipt", self.receipt);
+        entity.set("topic1", topic_values(self.topic1));
+        entity.set("topic2", topic_values(self.topic2));
+        entity.set("topic3", topic_values(self.topic3));
+        entity.set("anonymous", self.anonymous);
         ops.add(Self::TYPENAME, id.to_owned(), entity);
     }
 }
@@ -1077,6 +1272,11 @@ impl From<super::MappingEventHandler> for EthereumContractEventHandlerEntity {
             event: event_handler.event,
             topic0: event_handler.topic0,
             handler: event_handler.handler,
+            receipt: event_handler.receipt,
+            topic1: event_handler.topic1,
+            topic2: event_handler.topic2,
+            topic3: event_handler.topic3,
+            anonymous: event_handler.anonymous,
         }
     }
 }
@@ -1095,10 +1295,21 @@ impl TryFromValue for EthereumContractEventHandlerEntity {
             event: map.get_required("event")?,
             topic0: map.get_optional("topic0")?,
             handler: map.get_required("handler")?,
+            receipt: map.get_optional("receipt")?.unwrap_or(false),
+            topic1: map.get_optional("topic1")?,
+            topic2: map.get_optional("topic2")?,
+            topic3: map.get_optional("topic3")?,
+            anonymous: map.get_optional("anonymous")?.unwrap_or(false),
         })
     }
 }
 
+fn topic_values(topics: Option<Vec<H256>>) -> Value {
+    topics.map_or(Value::Null, |topics| {
+        Value::List(topics.into_iter().map(Value::from).collect())
+    })
+}
+
 #[derive(Debug)]
 pub struct EthereumContractDataSourceTemplateEntity {
     pub kind: String,
@@ -25,6 +25,7 @@ pub mod prelude {
     pub use ethabi;
     pub use failure::{self, bail, err_msg, format_err, Error, Fail, SyncFailure};
     pub use hex;
+    pub use prometheus;
     pub use serde_derive::{Deserialize, Serialize};
     pub use serde_json;
     pub use slog::{self, crit, debug, error, info, o, trace, warn, Logger};
@@ -43,22 +44,32 @@ pub mod prelude {
         EthereumBlockData, EthereumBlockFilter, EthereumBlockPointer, EthereumBlockTriggerType,
         EthereumBlockWithCalls, EthereumBlockWithTriggers, EthereumCall, EthereumCallData,
         EthereumCallFilter, EthereumContractCall, EthereumContractCallError, EthereumEventData,
-        EthereumLogFilter, EthereumNetworkIdentifier, EthereumTransactionData, EthereumTrigger,
+        EthereumEventFilter, EthereumLogFilter, EthereumNetworkIdentifier, EthereumTransactionData,
+        EthereumTransactionReceiptData, EthereumTrigger,
     };
     pub use crate::components::graphql::{
         GraphQlRunner, QueryResultFuture, SubscriptionResultFuture,
     };
-    pub use crate::components::link_resolver::{JsonStreamValue, JsonValueStream, LinkResolver};
+    pub use crate::components::link_resolver::{
+        JsonStreamFormat, JsonStreamValue, JsonValueStream, LinkResolver, LinkResolverInvalidFile,
+        LinkResolverTimeout,
+    };
+    pub use crate::components::metrics::{
+        Collector, Counter, Gauge, Histogram, HistogramOpts, HistogramVec, MetricsRegistry, Opts,
+        PrometheusError, Registry,
+    };
     pub use crate::components::server::admin::JsonRpcServer;
     pub use crate::components::server::index_node::IndexNodeServer;
+    pub use crate::components::server::metrics::MetricsServer;
     pub use crate::components::server::query::GraphQLServer;
     pub use crate::components::server::subscription::SubscriptionServer;
     pub use crate::components::store::{
-        AttributeIndexDefinition, ChainStore, EntityCache, EntityChange, EntityChangeOperation,
-        EntityFilter, EntityKey, EntityModification, EntityOperation, EntityOrder, EntityQuery,
-        EntityRange, EventSource, HistoryEvent, MetadataOperation, Store, StoreError, StoreEvent,
-        StoreEventStream, StoreEventStreamBox, SubgraphDeploymentStore, TransactionAbortError,
-        SUBSCRIPTION_THROTTLE_INTERVAL,
+        AttributeIndexDefinition, BlockNumber, ChainStore, EntityAggregate, EntityCache,
+        EntityChange, EntityChangeOperation, EntityFilter, EntityKey, EntityLink,
+        EntityModification, EntityOperation, EntityOrder, EntityQuery, EntityRange,
+        EthereumCallCache, EventSource, HistoryEvent, MetadataOperation, Store, StoreError,
+        StoreEvent, StoreEventStream, StoreEventStreamBox, SubgraphDeploymentStore,
+        TransactionAbortError, BLOCK_NUMBER_MAX, SUBSCRIPTION_THROTTLE_INTERVAL,
     };
     pub use crate::components::subgraph::{
         BlockState, DataSourceLoader, DataSourceTemplateInfo, RuntimeHost, RuntimeHostBuilder,
@@ -71,7 +82,7 @@ pub mod prelude {
     pub use crate::data::query::{
         Query, QueryError, QueryExecutionError, QueryResult, QueryVariables,
     };
-    pub use crate::data::schema::Schema;
+    pub use crate::data::schema::{EntityValidationError, GraftIncompatibility, Schema};
     pub use crate::data::store::scalar::{BigDecimal, BigInt, BigIntSign};
     pub use crate::data::store::{
         AssignmentEvent, Attribute, Entity, NodeId, SubgraphEntityPair, SubgraphVersionSummary,
@@ -79,11 +90,12 @@ pub mod prelude {
     };
     pub use crate::data::subgraph::schema::{SubgraphDeploymentEntity, TypedEntity};
     pub use crate::data::subgraph::{
-        BlockHandlerFilter, CreateSubgraphResult, DataSource, DataSourceTemplate, Link, MappingABI,
-        MappingBlockHandler, MappingCallHandler, MappingEventHandler,
-        SubgraphAssignmentProviderError, SubgraphAssignmentProviderEvent, SubgraphDeploymentId,
-        SubgraphManifest, SubgraphManifestResolveError, SubgraphManifestValidationError,
-        SubgraphName, SubgraphRegistrarError,
+        BlockHandlerFilter, CreateSubgraphResult, DataSource, DataSourceContext,
+        DataSourceTemplate, Graft, Link, MappingABI, MappingBlockHandler, MappingCallHandler,
+        MappingEventHandler, Pruning, SubgraphAssignmentProviderError,
+        SubgraphAssignmentProviderEvent, SubgraphDeploymentId, SubgraphManifest,
+        SubgraphManifestResolveError, SubgraphManifestValidationError, SubgraphName,
+        SubgraphRegistrarError,
     };
     pub use crate::data::subscription::{
         QueryResultStream, Subscription, SubscriptionError, SubscriptionResult,
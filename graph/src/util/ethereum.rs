@@ -78,6 +78,20 @@ fn event_signature(event: &Event) -> String {
     )
 }
 
+/// Returns the hashes of the signatures of all events in the contract that
+/// are not anonymous, sorted and without duplicates. These hashes are the
+/// first topic of every log of such an event.
+pub fn named_event_signatures(contract: &Contract) -> Vec<H256> {
+    let mut signatures = contract
+        .events()
+        .filter(|event| !event.anonymous)
+        .map(|event| string_to_h256(&ambiguous_event_signature(event)))
+        .collect::<Vec<_>>();
+    signatures.sort();
+    signatures.dedup();
+    signatures
+}
+
 /// Returns the contract event with the given signature, if it exists.
 pub fn contract_event_with_signature<'a>(
     contract: &'a Contract,
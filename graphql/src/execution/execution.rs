@@ -14,6 +14,19 @@ use crate::query::ast as qast;
 use crate::schema::ast as sast;
 use crate::values::coercion;
 
+/// Prefix of the keys under which `prefetch` stores the values it loaded
+/// for a field in the parent object of that field.
+const PREFETCHED_PREFIX: &str = "__prefetched__";
+
+/// The complexity of an aggregate field
+const AGGREGATE_COMPLEXITY: u64 = 10;
+
+/// The key under which the prefetched value for the field with the given
+/// response key is stored in its parent object.
+fn prefetched_key(response_key: &str) -> String {
+    format!("{}{}", PREFETCHED_PREFIX, response_key)
+}
+
 /// Contextual information passed around during query execution.
 #[derive(Clone)]
 pub struct ExecutionContext<'a, R>
@@ -43,6 +56,9 @@ where
 
     /// Max value for `first`.
     pub max_first: u32,
+
+    /// The block at which entities are resolved.
+    pub block: BlockNumber,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -77,6 +93,7 @@ where
             variable_values: self.variable_values.clone(),
             deadline: self.deadline,
             max_first: std::u32::MAX,
+            block: self.block,
         }
     }
 
@@ -175,8 +192,20 @@ where
                         }
                         .ok_or(Invalid)?;
 
+                        // Aggregates return a single object of scalars that the
+                        // database computes; they cost a small constant.
+                        let field_type =
+                            get_type_definition_from_type(schema, &s_field.field_type)?;
+                        if let s::TypeDefinition::Object(object_type) = &field_type {
+                            if sast::get_aggregated_type_name(object_type).is_some() {
+                                return total_complexity
+                                    .checked_add(AGGREGATE_COMPLEXITY)
+                                    .ok_or(Overflow);
+                            }
+                        }
+
                         let field_complexity = self.query_complexity(
-                            &get_type_definition_from_type(schema, &s_field.field_type)?,
+                            &field_type,
                             &field.selection_set,
                             max_depth,
                             depth + 1,
@@ -510,19 +539,134 @@ fn execute_field<'a, R>(
 where
     R: Resolver,
 {
-    coerce_argument_values(ctx, object_type, field)
-        .and_then(|argument_values| {
-            resolve_field_value(
-                ctx,
-                object_type,
-                object_value,
+    let argument_values = coerce_argument_values(ctx, object_type, field)?;
+
+    // The `block` argument of a top-level field determines the block at which
+    // that field and all fields nested in it are resolved
+    let ctx = match object_value {
+        None => {
+            let mut ctx = ctx.clone();
+            ctx.block = ctx
+                .resolver
+                .resolve_block(&ctx.schema.id, field, &argument_values)
+                .map_err(|e| vec![e])?;
+            ctx
+        }
+        Some(_) => ctx.clone(),
+    };
+
+    // Use the value that `prefetch` loaded together with the values of this
+    // field for the siblings of `object_value`, if there is one
+    let prefetched = match object_value {
+        Some(q::Value::Object(object)) => {
+            object.get(&prefetched_key(qast::get_response_key(field)))
+        }
+        _ => None,
+    };
+    match prefetched {
+        Some(value) => Ok(value.clone()),
+        None => resolve_field_value(
+            &ctx,
+            object_type,
+            object_value,
+            field,
+            field_definition,
+            &field_definition.field_type,
+            &argument_values,
+        ),
+    }
+    .and_then(|value| complete_value(&ctx, field, &field_definition.field_type, fields, value))
+}
+
+/// Loads the fields of `selection_set` that reference other objects for all
+/// `objects` of type `object_type` at once, and stores the result for each
+/// object in the object itself, where `execute_field` picks it up. This
+/// continues recursively for the objects that were loaded, so that every
+/// level of the result takes one store query per field rather than one
+/// per field and parent object.
+fn prefetch<'a, R>(
+    ctx: &ExecutionContext<'a, R>,
+    object_type: &s::ObjectType,
+    selection_set: &'a q::SelectionSet,
+    mut objects: Vec<&mut q::Value>,
+) -> Result<(), Vec<QueryExecutionError>>
+where
+    R: Resolver,
+{
+    if objects.is_empty() {
+        return Ok(());
+    }
+
+    for (response_key, fields) in collect_fields(ctx.clone(), object_type, selection_set, None) {
+        let key = prefetched_key(response_key);
+        let already_prefetched = objects.iter().all(|object| match object {
+            q::Value::Object(object) => object.contains_key(&key),
+            _ => true,
+        });
+        if already_prefetched {
+            continue;
+        }
+
+        // Unknown fields are reported when the selection set is executed
+        let field = fields[0];
+        let field_definition = match sast::get_field(object_type, &field.name) {
+            Some(field_definition) => field_definition,
+            None => continue,
+        };
+        let named_type =
+            match sast::get_type_definition_from_field(&ctx.schema.document, field_definition) {
+                Some(named_type) => named_type,
+                None => continue,
+            };
+        let child_type = match named_type {
+            s::TypeDefinition::Object(t) => ObjectOrInterface::from(t),
+            s::TypeDefinition::Interface(t) => ObjectOrInterface::from(t),
+            _ => continue,
+        };
+
+        let ctx = ctx.for_field(field);
+        let argument_values = coerce_argument_values(&ctx, object_type, field)?;
+        let parents: Vec<_> = objects.iter().map(|object| &**object).collect();
+        let mut values = match ctx
+            .resolver
+            .prefetch_objects(
+                &parents,
                 field,
                 field_definition,
-                &field_definition.field_type,
+                child_type,
                 &argument_values,
+                &ctx.schema,
+                ctx.block,
+                ctx.max_first,
             )
-        })
-        .and_then(|value| complete_value(ctx, field, &field_definition.field_type, fields, value))
+            .map_err(|e| vec![e])?
+        {
+            Some(values) => values,
+            None => continue,
+        };
+
+        // Load the next level for the children of all objects together.
+        // The children of an interface field can be of different types,
+        // and are left to be prefetched when their values are completed
+        if let s::TypeDefinition::Object(child_type) = named_type {
+            let children: Vec<_> = values
+                .iter_mut()
+                .flat_map(|value| match value {
+                    q::Value::List(values) => values.iter_mut().collect::<Vec<_>>(),
+                    q::Value::Null => vec![],
+                    value => vec![value],
+                })
+                .collect();
+            prefetch(&ctx, child_type, &merge_selection_sets(fields), children)?;
+        }
+
+        for (object, value) in objects.iter_mut().zip(values) {
+            if let q::Value::Object(object) = object {
+                object.insert(key.clone(), value);
+            }
+        }
+    }
+    Ok(())
 }
 
 /// Resolves the value of a field.
@@ -597,7 +741,8 @@ where
             field_definition,
             t.into(),
             argument_values,
-            ctx.schema.types_for_interface(),
+            &ctx.schema,
+            ctx.block,
         ),
 
         // Let the resolver decide how values in the resolved object value
@@ -626,7 +771,8 @@ where
             field_definition,
             i.into(),
             argument_values,
-            ctx.schema.types_for_interface(),
+            &ctx.schema,
+            ctx.block,
         ),
 
         s::TypeDefinition::Union(_) => Err(QueryExecutionError::Unimplemented("unions".to_owned())),
@@ -675,7 +821,8 @@ where
                         field_definition,
                         t.into(),
                         argument_values,
-                        ctx.schema.types_for_interface(),
+                        &ctx.schema,
+                        ctx.block,
                         ctx.max_first,
                     )
                     .map_err(|e| vec![e]),
@@ -708,7 +855,8 @@ where
                         field_definition,
                         t.into(),
                         argument_values,
-                        ctx.schema.types_for_interface(),
+                        &ctx.schema,
+                        ctx.block,
                         ctx.max_first,
                     )
                     .map_err(|e| vec![e]),
@@ -762,8 +910,20 @@ where
         // Complete list values
         s::Type::ListType(inner_type) => {
             match resolved_value {
-                // Complete list values individually
-                q::Value::List(values) => {
+                // Complete list values individually, after loading the
+                // fields they reference for all of them at once
+                q::Value::List(mut values) => {
+                    if let Some(s::TypeDefinition::Object(object_type)) =
+                        sast::unpack_type(&ctx.schema.document, inner_type)
+                    {
+                        prefetch(
+                            ctx,
+                            object_type,
+                            &merge_selection_sets(fields.clone()),
+                            values.iter_mut().collect(),
+                        )?;
+                    }
+
                     let mut errors = Vec::new();
                     let mut out = Vec::with_capacity(values.len());
                     for value in values.into_iter() {
@@ -3,7 +3,10 @@ use std::collections::{BTreeMap, HashMap};
 
 use crate::prelude::*;
 use crate::schema::ast::get_named_type;
-use graph::prelude::{QueryExecutionError, StoreEventStreamBox};
+use graph::prelude::{
+    BlockNumber, QueryExecutionError, Schema, StoreEventStreamBox, SubgraphDeploymentId,
+    BLOCK_NUMBER_MAX,
+};
 
 #[derive(Copy, Clone, Debug)]
 pub enum ObjectOrInterface<'a> {
@@ -56,7 +59,8 @@ pub trait Resolver: Clone + Send + Sync {
         field_definition: &s::Field,
         object_type: ObjectOrInterface<'_>,
         arguments: &HashMap<&q::Name, q::Value>,
-        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        schema: &Schema,
+        block: BlockNumber,
         max_first: u32,
     ) -> Result<q::Value, QueryExecutionError>;
 
@@ -68,9 +72,41 @@ pub trait Resolver: Clone + Send + Sync {
         field_definition: &s::Field,
         object_type: ObjectOrInterface<'_>,
         arguments: &HashMap<&q::Name, q::Value>,
-        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        schema: &Schema,
+        block: BlockNumber,
     ) -> Result<q::Value, QueryExecutionError>;
 
+    /// Resolves the objects that `field` references for all `parents` at
+    /// once, which lets the executor load a field for all objects on one
+    /// level of the result with a single store query. Returns one value per
+    /// parent, in the order of `parents`: a list for list fields, and an
+    /// object or `null` otherwise. Resolvers that can not do that return
+    /// `None`, and the field is resolved for each parent separately.
+    fn prefetch_objects(
+        &self,
+        _parents: &[&q::Value],
+        _field: &q::Field,
+        _field_definition: &s::Field,
+        _object_type: ObjectOrInterface<'_>,
+        _arguments: &HashMap<&q::Name, q::Value>,
+        _schema: &Schema,
+        _block: BlockNumber,
+        _max_first: u32,
+    ) -> Result<Option<Vec<q::Value>>, QueryExecutionError> {
+        Ok(None)
+    }
+
+    /// Resolves the `block` argument of a top-level field into the number of
+    /// the block at which that field and all fields nested in it are resolved.
+    fn resolve_block(
+        &self,
+        _subgraph_id: &SubgraphDeploymentId,
+        _field: &q::Field,
+        _arguments: &HashMap<&q::Name, q::Value>,
+    ) -> Result<BlockNumber, QueryExecutionError> {
+        Ok(BLOCK_NUMBER_MAX)
+    }
+
     /// Resolves an enum value for a given enum type.
     fn resolve_enum_value(
         &self,
@@ -462,7 +462,8 @@ impl<'a> Resolver for IntrospectionResolver<'a> {
         _field_definition: &s::Field,
         _object_type: ObjectOrInterface<'_>,
         _arguments: &HashMap<&q::Name, q::Value>,
-        _types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        _schema: &Schema,
+        _block: BlockNumber,
         _max_first: u32,
     ) -> Result<q::Value, QueryExecutionError> {
         match field.as_str() {
@@ -501,7 +502,8 @@ impl<'a> Resolver for IntrospectionResolver<'a> {
         _field_definition: &s::Field,
         _object_type: ObjectOrInterface<'_>,
         arguments: &HashMap<&q::Name, q::Value>,
-        _: &BTreeMap<Name, Vec<ObjectType>>,
+        _: &Schema,
+        _: BlockNumber,
     ) -> Result<q::Value, QueryExecutionError> {
         let object = match field.name.as_str() {
             "__schema" => self.schema_object(),
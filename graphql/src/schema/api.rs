@@ -1,4 +1,5 @@
 use crate::schema::ast;
+use graph::data::schema::{FulltextDefinition, SCHEMA_TYPE_NAME};
 use graph::prelude::*;
 use graphql_parser::schema::{Value, *};
 use graphql_parser::Pos;
@@ -10,6 +11,8 @@ pub enum APISchemaError {
     TypeExists(String),
     #[fail(display = "Type {} not found", _0)]
     TypeNotFound(String),
+    #[fail(display = "{}", _0)]
+    InvalidFulltextDirective(String),
 }
 
 /// Derives a full-fledged GraphQL API schema from an input schema.
@@ -20,21 +23,42 @@ pub enum APISchemaError {
 /// types.
 pub fn api_schema(input_schema: &Document) -> Result<Document, APISchemaError> {
     // Refactor: Take `input_schema` by value.
-    let object_types = ast::get_object_type_definitions(input_schema);
+    let object_types = ast::get_object_type_definitions(input_schema)
+        .into_iter()
+        .filter(|object_type| object_type.name != SCHEMA_TYPE_NAME)
+        .collect::<Vec<_>>();
     let interface_types = ast::get_interface_type_definitions(input_schema);
+    let fulltext_definitions = FulltextDefinition::from_document(input_schema)
+        .map_err(|e| APISchemaError::InvalidFulltextDirective(e.to_string()))?;
 
     // Refactor: Don't clone the schema.
     let mut schema = input_schema.clone();
+    remove_schema_type(&mut schema);
     add_builtin_scalar_types(&mut schema)?;
     add_order_direction_enum(&mut schema);
+    add_block_height_type(&mut schema);
     add_field_arguments(&mut schema, &input_schema)?;
     add_types_for_object_types(&mut schema, &object_types)?;
     add_types_for_interface_types(&mut schema, &interface_types)?;
-    add_query_type(&mut schema, &object_types, &interface_types)?;
+    add_query_type(
+        &mut schema,
+        input_schema,
+        &object_types,
+        &interface_types,
+        &fulltext_definitions,
+    )?;
     add_subscription_type(&mut schema, &object_types, &interface_types)?;
     Ok(schema)
 }
 
+/// Removes the `_Schema_` type, which only holds schema-level directives.
+fn remove_schema_type(schema: &mut Document) {
+    schema.definitions.retain(|definition| match definition {
+        Definition::TypeDefinition(TypeDefinition::Object(t)) => t.name != SCHEMA_TYPE_NAME,
+        _ => true,
+    });
+}
+
 /// Adds built-in GraphQL scalar types (`Int`, `String` etc.) to the schema.
 fn add_builtin_scalar_types(schema: &mut Document) -> Result<(), APISchemaError> {
     for name in [
@@ -86,6 +110,31 @@ fn add_order_direction_enum(schema: &mut Document) {
     schema.definitions.push(def);
 }
 
+/// Adds a global `Block_height` input type to the schema. It is used to
+/// select the block at which a query is run.
+fn add_block_height_type(schema: &mut Document) {
+    let typedef = TypeDefinition::InputObject(InputObjectType {
+        position: Pos::default(),
+        description: None,
+        name: "Block_height".to_string(),
+        directives: vec![],
+        fields: vec![
+            input_value(
+                &"hash".to_string(),
+                "",
+                Type::NamedType("Bytes".to_string()),
+            ),
+            input_value(
+                &"number".to_string(),
+                "",
+                Type::NamedType("Int".to_string()),
+            ),
+        ],
+    });
+    let def = Definition::TypeDefinition(typedef);
+    schema.definitions.push(def);
+}
+
 fn add_types_for_object_types(
     schema: &mut Document,
     object_types: &Vec<&ObjectType>,
@@ -93,11 +142,13 @@ fn add_types_for_object_types(
     for object_type in object_types {
         add_order_by_type(schema, &object_type.name, &object_type.fields)?;
         add_filter_type(schema, &object_type.name, &object_type.fields)?;
+        add_aggregate_type(schema, &object_type.name, &object_type.fields)?;
     }
     Ok(())
 }
 
-/// Adds `*_orderBy` and `*_filter` enum types for the given interfaces to the schema.
+/// Adds `*_orderBy` and `*_filter` enum types and `*_aggregate` object types
+/// for the given interfaces to the schema.
 fn add_types_for_interface_types(
     schema: &mut Document,
     interface_types: &[&InterfaceType],
@@ -105,6 +156,7 @@ fn add_types_for_interface_types(
     for interface_type in interface_types {
         add_order_by_type(schema, &interface_type.name, &interface_type.fields)?;
         add_filter_type(schema, &interface_type.name, &interface_type.fields)?;
+        add_aggregate_type(schema, &interface_type.name, &interface_type.fields)?;
     }
     Ok(())
 }
@@ -177,6 +229,72 @@ fn add_filter_type(
     Ok(())
 }
 
+/// Adds a `<type_name>_aggregate` object type to the schema. It has a
+/// `count` field, and `<field>_sum`, `<field>_min`, `<field>_max` and
+/// `<field>_avg` fields for each numeric field. The `@aggregate` directive
+/// records the type that is aggregated over.
+fn add_aggregate_type(
+    schema: &mut Document,
+    type_name: &Name,
+    fields: &[Field],
+) -> Result<(), APISchemaError> {
+    let aggregate_type_name = format!("{}_aggregate", type_name);
+    if ast::get_named_type(schema, &aggregate_type_name).is_some() {
+        return Err(APISchemaError::TypeExists(aggregate_type_name));
+    }
+
+    let aggregate_field = |name: String, type_name: &str| Field {
+        position: Pos::default(),
+        description: None,
+        name,
+        arguments: vec![],
+        field_type: Type::NamedType(type_name.to_owned()),
+        directives: vec![],
+    };
+
+    let mut aggregate_fields = vec![Field {
+        field_type: Type::NonNullType(Box::new(Type::NamedType("BigInt".to_owned()))),
+        ..aggregate_field("count".to_owned(), "BigInt")
+    }];
+    for field in fields {
+        let field_type = match &field.field_type {
+            Type::NonNullType(inner) => inner.as_ref(),
+            field_type => field_type,
+        };
+        let field_type = match field_type {
+            Type::NamedType(name) if ["Int", "BigInt", "BigDecimal"].contains(&name.as_str()) => {
+                name.as_str()
+            }
+            _ => continue,
+        };
+        let sum_type = if field_type == "BigDecimal" {
+            "BigDecimal"
+        } else {
+            "BigInt"
+        };
+        aggregate_fields.push(aggregate_field(format!("{}_sum", field.name), sum_type));
+        aggregate_fields.push(aggregate_field(format!("{}_min", field.name), field_type));
+        aggregate_fields.push(aggregate_field(format!("{}_max", field.name), field_type));
+        aggregate_fields.push(aggregate_field(format!("{}_avg", field.name), "BigDecimal"));
+    }
+
+    let typedef = TypeDefinition::Object(ObjectType {
+        position: Pos::default(),
+        description: None,
+        name: aggregate_type_name,
+        implements_interfaces: vec![],
+        directives: vec![Directive {
+            position: Pos::default(),
+            name: "aggregate".to_owned(),
+            arguments: vec![("entity".to_owned(), Value::String(type_name.to_owned()))],
+        }],
+        fields: aggregate_fields,
+    });
+    let def = Definition::TypeDefinition(typedef);
+    schema.definitions.push(def);
+    Ok(())
+}
+
 /// Generates `*_filter` input values for the given set of fields.
 fn field_input_values(
     schema: &Document,
@@ -205,9 +323,9 @@ fn field_filter_input_values(
                 .ok_or_else(|| APISchemaError::TypeNotFound(name.clone()))?;
             Ok(match named_type {
                 TypeDefinition::Object(_) | TypeDefinition::Interface(_) => {
-                    // Only add `where` filter fields for object and interface fields
-                    // if they are not @derivedFrom
-                    if ast::get_derived_from_directive(field).is_some() {
+                    // Only add `where` filter fields that compare ids for
+                    // object and interface fields if they are not @derivedFrom
+                    let mut input_values = if ast::get_derived_from_directive(field).is_some() {
                         vec![]
                     } else {
                         // We allow filtering with `where: { other: "some-id" }` and
@@ -219,7 +337,9 @@ fn field_filter_input_values(
                             field,
                             &ScalarType::new(Name::from("String")),
                         )
-                    }
+                    };
+                    input_values.push(field_child_filter_input_value(field, name));
+                    input_values
                 }
                 TypeDefinition::Scalar(ref t) => field_scalar_filter_input_values(schema, field, t),
                 TypeDefinition::Enum(ref t) => field_enum_filter_input_values(schema, field, t),
@@ -310,17 +430,21 @@ fn field_list_filter_input_values(
     ast::get_type_definition_from_type(schema, field_type).and_then(|typedef| {
         // Decide what type of values can be passed to the filter. In the case
         // one-to-many or many-to-many object or interface fields that are not
-        // derived, we allow ID strings to be passed on.
-        let input_field_type = match typedef {
+        // derived, we allow ID strings to be passed on. All object and
+        // interface fields can also be filtered by the properties of the
+        // entities they reference.
+        let (input_field_type, child_filter) = match typedef {
             TypeDefinition::Interface(_) | TypeDefinition::Object(_) => {
+                let child_filter =
+                    field_child_filter_input_value(field, ast::get_type_name(typedef));
                 if ast::get_derived_from_directive(field).is_some() {
-                    return None;
+                    return Some(vec![child_filter]);
                 } else {
-                    Type::NamedType("String".into())
+                    (Type::NamedType("String".into()), Some(child_filter))
                 }
             }
-            TypeDefinition::Scalar(ref t) => Type::NamedType(t.name.to_owned()),
-            TypeDefinition::Enum(ref t) => Type::NamedType(t.name.to_owned()),
+            TypeDefinition::Scalar(ref t) => (Type::NamedType(t.name.to_owned()), None),
+            TypeDefinition::Enum(ref t) => (Type::NamedType(t.name.to_owned()), None),
             TypeDefinition::InputObject(_) | TypeDefinition::Union(_) => return None,
         };
 
@@ -336,11 +460,25 @@ fn field_list_filter_input_values(
                         )))),
                     )
                 })
+                .chain(child_filter)
                 .collect(),
         )
     })
 }
 
+/// Generates a `<field>_` input value that filters by the properties of
+/// the entities the given field references, using their `*_filter` type.
+fn field_child_filter_input_value(field: &Field, type_name: &Name) -> InputValue {
+    InputValue {
+        position: Pos::default(),
+        description: None,
+        name: format!("{}_", field.name),
+        value_type: Type::NamedType(format!("{}_filter", type_name)),
+        default_value: None,
+        directives: vec![],
+    }
+}
+
 /// Generates a `*_filter` input value for the given field name, suffix and value type.
 fn input_value(name: &Name, suffix: &'static str, value_type: Type) -> InputValue {
     InputValue {
@@ -360,8 +498,10 @@ fn input_value(name: &Name, suffix: &'static str, value_type: Type) -> InputValu
 /// Adds a root `Query` object type to the schema.
 fn add_query_type(
     schema: &mut Document,
+    input_schema: &Document,
     object_types: &[&ObjectType],
     interface_types: &[&InterfaceType],
+    fulltext_definitions: &[FulltextDefinition],
 ) -> Result<(), APISchemaError> {
     let type_name = String::from("Query");
 
@@ -380,6 +520,15 @@ fn add_query_type(
             .map(|t| &t.name)
             .chain(interface_types.iter().map(|t| &t.name))
             .flat_map(|name| query_fields_for_type(schema, name))
+            .chain(
+                fulltext_definitions
+                    .iter()
+                    .map(|definition| fulltext_query_field(input_schema, definition)),
+            )
+            .map(|mut field| {
+                field.arguments.push(block_argument());
+                field
+            })
             .collect(),
     });
     let def = Definition::TypeDefinition(typedef);
@@ -387,6 +536,16 @@ fn add_query_type(
     Ok(())
 }
 
+/// Generates the `block` argument that top-level query fields use to
+/// query the state of entities as of a past block.
+fn block_argument() -> InputValue {
+    input_value(
+        &"block".to_string(),
+        "",
+        Type::NamedType("Block_height".to_string()),
+    )
+}
+
 /// Adds a root `Subscription` object type to the schema.
 fn add_subscription_type(
     schema: &mut Document,
@@ -417,9 +576,23 @@ fn add_subscription_type(
     Ok(())
 }
 
-/// Generates `Query` fields for the given type name (e.g. `users` and `user`).
+/// Generates `Query` fields for the given type name (e.g. `users`, `user`
+/// and `userAggregate`).
 fn query_fields_for_type(schema: &Document, type_name: &Name) -> Vec<Field> {
     let input_objects = ast::get_input_object_definitions(schema);
+
+    // Not all types have filter types, see comment in `add_filter_type`.
+    let filter_name = format!("{}_filter", type_name);
+    let aggregate_arguments = if input_objects.iter().any(|o| o.name == filter_name) {
+        vec![input_value(
+            &"where".to_string(),
+            "",
+            Type::NamedType(filter_name),
+        )]
+    } else {
+        vec![]
+    };
+
     vec![
         Field {
             position: Pos::default(),
@@ -446,9 +619,53 @@ fn query_fields_for_type(schema: &Document, type_name: &Name) -> Vec<Field> {
             ))))),
             directives: vec![],
         },
+        Field {
+            position: Pos::default(),
+            description: None,
+            name: format!("{}Aggregate", type_name.as_str().to_camel_case()),
+            arguments: aggregate_arguments,
+            field_type: Type::NonNullType(Box::new(Type::NamedType(format!(
+                "{}_aggregate",
+                type_name
+            )))),
+            directives: vec![],
+        },
     ]
 }
 
+/// Generates the `Query` field for a full-text search (e.g. `bandSearch`).
+/// Its results are ranked by how well they match the `text` argument.
+fn fulltext_query_field(input_schema: &Document, definition: &FulltextDefinition) -> Field {
+    let result_type = definition
+        .result_type(input_schema)
+        .expect("the result type of full-text searches is checked during schema validation");
+
+    let mut skip = input_value(&"skip".to_string(), "", Type::NamedType("Int".to_string()));
+    skip.default_value = Some(Value::Int(0.into()));
+
+    let mut first = input_value(&"first".to_string(), "", Type::NamedType("Int".to_string()));
+    first.default_value = Some(Value::Int(100.into()));
+
+    Field {
+        position: Pos::default(),
+        description: None,
+        name: definition.name.clone(),
+        arguments: vec![
+            input_value(
+                &"text".to_string(),
+                "",
+                Type::NonNullType(Box::new(Type::NamedType("String".to_string()))),
+            ),
+            skip,
+            first,
+        ],
+        field_type: Type::NonNullType(Box::new(Type::ListType(Box::new(Type::NonNullType(
+            Box::new(Type::NamedType(result_type)),
+        ))))),
+        directives: vec![],
+    }
+}
+
 /// Generates arguments for collection queries of a named type (e.g. User).
 fn collection_arguments_for_named_type(
     input_objects: &[InputObjectType],
@@ -466,6 +683,7 @@ fn collection_arguments_for_named_type(
     let mut args = vec![
         skip,
         first,
+        input_value(&"after".to_string(), "", Type::NamedType("ID".to_string())),
         input_value(
             &"orderBy".to_string(),
             "",
@@ -711,6 +929,7 @@ mod tests {
                 "pets_not",
                 "pets_contains",
                 "pets_not_contains",
+                "pets_",
                 "favoritePet",
                 "favoritePet_not",
                 "favoritePet_gt",
@@ -725,6 +944,9 @@ mod tests {
                 "favoritePet_not_starts_with",
                 "favoritePet_ends_with",
                 "favoritePet_not_ends_with",
+                "favoritePet_",
+                "leastFavoritePet_",
+                "mostFavoritePets_",
             ]
             .iter()
             .map(|name| name.to_string())
@@ -760,7 +982,7 @@ mod tests {
                 .iter()
                 .map(|input_value| input_value.name.to_owned())
                 .collect::<Vec<String>>(),
-            vec!["id".to_string()],
+            vec!["id".to_string(), "block".to_string()],
         );
 
         let user_plural_field = match query_type {
@@ -782,10 +1004,18 @@ mod tests {
                 .iter()
                 .map(|input_value| input_value.name.to_owned())
                 .collect::<Vec<String>>(),
-            ["skip", "first", "orderBy", "orderDirection", "where",]
-                .into_iter()
-                .map(|name| name.to_string())
-                .collect::<Vec<String>>()
+            [
+                "skip",
+                "first",
+                "after",
+                "orderBy",
+                "orderDirection",
+                "where",
+                "block"
+            ]
+            .into_iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<String>>()
         );
 
         let user_profile_singular_field = match query_type {
@@ -844,7 +1074,7 @@ mod tests {
                 .iter()
                 .map(|input_value| input_value.name.to_owned())
                 .collect::<Vec<String>>(),
-            vec!["id".to_string()],
+            vec!["id".to_string(), "block".to_string()],
         );
 
         let plural_field = match query_type {
@@ -866,7 +1096,130 @@ mod tests {
                 .iter()
                 .map(|input_value| input_value.name.to_owned())
                 .collect::<Vec<String>>(),
-            ["skip", "first", "orderBy", "orderDirection", "where",]
+            [
+                "skip",
+                "first",
+                "after",
+                "orderBy",
+                "orderDirection",
+                "where",
+                "block"
+            ]
+            .into_iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<String>>()
+        );
+    }
+
+    #[test]
+    fn api_schema_contains_aggregate_fields_on_query_type() {
+        let input_schema = parse_schema(
+            "type Token { id: ID!, name: String!, supply: BigInt!, price: BigDecimal, \
+                          holders: Int, prices: [BigDecimal!]! }",
+        )
+        .expect("Failed to parse input schema");
+        let schema = api_schema(&input_schema).expect("Failed to derived API schema");
+
+        let query_type = ast::get_named_type(&schema, &"Query".to_string())
+            .expect("Query type is missing in derived API schema");
+
+        let aggregate_field = match query_type {
+            TypeDefinition::Object(t) => ast::get_field(t, &"tokenAggregate".to_string()),
+            _ => None,
+        }
+        .expect("\"tokenAggregate\" field is missing on Query type");
+
+        assert_eq!(
+            aggregate_field.field_type,
+            Type::NonNullType(Box::new(Type::NamedType("Token_aggregate".to_string())))
+        );
+        assert_eq!(
+            aggregate_field
+                .arguments
+                .iter()
+                .map(|input_value| input_value.name.to_owned())
+                .collect::<Vec<String>>(),
+            vec!["where".to_string(), "block".to_string()],
+        );
+
+        let aggregate_type = match ast::get_named_type(&schema, &"Token_aggregate".to_string()) {
+            Some(TypeDefinition::Object(t)) => t,
+            _ => panic!("Token_aggregate type is missing in derived API schema"),
+        };
+        assert_eq!(
+            ast::get_aggregated_type_name(aggregate_type),
+            Some(&"Token".to_string())
+        );
+        assert_eq!(
+            aggregate_type
+                .fields
+                .iter()
+                .map(|field| format!("{}: {}", field.name, field.field_type))
+                .collect::<Vec<String>>(),
+            [
+                "count: BigInt!",
+                "supply_sum: BigInt",
+                "supply_min: BigInt",
+                "supply_max: BigInt",
+                "supply_avg: BigDecimal",
+                "price_sum: BigDecimal",
+                "price_min: BigDecimal",
+                "price_max: BigDecimal",
+                "price_avg: BigDecimal",
+                "holders_sum: BigInt",
+                "holders_min: Int",
+                "holders_max: Int",
+                "holders_avg: BigDecimal",
+            ]
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<String>>()
+        );
+    }
+
+    #[test]
+    fn api_schema_contains_fulltext_fields_on_query_type() {
+        let input_schema = parse_schema(
+            r#"
+            type _Schema_
+              @fulltext(
+                name: "userSearch"
+                language: en
+                algorithm: rank
+                include: [{ entity: "User", fields: [{ name: "name" }] }]
+              )
+
+            type User @entity { id: ID!, name: String! }
+            "#,
+        )
+        .expect("Failed to parse input schema");
+        let schema = api_schema(&input_schema).expect("Failed to derive API schema");
+
+        assert!(ast::get_named_type(&schema, &"_Schema_".to_string()).is_none());
+
+        let query_type = ast::get_named_type(&schema, &"Query".to_string())
+            .expect("Query type is missing in derived API schema");
+
+        let search_field = match query_type {
+            TypeDefinition::Object(ref t) => ast::get_field(t, &"userSearch".to_string()),
+            _ => None,
+        }
+        .expect("\"userSearch\" field is missing on Query type");
+
+        assert_eq!(
+            search_field.field_type,
+            Type::NonNullType(Box::new(Type::ListType(Box::new(Type::NonNullType(
+                Box::new(Type::NamedType("User".to_string()))
+            )))))
+        );
+
+        assert_eq!(
+            search_field
+                .arguments
+                .iter()
+                .map(|input_value| input_value.name.to_owned())
+                .collect::<Vec<String>>(),
+            vec!["text", "skip", "first", "block"]
                 .into_iter()
                 .map(|name| name.to_string())
                 .collect::<Vec<String>>()
@@ -23,6 +23,7 @@ pub(crate) enum FilterOp {
     EndsWith,
     NotEndsWith,
     Equal,
+    Child,
 }
 
 /// Split a "name_eq" style name into an attribute ("name") and a filter op (`Equal`).
@@ -41,6 +42,7 @@ pub(crate) fn parse_field_as_filter(key: &Name) -> (Name, FilterOp) {
         k if k.ends_with("_not_ends_with") => ("_not_ends_with", FilterOp::NotEndsWith),
         k if k.ends_with("_starts_with") => ("_starts_with", FilterOp::StartsWith),
         k if k.ends_with("_ends_with") => ("_ends_with", FilterOp::EndsWith),
+        k if k.ends_with("_") => ("_", FilterOp::Child),
         _ => ("", FilterOp::Equal),
     };
 
@@ -336,6 +338,18 @@ pub fn get_object_type_directive(object_type: &ObjectType, name: Name) -> Option
         .find(|directive| directive.name == name)
 }
 
+/// Returns the name of the entity type that an aggregate type (e.g.
+/// `User_aggregate`) aggregates over, or `None` if `object_type` is not an
+/// aggregate type.
+pub fn get_aggregated_type_name(object_type: &ObjectType) -> Option<&Name> {
+    get_object_type_directive(object_type, Name::from("aggregate"))
+        .and_then(|directive| qast::get_argument_value(&directive.arguments, "entity"))
+        .and_then(|value| match value {
+            Value::String(name) => Some(name),
+            _ => None,
+        })
+}
+
 // Returns true if the given type is a non-null type.
 pub fn is_non_null_type(t: &Type) -> bool {
     match t {
@@ -1,4 +1,4 @@
-use graphql_parser::{query as q, query::Name, schema as s, schema::ObjectType};
+use graphql_parser::{query as q, schema as s};
 use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::mem::discriminant;
 
@@ -13,27 +13,78 @@ use crate::schema::ast as sast;
 pub fn build_query<'a>(
     entity: impl Into<ObjectOrInterface<'a>>,
     arguments: &HashMap<&q::Name, q::Value>,
-    types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+    schema: &Schema,
     max_first: u32,
+    block: BlockNumber,
 ) -> Result<EntityQuery, QueryExecutionError> {
     let entity = entity.into();
-    let entity_types = match &entity {
-        ObjectOrInterface::Object(object) => vec![object.name.clone()],
-        ObjectOrInterface::Interface(interface) => types_for_interface[&interface.name]
-            .iter()
-            .map(|o| o.name.clone())
-            .collect(),
-    };
     Ok(EntityQuery {
         subgraph_id: parse_subgraph_id(entity)?,
-        entity_types,
+        entity_types: entity_types(entity, schema),
         range: build_range(arguments, max_first)?,
-        filter: build_filter(entity, arguments)?,
+        filter: build_filter(entity, arguments, schema)?,
         order_by: build_order_by(entity, arguments)?,
         order_direction: build_order_direction(arguments)?,
+        block,
+        after: build_after(arguments)?,
     })
 }
 
+/// Builds an `EntityQuery` for computing aggregates over all entities of type
+/// `entity` that match the `where` argument.
+pub fn build_aggregate_query<'a>(
+    entity: impl Into<ObjectOrInterface<'a>>,
+    arguments: &HashMap<&q::Name, q::Value>,
+    schema: &Schema,
+    block: BlockNumber,
+) -> Result<EntityQuery, QueryExecutionError> {
+    let entity = entity.into();
+    let range = EntityRange {
+        first: None,
+        skip: 0,
+    };
+    let mut query = EntityQuery::new(
+        parse_subgraph_id(entity)?,
+        entity_types(entity, schema),
+        range,
+    )
+    .at_block(block);
+    query.filter = build_filter(entity, arguments, schema)?;
+    Ok(query)
+}
+
+/// Returns the aggregate that each field of an aggregate type (e.g.
+/// `User_aggregate`) stands for, in the order of the fields. See
+/// `add_aggregate_type` in the API schema for how the fields are named.
+pub fn build_aggregates(aggregate_type: &s::ObjectType) -> Vec<EntityAggregate> {
+    aggregate_type
+        .fields
+        .iter()
+        .map(|field| {
+            let mut parts = field.name.rsplitn(2, '_');
+            match (parts.next(), parts.next()) {
+                (Some("sum"), Some(attribute)) => EntityAggregate::Sum(attribute.to_owned()),
+                (Some("min"), Some(attribute)) => EntityAggregate::Min(attribute.to_owned()),
+                (Some("max"), Some(attribute)) => EntityAggregate::Max(attribute.to_owned()),
+                (Some("avg"), Some(attribute)) => EntityAggregate::Avg(attribute.to_owned()),
+                _ => EntityAggregate::Count,
+            }
+        })
+        .collect()
+}
+
+/// Returns the names of the entity types that store entities of type
+/// `entity`, i.e., `entity` itself or the types implementing it.
+fn entity_types(entity: ObjectOrInterface, schema: &Schema) -> Vec<String> {
+    match entity {
+        ObjectOrInterface::Object(object) => vec![object.name.clone()],
+        ObjectOrInterface::Interface(interface) => schema.types_for_interface()[&interface.name]
+            .iter()
+            .map(|o| o.name.clone())
+            .collect(),
+    }
+}
+
 /// Parses GraphQL arguments into a EntityRange, if present.
 fn build_range(
     arguments: &HashMap<&q::Name, q::Value>,
@@ -85,9 +136,10 @@ fn build_range(
 fn build_filter(
     entity: ObjectOrInterface,
     arguments: &HashMap<&q::Name, q::Value>,
+    schema: &Schema,
 ) -> Result<Option<EntityFilter>, QueryExecutionError> {
     match arguments.get(&"where".to_string()) {
-        Some(q::Value::Object(object)) => build_filter_from_object(entity, object),
+        Some(q::Value::Object(object)) => build_filter_from_object(entity, object, schema),
         None | Some(q::Value::Null) => Ok(None),
         _ => Err(QueryExecutionError::InvalidFilterError),
     }
@@ -97,6 +149,7 @@ fn build_filter(
 fn build_filter_from_object(
     entity: ObjectOrInterface,
     object: &BTreeMap<q::Name, q::Value>,
+    schema: &Schema,
 ) -> Result<Option<EntityFilter>, QueryExecutionError> {
     Ok(Some(EntityFilter::And({
         object
@@ -113,6 +166,10 @@ fn build_filter_from_object(
                     )
                 })?;
 
+                if let Child = op {
+                    return build_child_filter(field, value, schema);
+                }
+
                 let ty = &field.field_type;
                 let store_value = Value::from_query_value(value, &ty)?;
 
@@ -131,12 +188,53 @@ fn build_filter_from_object(
                     EndsWith => EntityFilter::EndsWith(field_name, store_value),
                     NotEndsWith => EntityFilter::NotEndsWith(field_name, store_value),
                     Equal => EntityFilter::Equal(field_name, store_value),
+                    Child => unreachable!("child filters are handled above"),
                 })
             })
             .collect::<Result<Vec<EntityFilter>, QueryExecutionError>>()?
     })))
 }
 
+/// Parses the nested filter `value` for the entities that `field` links
+/// to into an `EntityFilter::Child`
+fn build_child_filter(
+    field: &s::Field,
+    value: &q::Value,
+    schema: &Schema,
+) -> Result<EntityFilter, QueryExecutionError> {
+    let child_type: ObjectOrInterface =
+        match sast::get_type_definition_from_field(&schema.document, field) {
+            Some(s::TypeDefinition::Object(t)) => t.into(),
+            Some(s::TypeDefinition::Interface(t)) => t.into(),
+            _ => return Err(QueryExecutionError::InvalidFilterError),
+        };
+    let object = match value {
+        q::Value::Object(object) => object,
+        _ => return Err(QueryExecutionError::InvalidFilterError),
+    };
+
+    // For derived fields, the link is stored in the field of the child
+    // that the field is derived from
+    let (attribute, derived) = if sast::get_derived_from_directive(field).is_some() {
+        let derived_from = sast::get_derived_from_field(child_type, field).ok_or_else(|| {
+            QueryExecutionError::EntityFieldError(child_type.name().to_owned(), field.name.clone())
+        })?;
+        (derived_from.name.clone(), true)
+    } else {
+        (field.name.clone(), false)
+    };
+
+    Ok(EntityFilter::Child {
+        attribute,
+        entity_types: entity_types(child_type, schema),
+        filter: Box::new(
+            build_filter_from_object(child_type, object, schema)?
+                .unwrap_or_else(|| EntityFilter::And(vec![])),
+        ),
+        derived,
+    })
+}
+
 /// Parses a list of GraphQL values into a vector of entity field values.
 fn list_values(value: Value, filter_type: &str) -> Result<Vec<Value>, QueryExecutionError> {
     match value {
@@ -203,6 +301,18 @@ fn build_order_direction(
         }))
 }
 
+/// Parses the `after` cursor, the id of the entity to continue after, if
+/// present.
+fn build_after(
+    arguments: &HashMap<&q::Name, q::Value>,
+) -> Result<Option<String>, QueryExecutionError> {
+    match arguments.get(&"after".to_string()) {
+        Some(q::Value::String(id)) => Ok(Some(id.to_owned())),
+        None | Some(q::Value::Null) => Ok(None),
+        _ => unreachable!("after is an ID"),
+    }
+}
+
 /// Parses the subgraph ID from the ObjectType directives.
 pub fn parse_subgraph_id<'a>(
     entity: impl Into<ObjectOrInterface<'a>>,
@@ -250,6 +360,11 @@ pub fn collect_entities_from_query_field(
             {
                 // If the field's type definition is an object type, extract that type
                 if let s::TypeDefinition::Object(object_type) = type_definition {
+                    // Aggregates change with any of the entities they aggregate over
+                    if let Some(aggregated_type) = sast::get_aggregated_type_name(object_type) {
+                        entities.extend(aggregated_entities(schema, aggregated_type));
+                    }
+
                     // Only collect whether the field's type has an @entity directive
                     if sast::get_object_type_directive(object_type, String::from("entity"))
                         .is_some()
@@ -276,6 +391,26 @@ pub fn collect_entities_from_query_field(
     entities.into_iter().collect()
 }
 
+/// Collects the entities that the aggregates over `aggregated_type` are
+/// computed from, i.e., the type itself or the types implementing it.
+fn aggregated_entities(
+    schema: &s::Document,
+    aggregated_type: &s::Name,
+) -> Vec<(SubgraphDeploymentId, String)> {
+    sast::get_object_type_definitions(schema)
+        .into_iter()
+        .filter(|object_type| {
+            &object_type.name == aggregated_type
+                || object_type.implements_interfaces.contains(aggregated_type)
+        })
+        .filter_map(|object_type| {
+            parse_subgraph_id(object_type)
+                .ok()
+                .map(|subgraph_id| (subgraph_id, object_type.name.to_owned()))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use graphql_parser::{
@@ -352,6 +487,15 @@ mod tests {
         }
     }
 
+    fn default_schema() -> Schema {
+        Schema::new(
+            SubgraphDeploymentId::new("test").unwrap(),
+            s::Document {
+                definitions: vec![],
+            },
+        )
+    }
+
     fn default_arguments<'a>() -> HashMap<&'a String, q::Value> {
         let mut map = HashMap::new();
         let first: &String = Box::leak(Box::new("first".to_owned()));
@@ -367,8 +511,9 @@ mod tests {
             build_query(
                 &object("Entity1"),
                 &default_arguments(),
-                &BTreeMap::new(),
-                std::u32::MAX
+                &default_schema(),
+                std::u32::MAX,
+                BLOCK_NUMBER_MAX
             )
             .unwrap()
             .entity_types,
@@ -378,8 +523,9 @@ mod tests {
             build_query(
                 &object("Entity2"),
                 &default_arguments(),
-                &BTreeMap::new(),
-                std::u32::MAX
+                &default_schema(),
+                std::u32::MAX,
+                BLOCK_NUMBER_MAX
             )
             .unwrap()
             .entity_types,
@@ -393,8 +539,9 @@ mod tests {
             build_query(
                 &default_object(),
                 &default_arguments(),
-                &BTreeMap::new(),
-                std::u32::MAX
+                &default_schema(),
+                std::u32::MAX,
+                BLOCK_NUMBER_MAX
             )
             .unwrap()
             .order_by,
@@ -404,8 +551,9 @@ mod tests {
             build_query(
                 &default_object(),
                 &default_arguments(),
-                &BTreeMap::new(),
-                std::u32::MAX
+                &default_schema(),
+                std::u32::MAX,
+                BLOCK_NUMBER_MAX
             )
             .unwrap()
             .order_direction,
@@ -419,18 +567,30 @@ mod tests {
         let mut args = default_arguments();
         args.insert(&order_by, q::Value::Enum("name".to_string()));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .order_by,
+            build_query(
+                &default_object(),
+                &args,
+                &default_schema(),
+                std::u32::MAX,
+                BLOCK_NUMBER_MAX
+            )
+            .unwrap()
+            .order_by,
             Some(("name".to_string(), ValueType::String))
         );
 
         let mut args = default_arguments();
         args.insert(&order_by, q::Value::Enum("email".to_string()));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .order_by,
+            build_query(
+                &default_object(),
+                &args,
+                &default_schema(),
+                std::u32::MAX,
+                BLOCK_NUMBER_MAX
+            )
+            .unwrap()
+            .order_by,
             Some(("email".to_string(), ValueType::String))
         );
     }
@@ -441,18 +601,30 @@ mod tests {
         let mut args = default_arguments();
         args.insert(&order_by, q::Value::String("name".to_string()));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .order_by,
+            build_query(
+                &default_object(),
+                &args,
+                &default_schema(),
+                std::u32::MAX,
+                BLOCK_NUMBER_MAX
+            )
+            .unwrap()
+            .order_by,
             None,
         );
 
         let mut args = default_arguments();
         args.insert(&order_by, q::Value::String("email".to_string()));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .order_by,
+            build_query(
+                &default_object(),
+                &args,
+                &default_schema(),
+                std::u32::MAX,
+                BLOCK_NUMBER_MAX
+            )
+            .unwrap()
+            .order_by,
             None,
         );
     }
@@ -463,27 +635,45 @@ mod tests {
         let mut args = default_arguments();
         args.insert(&order_direction, q::Value::Enum("asc".to_string()));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .order_direction,
+            build_query(
+                &default_object(),
+                &args,
+                &default_schema(),
+                std::u32::MAX,
+                BLOCK_NUMBER_MAX
+            )
+            .unwrap()
+            .order_direction,
             Some(EntityOrder::Ascending)
         );
 
         let mut args = default_arguments();
         args.insert(&order_direction, q::Value::Enum("desc".to_string()));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .order_direction,
+            build_query(
+                &default_object(),
+                &args,
+                &default_schema(),
+                std::u32::MAX,
+                BLOCK_NUMBER_MAX
+            )
+            .unwrap()
+            .order_direction,
             Some(EntityOrder::Descending)
         );
 
         let mut args = default_arguments();
         args.insert(&order_direction, q::Value::Enum("ascending...".to_string()));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .order_direction,
+            build_query(
+                &default_object(),
+                &args,
+                &default_schema(),
+                std::u32::MAX,
+                BLOCK_NUMBER_MAX
+            )
+            .unwrap()
+            .order_direction,
             None,
         );
     }
@@ -494,18 +684,30 @@ mod tests {
         let mut args = default_arguments();
         args.insert(&order_direction, q::Value::String("asc".to_string()));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .order_direction,
+            build_query(
+                &default_object(),
+                &args,
+                &default_schema(),
+                std::u32::MAX,
+                BLOCK_NUMBER_MAX
+            )
+            .unwrap()
+            .order_direction,
             None,
         );
 
         let mut args = default_arguments();
         args.insert(&order_direction, q::Value::String("desc".to_string()));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .order_direction,
+            build_query(
+                &default_object(),
+                &args,
+                &default_schema(),
+                std::u32::MAX,
+                BLOCK_NUMBER_MAX
+            )
+            .unwrap()
+            .order_direction,
             None,
         );
     }
@@ -516,8 +718,9 @@ mod tests {
             build_query(
                 &default_object(),
                 &default_arguments(),
-                &BTreeMap::new(),
-                std::u32::MAX
+                &default_schema(),
+                std::u32::MAX,
+                BLOCK_NUMBER_MAX
             )
             .unwrap()
             .range,
@@ -531,9 +734,15 @@ mod tests {
         let mut args = default_arguments();
         args.insert(&skip, q::Value::Int(q::Number::from(50)));
         assert_eq!(
-            build_query(&default_object(), &args, &BTreeMap::new(), std::u32::MAX)
-                .unwrap()
-                .range,
+            build_query(
+                &default_object(),
+                &args,
+                &default_schema(),
+                std::u32::MAX,
+                BLOCK_NUMBER_MAX
+            )
+            .unwrap()
+            .range,
             EntityRange {
                 first: Some(100),
                 skip: 50,
@@ -559,8 +768,9 @@ mod tests {
                     ..default_object()
                 },
                 &args,
-                &BTreeMap::new(),
+                &default_schema(),
                 std::u32::MAX,
+                BLOCK_NUMBER_MAX,
             )
             .unwrap()
             .filter,
@@ -570,4 +780,119 @@ mod tests {
             )]))
         )
     }
+
+    #[test]
+    fn build_query_yields_child_filters() {
+        let schema = Schema::new(
+            SubgraphDeploymentId::new("test").unwrap(),
+            graphql_parser::parse_schema(
+                "
+                type User {
+                    id: ID!
+                    name: String!
+                    tokens: [Token!]! @derivedFrom(field: \"owner\")
+                }
+
+                type Token {
+                    id: ID!
+                    owner: User!
+                }
+                ",
+            )
+            .unwrap(),
+        );
+        let user_type = Type::NonNullType(Box::new(Type::NamedType("User".to_owned())));
+        let token_list_type = Type::NonNullType(Box::new(Type::ListType(Box::new(
+            Type::NonNullType(Box::new(Type::NamedType("Token".to_owned()))),
+        ))));
+        let tokens_field = Field {
+            directives: vec![Directive {
+                name: "derivedFrom".to_owned(),
+                position: Pos::default(),
+                arguments: vec![("field".to_owned(), SchemaValue::String("owner".to_owned()))],
+            }],
+            ..field("tokens", token_list_type)
+        };
+
+        let name_filter = |name: &str| {
+            q::Value::Object(BTreeMap::from_iter(vec![(
+                "name".to_string(),
+                q::Value::String(name.to_string()),
+            )]))
+        };
+        let user_filter = EntityFilter::And(vec![EntityFilter::Equal(
+            "name".to_string(),
+            Value::String("Alice".to_string()),
+        )]);
+
+        // Filter tokens by their owner
+        let whre = "where".to_string();
+        let mut args = default_arguments();
+        args.insert(
+            &whre,
+            q::Value::Object(BTreeMap::from_iter(vec![(
+                "owner_".to_string(),
+                name_filter("Alice"),
+            )])),
+        );
+        assert_eq!(
+            build_query(
+                &ObjectType {
+                    fields: vec![field("owner", user_type)],
+                    ..object("Token")
+                },
+                &args,
+                &schema,
+                std::u32::MAX,
+                BLOCK_NUMBER_MAX,
+            )
+            .unwrap()
+            .filter,
+            Some(EntityFilter::And(vec![EntityFilter::Child {
+                attribute: "owner".to_string(),
+                entity_types: vec!["User".to_string()],
+                filter: Box::new(user_filter.clone()),
+                derived: false,
+            }]))
+        );
+
+        // Filter users by their tokens, which are derived from the token's
+        // `owner`
+        let mut args = default_arguments();
+        args.insert(
+            &whre,
+            q::Value::Object(BTreeMap::from_iter(vec![(
+                "tokens_".to_string(),
+                q::Value::Object(BTreeMap::from_iter(vec![(
+                    "owner_".to_string(),
+                    name_filter("Alice"),
+                )])),
+            )])),
+        );
+        assert_eq!(
+            build_query(
+                &ObjectType {
+                    fields: vec![tokens_field],
+                    ..object("User")
+                },
+                &args,
+                &schema,
+                std::u32::MAX,
+                BLOCK_NUMBER_MAX,
+            )
+            .unwrap()
+            .filter,
+            Some(EntityFilter::And(vec![EntityFilter::Child {
+                attribute: "owner".to_string(),
+                entity_types: vec!["Token".to_string()],
+                filter: Box::new(EntityFilter::And(vec![EntityFilter::Child {
+                    attribute: "owner".to_string(),
+                    entity_types: vec!["User".to_string()],
+                    filter: Box::new(user_filter),
+                    derived: false,
+                }])),
+                derived: true,
+            }]))
+        );
+    }
 }
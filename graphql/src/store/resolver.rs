@@ -1,14 +1,18 @@
 use graphql_parser::{query as q, schema as s};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{HashMap, HashSet};
 use std::result;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use graph::components::store::*;
+use graph::prelude::web3::types::H256;
 use graph::prelude::*;
 
 use crate::prelude::*;
 use crate::schema::ast as sast;
-use crate::store::query::{collect_entities_from_query_field, parse_subgraph_id};
+use crate::store::query::{
+    build_aggregate_query, build_aggregates, collect_entities_from_query_field, parse_subgraph_id,
+};
 
 /// A resolver that fetches entities from a `Store`.
 pub struct StoreResolver<S> {
@@ -139,6 +143,81 @@ where
         }
     }
 
+    /// Adds a filter for the full-text search of a search field. Search fields
+    /// are the only collection fields that have a `text` argument.
+    fn add_filter_for_fulltext_field(
+        query: &mut EntityQuery,
+        field_definition: &s::Field,
+        arguments: &HashMap<&q::Name, q::Value>,
+    ) {
+        if let Some(q::Value::String(text)) = arguments.get(&"text".to_string()) {
+            let filter = EntityFilter::Fulltext(field_definition.name.clone(), Value::from(text));
+
+            // Add the `Fulltext` filter to the top-level `And` filter, creating
+            // one if necessary
+            let top_level_filter = query.filter.get_or_insert(EntityFilter::And(vec![]));
+            match top_level_filter {
+                EntityFilter::And(ref mut filters) => {
+                    filters.push(filter);
+                }
+                _ => unreachable!("top level filter is always `And`"),
+            };
+        }
+    }
+
+    /// Looks up an entity by its key, as it was at the given block.
+    fn get_entity(
+        &self,
+        key: EntityKey,
+        block: BlockNumber,
+    ) -> Result<Option<Entity>, QueryExecutionError> {
+        if block == BLOCK_NUMBER_MAX {
+            return self.store.get(key);
+        }
+
+        let query = EntityQuery::new(
+            key.subgraph_id,
+            vec![key.entity_type],
+            EntityRange::first(1),
+        )
+        .filter(EntityFilter::Equal(
+            String::from("id"),
+            Value::from(key.entity_id),
+        ))
+        .at_block(block);
+        self.store.find_one(query)
+    }
+
+    /// Computes the fields of an aggregate type like `User_aggregate` over
+    /// all entities of `entity_type` that match the `where` argument.
+    fn resolve_aggregate(
+        &self,
+        aggregate_type: &s::ObjectType,
+        entity_type: &s::Name,
+        arguments: &HashMap<&q::Name, q::Value>,
+        schema: &Schema,
+        block: BlockNumber,
+    ) -> Result<q::Value, QueryExecutionError> {
+        let entity_type = match sast::get_named_type(&schema.document, entity_type) {
+            Some(s::TypeDefinition::Object(t)) => ObjectOrInterface::Object(t),
+            Some(s::TypeDefinition::Interface(t)) => ObjectOrInterface::Interface(t),
+            _ => return Err(QueryExecutionError::NamedTypeError(entity_type.to_owned())),
+        };
+        let query = build_aggregate_query(entity_type, arguments, schema, block)?;
+        let values = self
+            .store
+            .aggregate(query, build_aggregates(aggregate_type))?;
+
+        Ok(q::Value::Object(
+            aggregate_type
+                .fields
+                .iter()
+                .map(|field| field.name.to_owned())
+                .zip(values.into_iter().map(q::Value::from))
+                .collect(),
+        ))
+    }
+
     /// Returns true if the object has no references in the given field.
     fn references_field_is_empty(parent: &Option<q::Value>, field: &q::Name) -> bool {
         parent
@@ -167,11 +246,17 @@ where
         field_definition: &s::Field,
         object_type: ObjectOrInterface<'_>,
         arguments: &HashMap<&q::Name, q::Value>,
-        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        schema: &Schema,
+        block: BlockNumber,
         max_first: u32,
     ) -> Result<q::Value, QueryExecutionError> {
         let object_type = object_type.into();
-        let mut query = build_query(object_type, arguments, types_for_interface, max_first)?;
+        let mut query = build_query(object_type, arguments, schema, max_first, block)?;
+
+        // Add matching filter for full-text search fields
+        if parent.is_none() {
+            Self::add_filter_for_fulltext_field(&mut query, field_definition, arguments);
+        }
 
         // Add matching filter for derived fields
         let derived_from_field = sast::get_derived_from_field(object_type, field_definition);
@@ -202,6 +287,105 @@ where
         Ok(q::Value::List(entity_values))
     }
 
+    fn prefetch_objects(
+        &self,
+        parents: &[&q::Value],
+        field: &q::Field,
+        field_definition: &s::Field,
+        object_type: ObjectOrInterface<'_>,
+        arguments: &HashMap<&q::Name, q::Value>,
+        schema: &Schema,
+        block: BlockNumber,
+        max_first: u32,
+    ) -> Result<Option<Vec<q::Value>>, QueryExecutionError> {
+        let is_list = sast::is_list_or_non_null_list_field(field_definition);
+        let derived_from_field = sast::get_derived_from_field(object_type, field_definition);
+
+        let query = if is_list {
+            build_query(object_type, arguments, schema, max_first, block)?
+        } else {
+            // Like `resolve_object`, we use first: 2 to detect and fail if
+            // more than one entity matches a `@derivedFrom`
+            let mut arguments = arguments.clone();
+            let first_arg_name = q::Name::from("first");
+            arguments.insert(&first_arg_name, q::Value::Int(q::Number::from(2)));
+            let skip_arg_name = q::Name::from("skip");
+            arguments.insert(&skip_arg_name, q::Value::Int(q::Number::from(0)));
+            build_query(object_type, &arguments, schema, 2, block)?
+        };
+
+        // Collect the ids of the parents, and for non-derived fields the ids
+        // of the entities they reference. Parents can appear more than once
+        // when they were themselves referenced by several entities
+        let mut ids = Vec::with_capacity(parents.len());
+        let mut parent_ids = Vec::new();
+        let mut child_ids = Vec::new();
+        let mut seen = HashSet::new();
+        for parent in parents {
+            let (object, id) = match parent {
+                q::Value::Object(object) => match object.get("id") {
+                    Some(q::Value::String(id)) => (object, id),
+                    _ => return Ok(None),
+                },
+                _ => return Ok(None),
+            };
+            ids.push(id);
+            if !seen.insert(id) {
+                continue;
+            }
+            parent_ids.push(id.clone());
+            if derived_from_field.is_none() {
+                child_ids.push(match object.get(&field_definition.name) {
+                    Some(q::Value::String(id)) => vec![id.clone()],
+                    Some(q::Value::List(ids)) => ids
+                        .iter()
+                        .filter_map(|id| match id {
+                            q::Value::String(id) => Some(id.clone()),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => vec![],
+                });
+            }
+        }
+
+        let link = match derived_from_field {
+            Some(derived_from_field) => EntityLink::Derived {
+                attribute: derived_from_field.name.clone(),
+                list: sast::is_list_or_non_null_list_field(derived_from_field),
+            },
+            None => EntityLink::Direct { child_ids },
+        };
+        let children = self.store.find_children(query, parent_ids, link)?;
+
+        ids.into_iter()
+            .map(|id| {
+                let entities = children.get(id).cloned().unwrap_or_default();
+                if is_list {
+                    return Ok(q::Value::List(
+                        entities.into_iter().map(|entity| entity.into()).collect(),
+                    ));
+                }
+                match derived_from_field {
+                    Some(derived_from_field) if entities.len() > 1 => {
+                        Err(QueryExecutionError::AmbiguousDerivedFromResult(
+                            field.position.clone(),
+                            field.name.to_owned(),
+                            object_type.name().to_owned(),
+                            derived_from_field.name.to_owned(),
+                        ))
+                    }
+                    _ => Ok(entities
+                        .into_iter()
+                        .next()
+                        .map(|entity| entity.into())
+                        .unwrap_or(q::Value::Null)),
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(Some)
+    }
+
     fn resolve_object(
         &self,
         parent: &Option<q::Value>,
@@ -209,8 +393,21 @@ where
         field_definition: &s::Field,
         object_type: ObjectOrInterface<'_>,
         arguments: &HashMap<&q::Name, q::Value>,
-        types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        schema: &Schema,
+        block: BlockNumber,
     ) -> Result<q::Value, QueryExecutionError> {
+        if let ObjectOrInterface::Object(aggregate_type) = object_type {
+            if let Some(entity_type) = sast::get_aggregated_type_name(aggregate_type) {
+                return self.resolve_aggregate(
+                    aggregate_type,
+                    entity_type,
+                    arguments,
+                    schema,
+                    block,
+                );
+            }
+        }
+
         let id = arguments.get(&"id".to_string()).and_then(|id| match id {
             q::Value::String(s) => Some(s),
             _ => None,
@@ -220,18 +417,21 @@ where
         let subgraph_id = parse_subgraph_id(object_type).unwrap();
         let entity = if let Some(id) = id {
             match object_type {
-                ObjectOrInterface::Object(_) => self.store.get(EntityKey {
-                    subgraph_id,
-                    entity_type: object_type.name().to_owned(),
-                    entity_id: id.to_owned(),
-                })?,
+                ObjectOrInterface::Object(_) => self.get_entity(
+                    EntityKey {
+                        subgraph_id,
+                        entity_type: object_type.name().to_owned(),
+                        entity_id: id.to_owned(),
+                    },
+                    block,
+                )?,
                 ObjectOrInterface::Interface(interface) => {
-                    let entity_types = types_for_interface[&interface.name]
+                    let entity_types = schema.types_for_interface()[&interface.name]
                         .iter()
                         .map(|o| o.name.clone())
                         .collect();
                     let range = EntityRange::first(1);
-                    let query = EntityQuery::new(subgraph_id, entity_types, range);
+                    let query = EntityQuery::new(subgraph_id, entity_types, range).at_block(block);
                     self.store.find(query)?.into_iter().next()
                 }
             }
@@ -251,7 +451,7 @@ where
 
                 let skip_arg_name = q::Name::from("skip");
                 arguments.insert(&skip_arg_name, q::Value::Int(q::Number::from(0)));
-                let mut query = build_query(object_type, &arguments, types_for_interface, 2)?;
+                let mut query = build_query(object_type, &arguments, schema, 2, block)?;
                 Self::add_filter_for_derived_field(&mut query, parent, derived_from_field);
 
                 // Find the entity or entities that reference the parent entity
@@ -270,11 +470,14 @@ where
             } else {
                 match parent {
                     Some(q::Value::Object(parent_object)) => match parent_object.get(&field.name) {
-                        Some(q::Value::String(id)) => self.store.get(EntityKey {
-                            subgraph_id,
-                            entity_type: object_type.name().to_owned(),
-                            entity_id: id.to_owned(),
-                        })?,
+                        Some(q::Value::String(id)) => self.get_entity(
+                            EntityKey {
+                                subgraph_id,
+                                entity_type: object_type.name().to_owned(),
+                                entity_id: id.to_owned(),
+                            },
+                            block,
+                        )?,
                         _ => None,
                     },
                     _ => panic!("top level queries must either take an `id` or return a list"),
@@ -288,6 +491,66 @@ where
         })
     }
 
+    fn resolve_block(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        field: &q::Field,
+        arguments: &HashMap<&q::Name, q::Value>,
+    ) -> Result<BlockNumber, QueryExecutionError> {
+        let block = match arguments.get(&"block".to_string()) {
+            Some(q::Value::Object(block)) => block,
+            _ => return Ok(BLOCK_NUMBER_MAX),
+        };
+
+        // A block hash takes precedence over a block number
+        let number = match (block.get("hash"), block.get("number")) {
+            (Some(q::Value::String(hash)), _) => {
+                // `H256::from_str` takes a hex string with no leading `0x`.
+                let block_hash = H256::from_str(hash.trim_start_matches("0x")).map_err(|_| {
+                    QueryExecutionError::InvalidArgumentError(
+                        field.position.clone(),
+                        "block.hash".to_string(),
+                        q::Value::String(hash.clone()),
+                    )
+                })?;
+                self.store
+                    .block_number(subgraph_id, block_hash)?
+                    .ok_or_else(|| QueryExecutionError::BlockNotFound(hash.clone()))?
+            }
+            (_, Some(q::Value::Int(number))) => match number.as_i64() {
+                Some(number) if number >= 0 && number <= BLOCK_NUMBER_MAX as i64 => {
+                    number as BlockNumber
+                }
+                _ => {
+                    return Err(QueryExecutionError::InvalidArgumentError(
+                        field.position.clone(),
+                        "block.number".to_string(),
+                        q::Value::Int(number.clone()),
+                    ));
+                }
+            },
+            _ => return Ok(BLOCK_NUMBER_MAX),
+        };
+
+        // Refuse to answer queries about blocks the subgraph has not
+        // processed yet
+        let latest = self
+            .store
+            .block_ptr(subgraph_id.clone())
+            .map_err(|e| QueryExecutionError::StoreError(e))?;
+        if number as u64 > latest.number {
+            return Err(QueryExecutionError::BlockNotIndexed(number, latest.number));
+        }
+
+        // Refuse to answer queries about blocks whose history has been
+        // pruned, since we would silently return incomplete results
+        let earliest = self.store.earliest_block(subgraph_id)?;
+        if number < earliest {
+            return Err(QueryExecutionError::BlockPruned(number, earliest));
+        }
+        Ok(number)
+    }
+
     fn resolve_field_stream<'a, 'b>(
         &self,
         schema: &'a s::Document,
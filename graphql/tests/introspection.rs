@@ -2,7 +2,7 @@
 extern crate pretty_assertions;
 
 use graphql_parser::{query as q, schema as s};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::HashMap;
 
 use graph::prelude::*;
 use graph_graphql::prelude::*;
@@ -19,7 +19,8 @@ impl Resolver for MockResolver {
         _field_definition: &s::Field,
         _object_type: ObjectOrInterface<'_>,
         _arguments: &HashMap<&q::Name, q::Value>,
-        _types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        _schema: &Schema,
+        _block: BlockNumber,
         _max_first: u32,
     ) -> Result<q::Value, QueryExecutionError> {
         Ok(q::Value::Null)
@@ -32,7 +33,8 @@ impl Resolver for MockResolver {
         _field_definition: &s::Field,
         _object_type: ObjectOrInterface<'_>,
         _arguments: &HashMap<&q::Name, q::Value>,
-        _types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        _schema: &Schema,
+        _block: BlockNumber,
     ) -> Result<q::Value, QueryExecutionError> {
         Ok(q::Value::Null)
     }
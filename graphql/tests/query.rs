@@ -75,6 +75,8 @@ fn insert_test_entities(store: &impl Store, id: SubgraphDeploymentId) {
         schema: schema.clone(),
         data_sources: vec![],
         templates: vec![],
+        pruning: None,
+        graft: None,
     };
 
     let logger = Logger::root(slog::Discard, o!());
@@ -85,7 +87,7 @@ fn insert_test_entities(store: &impl Store, id: SubgraphDeploymentId) {
         .map(|op| op.into())
         .collect();
     store
-        .create_subgraph_deployment(&logger, &schema, ops)
+        .create_subgraph_deployment(&logger, &SubgraphName::new("query").unwrap(), &schema, ops)
         .unwrap();
 
     let entities = vec![
@@ -765,6 +767,43 @@ fn query_complexity() {
     };
 }
 
+#[test]
+fn aggregate_complexity() {
+    let logger = Logger::root(slog::Discard, o!());
+    let store_resolver = StoreResolver::new(&logger, STORE.clone());
+
+    let query = Query {
+        schema: Arc::new(api_test_schema()),
+        document: graphql_parser::parse_query(
+            "query {
+                musicianAggregate {
+                    count
+                }
+            }",
+        )
+        .unwrap(),
+        variables: None,
+    };
+    let options = |max_complexity| QueryExecutionOptions {
+        logger: logger.clone(),
+        resolver: store_resolver.clone(),
+        deadline: None,
+        max_complexity: Some(max_complexity),
+        max_depth: 100,
+        max_first: 1000,
+    };
+
+    // An aggregate costs a small constant, regardless of `max_first`
+    let result = execute_query(&query, options(10));
+    assert!(result.errors.is_none());
+
+    let result = execute_query(&query, options(9));
+    match result.errors.unwrap()[0] {
+        QueryError::ExecutionError(QueryExecutionError::TooComplex(10, 9)) => (),
+        _ => panic!("did not catch complexity"),
+    };
+}
+
 #[test]
 fn query_complexity_subscriptions() {
     let logger = Logger::root(slog::Discard, o!());
@@ -1142,6 +1181,237 @@ fn cannot_filter_by_derved_relationship_fields() {
     };
 }
 
+#[test]
+fn can_filter_by_nested_relationship_fields() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+        query {
+            musicians(orderBy: id, where: { mainBand_: { name: \"The Amateurs\" } }) {
+                id
+            }
+            authors: musicians(orderBy: id, where: { writtenSongs_: { title_starts_with: \"Pop\" } }) {
+                id
+            }
+            bands(orderBy: id, where: { members_: { name: \"Lisa\" } }) {
+                id
+            }
+            folkBands: bands(orderBy: id, where: { originalSongs_: { writtenBy_: { name: \"Tom\" } } }) {
+                id
+            }
+        }
+        ",
+        )
+        .expect("invalid test query"),
+    );
+
+    let ids = |ids: Vec<&str>| {
+        q::Value::List(
+            ids.into_iter()
+                .map(|id| object_value(vec![("id", q::Value::String(String::from(id)))]))
+                .collect(),
+        )
+    };
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            ("musicians", ids(vec!["m3"])),
+            ("authors", ids(vec!["m1"])),
+            ("bands", ids(vec!["b1"])),
+            ("folkBands", ids(vec!["b2"])),
+        ]))
+    );
+}
+
+#[test]
+fn can_page_with_after_cursor() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+        query {
+            musicians(orderBy: id, after: \"m2\") {
+                id
+            }
+            byName: musicians(orderBy: name, orderDirection: desc, first: 2, after: \"m3\") {
+                id
+            }
+            missing: musicians(orderBy: name, after: \"m5\") {
+                id
+            }
+        }
+        ",
+        )
+        .expect("invalid test query"),
+    );
+
+    let ids = |ids: Vec<&str>| {
+        q::Value::List(
+            ids.into_iter()
+                .map(|id| object_value(vec![("id", q::Value::String(String::from(id)))]))
+                .collect(),
+        )
+    };
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            ("musicians", ids(vec!["m3", "m4"])),
+            ("byName", ids(vec!["m2", "m1"])),
+            ("missing", ids(vec![])),
+        ]))
+    );
+}
+
+#[test]
+fn can_query_aggregates() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+        query {
+            musicianAggregate {
+                count
+            }
+            songStatAggregate {
+                count
+                played_sum
+                played_min
+                played_max
+                played_avg
+            }
+            filtered: songStatAggregate(where: { played_gt: 10 }) {
+                count
+                played_sum
+                played_avg
+            }
+            none: songStatAggregate(where: { played_gt: 100 }) {
+                count
+                played_sum
+                played_min
+            }
+        }
+        ",
+        )
+        .expect("invalid test query"),
+    );
+
+    let int = |n: i32| q::Value::Int(q::Number::from(n));
+    let string = |s: &str| q::Value::String(String::from(s));
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            (
+                "musicianAggregate",
+                object_value(vec![("count", string("4"))])
+            ),
+            (
+                "songStatAggregate",
+                object_value(vec![
+                    ("count", string("2")),
+                    ("played_sum", string("25")),
+                    ("played_min", int(10)),
+                    ("played_max", int(15)),
+                    ("played_avg", string("12.5")),
+                ])
+            ),
+            (
+                "filtered",
+                object_value(vec![
+                    ("count", string("1")),
+                    ("played_sum", string("15")),
+                    ("played_avg", string("15")),
+                ])
+            ),
+            (
+                "none",
+                object_value(vec![
+                    ("count", string("0")),
+                    ("played_sum", q::Value::Null),
+                    ("played_min", q::Value::Null),
+                ])
+            ),
+        ]))
+    );
+}
+
+#[test]
+fn applies_range_and_order_to_nested_fields_per_parent() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+        query {
+            musicians(orderBy: id) {
+                id
+                bands(first: 1, orderBy: name) {
+                    id
+                }
+            }
+            bands(orderBy: id) {
+                id
+                members(first: 2, orderBy: name, orderDirection: desc) {
+                    id
+                }
+            }
+        }
+        ",
+        )
+        .expect("invalid test query"),
+    );
+
+    let object = |id: &str, field: &str, ids: Vec<&str>| {
+        object_value(vec![
+            ("id", q::Value::String(String::from(id))),
+            (
+                field,
+                q::Value::List(
+                    ids.into_iter()
+                        .map(|id| object_value(vec![("id", q::Value::String(String::from(id)))]))
+                        .collect(),
+                ),
+            ),
+        ])
+    };
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![
+            (
+                "musicians",
+                q::Value::List(vec![
+                    object("m1", "bands", vec!["b2"]),
+                    object("m2", "bands", vec!["b1"]),
+                    object("m3", "bands", vec!["b2"]),
+                    object("m4", "bands", vec![]),
+                ])
+            ),
+            (
+                "bands",
+                q::Value::List(vec![
+                    object("b1", "members", vec!["m3", "m2"]),
+                    object("b2", "members", vec!["m3", "m1"]),
+                ])
+            ),
+        ]))
+    );
+}
+
 #[test]
 fn subscription_gets_result_even_without_events() {
     let logger = Logger::root(slog::Discard, o!());
@@ -1192,3 +1462,95 @@ fn subscription_gets_result_even_without_events() {
         )])),
     );
 }
+
+#[test]
+fn can_query_at_indexed_block() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+        query {
+            musicians(orderBy: id, first: 2, block: { number: 1 }) {
+                name
+            }
+        }
+        ",
+        )
+        .expect("invalid test query"),
+    );
+
+    if std::env::var_os("RELATIONAL_SCHEMA").is_none() {
+        // JSONB storage only has the latest version of each entity
+        match &result.errors.expect("querying JSONB at a block fails")[0] {
+            QueryError::ExecutionError(QueryExecutionError::NotSupported(_)) => (),
+            e => panic!(format!("expected NotSupported, got {}", e)),
+        };
+        return;
+    }
+
+    assert!(
+        result.errors.is_none(),
+        format!("Unexpected errors return for query: {:#?}", result.errors)
+    );
+    assert_eq!(
+        result.data,
+        Some(object_value(vec![(
+            "musicians",
+            q::Value::List(vec![
+                object_value(vec![("name", q::Value::String(String::from("John")))]),
+                object_value(vec![("name", q::Value::String(String::from("Lisa")))])
+            ])
+        )])),
+    );
+}
+
+#[test]
+fn cannot_query_beyond_indexed_block() {
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+        query {
+            musician(id: \"m1\", block: { number: 2000 }) {
+                name
+            }
+        }
+        ",
+        )
+        .expect("invalid test query"),
+    );
+
+    assert!(result.errors.is_some());
+    match &result.errors.unwrap()[0] {
+        QueryError::ExecutionError(QueryExecutionError::BlockNotIndexed(2000, 1)) => (),
+        e => panic!(format!("expected BlockNotIndexed, got {}", e)),
+    };
+}
+
+#[test]
+fn cannot_query_pruned_block() {
+    // Queries at block 1 and later can still be answered after this
+    STORE
+        .apply_metadata_operations(SubgraphDeploymentEntity::update_pruned_block_operations(
+            &*TEST_SUBGRAPH_ID,
+            1,
+        ))
+        .unwrap();
+
+    let result = execute_query_document(
+        graphql_parser::parse_query(
+            "
+        query {
+            musician(id: \"m1\", block: { number: 0 }) {
+                name
+            }
+        }
+        ",
+        )
+        .expect("invalid test query"),
+    );
+
+    assert!(result.errors.is_some());
+    match &result.errors.unwrap()[0] {
+        QueryError::ExecutionError(QueryExecutionError::BlockPruned(0, 1)) => (),
+        e => panic!(format!("expected BlockPruned, got {}", e)),
+    };
+}
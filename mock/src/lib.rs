@@ -6,7 +6,9 @@ extern crate graphql_parser;
 extern crate rand;
 
 mod block_stream;
+mod metrics_registry;
 mod store;
 
 pub use self::block_stream::{MockBlockStream, MockBlockStreamBuilder};
+pub use self::metrics_registry::MockMetricsRegistry;
 pub use self::store::{FakeStore, MockStore};
@@ -0,0 +1,15 @@
+use graph::prelude::*;
+
+/// A metrics registry that discards all metrics.
+#[derive(Clone, Default)]
+pub struct MockMetricsRegistry {}
+
+impl MockMetricsRegistry {
+    pub fn new() -> Self {
+        MockMetricsRegistry {}
+    }
+}
+
+impl MetricsRegistry for MockMetricsRegistry {
+    fn register(&self, _name: &str, _collector: Box<dyn Collector>) {}
+}
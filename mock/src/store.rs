@@ -3,6 +3,7 @@ use rand::rngs::OsRng;
 use rand::seq::SliceRandom;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::path::Path;
 use std::sync::Mutex;
 
 use graph::components::store::*;
@@ -73,6 +74,8 @@ impl MockStore {
             order_by,
             order_direction,
             range: _,
+            block: _,
+            after,
         } = query;
 
         // List all entities with correct type
@@ -94,6 +97,20 @@ impl MockStore {
             entities_of_type.collect()
         };
 
+        // Skip entities up to and including the `after` cursor; since we
+        // only support ordering by `id`, that is all entities whose id is not
+        // greater than the cursor
+        let filtered_entities: Vec<_> = match after {
+            Some(after) => filtered_entities
+                .into_iter()
+                .filter(|entity| match entity.get("id") {
+                    Some(Value::String(id)) => id > &after,
+                    _ => false,
+                })
+                .collect(),
+            None => filtered_entities,
+        };
+
         // Sort results
         let sorted_entities = if let Some((order_by_attr_name, _order_by_attr_type)) = order_by {
             if order_by_attr_name == "id" {
@@ -143,6 +160,14 @@ impl Store for MockStore {
         Ok(None)
     }
 
+    fn block_number(
+        &self,
+        _: &SubgraphDeploymentId,
+        _: H256,
+    ) -> Result<Option<BlockNumber>, StoreError> {
+        unimplemented!();
+    }
+
     fn block_ptr(&self, _: SubgraphDeploymentId) -> Result<EthereumBlockPointer, Error> {
         unimplemented!();
     }
@@ -333,9 +358,14 @@ impl Store for MockStore {
         StoreEventStream::new(Box::new(receiver))
     }
 
+    fn uses_relational_schema(&self, _: &SubgraphDeploymentId) -> Result<bool, StoreError> {
+        Ok(true)
+    }
+
     fn create_subgraph_deployment(
         &self,
         _logger: &Logger,
+        _name: &SubgraphName,
         _schema: &Schema,
         ops: Vec<MetadataOperation>,
     ) -> Result<(), StoreError> {
@@ -357,6 +387,38 @@ impl Store for MockStore {
         _: &EthereumBlockPointer,
     ) {
     }
+
+    fn copy_graft_base(&self, _: &Logger, _: &SubgraphDeploymentId) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    fn prune_subgraph_deployment(&self, _: &Logger, _: &SubgraphDeploymentId, _: BlockNumber) {}
+
+    fn export_subgraph_deployment(
+        &self,
+        _: &Logger,
+        _: &SubgraphDeploymentId,
+        _: &Path,
+    ) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn import_subgraph_deployment(
+        &self,
+        _: &Logger,
+        _: &Path,
+    ) -> Result<SubgraphDeploymentId, StoreError> {
+        unimplemented!()
+    }
+
+    fn move_subgraph_deployment(
+        &self,
+        _: &Logger,
+        _: &SubgraphDeploymentId,
+        _: &str,
+    ) -> Result<(), StoreError> {
+        unimplemented!()
+    }
 }
 
 impl SubgraphDeploymentStore for MockStore {
@@ -378,6 +440,27 @@ impl SubgraphDeploymentStore for MockStore {
     }
 }
 
+impl EthereumCallCache for MockStore {
+    fn get_call(
+        &self,
+        _: ethabi::Address,
+        _: &[u8],
+        _: EthereumBlockPointer,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+
+    fn set_call(
+        &self,
+        _: ethabi::Address,
+        _: &[u8],
+        _: EthereumBlockPointer,
+        _: &[u8],
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
 impl ChainStore for MockStore {
     type ChainHeadUpdateListener = MockChainHeadUpdateListener;
 
@@ -388,12 +471,15 @@ impl ChainStore for MockStore {
         })
     }
 
-    fn upsert_blocks<'a, B, E>(&self, _: B) -> Box<dyn Future<Item = (), Error = E> + Send + 'a>
+    fn upsert_blocks<'a, B, E>(
+        &self,
+        blocks: B,
+    ) -> Box<dyn Future<Item = (), Error = E> + Send + 'a>
     where
         B: Stream<Item = EthereumBlock, Error = E> + Send + 'a,
         E: From<Error> + Send + 'a,
     {
-        unimplemented!();
+        Box::new(blocks.for_each(|_| Ok(())))
     }
 
     fn attempt_chain_head_update(&self, _: u64) -> Result<Vec<H256>, Error> {
@@ -444,6 +530,14 @@ impl Store for FakeStore {
         }
     }
 
+    fn block_number(
+        &self,
+        _: &SubgraphDeploymentId,
+        _: H256,
+    ) -> Result<Option<BlockNumber>, StoreError> {
+        unimplemented!();
+    }
+
     fn block_ptr(&self, _: SubgraphDeploymentId) -> Result<EthereumBlockPointer, Error> {
         unimplemented!();
     }
@@ -491,9 +585,14 @@ impl Store for FakeStore {
         unimplemented!();
     }
 
+    fn uses_relational_schema(&self, _: &SubgraphDeploymentId) -> Result<bool, StoreError> {
+        unimplemented!();
+    }
+
     fn create_subgraph_deployment(
         &self,
         _logger: &Logger,
+        _name: &SubgraphName,
         _schema: &Schema,
         _ops: Vec<MetadataOperation>,
     ) -> Result<(), StoreError> {
@@ -516,6 +615,61 @@ impl Store for FakeStore {
     ) {
         unimplemented!()
     }
+
+    fn copy_graft_base(&self, _: &Logger, _: &SubgraphDeploymentId) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn prune_subgraph_deployment(&self, _: &Logger, _: &SubgraphDeploymentId, _: BlockNumber) {
+        unimplemented!()
+    }
+
+    fn export_subgraph_deployment(
+        &self,
+        _: &Logger,
+        _: &SubgraphDeploymentId,
+        _: &Path,
+    ) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+
+    fn import_subgraph_deployment(
+        &self,
+        _: &Logger,
+        _: &Path,
+    ) -> Result<SubgraphDeploymentId, StoreError> {
+        unimplemented!()
+    }
+
+    fn move_subgraph_deployment(
+        &self,
+        _: &Logger,
+        _: &SubgraphDeploymentId,
+        _: &str,
+    ) -> Result<(), StoreError> {
+        unimplemented!()
+    }
+}
+
+impl EthereumCallCache for FakeStore {
+    fn get_call(
+        &self,
+        _: ethabi::Address,
+        _: &[u8],
+        _: EthereumBlockPointer,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        Ok(None)
+    }
+
+    fn set_call(
+        &self,
+        _: ethabi::Address,
+        _: &[u8],
+        _: EthereumBlockPointer,
+        _: &[u8],
+    ) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 impl ChainStore for FakeStore {
@@ -23,14 +23,14 @@ use futures::sync::mpsc;
 use git_testament::{git_testament, render_testament};
 use ipfs_api::IpfsClient;
 use lazy_static::lazy_static;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::str::FromStr;
 use std::time::Duration;
 
 use graph::components::forward;
 use graph::log::logger;
-use graph::prelude::{IndexNodeServer as _, JsonRpcServer as _, *};
+use graph::prelude::{IndexNodeServer as _, JsonRpcServer as _, MetricsServer as _, *};
 use graph::util::security::SafeDisplay;
 use graph_core::{
     LinkResolver, SubgraphAssignmentProvider as IpfsSubgraphAssignmentProvider,
@@ -41,8 +41,9 @@ use graph_runtime_wasm::RuntimeHostBuilder as WASMRuntimeHostBuilder;
 use graph_server_http::GraphQLServer as GraphQLQueryServer;
 use graph_server_index_node::IndexNodeServer;
 use graph_server_json_rpc::JsonRpcServer;
+use graph_server_metrics::PrometheusMetricsServer;
 use graph_server_websocket::SubscriptionServer as GraphQLSubscriptionServer;
-use graph_store_postgres::{Store as DieselStore, StoreConfig};
+use graph_store_postgres::{PlacementRule, Store as DieselStore, StoreConfig};
 
 use tokio_timer::timer::Timer;
 
@@ -157,6 +158,32 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                 .value_name("URL")
                 .help("Location of the Postgres database used for storing entities"),
         )
+        .arg(
+            Arg::with_name("postgres-shard")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .long("postgres-shard")
+                .value_name("SHARD:URL")
+                .help(
+                    "Name and location of an additional Postgres database that \
+                     stores the entities of the subgraphs placed in it, separated by a ':'",
+                ),
+        )
+        .arg(
+            Arg::with_name("shard-placement")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .long("shard-placement")
+                .value_name("SHARD:PATTERN")
+                .help(
+                    "Place new deployments of subgraphs whose name matches PATTERN \
+                     in SHARD; '*' in PATTERN matches any characters. The first \
+                     matching rule wins, and deployments that match no rule are \
+                     placed in the 'primary' shard",
+                ),
+        )
         .arg(
             Arg::with_name("ethereum-rpc")
                 .takes_value(true)
@@ -221,6 +248,13 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                 .value_name("PORT")
                 .help("Port for the index node server"),
         )
+        .arg(
+            Arg::with_name("metrics-port")
+                .default_value("8040")
+                .long("metrics-port")
+                .value_name("PORT")
+                .help("Port for the Prometheus metrics server"),
+        )
         .arg(
             Arg::with_name("ws-port")
                 .default_value("8001")
@@ -309,6 +343,12 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
     // Safe to unwrap because a value is required by CLI
     let postgres_url = matches.value_of("postgres-url").unwrap().to_string();
 
+    // Obtain the additional Postgres shards and where to place subgraphs
+    let shards = parse_postgres_shards(matches.values_of("postgres-shard"))
+        .unwrap_or_else(|e| panic!("Failed to parse Postgres shards: {}", e));
+    let placement = parse_shard_placement(matches.values_of("shard-placement"))
+        .unwrap_or_else(|e| panic!("Failed to parse shard placement: {}", e));
+
     let node_id = NodeId::new(matches.value_of("node-id").unwrap())
         .expect("Node ID must contain only a-z, A-Z, 0-9, and '_'");
 
@@ -354,6 +394,13 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
         .parse()
         .expect("invalid index node server port");
 
+    // Obtain metrics server port
+    let metrics_port = matches
+        .value_of("metrics-port")
+        .unwrap()
+        .parse()
+        .expect("invalid metrics server port");
+
     debug!(logger, "Setting up Sentry");
 
     // Obtain DISABLE_BLOCK_INGESTOR setting
@@ -414,6 +461,15 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
     // Create a component and subgraph logger factory
     let logger_factory = LoggerFactory::new(logger.clone(), elastic_config);
 
+    // Set up Prometheus registry
+    let prometheus_registry = Arc::new(Registry::new());
+    let metrics_registry = Arc::new(graph_core::MetricsRegistry::new(
+        logger.clone(),
+        prometheus_registry.clone(),
+    ));
+    let mut metrics_server =
+        PrometheusMetricsServer::new(&logger_factory, prometheus_registry.clone());
+
     info!(
         logger,
         "Trying IPFS node at: {}",
@@ -471,7 +527,12 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
     .cloned()
     .filter(|(_, values)| values.is_some())
     .fold(HashMap::new(), |adapters, (connection_type, values)| {
-        match parse_ethereum_networks_and_nodes(logger.clone(), values.unwrap(), connection_type) {
+        match parse_ethereum_networks_and_nodes(
+            logger.clone(),
+            values.unwrap(),
+            connection_type,
+            metrics_registry.clone(),
+        ) {
             Ok(adapter) => adapters.into_iter().chain(adapter).collect(),
             Err(e) => {
                 panic!(
@@ -523,9 +584,12 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
                                 network_name: network_name.to_string(),
                                 start_block: *ETHEREUM_START_BLOCK,
                                 conn_pool_size: store_conn_pool_size,
+                                shards: shards.clone(),
+                                placement: placement.clone(),
                             },
                             &logger,
                             network_identifier,
+                            metrics_registry.clone(),
                         )),
                     )
                 }
@@ -542,6 +606,7 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
     let graphql_runner = Arc::new(graph_core::GraphQlRunner::new(
         &logger,
         generic_store.clone(),
+        metrics_registry.clone(),
     ));
     let mut graphql_server = GraphQLQueryServer::new(
         &logger_factory,
@@ -592,14 +657,20 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
         node_id.clone(),
         *REORG_THRESHOLD,
     );
-    let runtime_host_builder =
-        WASMRuntimeHostBuilder::new(eth_adapters.clone(), link_resolver.clone(), stores.clone());
+    let runtime_host_builder = WASMRuntimeHostBuilder::new(
+        eth_adapters.clone(),
+        link_resolver.clone(),
+        stores.clone(),
+        metrics_registry.clone(),
+    );
 
     let subgraph_instance_manager = SubgraphInstanceManager::new(
         &logger_factory,
         stores.clone(),
         runtime_host_builder,
         block_stream_builder,
+        metrics_registry.clone(),
+        *REORG_THRESHOLD,
     );
 
     // Create IPFS-based subgraph provider
@@ -630,6 +701,7 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
         stores,
         node_id.clone(),
         version_switching_mode,
+        *REORG_THRESHOLD,
     ));
     tokio::spawn(
         subgraph_registrar
@@ -701,6 +773,13 @@ fn async_main() -> impl Future<Item = (), Error = ()> + Send + 'static {
             .expect("Failed to start index node server"),
     );
 
+    // Serve Prometheus metrics
+    tokio::spawn(
+        metrics_server
+            .serve(metrics_port)
+            .expect("Failed to start metrics server"),
+    );
+
     // Periodically check for contention in the tokio threadpool. First spawn a
     // task that simply responds to "ping" requests. Then spawn a separate
     // thread to periodically ping it and check responsiveness.
@@ -738,6 +817,7 @@ fn parse_ethereum_networks_and_nodes(
     logger: Logger,
     networks: clap::Values,
     connection_type: ConnectionType,
+    metrics_registry: Arc<dyn MetricsRegistry>,
 ) -> Result<
     HashMap<
         String,
@@ -799,9 +879,60 @@ fn parse_ethereum_networks_and_nodes(
                     Arc::new(graph_datasource_ethereum::EthereumAdapter::new(
                         transport,
                         *ETHEREUM_START_BLOCK,
+                        name,
+                        metrics_registry.clone(),
                     )),
                 ))
             }
         })
         .collect()
 }
+
+/// Split `value` of the form `SHARD:REST` at the first ':'
+fn split_name<'a>(value: &'a str, what: &str) -> Result<(&'a str, &'a str), Error> {
+    let split_at = value
+        .find(':')
+        .ok_or_else(|| format_err!("`{}` must have the form SHARD:{}", value, what))?;
+    let (name, rest) = value.split_at(split_at);
+    let rest = &rest[1..];
+
+    if name.is_empty() {
+        return Err(format_err!(
+            "Shard name in `{}` cannot be an empty string",
+            value
+        ));
+    }
+    if rest.is_empty() {
+        return Err(format_err!(
+            "{} in `{}` cannot be an empty string",
+            what,
+            value
+        ));
+    }
+    Ok((name, rest))
+}
+
+fn parse_postgres_shards(values: Option<clap::Values>) -> Result<BTreeMap<String, String>, Error> {
+    let mut shards = BTreeMap::new();
+    for value in values.into_iter().flatten() {
+        let (name, url) = split_name(value, "URL")?;
+        if shards.insert(name.to_owned(), url.to_owned()).is_some() {
+            return Err(format_err!("Shard `{}` is defined more than once", name));
+        }
+    }
+    Ok(shards)
+}
+
+fn parse_shard_placement(values: Option<clap::Values>) -> Result<Vec<PlacementRule>, Error> {
+    values
+        .into_iter()
+        .flatten()
+        .map(|value| {
+            let (shard, pattern) = split_name(value, "PATTERN")?;
+            Ok(PlacementRule {
+                shard: shard.to_owned(),
+                pattern: pattern.to_owned(),
+            })
+        })
+        .collect()
+}
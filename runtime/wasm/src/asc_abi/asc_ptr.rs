@@ -34,7 +34,7 @@ impl<C: AscType> AscPtr<C> {
     }
 
     /// Read from `self` into the Rust struct `C`.
-    pub(super) fn read_ptr<H: AscHeap>(self, heap: &H) -> C {
+    pub(crate) fn read_ptr<H: AscHeap>(self, heap: &H) -> C {
         C::from_asc_bytes(&heap.get(self.0, C::asc_size(self, heap)).unwrap())
     }
 
@@ -458,6 +458,53 @@ where
     pub params: AscPtr<AscLogParamArray>,
 }
 
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscEthereumLog {
+    pub address: AscPtr<AscAddress>,
+    pub topics: AscPtr<Array<AscPtr<AscH256>>>,
+    pub data: AscPtr<Bytes>,
+    pub block_hash: AscPtr<AscH256>,
+    pub block_number: AscPtr<AscBigInt>,
+    pub transaction_hash: AscPtr<AscH256>,
+    pub transaction_index: AscPtr<AscBigInt>,
+    pub log_index: AscPtr<AscBigInt>,
+    pub transaction_log_index: AscPtr<AscBigInt>,
+    pub log_type: AscPtr<AscString>,
+}
+
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscEthereumTransactionReceipt {
+    pub transaction_hash: AscPtr<AscH256>,
+    pub transaction_index: AscPtr<AscBigInt>,
+    pub block_hash: AscPtr<AscH256>,
+    pub block_number: AscPtr<AscBigInt>,
+    pub cumulative_gas_used: AscPtr<AscBigInt>,
+    pub gas_used: AscPtr<AscBigInt>,
+    pub contract_address: AscPtr<AscAddress>,
+    pub status: AscPtr<AscBigInt>,
+    pub logs: AscPtr<Array<AscPtr<AscEthereumLog>>>,
+}
+
+/// The event passed to handlers that set `receipt: true` in the manifest.
+/// It extends `AscEthereumEvent` with the receipt of the transaction.
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscEthereumEventWithReceipt<T>
+where
+    T: AscType,
+{
+    pub address: AscPtr<AscAddress>,
+    pub log_index: AscPtr<AscBigInt>,
+    pub transaction_log_index: AscPtr<AscBigInt>,
+    pub log_type: AscPtr<AscString>,
+    pub block: AscPtr<AscEthereumBlock>,
+    pub transaction: AscPtr<T>,
+    pub params: AscPtr<AscLogParamArray>,
+    pub receipt: AscPtr<AscEthereumTransactionReceipt>,
+}
+
 #[repr(C)]
 #[derive(AscType)]
 pub(crate) struct AscEthereumCall {
@@ -540,6 +587,23 @@ impl JsonValueKind {
     }
 }
 
+/// Boxes a value so that `AscResult` can point to it even if it is a
+/// primitive, which lets it use `null` for the side that is absent.
+#[repr(C)]
+#[derive(AscType, Copy, Clone)]
+pub(crate) struct AscWrapped<V: AscValue> {
+    pub inner: V,
+}
+
+/// A Rust `Result` as it is passed to the mapping. Exactly one of `value`
+/// and `error` is not null.
+#[repr(C)]
+#[derive(AscType)]
+pub(crate) struct AscResult<V: AscValue, E: AscValue> {
+    pub value: AscPtr<AscWrapped<V>>,
+    pub error: AscPtr<AscWrapped<E>>,
+}
+
 #[repr(C)]
 #[derive(AscType)]
 pub(crate) struct AscBigDecimal {
@@ -6,6 +6,7 @@
 //! Implementations of `To`/`FromAscObj` live in the `to_from` module.
 
 pub use self::asc_ptr::AscPtr;
+use graph::prelude::Error;
 use std::mem::size_of;
 use wasmi;
 
@@ -52,6 +53,16 @@ pub trait AscHeap: Sized {
     {
         T::from_asc_obj(asc_ptr.read_ptr(self), self)
     }
+
+    ///  Like `asc_get`, for types that can not represent every value of the
+    ///  Asc object.
+    fn try_asc_get<T, C>(&self, asc_ptr: AscPtr<C>) -> Result<T, Error>
+    where
+        C: AscType,
+        T: TryFromAscObj<C>,
+    {
+        T::try_from_asc_obj(asc_ptr.read_ptr(self), self)
+    }
 }
 
 /// Type that can be converted to an Asc object of class `C`.
@@ -64,6 +75,12 @@ pub trait FromAscObj<C: AscType> {
     fn from_asc_obj<H: AscHeap>(obj: C, heap: &H) -> Self;
 }
 
+/// Type that can be converted from an Asc object of class `C`, but not from
+/// every value of that class.
+pub trait TryFromAscObj<C: AscType>: Sized {
+    fn try_from_asc_obj<H: AscHeap>(obj: C, heap: &H) -> Result<Self, Error>;
+}
+
 // `AscType` is not really public, implementors should live inside the `class` module.
 
 /// A type that has a direct corespondence to an Asc type.
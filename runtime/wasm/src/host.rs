@@ -18,14 +18,18 @@ use graph::prelude::{
     RuntimeHost as RuntimeHostTrait, RuntimeHostBuilder as RuntimeHostBuilderTrait, *,
 };
 use graph::util;
-use web3::types::{Log, Transaction};
+use web3::types::{Log, Transaction, H256};
 
 pub(crate) const TIMEOUT_ENV_VAR: &str = "GRAPH_MAPPING_HANDLER_TIMEOUT";
+pub(crate) const GAS_LIMIT_ENV_VAR: &str = "GRAPH_MAPPING_HANDLER_GAS_LIMIT";
 
 pub struct RuntimeHostConfig {
     subgraph_id: SubgraphDeploymentId,
+    schema: Arc<Schema>,
     mapping: Mapping,
     data_source_name: String,
+    data_source_network: String,
+    data_source_context: Option<DataSourceContext>,
     contract: Source,
     templates: Vec<DataSourceTemplate>,
 }
@@ -34,19 +38,21 @@ pub struct RuntimeHostBuilder<T, L, S> {
     ethereum_adapters: HashMap<String, Arc<T>>,
     link_resolver: Arc<L>,
     stores: HashMap<String, Arc<S>>,
+    handler_execution_time: Box<HistogramVec>,
 }
 
 impl<T, L, S> Clone for RuntimeHostBuilder<T, L, S>
 where
     T: EthereumAdapter,
     L: LinkResolver,
-    S: Store,
+    S: Store + EthereumCallCache,
 {
     fn clone(&self) -> Self {
         RuntimeHostBuilder {
             ethereum_adapters: self.ethereum_adapters.clone(),
             link_resolver: self.link_resolver.clone(),
             stores: self.stores.clone(),
+            handler_execution_time: self.handler_execution_time.clone(),
         }
     }
 }
@@ -55,17 +61,29 @@ impl<T, L, S> RuntimeHostBuilder<T, L, S>
 where
     T: EthereumAdapter,
     L: LinkResolver,
-    S: Store,
+    S: Store + EthereumCallCache,
 {
     pub fn new(
         ethereum_adapters: HashMap<String, Arc<T>>,
         link_resolver: Arc<L>,
         stores: HashMap<String, Arc<S>>,
+        metrics_registry: Arc<dyn MetricsRegistry>,
     ) -> Self {
+        let handler_execution_time = metrics_registry
+            .new_histogram_vec(
+                "subgraph_handler_execution_time_ms",
+                "Time taken to run a mapping handler, by deployment and handler name",
+                HashMap::new(),
+                &["deployment", "handler"],
+                vec![1.0, 5.0, 10.0, 50.0, 100.0, 500.0, 1000.0, 5000.0, 30000.0],
+            )
+            .expect("failed to create `subgraph_handler_execution_time_ms` histogram");
+
         RuntimeHostBuilder {
             ethereum_adapters,
             link_resolver,
             stores,
+            handler_execution_time,
         }
     }
 }
@@ -74,7 +92,7 @@ impl<T, L, S> RuntimeHostBuilderTrait for RuntimeHostBuilder<T, L, S>
 where
     T: EthereumAdapter,
     L: LinkResolver,
-    S: Store,
+    S: Store + EthereumCallCache,
 {
     type Host = RuntimeHost;
 
@@ -83,6 +101,7 @@ where
         logger: &Logger,
         network_name: String,
         subgraph_id: SubgraphDeploymentId,
+        schema: Arc<Schema>,
         data_source: DataSource,
         top_level_templates: Vec<DataSourceTemplate>,
     ) -> Result<Self::Host, Error> {
@@ -112,10 +131,14 @@ where
             ethereum_adapter.clone(),
             self.link_resolver.clone(),
             store.clone(),
+            self.handler_execution_time.clone(),
             RuntimeHostConfig {
                 subgraph_id,
+                schema,
                 mapping: data_source.mapping,
                 data_source_name: data_source.name,
+                data_source_network: network_name,
+                data_source_context: data_source.context,
                 contract: data_source.source,
                 templates,
             },
@@ -154,12 +177,24 @@ enum MappingTrigger {
     },
 }
 
+impl MappingTrigger {
+    /// Name of the mapping handler that processes this trigger.
+    fn handler_name(&self) -> &str {
+        match self {
+            MappingTrigger::Log { handler, .. } => handler.handler.as_str(),
+            MappingTrigger::Call { handler, .. } => handler.handler.as_str(),
+            MappingTrigger::Block { handler } => handler.handler.as_str(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct RuntimeHost {
     data_source_name: String,
     data_source_contract: Source,
     data_source_contract_abi: MappingABI,
     data_source_event_handlers: Vec<MappingEventHandler>,
+    data_source_named_event_signatures: Vec<H256>,
     data_source_call_handlers: Vec<MappingCallHandler>,
     data_source_block_handlers: Vec<MappingBlockHandler>,
     mapping_request_sender: Sender<MappingRequest>,
@@ -172,12 +207,13 @@ impl RuntimeHost {
         ethereum_adapter: Arc<T>,
         link_resolver: Arc<L>,
         store: Arc<S>,
+        handler_execution_time: Box<HistogramVec>,
         config: RuntimeHostConfig,
     ) -> Result<Self, Error>
     where
         T: EthereumAdapter,
         L: LinkResolver,
-        S: Store,
+        S: Store + EthereumCallCache,
     {
         let logger = logger.new(o!(
             "component" => "RuntimeHost",
@@ -227,6 +263,8 @@ impl RuntimeHost {
                 )
             })?
             .clone();
+        let data_source_named_event_signatures =
+            util::ethereum::named_event_signatures(&data_source_contract_abi.contract);
 
         // Spawn a dedicated thread for the runtime.
         //
@@ -239,12 +277,17 @@ impl RuntimeHost {
         ));
         conf.spawn(move || {
             debug!(module_logger, "Start WASM runtime");
+            let deployment_label = config.subgraph_id.to_string();
             let wasmi_config = WasmiModuleConfig {
                 subgraph_id: config.subgraph_id,
+                schema: config.schema,
                 api_version: Version::parse(&config.mapping.api_version).unwrap(),
                 parsed_module: config.mapping.runtime,
                 abis: config.mapping.abis,
                 data_source_name: config.data_source_name,
+                data_source_address: config.contract.address,
+                data_source_network: config.data_source_network,
+                data_source_context: config.data_source_context,
                 templates: config.templates,
                 ethereum_adapter: ethereum_adapter.clone(),
                 link_resolver: link_resolver.clone(),
@@ -253,6 +296,9 @@ impl RuntimeHost {
                     .ok()
                     .and_then(|s| u64::from_str(&s).ok())
                     .map(Duration::from_secs),
+                handler_gas_limit: std::env::var(GAS_LIMIT_ENV_VAR)
+                    .ok()
+                    .and_then(|s| u64::from_str(&s).ok()),
             };
             let valid_module = ValidModule::new(&module_logger, wasmi_config, task_sender)
                 .expect("Failed to validate module");
@@ -286,6 +332,10 @@ impl RuntimeHost {
                     let module =
                         WasmiModule::from_valid_module_with_ctx(valid_module.clone(), ctx)?;
 
+                    let handler_histogram = handler_execution_time
+                        .with_label_values(&[&deployment_label, trigger.handler_name()]);
+                    let start_time = Instant::now();
+
                     let result = match trigger {
                         MappingTrigger::Log {
                             transaction,
@@ -297,6 +347,7 @@ impl RuntimeHost {
                             transaction,
                             log,
                             params,
+                            handler.receipt,
                         ),
                         MappingTrigger::Call {
                             transaction,
@@ -316,6 +367,8 @@ impl RuntimeHost {
                         }
                     };
 
+                    handler_histogram.observe(start_time.elapsed().as_millis() as f64);
+
                     result_sender
                         .send((result, future::ok(Instant::now())))
                         .map_err(|_| err_msg("WASM module result receiver dropped."))
@@ -333,6 +386,7 @@ impl RuntimeHost {
             data_source_contract,
             data_source_contract_abi,
             data_source_event_handlers,
+            data_source_named_event_signatures,
             data_source_call_handlers,
             data_source_block_handlers,
             mapping_request_sender,
@@ -368,14 +422,13 @@ impl RuntimeHost {
     }
 
     fn matches_log_signature(&self, log: &Log) -> bool {
-        let topic0 = match log.topics.iter().next() {
-            Some(topic0) => topic0,
-            None => return false,
-        };
-
-        self.data_source_event_handlers
-            .iter()
-            .any(|handler| *topic0 == handler.topic0())
+        self.data_source_event_handlers.iter().any(|handler| {
+            handler.matches_signature(
+                log,
+                &self.data_source_contract_abi.contract,
+                &self.data_source_named_event_signatures,
+            ) && handler.matches_topics(&log.topics)
+        })
     }
 
     fn matches_block_trigger(&self, block_trigger_type: EthereumBlockTriggerType) -> bool {
@@ -386,22 +439,24 @@ impl RuntimeHost {
                     // Do not match if this datasource has no address
                     .map_or(false, |addr| addr == address)
             }
-            EthereumBlockTriggerType::Every => true,
+            EthereumBlockTriggerType::Every
+            | EthereumBlockTriggerType::Polling(_)
+            | EthereumBlockTriggerType::Once(_) => true,
         };
         source_address_matches && self.handler_for_block(block_trigger_type).is_ok()
     }
 
     fn handlers_for_log(&self, log: &Arc<Log>) -> Result<Vec<MappingEventHandler>, Error> {
-        // Get signature from the log
-        let topic0 = match log.topics.iter().next() {
-            Some(topic0) => topic0,
-            None => return Err(format_err!("Ethereum event has no topics")),
-        };
-
         let handlers = self
             .data_source_event_handlers
             .iter()
-            .filter(|handler| *topic0 == handler.topic0())
+            .filter(|handler| {
+                handler.matches_signature(
+                    log,
+                    &self.data_source_contract_abi.contract,
+                    &self.data_source_named_event_signatures,
+                ) && handler.matches_topics(&log.topics)
+            })
             .cloned()
             .collect::<Vec<_>>();
 
@@ -474,6 +529,31 @@ impl RuntimeHost {
                         self.data_source_name,
                     )
                 }),
+            EthereumBlockTriggerType::Polling(number) | EthereumBlockTriggerType::Once(number) => {
+                let start_block = self.data_source_contract.start_block;
+                self.data_source_block_handlers
+                    .iter()
+                    .find(|handler| match (&handler.filter, &trigger_type) {
+                        (
+                            Some(filter @ BlockHandlerFilter::Polling { .. }),
+                            EthereumBlockTriggerType::Polling(_),
+                        )
+                        | (
+                            Some(filter @ BlockHandlerFilter::Once),
+                            EthereumBlockTriggerType::Once(_),
+                        ) => filter.matches_block_number(start_block, number),
+                        _ => false,
+                    })
+                    .cloned()
+                    .ok_or_else(|| {
+                        format_err!(
+                            "No block handler for `{:?}` block trigger \
+                             type found in data source \"{}\"",
+                            trigger_type,
+                            self.data_source_name,
+                        )
+                    })
+            }
         }
     }
 }
@@ -879,3 +959,110 @@ impl RuntimeHostTrait for RuntimeHost {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethabi::Contract;
+    use graph::prelude::serde_json::json;
+    use web3::types::Address;
+
+    /// A contract with an anonymous event `Stored(indexed uint256,uint256)`.
+    const ABI: &str = r#"[{
+        "type": "event",
+        "name": "Stored",
+        "anonymous": true,
+        "inputs": [
+            { "name": "id", "type": "uint256", "indexed": true },
+            { "name": "value", "type": "uint256", "indexed": false }
+        ]
+    }]"#;
+
+    fn address() -> Address {
+        Address::from([1; 20])
+    }
+
+    fn topic(n: u8) -> H256 {
+        H256::from([n; 32])
+    }
+
+    fn runtime_host(event_handlers: Vec<MappingEventHandler>) -> RuntimeHost {
+        let contract = Contract::load(ABI.as_bytes()).unwrap();
+        RuntimeHost {
+            data_source_name: String::from("test"),
+            data_source_contract: Source {
+                address: Some(address()),
+                abi: String::from("Contract"),
+                start_block: 0,
+            },
+            data_source_named_event_signatures: util::ethereum::named_event_signatures(&contract),
+            data_source_contract_abi: MappingABI {
+                name: String::from("Contract"),
+                contract,
+                link: Link::from(String::from("link")),
+            },
+            data_source_event_handlers: event_handlers,
+            data_source_call_handlers: vec![],
+            data_source_block_handlers: vec![],
+            mapping_request_sender: channel(1).0,
+            _guard: oneshot::channel().0,
+        }
+    }
+
+    fn anonymous_handler() -> MappingEventHandler {
+        MappingEventHandler {
+            event: String::from("Stored(indexed uint256,uint256)"),
+            topic0: None,
+            handler: String::from("handleStored"),
+            receipt: false,
+            topic1: None,
+            topic2: None,
+            topic3: None,
+            anonymous: true,
+        }
+    }
+
+    fn log(topics: Vec<H256>, data: &str) -> Log {
+        serde_json::from_value(json!({
+            "address": address(),
+            "topics": topics,
+            "data": data,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn matches_anonymous_events_that_decode() {
+        let host = runtime_host(vec![anonymous_handler()]);
+
+        let value = format!("0x{}", "00".repeat(32));
+        assert!(host.matches_log(&log(vec![topic(1)], &value)));
+        // Logs with the right number of topics whose data doesn't decode
+        // are not instances of the event
+        assert!(!host.matches_log(&log(vec![topic(1)], "0x")));
+        assert!(!host.matches_log(&log(vec![topic(1), topic(2)], &value)));
+    }
+
+    #[test]
+    fn matches_topic_filters() {
+        let host = runtime_host(vec![MappingEventHandler {
+            event: String::from("Transfer(indexed address,indexed address,indexed uint256)"),
+            topic0: Some(topic(9)),
+            handler: String::from("handleTransfer"),
+            receipt: false,
+            topic1: Some(vec![topic(1), topic(2)]),
+            topic2: Some(vec![topic(3)]),
+            topic3: Some(vec![topic(4)]),
+            anonymous: false,
+        }]);
+
+        assert!(host.matches_log(&log(vec![topic(9), topic(1), topic(3), topic(4)], "0x")));
+        assert!(host.matches_log(&log(vec![topic(9), topic(2), topic(3), topic(4)], "0x")));
+        // Each of topics 1-3 has to be one of its accepted values
+        assert!(!host.matches_log(&log(vec![topic(9), topic(5), topic(3), topic(4)], "0x")));
+        assert!(!host.matches_log(&log(vec![topic(9), topic(1), topic(5), topic(4)], "0x")));
+        assert!(!host.matches_log(&log(vec![topic(9), topic(1), topic(3), topic(5)], "0x")));
+        // Logs without the restricted topics don't match
+        assert!(!host.matches_log(&log(vec![topic(9), topic(1), topic(3)], "0x")));
+    }
+}
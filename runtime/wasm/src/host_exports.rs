@@ -1,5 +1,6 @@
 use crate::MappingContext;
 use crate::UnresolvedContractCall;
+use ethabi::param_type::Reader;
 use ethabi::Token;
 use futures::sync::oneshot;
 use graph::components::ethereum::*;
@@ -7,7 +8,12 @@ use graph::components::store::EntityKey;
 use graph::data::store;
 use graph::prelude::serde_json;
 use graph::prelude::{slog::b, slog::record_static, *};
+use lazy_static::lazy_static;
+use ripemd160::Ripemd160;
+use secp256k1::recovery::{RecoverableSignature, RecoveryId};
+use secp256k1::{Message, Secp256k1, VerifyOnly};
 use semver::Version;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::fmt;
 use std::ops::Deref;
@@ -17,6 +23,11 @@ use web3::types::H160;
 
 use crate::module::WasmiModule;
 
+lazy_static! {
+    /// Setting up a secp256k1 context is expensive, so we share one.
+    static ref SECP256K1: Secp256k1<VerifyOnly> = Secp256k1::verification_only();
+}
+
 pub(crate) trait ExportError: fmt::Debug + fmt::Display + Send + Sync + 'static {}
 
 impl<E> ExportError for E where E: fmt::Debug + fmt::Display + Send + Sync + 'static {}
@@ -39,8 +50,12 @@ impl From<graph::prelude::Error> for HostExportError<String> {
 
 pub(crate) struct HostExports<E, L, S, U> {
     subgraph_id: SubgraphDeploymentId,
+    schema: Arc<Schema>,
     pub api_version: Version,
     data_source_name: String,
+    data_source_address: Option<H160>,
+    data_source_network: String,
+    data_source_context: Option<DataSourceContext>,
     templates: Vec<DataSourceTemplate>,
     abis: Vec<MappingABI>,
     ethereum_adapter: Arc<E>,
@@ -48,13 +63,14 @@ pub(crate) struct HostExports<E, L, S, U> {
     store: Arc<S>,
     task_sink: U,
     handler_timeout: Option<Duration>,
+    handler_gas_limit: Option<u64>,
 }
 
 impl<E, L, S, U> HostExports<E, L, S, U>
 where
     E: EthereumAdapter,
     L: LinkResolver,
-    S: Store + Send + Sync,
+    S: Store + EthereumCallCache + Send + Sync,
     U: Sink<SinkItem = Box<dyn Future<Item = (), Error = ()> + Send>>
         + Clone
         + Send
@@ -63,8 +79,12 @@ where
 {
     pub(crate) fn new(
         subgraph_id: SubgraphDeploymentId,
+        schema: Arc<Schema>,
         api_version: Version,
         data_source_name: String,
+        data_source_address: Option<H160>,
+        data_source_network: String,
+        data_source_context: Option<DataSourceContext>,
         templates: Vec<DataSourceTemplate>,
         abis: Vec<MappingABI>,
         ethereum_adapter: Arc<E>,
@@ -72,11 +92,16 @@ where
         store: Arc<S>,
         task_sink: U,
         handler_timeout: Option<Duration>,
+        handler_gas_limit: Option<u64>,
     ) -> Self {
         HostExports {
             subgraph_id,
+            schema,
             api_version,
             data_source_name,
+            data_source_address,
+            data_source_network,
+            data_source_context,
             templates,
             abis,
             ethereum_adapter,
@@ -84,6 +109,7 @@ where
             store,
             task_sink,
             handler_timeout,
+            handler_gas_limit,
         }
     }
 
@@ -139,7 +165,29 @@ where
             entity_type,
             entity_id,
         };
-        ctx.state.entity_cache.set(key, Entity::from(data));
+        let entity = Entity::from(data);
+
+        match self.schema.validate_entity(&key, &entity) {
+            Ok(()) => (),
+            // Fields that `store.set` does not mention keep their current
+            // value, so an entity that only lacks required fields may still be
+            // valid once it is merged with its current version.
+            Err(EntityValidationError::MissingField(..)) => {
+                let mut merged = ctx
+                    .state
+                    .entity_cache
+                    .get(self.store.as_ref(), key.clone())
+                    .map_err(|e| HostExportError(e.to_string()))?
+                    .unwrap_or_default();
+                merged.merge(entity.clone());
+                self.schema
+                    .validate_entity(&key, &merged)
+                    .map_err(|e| HostExportError(e.to_string()))?;
+            }
+            Err(e) => return Err(HostExportError(e.to_string())),
+        }
+
+        ctx.state.entity_cache.set(key, entity);
 
         Ok(())
     }
@@ -227,8 +275,9 @@ where
         // Run Ethereum call in tokio runtime
         let eth_adapter = self.ethereum_adapter.clone();
         let logger = ctx.logger.clone();
+        let call_cache = self.store.clone();
         let result = match self.block_on(future::lazy(move || {
-            eth_adapter.contract_call(&logger, call)
+            eth_adapter.contract_call(&logger, call, call_cache)
         })) {
             Ok(tokens) => Ok(Some(tokens)),
             Err(EthereumContractCallError::Revert(reason)) => {
@@ -328,6 +377,12 @@ where
         })
     }
 
+    /// Like `json_from_bytes`, but malformed JSON is returned to the mapping
+    /// as an error message instead of failing the handler.
+    pub(crate) fn json_try_from_bytes(&self, bytes: Vec<u8>) -> Result<serde_json::Value, String> {
+        serde_json::from_reader(&*bytes).map_err(|e| e.to_string())
+    }
+
     pub(crate) fn ipfs_cat(
         &self,
         logger: &Logger,
@@ -340,8 +395,29 @@ where
         )
     }
 
+    /// Like `ipfs_cat`, but a link that does not refer to a file is returned
+    /// to the mapping as an error message. All other errors, like timeouts,
+    /// an unreachable IPFS node or a file that exceeds the size limit of this
+    /// node, still fail the handler; see `block_on` for why.
+    pub(crate) fn ipfs_try_cat(
+        &self,
+        logger: &Logger,
+        link: String,
+    ) -> Result<Result<Vec<u8>, String>, HostExportError<impl ExportError>> {
+        match self.block_on(self.link_resolver.cat(logger, &Link { link })) {
+            Ok(bytes) => Ok(Ok(bytes)),
+            Err(e) => match e.downcast::<LinkResolverInvalidFile>() {
+                Ok(invalid) => Ok(Err(invalid.to_string())),
+                Err(e) => Err(HostExportError(e.to_string())),
+            },
+        }
+    }
+
     // Read the IPFS file `link`, split it into JSON objects, and invoke the
-    // exported function `callback` on each JSON object. The successful return
+    // exported function `callback` on each JSON object. The `flags` determine
+    // the format of the file: `json` for one JSON value per line, `json-array`
+    // for a single JSON array, and `csv` for CSV with a header row, whose
+    // records are passed as JSON objects keyed by column name. The successful return
     // value contains the block state produced by each callback invocation. Each
     // invocation of `callback` happens in its own instance of a WASM module,
     // which is identical to `module` when it was first started. The signature
@@ -354,11 +430,25 @@ where
         callback: &str,
         user_data: store::Value,
         flags: Vec<String>,
-    ) -> Result<Vec<BlockState>, HostExportError<impl ExportError>> {
-        const JSON_FLAG: &str = "json";
-        if !flags.contains(&JSON_FLAG.to_string()) {
-            return Err(HostExportError(format!("Flags must contain 'json'")));
-        }
+        gas_used: u64,
+    ) -> Result<(Vec<BlockState>, u64), HostExportError<impl ExportError>> {
+        let formats: Vec<_> = flags
+            .iter()
+            .filter_map(|flag| match flag.as_str() {
+                "json" => Some(JsonStreamFormat::JsonLines),
+                "json-array" => Some(JsonStreamFormat::JsonArray),
+                "csv" => Some(JsonStreamFormat::Csv),
+                _ => None,
+            })
+            .collect();
+        let format = match formats.as_slice() {
+            [format] => *format,
+            _ => {
+                return Err(HostExportError(format!(
+                    "Flags must contain exactly one of 'json', 'json-array' or 'csv'"
+                )))
+            }
+        };
 
         let valid_module = module.valid_module.clone();
         let ctx = module.ctx.clone();
@@ -372,9 +462,11 @@ where
         let start = Instant::now();
         let mut last_log = Instant::now();
         let logger = ctx.logger.new(o!("ipfs_map" => link.clone()));
+        // The callbacks use up the gas of the calling handler in turn
+        let mut gas = gas_used;
         self.block_on(
             self.link_resolver
-                .json_stream(&Link { link })
+                .json_stream(&Link { link }, format)
                 .and_then(move |stream| {
                     stream
                         .and_then(move |sv| {
@@ -382,8 +474,12 @@ where
                                 valid_module.clone(),
                                 ctx.clone(),
                             )?;
-                            let result =
-                                module.handle_json_callback(&*callback, &sv.value, &user_data);
+                            let result = module
+                                .handle_json_callback(&*callback, &sv.value, &user_data, gas)
+                                .map(|(state, used)| {
+                                    gas = used;
+                                    (state, used)
+                                });
                             // Log progress every 15s
                             if last_log.elapsed() > Duration::from_secs(15) {
                                 debug!(
@@ -397,6 +493,10 @@ where
                             result
                         })
                         .collect()
+                        .map(move |results: Vec<_>| {
+                            let gas = results.last().map_or(gas_used, |(_, gas)| *gas);
+                            (results.into_iter().map(|(state, _)| state).collect(), gas)
+                        })
                 })
                 .map_err(move |e| HostExportError(format!("{}: {}", errmsg, e.to_string()))),
         )
@@ -439,10 +539,94 @@ where
         Ok(big_int.to_signed_bytes_le())
     }
 
+    /// Encodes `token` as ABI data of type `types`, the way it would be
+    /// encoded as the only argument of a contract call.
+    pub(crate) fn ethereum_encode(
+        &self,
+        token: Token,
+        types: String,
+    ) -> Result<Vec<u8>, HostExportError<impl ExportError>> {
+        let param_type = Reader::read(&types)
+            .map_err(|e| HostExportError(format!("Failed to parse ABI type `{}`: {}", types, e)))?;
+        if !token.type_check(&param_type) {
+            return Err(HostExportError(format!(
+                "Value `{}` is not of type `{}`",
+                token, types
+            )));
+        }
+        Ok(ethabi::encode(&[token]))
+    }
+
+    /// Decodes `data` as a single ABI value of type `types`, for example
+    /// `(address,uint256[],bytes)`.
+    pub(crate) fn ethereum_decode(
+        &self,
+        types: String,
+        data: Vec<u8>,
+    ) -> Result<Token, HostExportError<impl ExportError>> {
+        let param_type = Reader::read(&types)
+            .map_err(|e| HostExportError(format!("Failed to parse ABI type `{}`: {}", types, e)))?;
+        ethabi::decode(&[param_type], &data)
+            .map_err(|e| HostExportError(format!("Failed to decode data as `{}`: {}", types, e)))?
+            .pop()
+            .ok_or_else(|| HostExportError(format!("No value of type `{}` in data", types)))
+    }
+
     pub(crate) fn crypto_keccak_256(&self, input: Vec<u8>) -> [u8; 32] {
         ::tiny_keccak::keccak256(&input)
     }
 
+    pub(crate) fn crypto_sha256(&self, input: Vec<u8>) -> [u8; 32] {
+        let mut hash = [0; 32];
+        hash.copy_from_slice(&Sha256::digest(&input));
+        hash
+    }
+
+    pub(crate) fn crypto_ripemd160(&self, input: Vec<u8>) -> [u8; 20] {
+        let mut hash = [0; 20];
+        hash.copy_from_slice(&Ripemd160::digest(&input));
+        hash
+    }
+
+    /// Recovers the address of the account that signed `hash` with the
+    /// signature `(v, r, s)`, like Solidity's `ecrecover`. `v` can be
+    /// either 0 or 1, or 27 or 28 as is common on Ethereum. Returns `None`
+    /// if `hash`, `r` or `s` do not have 32 bytes or the signature is
+    /// invalid.
+    pub(crate) fn crypto_secp256k1_recover(
+        &self,
+        hash: Vec<u8>,
+        v: i32,
+        r: Vec<u8>,
+        s: Vec<u8>,
+    ) -> Option<H160> {
+        if [&hash, &r, &s].iter().any(|bytes| bytes.len() != 32) {
+            return None;
+        }
+
+        let recovery_id = match v {
+            0 | 1 => v,
+            27 | 28 => v - 27,
+            _ => return None,
+        };
+        let mut signature = [0; 64];
+        signature[..32].copy_from_slice(&r);
+        signature[32..].copy_from_slice(&s);
+        let public_key = RecoveryId::from_i32(recovery_id)
+            .and_then(|recovery_id| RecoverableSignature::from_compact(&signature, recovery_id))
+            .and_then(|signature| {
+                let message = Message::from_slice(&hash)?;
+                SECP256K1.recover(&message, &signature)
+            });
+
+        // The address is the last 20 bytes of the hash of the public key,
+        // without the leading byte that marks it as uncompressed
+        public_key.ok().map(|public_key| {
+            let hash = ::tiny_keccak::keccak256(&public_key.serialize_uncompressed()[1..]);
+            H160::from_slice(&hash[12..])
+        })
+    }
+
     pub(crate) fn big_int_plus(&self, x: BigInt, y: BigInt) -> BigInt {
         x + y
     }
@@ -478,6 +662,53 @@ where
         x.pow(exponent)
     }
 
+    pub(crate) fn big_int_bit_or(&self, x: BigInt, y: BigInt) -> BigInt {
+        x | y
+    }
+
+    pub(crate) fn big_int_bit_and(&self, x: BigInt, y: BigInt) -> BigInt {
+        x & y
+    }
+
+    /// The shift is a `u8`, so `x` grows by at most 255 bits.
+    pub(crate) fn big_int_left_shift(&self, x: BigInt, bits: u8) -> BigInt {
+        x << bits
+    }
+
+    pub(crate) fn big_int_right_shift(&self, x: BigInt, bits: u8) -> BigInt {
+        x >> bits
+    }
+
+    pub(crate) fn big_int_sqrt(
+        &self,
+        x: BigInt,
+    ) -> Result<BigInt, HostExportError<impl ExportError>> {
+        if x < 0.into() {
+            return Err(HostExportError(format!(
+                "attempted to take the square root of negative BigInt `{}`",
+                x
+            )));
+        }
+        Ok(x.sqrt())
+    }
+
+    /// Returns -1, 0 or 1 if `x` is less than, equal to or greater than `y`.
+    pub(crate) fn big_int_compare(&self, x: BigInt, y: BigInt) -> i32 {
+        match x.cmp(&y) {
+            std::cmp::Ordering::Less => -1,
+            std::cmp::Ordering::Equal => 0,
+            std::cmp::Ordering::Greater => 1,
+        }
+    }
+
+    /// Run `future` on the task sink and wait for its result. This does not
+    /// add a timeout of its own; requests made through the `LinkResolver`
+    /// time out after `GRAPH_IPFS_TIMEOUT` seconds and then fail with a
+    /// `LinkResolverTimeout`. Whether a request times out depends on the IPFS
+    /// node and not on the file, so to keep indexing deterministic, callers
+    /// must let a timeout fail the handler and must never hand it to the
+    /// mapping as an error value. The same holds for all other errors except
+    /// for `LinkResolverInvalidFile`.
     pub(crate) fn block_on<I: Send + 'static, ER: Send + 'static>(
         &self,
         future: impl Future<Item = I, Error = ER> + Send + 'static,
@@ -506,6 +737,18 @@ where
         Ok(())
     }
 
+    pub(crate) fn check_gas(&self, gas_used: u64) -> Result<(), HostExportError<impl ExportError>> {
+        if let Some(gas_limit) = self.handler_gas_limit {
+            if gas_used > gas_limit {
+                return Err(HostExportError(format!(
+                    "Mapping handler exceeded the gas limit of {}",
+                    gas_limit
+                )));
+            }
+        }
+        Ok(())
+    }
+
     /// Useful for IPFS hashes stored as bytes
     pub(crate) fn bytes_to_base58(&self, bytes: Vec<u8>) -> String {
         ::bs58::encode(&bytes).into_string()
@@ -560,6 +803,7 @@ where
         ctx: &mut MappingContext,
         name: String,
         params: Vec<String>,
+        context: Option<DataSourceContext>,
     ) -> Result<(), HostExportError<impl ExportError>> {
         info!(
             ctx.logger,
@@ -594,11 +838,26 @@ where
             data_source: self.data_source_name.clone(),
             template,
             params,
+            context,
         });
 
         Ok(())
     }
 
+    pub(crate) fn data_source_address(&self) -> Option<H160> {
+        self.data_source_address
+    }
+
+    pub(crate) fn data_source_network(&self) -> String {
+        self.data_source_network.clone()
+    }
+
+    /// Returns an empty context for data sources that were not created with
+    /// `dataSource.createWithContext`.
+    pub(crate) fn data_source_context(&self) -> DataSourceContext {
+        self.data_source_context.clone().unwrap_or_default()
+    }
+
     pub(crate) fn ens_name_by_hash(
         &self,
         hash: &str,
@@ -0,0 +1,67 @@
+//! Gas costs for host exports.
+//!
+//! The WASM instructions of a mapping are metered by the counter that
+//! `pwasm_utils` injects into the module. Host exports are charged on top of
+//! that, with fixed costs that roughly reflect the work they do outside of
+//! the WASM runtime. All costs only depend on the inputs of a handler, so
+//! every indexer charges the same amount of gas for the same handler.
+
+use graph::prelude::{BigDecimal, BigInt};
+
+/// Gas charged for any call into a host export, on top of the costs below.
+pub const HOST_EXPORT_GAS: u64 = 1_000;
+
+/// Gas charged for `store.get` and `store.remove`.
+pub const STORE_ACCESS_GAS: u64 = 100_000;
+
+/// Gas charged for `store.set`, in addition to `STORE_SET_GAS_PER_FIELD`
+/// for every field of the entity.
+pub const STORE_SET_GAS: u64 = 100_000;
+pub const STORE_SET_GAS_PER_FIELD: u64 = 1_000;
+
+/// Gas charged for `ethereum.call`.
+pub const ETHEREUM_CALL_GAS: u64 = 5_000_000;
+
+/// Gas charged for `ipfs.cat` and `ipfs.map`, in addition to
+/// `IPFS_GAS_PER_BYTE` for every byte of the file. For `ipfs.map`, the bytes
+/// are those of each value passed to the callback, serialized as JSON, and
+/// the callbacks use up the gas of the handler that called `ipfs.map`.
+pub const IPFS_GAS: u64 = 5_000_000;
+pub const IPFS_GAS_PER_BYTE: u64 = 10;
+
+/// Gas charged for `dataSource.create` and `ens.nameByHash`.
+pub const DATA_SOURCE_CREATE_GAS: u64 = 100_000;
+pub const ENS_NAME_BY_HASH_GAS: u64 = 100_000;
+
+/// Gas charged for every byte that `crypto.keccak256`, `crypto.sha256` and
+/// `crypto.ripemd160` hash.
+pub const HASH_GAS_PER_BYTE: u64 = 10;
+
+/// Gas charged for `crypto.secp256k1Recover`, which is much more expensive
+/// than the other cryptographic functions.
+pub const SECP256K1_RECOVER_GAS: u64 = 1_000_000;
+
+/// Gas charged for every byte of the operands of bigInt and bigDecimal
+/// arithmetic.
+pub const BIG_MATH_GAS_PER_BYTE: u64 = 100;
+
+pub fn big_int_size(x: &BigInt) -> u64 {
+    x.to_signed_bytes_le().len() as u64
+}
+
+pub fn big_decimal_size(x: &BigDecimal) -> u64 {
+    // The exponent takes 8 bytes in the AssemblyScript representation
+    (x.as_bigint_and_exponent().0.bits() as u64 + 7) / 8 + 8
+}
+
+/// Gas for an operation whose cost grows linearly with the size of its
+/// operands, like addition.
+pub fn big_math_linear_gas(x_size: u64, y_size: u64) -> u64 {
+    BIG_MATH_GAS_PER_BYTE.saturating_mul(x_size.saturating_add(y_size))
+}
+
+/// Gas for an operation whose cost grows with the product of the size of
+/// its operands, like multiplication and division.
+pub fn big_math_quadratic_gas(x_size: u64, y_size: u64) -> u64 {
+    BIG_MATH_GAS_PER_BYTE.saturating_mul(x_size.max(1).saturating_mul(y_size.max(1)))
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::fmt;
 use std::ops::Deref;
@@ -16,12 +17,14 @@ use ethabi::LogParam;
 use graph::components::ethereum::*;
 use graph::data::store;
 use graph::prelude::{Error as FailureError, *};
-use web3::types::{Log, Transaction, U256};
+use web3::types::{Address, Log, Transaction, U256};
 
 use crate::asc_abi::asc_ptr::*;
 use crate::asc_abi::class::*;
 use crate::asc_abi::*;
 
+mod gas;
+
 #[cfg(test)]
 mod test;
 
@@ -65,6 +68,23 @@ const DATA_SOURCE_CREATE_INDEX: usize = 35;
 const ENS_NAME_BY_HASH: usize = 36;
 const LOG_LOG: usize = 37;
 const BIG_INT_POW: usize = 38;
+const CRYPTO_SHA256_INDEX: usize = 39;
+const CRYPTO_RIPEMD160_INDEX: usize = 40;
+const CRYPTO_SECP256K1_RECOVER_INDEX: usize = 41;
+const ETHEREUM_ENCODE_FUNC_INDEX: usize = 42;
+const ETHEREUM_DECODE_FUNC_INDEX: usize = 43;
+const DATA_SOURCE_CREATE_WITH_CONTEXT_INDEX: usize = 44;
+const DATA_SOURCE_ADDRESS_INDEX: usize = 45;
+const DATA_SOURCE_NETWORK_INDEX: usize = 46;
+const DATA_SOURCE_CONTEXT_INDEX: usize = 47;
+const JSON_TRY_FROM_BYTES_FUNC_INDEX: usize = 48;
+const IPFS_TRY_CAT_FUNC_INDEX: usize = 49;
+const BIG_INT_BIT_OR: usize = 50;
+const BIG_INT_BIT_AND: usize = 51;
+const BIG_INT_LEFT_SHIFT: usize = 52;
+const BIG_INT_RIGHT_SHIFT: usize = 53;
+const BIG_INT_SQRT: usize = 54;
+const BIG_INT_COMPARE: usize = 55;
 
 /// A common error is a trap in the host, so simplify the message in that case.
 fn format_wasmi_error(e: Error) -> String {
@@ -79,15 +99,20 @@ fn format_wasmi_error(e: Error) -> String {
 
 pub struct WasmiModuleConfig<T, L, S> {
     pub subgraph_id: SubgraphDeploymentId,
+    pub schema: Arc<Schema>,
     pub parsed_module: Arc<parity_wasm::elements::Module>,
     pub api_version: Version,
     pub data_source_name: String,
+    pub data_source_address: Option<Address>,
+    pub data_source_network: String,
+    pub data_source_context: Option<DataSourceContext>,
     pub templates: Vec<DataSourceTemplate>,
     pub abis: Vec<MappingABI>,
     pub ethereum_adapter: Arc<T>,
     pub link_resolver: Arc<L>,
     pub store: Arc<S>,
     pub handler_timeout: Option<Duration>,
+    pub handler_gas_limit: Option<u64>,
 }
 
 /// A pre-processed and valid WASM module, ready to be started as a WasmiModule.
@@ -102,7 +127,7 @@ impl<T, L, S, U> ValidModule<T, L, S, U>
 where
     T: EthereumAdapter,
     L: LinkResolver,
-    S: Store + Send + Sync + 'static,
+    S: Store + EthereumCallCache + Send + Sync + 'static,
     U: Sink<SinkItem = Box<dyn Future<Item = (), Error = ()> + Send>>
         + Clone
         + Send
@@ -120,7 +145,8 @@ where
         // Clone the parsed module so we can create an instance of `Module` from it
         let parsed_module = config.parsed_module.as_ref().clone();
 
-        // Inject metering calls, which are used for checking timeouts.
+        // Inject metering calls, which are used for gas accounting and for
+        // checking timeouts.
         let parsed_module = pwasm_utils::inject_gas_counter(parsed_module, &Default::default())
             .map_err(|_| err_msg("failed to inject gas counter"))?;
 
@@ -155,8 +181,12 @@ where
         // Create new instance of externally hosted functions invoker
         let host_exports = HostExports::new(
             config.subgraph_id,
+            config.schema,
             config.api_version,
             config.data_source_name,
+            config.data_source_address,
+            config.data_source_network,
+            config.data_source_context,
             config.templates,
             config.abis,
             config.ethereum_adapter.clone(),
@@ -164,6 +194,7 @@ where
             config.store.clone(),
             task_sink,
             config.handler_timeout,
+            config.handler_gas_limit,
         );
 
         Ok(ValidModule {
@@ -187,6 +218,9 @@ pub(crate) struct WasmiModule<T, L, S, U> {
     // Time when the current handler began processing.
     start_time: Instant,
 
+    // Gas used by the current handler so far.
+    gas_used: u64,
+
     // True if `run_start` has not yet been called on the module.
     // This is used to prevent mutating store state in start.
     running_start: bool,
@@ -202,7 +236,7 @@ impl<T, L, S, U> WasmiModule<T, L, S, U>
 where
     T: EthereumAdapter,
     L: LinkResolver,
-    S: Store + Send + Sync + 'static,
+    S: Store + EthereumCallCache + Send + Sync + 'static,
     U: Sink<SinkItem = Box<dyn Future<Item = (), Error = ()> + Send>>
         + Clone
         + Send
@@ -243,6 +277,7 @@ where
             ctx,
             valid_module: valid_module.clone(),
             start_time: Instant::now(),
+            gas_used: 0,
             running_start: true,
 
             // `arena_start_ptr` will be set on the first call to `raw_new`.
@@ -268,40 +303,43 @@ where
         transaction: Arc<Transaction>,
         log: Arc<Log>,
         params: Vec<LogParam>,
+        with_receipt: bool,
     ) -> Result<BlockState, FailureError> {
         self.start_time = Instant::now();
+        self.gas_used = 0;
+
+        let block = self.ctx.block.clone();
+
+        // The receipts of all transactions are part of every block that
+        // has triggers, so we only need to find the one for this log
+        let receipt = if with_receipt {
+            block
+                .transaction_receipts
+                .iter()
+                .find(|receipt| Some(receipt.transaction_hash) == log.transaction_hash)
+                .map(EthereumTransactionReceiptData::from)
+        } else {
+            None
+        };
 
-        let block = self.ctx.block.block.clone();
+        let event_data = EthereumEventData {
+            block: EthereumBlockData::from(&block.block),
+            transaction: EthereumTransactionData::from(transaction.deref()),
+            address: log.address,
+            log_index: log.log_index.unwrap_or(U256::zero()),
+            transaction_log_index: log.transaction_log_index.unwrap_or(U256::zero()),
+            log_type: log.log_type.clone(),
+            params,
+            receipt,
+        };
 
         // Prepare an EthereumEvent for the WASM runtime
         // Decide on the destination type using the mapping
         // api version provided in the subgraph manifest
         let event = if self.host_exports().api_version >= Version::new(0, 0, 2) {
-            RuntimeValue::from(
-                self.asc_new::<AscEthereumEvent<AscEthereumTransaction_0_0_2>, _>(
-                    &EthereumEventData {
-                        block: EthereumBlockData::from(&block),
-                        transaction: EthereumTransactionData::from(transaction.deref()),
-                        address: log.address,
-                        log_index: log.log_index.unwrap_or(U256::zero()),
-                        transaction_log_index: log.transaction_log_index.unwrap_or(U256::zero()),
-                        log_type: log.log_type.clone(),
-                        params,
-                    },
-                ),
-            )
+            self.asc_new_event::<AscEthereumTransaction_0_0_2>(&event_data, with_receipt)
         } else {
-            RuntimeValue::from(self.asc_new::<AscEthereumEvent<AscEthereumTransaction>, _>(
-                &EthereumEventData {
-                    block: EthereumBlockData::from(&block),
-                    transaction: EthereumTransactionData::from(transaction.deref()),
-                    address: log.address,
-                    log_index: log.log_index.unwrap_or(U256::zero()),
-                    transaction_log_index: log.transaction_log_index.unwrap_or(U256::zero()),
-                    log_type: log.log_type.clone(),
-                    params,
-                },
-            ))
+            self.asc_new_event::<AscEthereumTransaction>(&event_data, with_receipt)
         };
 
         // Invoke the event handler
@@ -320,23 +358,52 @@ where
         })
     }
 
+    /// Allocates `event` with the transaction type `T`, and with the
+    /// receipt if the handler asked for it.
+    fn asc_new_event<T: AscType>(
+        &mut self,
+        event: &EthereumEventData,
+        with_receipt: bool,
+    ) -> RuntimeValue
+    where
+        EthereumEventData: ToAscObj<AscEthereumEvent<T>> + ToAscObj<AscEthereumEventWithReceipt<T>>,
+    {
+        if with_receipt {
+            RuntimeValue::from(self.asc_new::<AscEthereumEventWithReceipt<T>, _>(event))
+        } else {
+            RuntimeValue::from(self.asc_new::<AscEthereumEvent<T>, _>(event))
+        }
+    }
+
+    /// Run the `ipfs.map` callback `handler_name` for `value`. The callback
+    /// is charged for the size of `value` and counts against the gas of the
+    /// handler that called `ipfs.map`, which has used `gas_used` so far.
+    /// Returns the gas used after the callback
     pub(crate) fn handle_json_callback(
         mut self,
         handler_name: &str,
         value: &serde_json::Value,
         user_data: &store::Value,
-    ) -> Result<BlockState, FailureError> {
-        let value = RuntimeValue::from(self.asc_new(value));
-        let user_data = RuntimeValue::from(self.asc_new(user_data));
-
-        // Invoke the callback
-        let result =
-            self.module
-                .clone()
-                .invoke_export(handler_name, &[value, user_data], &mut self);
+        gas_used: u64,
+    ) -> Result<(BlockState, u64), FailureError> {
+        self.gas_used = gas_used;
+        let size = serde_json::to_vec(value).map_or(0, |bytes| bytes.len());
+        let result = self
+            .consume_gas(gas::IPFS_GAS_PER_BYTE.saturating_mul(size as u64))
+            .map_err(Error::Trap)
+            .and_then(|()| {
+                let value = RuntimeValue::from(self.asc_new(value));
+                let user_data = RuntimeValue::from(self.asc_new(user_data));
+
+                // Invoke the callback
+                self.module
+                    .clone()
+                    .invoke_export(handler_name, &[value, user_data], &mut self)
+            });
 
         // Return either the collected entity operations or an error
-        result.map(|_| self.ctx.state).map_err(|e| {
+        let gas_used = self.gas_used;
+        result.map(|_| (self.ctx.state, gas_used)).map_err(|e| {
             format_err!(
                 "Failed to handle callback with handler \"{}\": {}",
                 handler_name,
@@ -354,6 +421,7 @@ where
         outputs: Vec<LogParam>,
     ) -> Result<BlockState, FailureError> {
         self.start_time = Instant::now();
+        self.gas_used = 0;
 
         let call = EthereumCallData {
             to: call.to,
@@ -388,6 +456,7 @@ where
         handler_name: &str,
     ) -> Result<BlockState, FailureError> {
         self.start_time = Instant::now();
+        self.gas_used = 0;
 
         // Prepare an EthereumBlock for the WASM runtime
         let arg = EthereumBlockData::from(&self.ctx.block.block);
@@ -412,7 +481,7 @@ impl<T, L, S, U> AscHeap for WasmiModule<T, L, S, U>
 where
     T: EthereumAdapter,
     L: LinkResolver,
-    S: Store + Send + Sync + 'static,
+    S: Store + EthereumCallCache + Send + Sync + 'static,
     U: Sink<SinkItem = Box<dyn Future<Item = (), Error = ()> + Send>>
         + Clone
         + Send
@@ -462,14 +531,23 @@ impl<T, L, S, U> WasmiModule<T, L, S, U>
 where
     T: EthereumAdapter,
     L: LinkResolver,
-    S: Store + Send + Sync + 'static,
+    S: Store + EthereumCallCache + Send + Sync + 'static,
     U: Sink<SinkItem = Box<dyn Future<Item = (), Error = ()> + Send>>
         + Clone
         + Send
         + Sync
         + 'static,
 {
-    fn gas(&mut self, _gas_spent: u32) -> Result<Option<RuntimeValue>, Trap> {
+    /// Adds `amount` to the gas used by the current handler and fails the
+    /// handler if that exceeds the gas limit.
+    fn consume_gas(&mut self, amount: u64) -> Result<(), Trap> {
+        self.gas_used = self.gas_used.saturating_add(amount);
+        self.host_exports().check_gas(self.gas_used)?;
+        Ok(())
+    }
+
+    fn gas(&mut self, gas_spent: u32) -> Result<Option<RuntimeValue>, Trap> {
+        self.consume_gas(gas_spent as u64)?;
         self.host_exports().check_timeout(self.start_time)?;
         Ok(None)
     }
@@ -518,7 +596,8 @@ where
         }
         let entity = self.asc_get(entity_ptr);
         let id = self.asc_get(id_ptr);
-        let data = self.asc_get(data_ptr);
+        let data: HashMap<String, Value> = self.asc_get(data_ptr);
+        self.consume_gas(gas::STORE_SET_GAS + gas::STORE_SET_GAS_PER_FIELD * data.len() as u64)?;
         self.valid_module
             .host_exports
             .store_set(&mut self.ctx, entity, id, data)?;
@@ -534,6 +613,7 @@ where
         if self.running_start {
             return Err(HostExportError("store.remove may not be called in start function").into());
         }
+        self.consume_gas(gas::STORE_ACCESS_GAS)?;
         let entity = self.asc_get(entity_ptr);
         let id = self.asc_get(id_ptr);
         self.valid_module
@@ -548,6 +628,7 @@ where
         entity_ptr: AscPtr<AscString>,
         id_ptr: AscPtr<AscString>,
     ) -> Result<Option<RuntimeValue>, Trap> {
+        self.consume_gas(gas::STORE_ACCESS_GAS)?;
         let entity_ptr = self.asc_get(entity_ptr);
         let id_ptr = self.asc_get(id_ptr);
         let entity_option =
@@ -566,6 +647,7 @@ where
         &mut self,
         call_ptr: AscPtr<AscUnresolvedContractCall>,
     ) -> Result<Option<RuntimeValue>, Trap> {
+        self.consume_gas(gas::ETHEREUM_CALL_GAS)?;
         let call = self.asc_get(call_ptr);
         let result = self
             .valid_module
@@ -577,6 +659,56 @@ where
         }))
     }
 
+    /// function ethereum.encode(token: ethereum.Value, type: String): Bytes | null
+    fn ethereum_encode(
+        &mut self,
+        token_ptr: AscPtr<AscEnum<EthereumValueKind>>,
+        type_ptr: AscPtr<AscString>,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let result = self
+            .try_asc_get(token_ptr)
+            .map_err(|e| e.to_string())
+            .and_then(|token| {
+                self.host_exports()
+                    .ethereum_encode(token, self.asc_get(type_ptr))
+                    .map_err(|e| e.to_string())
+            });
+        match result {
+            Ok(data) => {
+                let data_ptr: AscPtr<Uint8Array> = self.asc_new(&*data);
+                Ok(Some(RuntimeValue::from(data_ptr)))
+            }
+
+            // Return null in case of error.
+            Err(e) => {
+                info!(self.logger, "Failed ethereum.encode, returning `null`";
+                                    "error" => e);
+                Ok(Some(RuntimeValue::from(0)))
+            }
+        }
+    }
+
+    /// function ethereum.decode(types: String, data: Bytes): ethereum.Value | null
+    fn ethereum_decode(
+        &mut self,
+        types_ptr: AscPtr<AscString>,
+        data_ptr: AscPtr<Uint8Array>,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let result = self
+            .host_exports()
+            .ethereum_decode(self.asc_get(types_ptr), self.asc_get(data_ptr));
+        match result {
+            Ok(token) => Ok(Some(RuntimeValue::from(self.asc_new(&token)))),
+
+            // Return null in case of error.
+            Err(e) => {
+                info!(self.logger, "Failed ethereum.decode, returning `null`";
+                                    "error" => e.to_string());
+                Ok(Some(RuntimeValue::from(0)))
+            }
+        }
+    }
+
     /// function typeConversion.bytesToString(bytes: Bytes): string
     fn bytes_to_string(
         &mut self,
@@ -651,12 +783,27 @@ where
         Ok(Some(RuntimeValue::from(self.asc_new(&result))))
     }
 
+    /// function json.try_fromBytes(bytes: Bytes): Result<JSONValue, string>
+    fn json_try_from_bytes(
+        &mut self,
+        bytes_ptr: AscPtr<Uint8Array>,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let result = self
+            .host_exports()
+            .json_try_from_bytes(self.asc_get(bytes_ptr));
+        let result_obj: AscPtr<AscResult<AscPtr<AscEnum<JsonValueKind>>, AscPtr<AscString>>> =
+            self.asc_new(&result);
+        Ok(Some(RuntimeValue::from(result_obj)))
+    }
+
     /// function ipfs.cat(link: String): Bytes
     fn ipfs_cat(&mut self, link_ptr: AscPtr<AscString>) -> Result<Option<RuntimeValue>, Trap> {
+        self.consume_gas(gas::IPFS_GAS)?;
         let link = self.asc_get(link_ptr);
         let ipfs_res = self.host_exports().ipfs_cat(&self.ctx.logger, link);
         match ipfs_res {
             Ok(bytes) => {
+                self.consume_gas(gas::IPFS_GAS_PER_BYTE.saturating_mul(bytes.len() as u64))?;
                 let bytes_obj: AscPtr<Uint8Array> = self.asc_new(&*bytes);
                 Ok(Some(RuntimeValue::from(bytes_obj)))
             }
@@ -671,6 +818,19 @@ where
         }
     }
 
+    /// function ipfs.try_cat(link: String): Result<Bytes, string>
+    fn ipfs_try_cat(&mut self, link_ptr: AscPtr<AscString>) -> Result<Option<RuntimeValue>, Trap> {
+        self.consume_gas(gas::IPFS_GAS)?;
+        let link = self.asc_get(link_ptr);
+        let result = self.host_exports().ipfs_try_cat(&self.ctx.logger, link)?;
+        if let Ok(bytes) = &result {
+            self.consume_gas(gas::IPFS_GAS_PER_BYTE.saturating_mul(bytes.len() as u64))?;
+        }
+        let result_obj: AscPtr<AscResult<AscPtr<Uint8Array>, AscPtr<AscString>>> =
+            self.asc_new(&result);
+        Ok(Some(RuntimeValue::from(result_obj)))
+    }
+
     /// function ipfs.map(link: String, callback: String, flags: String[]): void
     fn ipfs_map(
         &mut self,
@@ -679,40 +839,46 @@ where
         user_data: AscPtr<AscEnum<StoreValueKind>>,
         flags: AscPtr<Array<AscPtr<AscString>>>,
     ) -> Result<Option<RuntimeValue>, Trap> {
+        self.consume_gas(gas::IPFS_GAS)?;
         let link: String = self.asc_get(link_ptr);
         let callback: String = self.asc_get(callback);
         let user_data: store::Value = self.asc_get(user_data);
 
         let flags = self.asc_get(flags);
         let start_time = Instant::now();
-        let result =
-            match self
-                .host_exports()
-                .ipfs_map(&self, link.clone(), &*callback, user_data, flags)
-            {
-                Ok(output_states) => {
-                    debug!(
-                        self.logger,
-                        "Successfully processed file with ipfs.map";
-                        "link" => &link,
-                        "callback" => &*callback,
-                        "n_calls" => output_states.len(),
-                        "time" => format!("{}ms", start_time.elapsed().as_millis())
-                    );
-                    for output_state in output_states {
-                        self.ctx
-                            .state
-                            .entity_cache
-                            .extend(output_state.entity_cache);
-                        self.ctx
-                            .state
-                            .created_data_sources
-                            .extend(output_state.created_data_sources);
-                    }
-                    Ok(None)
+        let gas_used = self.gas_used;
+        let result = match self.host_exports().ipfs_map(
+            &self,
+            link.clone(),
+            &*callback,
+            user_data,
+            flags,
+            gas_used,
+        ) {
+            Ok((output_states, gas_used)) => {
+                self.gas_used = gas_used;
+                debug!(
+                    self.logger,
+                    "Successfully processed file with ipfs.map";
+                    "link" => &link,
+                    "callback" => &*callback,
+                    "n_calls" => output_states.len(),
+                    "time" => format!("{}ms", start_time.elapsed().as_millis())
+                );
+                for output_state in output_states {
+                    self.ctx
+                        .state
+                        .entity_cache
+                        .extend(output_state.entity_cache);
+                    self.ctx
+                        .state
+                        .created_data_sources
+                        .extend(output_state.created_data_sources);
                 }
-                Err(e) => Err(e.into()),
-            };
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        };
 
         // Advance this module's start time by the time it took to run the entire
         // ipfs_map. This has the effect of not charging this module for the time
@@ -760,22 +926,74 @@ where
         &mut self,
         input_ptr: AscPtr<Uint8Array>,
     ) -> Result<Option<RuntimeValue>, Trap> {
-        let input = self
-            .host_exports()
-            .crypto_keccak_256(self.asc_get(input_ptr));
+        let input: Vec<u8> = self.asc_get(input_ptr);
+        self.consume_gas(gas::HASH_GAS_PER_BYTE.saturating_mul(input.len() as u64))?;
+        let input = self.host_exports().crypto_keccak_256(input);
         let hash_ptr: AscPtr<Uint8Array> = self.asc_new(input.as_ref());
         Ok(Some(RuntimeValue::from(hash_ptr)))
     }
 
+    /// function crypto.sha256(input: Bytes): Bytes
+    fn crypto_sha256(
+        &mut self,
+        input_ptr: AscPtr<Uint8Array>,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let input: Vec<u8> = self.asc_get(input_ptr);
+        self.consume_gas(gas::HASH_GAS_PER_BYTE.saturating_mul(input.len() as u64))?;
+        let hash = self.host_exports().crypto_sha256(input);
+        let hash_ptr: AscPtr<Uint8Array> = self.asc_new(hash.as_ref());
+        Ok(Some(RuntimeValue::from(hash_ptr)))
+    }
+
+    /// function crypto.ripemd160(input: Bytes): Bytes
+    fn crypto_ripemd160(
+        &mut self,
+        input_ptr: AscPtr<Uint8Array>,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let input: Vec<u8> = self.asc_get(input_ptr);
+        self.consume_gas(gas::HASH_GAS_PER_BYTE.saturating_mul(input.len() as u64))?;
+        let hash = self.host_exports().crypto_ripemd160(input);
+        let hash_ptr: AscPtr<Uint8Array> = self.asc_new(hash.as_ref());
+        Ok(Some(RuntimeValue::from(hash_ptr)))
+    }
+
+    /// function crypto.secp256k1Recover(hash: Bytes, v: i32, r: Bytes, s: Bytes): Address | null
+    fn crypto_secp256k1_recover(
+        &mut self,
+        hash_ptr: AscPtr<Uint8Array>,
+        v: i32,
+        r_ptr: AscPtr<Uint8Array>,
+        s_ptr: AscPtr<Uint8Array>,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        self.consume_gas(gas::SECP256K1_RECOVER_GAS)?;
+        let address = self.host_exports().crypto_secp256k1_recover(
+            self.asc_get(hash_ptr),
+            v,
+            self.asc_get(r_ptr),
+            self.asc_get(s_ptr),
+        );
+        Ok(Some(match address {
+            Some(address) => {
+                let address_ptr: AscPtr<AscH160> = self.asc_new(&address);
+                RuntimeValue::from(address_ptr)
+            }
+            None => RuntimeValue::from(0),
+        }))
+    }
+
     /// function bigInt.plus(x: BigInt, y: BigInt): BigInt
     fn big_int_plus(
         &mut self,
         x_ptr: AscPtr<AscBigInt>,
         y_ptr: AscPtr<AscBigInt>,
     ) -> Result<Option<RuntimeValue>, Trap> {
-        let result = self
-            .host_exports()
-            .big_int_plus(self.asc_get(x_ptr), self.asc_get(y_ptr));
+        let x: BigInt = self.asc_get(x_ptr);
+        let y: BigInt = self.asc_get(y_ptr);
+        self.consume_gas(gas::big_math_linear_gas(
+            gas::big_int_size(&x),
+            gas::big_int_size(&y),
+        ))?;
+        let result = self.host_exports().big_int_plus(x, y);
         let result_ptr: AscPtr<AscBigInt> = self.asc_new(&result);
         Ok(Some(RuntimeValue::from(result_ptr)))
     }
@@ -786,9 +1004,13 @@ where
         x_ptr: AscPtr<AscBigInt>,
         y_ptr: AscPtr<AscBigInt>,
     ) -> Result<Option<RuntimeValue>, Trap> {
-        let result = self
-            .host_exports()
-            .big_int_minus(self.asc_get(x_ptr), self.asc_get(y_ptr));
+        let x: BigInt = self.asc_get(x_ptr);
+        let y: BigInt = self.asc_get(y_ptr);
+        self.consume_gas(gas::big_math_linear_gas(
+            gas::big_int_size(&x),
+            gas::big_int_size(&y),
+        ))?;
+        let result = self.host_exports().big_int_minus(x, y);
         let result_ptr: AscPtr<AscBigInt> = self.asc_new(&result);
         Ok(Some(RuntimeValue::from(result_ptr)))
     }
@@ -799,9 +1021,13 @@ where
         x_ptr: AscPtr<AscBigInt>,
         y_ptr: AscPtr<AscBigInt>,
     ) -> Result<Option<RuntimeValue>, Trap> {
-        let result = self
-            .host_exports()
-            .big_int_times(self.asc_get(x_ptr), self.asc_get(y_ptr));
+        let x: BigInt = self.asc_get(x_ptr);
+        let y: BigInt = self.asc_get(y_ptr);
+        self.consume_gas(gas::big_math_quadratic_gas(
+            gas::big_int_size(&x),
+            gas::big_int_size(&y),
+        ))?;
+        let result = self.host_exports().big_int_times(x, y);
         let result_ptr: AscPtr<AscBigInt> = self.asc_new(&result);
         Ok(Some(RuntimeValue::from(result_ptr)))
     }
@@ -812,9 +1038,13 @@ where
         x_ptr: AscPtr<AscBigInt>,
         y_ptr: AscPtr<AscBigInt>,
     ) -> Result<Option<RuntimeValue>, Trap> {
-        let result = self
-            .host_exports()
-            .big_int_divided_by(self.asc_get(x_ptr), self.asc_get(y_ptr))?;
+        let x: BigInt = self.asc_get(x_ptr);
+        let y: BigInt = self.asc_get(y_ptr);
+        self.consume_gas(gas::big_math_quadratic_gas(
+            gas::big_int_size(&x),
+            gas::big_int_size(&y),
+        ))?;
+        let result = self.host_exports().big_int_divided_by(x, y)?;
         let result_ptr: AscPtr<AscBigInt> = self.asc_new(&result);
         Ok(Some(RuntimeValue::from(result_ptr)))
     }
@@ -826,9 +1056,12 @@ where
         y_ptr: AscPtr<AscBigDecimal>,
     ) -> Result<Option<RuntimeValue>, Trap> {
         let x = self.asc_get::<BigInt, _>(x_ptr).to_big_decimal(0.into());
-        let result = self
-            .host_exports()
-            .big_decimal_divided_by(x, self.asc_get(y_ptr))?;
+        let y: BigDecimal = self.asc_get(y_ptr);
+        self.consume_gas(gas::big_math_quadratic_gas(
+            gas::big_decimal_size(&x),
+            gas::big_decimal_size(&y),
+        ))?;
+        let result = self.host_exports().big_decimal_divided_by(x, y)?;
         Ok(Some(RuntimeValue::from(self.asc_new(&result))))
     }
 
@@ -838,9 +1071,13 @@ where
         x_ptr: AscPtr<AscBigInt>,
         y_ptr: AscPtr<AscBigInt>,
     ) -> Result<Option<RuntimeValue>, Trap> {
-        let result = self
-            .host_exports()
-            .big_int_mod(self.asc_get(x_ptr), self.asc_get(y_ptr));
+        let x: BigInt = self.asc_get(x_ptr);
+        let y: BigInt = self.asc_get(y_ptr);
+        self.consume_gas(gas::big_math_quadratic_gas(
+            gas::big_int_size(&x),
+            gas::big_int_size(&y),
+        ))?;
+        let result = self.host_exports().big_int_mod(x, y);
         let result_ptr: AscPtr<AscBigInt> = self.asc_new(&result);
         Ok(Some(RuntimeValue::from(result_ptr)))
     }
@@ -851,11 +1088,109 @@ where
         x_ptr: AscPtr<AscBigInt>,
         exp: u8,
     ) -> Result<Option<RuntimeValue>, Trap> {
-        let result = self.host_exports().big_int_pow(self.asc_get(x_ptr), exp);
+        let x: BigInt = self.asc_get(x_ptr);
+        let x_size = gas::big_int_size(&x);
+        self.consume_gas(gas::big_math_quadratic_gas(
+            x_size.saturating_mul(exp as u64),
+            x_size,
+        ))?;
+        let result = self.host_exports().big_int_pow(x, exp);
+        let result_ptr: AscPtr<AscBigInt> = self.asc_new(&result);
+        Ok(Some(RuntimeValue::from(result_ptr)))
+    }
+
+    /// function bigInt.bitOr(x: BigInt, y: BigInt): BigInt
+    fn big_int_bit_or(
+        &mut self,
+        x_ptr: AscPtr<AscBigInt>,
+        y_ptr: AscPtr<AscBigInt>,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let x: BigInt = self.asc_get(x_ptr);
+        let y: BigInt = self.asc_get(y_ptr);
+        self.consume_gas(gas::big_math_linear_gas(
+            gas::big_int_size(&x),
+            gas::big_int_size(&y),
+        ))?;
+        let result = self.host_exports().big_int_bit_or(x, y);
+        let result_ptr: AscPtr<AscBigInt> = self.asc_new(&result);
+        Ok(Some(RuntimeValue::from(result_ptr)))
+    }
+
+    /// function bigInt.bitAnd(x: BigInt, y: BigInt): BigInt
+    fn big_int_bit_and(
+        &mut self,
+        x_ptr: AscPtr<AscBigInt>,
+        y_ptr: AscPtr<AscBigInt>,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let x: BigInt = self.asc_get(x_ptr);
+        let y: BigInt = self.asc_get(y_ptr);
+        self.consume_gas(gas::big_math_linear_gas(
+            gas::big_int_size(&x),
+            gas::big_int_size(&y),
+        ))?;
+        let result = self.host_exports().big_int_bit_and(x, y);
+        let result_ptr: AscPtr<AscBigInt> = self.asc_new(&result);
+        Ok(Some(RuntimeValue::from(result_ptr)))
+    }
+
+    /// function bigInt.leftShift(x: BigInt, bits: u8): BigInt
+    fn big_int_left_shift(
+        &mut self,
+        x_ptr: AscPtr<AscBigInt>,
+        bits: u8,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let x: BigInt = self.asc_get(x_ptr);
+        self.consume_gas(gas::big_math_linear_gas(
+            gas::big_int_size(&x),
+            (bits as u64 + 7) / 8,
+        ))?;
+        let result = self.host_exports().big_int_left_shift(x, bits);
+        let result_ptr: AscPtr<AscBigInt> = self.asc_new(&result);
+        Ok(Some(RuntimeValue::from(result_ptr)))
+    }
+
+    /// function bigInt.rightShift(x: BigInt, bits: u8): BigInt
+    fn big_int_right_shift(
+        &mut self,
+        x_ptr: AscPtr<AscBigInt>,
+        bits: u8,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let x: BigInt = self.asc_get(x_ptr);
+        self.consume_gas(gas::big_math_linear_gas(
+            gas::big_int_size(&x),
+            (bits as u64 + 7) / 8,
+        ))?;
+        let result = self.host_exports().big_int_right_shift(x, bits);
+        let result_ptr: AscPtr<AscBigInt> = self.asc_new(&result);
+        Ok(Some(RuntimeValue::from(result_ptr)))
+    }
+
+    /// function bigInt.sqrt(x: BigInt): BigInt
+    fn big_int_sqrt(&mut self, x_ptr: AscPtr<AscBigInt>) -> Result<Option<RuntimeValue>, Trap> {
+        let x: BigInt = self.asc_get(x_ptr);
+        let x_size = gas::big_int_size(&x);
+        self.consume_gas(gas::big_math_quadratic_gas(x_size, x_size))?;
+        let result = self.host_exports().big_int_sqrt(x)?;
         let result_ptr: AscPtr<AscBigInt> = self.asc_new(&result);
         Ok(Some(RuntimeValue::from(result_ptr)))
     }
 
+    /// function bigInt.compare(x: BigInt, y: BigInt): i32
+    fn big_int_compare(
+        &mut self,
+        x_ptr: AscPtr<AscBigInt>,
+        y_ptr: AscPtr<AscBigInt>,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        let x: BigInt = self.asc_get(x_ptr);
+        let y: BigInt = self.asc_get(y_ptr);
+        self.consume_gas(gas::big_math_linear_gas(
+            gas::big_int_size(&x),
+            gas::big_int_size(&y),
+        ))?;
+        let ordering = self.host_exports().big_int_compare(x, y);
+        Ok(Some(RuntimeValue::I32(ordering)))
+    }
+
     /// function typeConversion.bytesToBase58(bytes: Bytes): string
     fn bytes_to_base58(
         &mut self,
@@ -894,9 +1229,13 @@ where
         x_ptr: AscPtr<AscBigDecimal>,
         y_ptr: AscPtr<AscBigDecimal>,
     ) -> Result<Option<RuntimeValue>, Trap> {
-        let result = self
-            .host_exports()
-            .big_decimal_plus(self.asc_get(x_ptr), self.asc_get(y_ptr));
+        let x: BigDecimal = self.asc_get(x_ptr);
+        let y: BigDecimal = self.asc_get(y_ptr);
+        self.consume_gas(gas::big_math_linear_gas(
+            gas::big_decimal_size(&x),
+            gas::big_decimal_size(&y),
+        ))?;
+        let result = self.host_exports().big_decimal_plus(x, y);
         Ok(Some(RuntimeValue::from(self.asc_new(&result))))
     }
 
@@ -906,9 +1245,13 @@ where
         x_ptr: AscPtr<AscBigDecimal>,
         y_ptr: AscPtr<AscBigDecimal>,
     ) -> Result<Option<RuntimeValue>, Trap> {
-        let result = self
-            .host_exports()
-            .big_decimal_minus(self.asc_get(x_ptr), self.asc_get(y_ptr));
+        let x: BigDecimal = self.asc_get(x_ptr);
+        let y: BigDecimal = self.asc_get(y_ptr);
+        self.consume_gas(gas::big_math_linear_gas(
+            gas::big_decimal_size(&x),
+            gas::big_decimal_size(&y),
+        ))?;
+        let result = self.host_exports().big_decimal_minus(x, y);
         Ok(Some(RuntimeValue::from(self.asc_new(&result))))
     }
 
@@ -918,9 +1261,13 @@ where
         x_ptr: AscPtr<AscBigDecimal>,
         y_ptr: AscPtr<AscBigDecimal>,
     ) -> Result<Option<RuntimeValue>, Trap> {
-        let result = self
-            .host_exports()
-            .big_decimal_times(self.asc_get(x_ptr), self.asc_get(y_ptr));
+        let x: BigDecimal = self.asc_get(x_ptr);
+        let y: BigDecimal = self.asc_get(y_ptr);
+        self.consume_gas(gas::big_math_quadratic_gas(
+            gas::big_decimal_size(&x),
+            gas::big_decimal_size(&y),
+        ))?;
+        let result = self.host_exports().big_decimal_times(x, y);
         Ok(Some(RuntimeValue::from(self.asc_new(&result))))
     }
 
@@ -930,9 +1277,13 @@ where
         x_ptr: AscPtr<AscBigDecimal>,
         y_ptr: AscPtr<AscBigDecimal>,
     ) -> Result<Option<RuntimeValue>, Trap> {
-        let result = self
-            .host_exports()
-            .big_decimal_divided_by(self.asc_get(x_ptr), self.asc_get(y_ptr))?;
+        let x: BigDecimal = self.asc_get(x_ptr);
+        let y: BigDecimal = self.asc_get(y_ptr);
+        self.consume_gas(gas::big_math_quadratic_gas(
+            gas::big_decimal_size(&x),
+            gas::big_decimal_size(&y),
+        ))?;
+        let result = self.host_exports().big_decimal_divided_by(x, y)?;
         Ok(Some(RuntimeValue::from(self.asc_new(&result))))
     }
 
@@ -942,9 +1293,13 @@ where
         x_ptr: AscPtr<AscBigDecimal>,
         y_ptr: AscPtr<AscBigDecimal>,
     ) -> Result<Option<RuntimeValue>, Trap> {
-        let equals = self
-            .host_exports()
-            .big_decimal_equals(self.asc_get(x_ptr), self.asc_get(y_ptr));
+        let x: BigDecimal = self.asc_get(x_ptr);
+        let y: BigDecimal = self.asc_get(y_ptr);
+        self.consume_gas(gas::big_math_linear_gas(
+            gas::big_decimal_size(&x),
+            gas::big_decimal_size(&y),
+        ))?;
+        let equals = self.host_exports().big_decimal_equals(x, y);
         Ok(Some(RuntimeValue::I32(if equals { 1 } else { 0 })))
     }
 
@@ -954,18 +1309,64 @@ where
         name_ptr: AscPtr<AscString>,
         params_ptr: AscPtr<Array<AscPtr<AscString>>>,
     ) -> Result<Option<RuntimeValue>, Trap> {
+        self.consume_gas(gas::DATA_SOURCE_CREATE_GAS)?;
         let name: String = self.asc_get(name_ptr);
         let params: Vec<String> = self.asc_get(params_ptr);
         self.valid_module
             .host_exports
-            .data_source_create(&mut self.ctx, name, params)?;
+            .data_source_create(&mut self.ctx, name, params, None)?;
+        Ok(None)
+    }
+
+    /// function dataSource.createWithContext(name: string, params: Array<string>, context: DataSourceContext): void
+    fn data_source_create_with_context(
+        &mut self,
+        name_ptr: AscPtr<AscString>,
+        params_ptr: AscPtr<Array<AscPtr<AscString>>>,
+        context_ptr: AscPtr<AscEntity>,
+    ) -> Result<Option<RuntimeValue>, Trap> {
+        self.consume_gas(gas::DATA_SOURCE_CREATE_GAS)?;
+        let name: String = self.asc_get(name_ptr);
+        let params: Vec<String> = self.asc_get(params_ptr);
+        let context: HashMap<String, Value> = self.asc_get(context_ptr);
+        self.valid_module.host_exports.data_source_create(
+            &mut self.ctx,
+            name,
+            params,
+            Some(DataSourceContext::from(context)),
+        )?;
         Ok(None)
     }
 
+    /// function dataSource.address(): Address | null
+    fn data_source_address(&mut self) -> Result<Option<RuntimeValue>, Trap> {
+        Ok(Some(match self.host_exports().data_source_address() {
+            Some(address) => {
+                let address_ptr: AscPtr<AscH160> = self.asc_new(&address);
+                RuntimeValue::from(address_ptr)
+            }
+            None => RuntimeValue::from(0),
+        }))
+    }
+
+    /// function dataSource.network(): string
+    fn data_source_network(&mut self) -> Result<Option<RuntimeValue>, Trap> {
+        let network = self.host_exports().data_source_network();
+        Ok(Some(RuntimeValue::from(self.asc_new(&network))))
+    }
+
+    /// function dataSource.context(): DataSourceContext
+    fn data_source_context(&mut self) -> Result<Option<RuntimeValue>, Trap> {
+        let context = self.host_exports().data_source_context();
+        let context_ptr: AscPtr<AscEntity> = self.asc_new(&context);
+        Ok(Some(RuntimeValue::from(context_ptr)))
+    }
+
     fn ens_name_by_hash(
         &mut self,
         hash_ptr: AscPtr<AscString>,
     ) -> Result<Option<RuntimeValue>, Trap> {
+        self.consume_gas(gas::ENS_NAME_BY_HASH_GAS)?;
         let hash: String = self.asc_get(hash_ptr);
         let name = self.valid_module.host_exports.ens_name_by_hash(&*hash)?;
         // map `None` to `null`, and `Some(s)` to a runtime string
@@ -992,7 +1393,7 @@ impl<T, L, S, U> Externals for WasmiModule<T, L, S, U>
 where
     T: EthereumAdapter,
     L: LinkResolver,
-    S: Store + Send + Sync + 'static,
+    S: Store + EthereumCallCache + Send + Sync + 'static,
     U: Sink<SinkItem = Box<dyn Future<Item = (), Error = ()> + Send>>
         + Clone
         + Send
@@ -1004,6 +1405,12 @@ where
         index: usize,
         args: RuntimeArgs,
     ) -> Result<Option<RuntimeValue>, Trap> {
+        // Every call into the host has a base cost; exports that do more
+        // work charge for it themselves
+        if index != GAS_FUNC_INDEX {
+            self.consume_gas(gas::HOST_EXPORT_GAS)?;
+        }
+
         match index {
             ABORT_FUNC_INDEX => self.abort(
                 args.nth_checked(0)?,
@@ -1021,6 +1428,12 @@ where
                 self.store_remove(args.nth_checked(0)?, args.nth_checked(1)?)
             }
             ETHEREUM_CALL_FUNC_INDEX => self.ethereum_call(args.nth_checked(0)?),
+            ETHEREUM_ENCODE_FUNC_INDEX => {
+                self.ethereum_encode(args.nth_checked(0)?, args.nth_checked(1)?)
+            }
+            ETHEREUM_DECODE_FUNC_INDEX => {
+                self.ethereum_decode(args.nth_checked(0)?, args.nth_checked(1)?)
+            }
             TYPE_CONVERSION_BYTES_TO_STRING_FUNC_INDEX => {
                 self.bytes_to_string(args.nth_checked(0)?)
             }
@@ -1033,12 +1446,22 @@ where
             TYPE_CONVERSION_I32_TO_BIG_INT_FUNC_INDEX => self.i32_to_big_int(args.nth_checked(0)?),
             TYPE_CONVERSION_BIG_INT_TO_I32_FUNC_INDEX => self.big_int_to_i32(args.nth_checked(0)?),
             JSON_FROM_BYTES_FUNC_INDEX => self.json_from_bytes(args.nth_checked(0)?),
+            JSON_TRY_FROM_BYTES_FUNC_INDEX => self.json_try_from_bytes(args.nth_checked(0)?),
             JSON_TO_I64_FUNC_INDEX => self.json_to_i64(args.nth_checked(0)?),
             JSON_TO_U64_FUNC_INDEX => self.json_to_u64(args.nth_checked(0)?),
             JSON_TO_F64_FUNC_INDEX => self.json_to_f64(args.nth_checked(0)?),
             JSON_TO_BIG_INT_FUNC_INDEX => self.json_to_big_int(args.nth_checked(0)?),
             IPFS_CAT_FUNC_INDEX => self.ipfs_cat(args.nth_checked(0)?),
+            IPFS_TRY_CAT_FUNC_INDEX => self.ipfs_try_cat(args.nth_checked(0)?),
             CRYPTO_KECCAK_256_INDEX => self.crypto_keccak_256(args.nth_checked(0)?),
+            CRYPTO_SHA256_INDEX => self.crypto_sha256(args.nth_checked(0)?),
+            CRYPTO_RIPEMD160_INDEX => self.crypto_ripemd160(args.nth_checked(0)?),
+            CRYPTO_SECP256K1_RECOVER_INDEX => self.crypto_secp256k1_recover(
+                args.nth_checked(0)?,
+                args.nth_checked(1)?,
+                args.nth_checked(2)?,
+                args.nth_checked(3)?,
+            ),
             BIG_INT_PLUS => self.big_int_plus(args.nth_checked(0)?, args.nth_checked(1)?),
             BIG_INT_MINUS => self.big_int_minus(args.nth_checked(0)?, args.nth_checked(1)?),
             BIG_INT_TIMES => self.big_int_times(args.nth_checked(0)?, args.nth_checked(1)?),
@@ -1050,6 +1473,16 @@ where
             }
             BIG_INT_MOD => self.big_int_mod(args.nth_checked(0)?, args.nth_checked(1)?),
             BIG_INT_POW => self.big_int_pow(args.nth_checked(0)?, args.nth_checked(1)?),
+            BIG_INT_BIT_OR => self.big_int_bit_or(args.nth_checked(0)?, args.nth_checked(1)?),
+            BIG_INT_BIT_AND => self.big_int_bit_and(args.nth_checked(0)?, args.nth_checked(1)?),
+            BIG_INT_LEFT_SHIFT => {
+                self.big_int_left_shift(args.nth_checked(0)?, args.nth_checked(1)?)
+            }
+            BIG_INT_RIGHT_SHIFT => {
+                self.big_int_right_shift(args.nth_checked(0)?, args.nth_checked(1)?)
+            }
+            BIG_INT_SQRT => self.big_int_sqrt(args.nth_checked(0)?),
+            BIG_INT_COMPARE => self.big_int_compare(args.nth_checked(0)?, args.nth_checked(1)?),
             GAS_FUNC_INDEX => self.gas(args.nth_checked(0)?),
             TYPE_CONVERSION_BYTES_TO_BASE_58_INDEX => self.bytes_to_base58(args.nth_checked(0)?),
             BIG_DECIMAL_PLUS => self.big_decimal_plus(args.nth_checked(0)?, args.nth_checked(1)?),
@@ -1072,6 +1505,14 @@ where
             DATA_SOURCE_CREATE_INDEX => {
                 self.data_source_create(args.nth_checked(0)?, args.nth_checked(1)?)
             }
+            DATA_SOURCE_CREATE_WITH_CONTEXT_INDEX => self.data_source_create_with_context(
+                args.nth_checked(0)?,
+                args.nth_checked(1)?,
+                args.nth_checked(2)?,
+            ),
+            DATA_SOURCE_ADDRESS_INDEX => self.data_source_address(),
+            DATA_SOURCE_NETWORK_INDEX => self.data_source_network(),
+            DATA_SOURCE_CONTEXT_INDEX => self.data_source_context(),
             ENS_NAME_BY_HASH => self.ens_name_by_hash(args.nth_checked(0)?),
             LOG_LOG => self.log_log(args.nth_checked(0)?, args.nth_checked(1)?),
             _ => panic!("Unimplemented function at {}", index),
@@ -1110,6 +1551,8 @@ impl ModuleImportResolver for ModuleResolver {
 
             // ethereum
             "ethereum.call" => FuncInstance::alloc_host(signature, ETHEREUM_CALL_FUNC_INDEX),
+            "ethereum.encode" => FuncInstance::alloc_host(signature, ETHEREUM_ENCODE_FUNC_INDEX),
+            "ethereum.decode" => FuncInstance::alloc_host(signature, ETHEREUM_DECODE_FUNC_INDEX),
 
             // typeConversion
             "typeConversion.bytesToString" => {
@@ -1139,6 +1582,9 @@ impl ModuleImportResolver for ModuleResolver {
 
             // json
             "json.fromBytes" => FuncInstance::alloc_host(signature, JSON_FROM_BYTES_FUNC_INDEX),
+            "json.try_fromBytes" => {
+                FuncInstance::alloc_host(signature, JSON_TRY_FROM_BYTES_FUNC_INDEX)
+            }
             "json.toI64" => FuncInstance::alloc_host(signature, JSON_TO_I64_FUNC_INDEX),
             "json.toU64" => FuncInstance::alloc_host(signature, JSON_TO_U64_FUNC_INDEX),
             "json.toF64" => FuncInstance::alloc_host(signature, JSON_TO_F64_FUNC_INDEX),
@@ -1146,10 +1592,16 @@ impl ModuleImportResolver for ModuleResolver {
 
             // ipfs
             "ipfs.cat" => FuncInstance::alloc_host(signature, IPFS_CAT_FUNC_INDEX),
+            "ipfs.try_cat" => FuncInstance::alloc_host(signature, IPFS_TRY_CAT_FUNC_INDEX),
             "ipfs.map" => FuncInstance::alloc_host(signature, IPFS_MAP_FUNC_INDEX),
 
             // crypto
             "crypto.keccak256" => FuncInstance::alloc_host(signature, CRYPTO_KECCAK_256_INDEX),
+            "crypto.sha256" => FuncInstance::alloc_host(signature, CRYPTO_SHA256_INDEX),
+            "crypto.ripemd160" => FuncInstance::alloc_host(signature, CRYPTO_RIPEMD160_INDEX),
+            "crypto.secp256k1Recover" => {
+                FuncInstance::alloc_host(signature, CRYPTO_SECP256K1_RECOVER_INDEX)
+            }
 
             // bigInt
             "bigInt.plus" => FuncInstance::alloc_host(signature, BIG_INT_PLUS),
@@ -1161,6 +1613,12 @@ impl ModuleImportResolver for ModuleResolver {
             }
             "bigInt.mod" => FuncInstance::alloc_host(signature, BIG_INT_MOD),
             "bigInt.pow" => FuncInstance::alloc_host(signature, BIG_INT_POW),
+            "bigInt.bitOr" => FuncInstance::alloc_host(signature, BIG_INT_BIT_OR),
+            "bigInt.bitAnd" => FuncInstance::alloc_host(signature, BIG_INT_BIT_AND),
+            "bigInt.leftShift" => FuncInstance::alloc_host(signature, BIG_INT_LEFT_SHIFT),
+            "bigInt.rightShift" => FuncInstance::alloc_host(signature, BIG_INT_RIGHT_SHIFT),
+            "bigInt.sqrt" => FuncInstance::alloc_host(signature, BIG_INT_SQRT),
+            "bigInt.compare" => FuncInstance::alloc_host(signature, BIG_INT_COMPARE),
 
             // bigDecimal
             "bigDecimal.plus" => FuncInstance::alloc_host(signature, BIG_DECIMAL_PLUS),
@@ -1173,6 +1631,12 @@ impl ModuleImportResolver for ModuleResolver {
 
             // dataSource
             "dataSource.create" => FuncInstance::alloc_host(signature, DATA_SOURCE_CREATE_INDEX),
+            "dataSource.createWithContext" => {
+                FuncInstance::alloc_host(signature, DATA_SOURCE_CREATE_WITH_CONTEXT_INDEX)
+            }
+            "dataSource.address" => FuncInstance::alloc_host(signature, DATA_SOURCE_ADDRESS_INDEX),
+            "dataSource.network" => FuncInstance::alloc_host(signature, DATA_SOURCE_NETWORK_INDEX),
+            "dataSource.context" => FuncInstance::alloc_host(signature, DATA_SOURCE_CONTEXT_INDEX),
 
             // ens.nameByHash
             "ens.nameByHash" => FuncInstance::alloc_host(signature, ENS_NAME_BY_HASH),
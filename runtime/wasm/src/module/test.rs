@@ -138,6 +138,7 @@ impl EthereumAdapter for MockEthereumAdapter {
         &self,
         _: &Logger,
         _: EthereumContractCall,
+        _: Arc<dyn EthereumCallCache>,
     ) -> Box<dyn Future<Item = Vec<Token>, Error = EthereumContractCallError> + Send> {
         unimplemented!();
     }
@@ -155,6 +156,23 @@ fn test_valid_module_and_store(
         >,
     >,
     Arc<FakeStore>,
+) {
+    test_valid_module_and_store_with_gas_limit(data_source, None)
+}
+
+fn test_valid_module_and_store_with_gas_limit(
+    data_source: DataSource,
+    handler_gas_limit: Option<u64>,
+) -> (
+    Arc<
+        ValidModule<
+            MockEthereumAdapter,
+            graph_core::LinkResolver,
+            FakeStore,
+            Sender<Box<dyn Future<Item = (), Error = ()> + Send>>,
+        >,
+    >,
+    Arc<FakeStore>,
 ) {
     let logger = Logger::root(slog::Discard, o!());
     let mock_ethereum_adapter = Arc::new(MockEthereumAdapter::default());
@@ -169,10 +187,14 @@ fn test_valid_module_and_store(
                 &logger,
                 WasmiModuleConfig {
                     subgraph_id: SubgraphDeploymentId::new("wasmModuleTest").unwrap(),
+                    schema: Arc::new(mock_schema()),
                     api_version: Version::parse(&data_source.mapping.api_version).unwrap(),
                     parsed_module: data_source.mapping.runtime,
                     abis: data_source.mapping.abis,
                     data_source_name: data_source.name,
+                    data_source_address: data_source.source.address,
+                    data_source_network: data_source.network.unwrap(),
+                    data_source_context: data_source.context,
                     templates: data_source.templates,
                     ethereum_adapter: mock_ethereum_adapter,
                     link_resolver: Arc::new(ipfs_api::IpfsClient::default().into()),
@@ -181,6 +203,7 @@ fn test_valid_module_and_store(
                         .ok()
                         .and_then(|s| u64::from_str(&s).ok())
                         .map(Duration::from_secs),
+                    handler_gas_limit,
                 },
                 task_sender,
             )
@@ -190,6 +213,14 @@ fn test_valid_module_and_store(
     )
 }
 
+fn mock_schema() -> Schema {
+    Schema::parse(
+        "type Thing @entity { id: ID!, value: String!, extra: String }",
+        SubgraphDeploymentId::new("wasmModuleTest").unwrap(),
+    )
+    .unwrap()
+}
+
 fn test_valid_module(
     data_source: DataSource,
 ) -> Arc<
@@ -213,6 +244,7 @@ fn mock_data_source(path: &str) -> DataSource {
         source: Source {
             address: Some(Address::from_str("0123123123012312312301231231230123123123").unwrap()),
             abi: String::from("123123"),
+            start_block: 0,
         },
         mapping: Mapping {
             kind: String::from("ethereum/events"),
@@ -250,6 +282,7 @@ fn mock_data_source(path: &str) -> DataSource {
                 runtime: Arc::new(runtime),
             },
         }],
+        context: None,
     }
 }
 
@@ -265,13 +298,23 @@ impl<T, L, S, U> WasmiModule<T, L, S, U>
 where
     T: EthereumAdapter,
     L: LinkResolver,
-    S: Store + Send + Sync + 'static,
+    S: Store + EthereumCallCache + Send + Sync + 'static,
     U: Sink<SinkItem = Box<dyn Future<Item = (), Error = ()> + Send>>
         + Clone
         + Send
         + Sync
         + 'static,
 {
+    fn takes_no_args_returns_ptr<P>(&mut self, fn_name: &str) -> AscPtr<P> {
+        self.module
+            .clone()
+            .invoke_export(fn_name, &[], self)
+            .expect("call failed")
+            .expect("call returned nothing")
+            .try_into()
+            .expect("call did not return pointer")
+    }
+
     fn takes_val_returns_ptr<P>(&mut self, fn_name: &str, val: RuntimeValue) -> AscPtr<P> {
         self.module
             .clone()
@@ -501,6 +544,47 @@ fn ipfs_map() {
     assert!(errmsg.contains("api returned error \\'invalid \\'ipfs ref\\' path\\'"))
 }
 
+#[test]
+fn ipfs_map_gas() {
+    // Enough for the handler and a few callbacks, but not for reading
+    // hundreds of kilobytes
+    const GAS_LIMIT: u64 = gas::IPFS_GAS + 1_000_000;
+
+    let (valid_module, _) = test_valid_module_and_store_with_gas_limit(
+        mock_data_source("wasm_test/ipfs_map.wasm"),
+        Some(GAS_LIMIT),
+    );
+    let ipfs = Arc::new(ipfs_api::IpfsClient::default());
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let mut run_ipfs_map = move |json_string: String| -> Result<(), Error> {
+        let mut module =
+            WasmiModule::from_valid_module_with_ctx(valid_module.clone(), mock_context()).unwrap();
+        let hash = runtime
+            .block_on(ipfs.add(Cursor::new(json_string)))
+            .unwrap()
+            .hash;
+        let user_data = RuntimeValue::from(module.asc_new(USER_DATA));
+        module.module.clone().invoke_export(
+            "ipfsMap",
+            &[RuntimeValue::from(module.asc_new(&hash)), user_data],
+            &mut module,
+        )?;
+        Ok(())
+    };
+
+    let (str1, _) = make_thing("one", "eins");
+    let (str2, _) = make_thing("two", "zwei");
+    run_ipfs_map(format!("{}\n{}", str1, str2)).expect("call failed");
+
+    // The callbacks count against the gas limit of the handler
+    let lines: Vec<_> = (0..10_000)
+        .map(|i| make_thing(&i.to_string(), "value").0)
+        .collect();
+    let errmsg = run_ipfs_map(lines.join("\n")).unwrap_err().to_string();
+    assert!(errmsg.contains("Mapping handler exceeded the gas limit"));
+}
+
 #[test]
 fn ipfs_fail() {
     let valid_module = test_valid_module(mock_data_source("wasm_test/ipfs_cat.wasm"));
@@ -512,6 +596,52 @@ fn ipfs_fail() {
         .is_null());
 }
 
+#[test]
+fn ipfs_try_cat() {
+    let valid_module = test_valid_module(mock_data_source("wasm_test/ipfs_cat.wasm"));
+    let mut module = WasmiModule::from_valid_module_with_ctx(valid_module, mock_context()).unwrap();
+    let ipfs = Arc::new(ipfs_api::IpfsClient::default());
+
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let hash = runtime.block_on(ipfs.add(Cursor::new("42"))).unwrap().hash;
+    let hash = module.asc_new(&hash);
+    let result: AscPtr<AscResult<AscPtr<Uint8Array>, AscPtr<AscString>>> =
+        module.takes_ptr_returns_ptr("ipfsTryCat", hash);
+    let result: Result<Vec<u8>, String> = module.asc_get(result);
+    assert_eq!(Ok(b"42".to_vec()), result);
+
+    // An invalid hash is reported to the mapping instead of failing it
+    let hash = module.asc_new("invalid hash");
+    let result: AscPtr<AscResult<AscPtr<Uint8Array>, AscPtr<AscString>>> =
+        module.takes_ptr_returns_ptr("ipfsTryCat", hash);
+    let result: Result<Vec<u8>, String> = module.asc_get(result);
+    assert!(result.is_err());
+}
+
+#[test]
+fn json_try_from_bytes() {
+    let valid_module = test_valid_module(mock_data_source("wasm_test/ipfs_cat.wasm"));
+    let mut module = WasmiModule::from_valid_module_with_ctx(valid_module, mock_context()).unwrap();
+
+    let bytes: &[u8] = b"{\"answer\": 42}";
+    let bytes: AscPtr<Uint8Array> = module.asc_new(bytes);
+    let result: AscPtr<AscResult<AscPtr<AscEnum<JsonValueKind>>, AscPtr<AscString>>> =
+        module.takes_ptr_returns_ptr("jsonTryFromBytes", bytes);
+    let result = result.read_ptr(&module);
+    assert!(result.error.is_null());
+    let value = result.value.read_ptr(&module).inner.read_ptr(&module);
+    assert_eq!(JsonValueKind::Object as u32, value.kind as u32);
+
+    let bytes: &[u8] = b"{\"answer\": ";
+    let bytes: AscPtr<Uint8Array> = module.asc_new(bytes);
+    let result: AscPtr<AscResult<AscPtr<AscEnum<JsonValueKind>>, AscPtr<AscString>>> =
+        module.takes_ptr_returns_ptr("jsonTryFromBytes", bytes);
+    let result = result.read_ptr(&module);
+    assert!(result.value.is_null());
+    let error: String = module.asc_get(result.error.read_ptr(&module).inner);
+    assert!(error.contains("EOF while parsing a value"));
+}
+
 #[test]
 fn crypto_keccak256() {
     let valid_module = test_valid_module(mock_data_source("wasm_test/crypto.wasm"));
@@ -534,6 +664,179 @@ fn crypto_keccak256() {
     );
 }
 
+#[test]
+fn crypto_sha256_and_ripemd160() {
+    let valid_module = test_valid_module(mock_data_source("wasm_test/crypto.wasm"));
+    let mut module = WasmiModule::from_valid_module_with_ctx(valid_module, mock_context()).unwrap();
+
+    let mut hash = |export: &str, input: &str| -> String {
+        let input: AscPtr<Uint8Array> = module.asc_new(input.as_bytes());
+        let hash: AscPtr<Uint8Array> = module
+            .module
+            .clone()
+            .invoke_export(export, &[RuntimeValue::from(input)], &mut module)
+            .expect("call failed")
+            .expect("call returned nothing")
+            .try_into()
+            .expect("call did not return pointer");
+        hex::encode(module.asc_get::<Vec<u8>, _>(hash))
+    };
+
+    assert_eq!(
+        hash("sha256", "eth"),
+        "c69ea13227bace6e1f8a06364d93f4a6f04632432b64ca9b1fc4036baea4d34c"
+    );
+    assert_eq!(
+        hash("ripemd160", "eth"),
+        "71ccfeb69d983a6af967c8afe14eeb420fa68f52"
+    );
+}
+
+#[test]
+fn crypto_secp256k1_recover() {
+    let valid_module = test_valid_module(mock_data_source("wasm_test/crypto.wasm"));
+    let mut module = WasmiModule::from_valid_module_with_ctx(valid_module, mock_context()).unwrap();
+
+    // A signature made with the private key 1, whose address is well known
+    let hash = "8bb3f901caabc7245438d2987e5e342bbf41621b39fb70d20a34eae6c517dca9";
+    let r = "bb50e2d89a4ed70663d080659fe0ad4b9bc3e06c17a227433966cb59ceee020d";
+    let s = "16530752ebbccce7cbc098b48fa7ab71c5c2452b236097069598573ce398258b";
+
+    let mut recover = |hash: &str, v: i32, r: &str, s: &str| {
+        let hash: AscPtr<Uint8Array> = module.asc_new(hex::decode(hash).unwrap().as_slice());
+        let r: AscPtr<Uint8Array> = module.asc_new(hex::decode(r).unwrap().as_slice());
+        let s: AscPtr<Uint8Array> = module.asc_new(hex::decode(s).unwrap().as_slice());
+        let args = [
+            RuntimeValue::from(hash),
+            RuntimeValue::from(v),
+            RuntimeValue::from(r),
+            RuntimeValue::from(s),
+        ];
+        module
+            .module
+            .clone()
+            .invoke_export("secp256k1Recover", &args, &mut module)
+            .map(|address| {
+                let address: AscPtr<Uint8Array> = address
+                    .expect("call returned nothing")
+                    .try_into()
+                    .expect("call did not return pointer");
+                if address.is_null() {
+                    None
+                } else {
+                    Some(hex::encode(module.asc_get::<Vec<u8>, _>(address)))
+                }
+            })
+    };
+
+    let address = Some("7e5f4552091a69125d5dfcb7b8c2659029395bdf".to_owned());
+    assert_eq!(recover(hash, 28, r, s).unwrap(), address);
+    assert_eq!(recover(hash, 1, r, s).unwrap(), address);
+
+    // The other recovery id yields a different address
+    assert_ne!(recover(hash, 27, r, s).unwrap(), address);
+
+    // Invalid signatures return `null`
+    assert_eq!(recover(hash, 29, r, s).unwrap(), None);
+    assert_eq!(recover(hash, 28, &"00".repeat(32), s).unwrap(), None);
+
+    // Arguments that don't have 32 bytes return `null`, too
+    assert_eq!(recover("00", 28, r, s).unwrap(), None);
+    assert_eq!(recover(hash, 28, &"00".repeat(33), s).unwrap(), None);
+    assert_eq!(recover(hash, 28, r, "").unwrap(), None);
+}
+
+#[test]
+fn crypto_hash_gas() {
+    let valid_module = test_valid_module(mock_data_source("wasm_test/crypto.wasm"));
+    let mut module = WasmiModule::from_valid_module_with_ctx(valid_module, mock_context()).unwrap();
+    let input: AscPtr<Uint8Array> = module.asc_new(vec![0u8; 1000].as_slice());
+
+    // Hashing is charged for every byte of the input
+    for export in &["hash", "sha256", "ripemd160"] {
+        module.gas_used = 0;
+        module
+            .module
+            .clone()
+            .invoke_export(export, &[RuntimeValue::from(input)], &mut module)
+            .expect("call failed");
+        assert!(module.gas_used >= gas::HASH_GAS_PER_BYTE * 1000);
+    }
+}
+
+#[test]
+fn ethereum_encode_and_decode() {
+    let valid_module = test_valid_module(mock_data_source("wasm_test/ethereum_abi.wasm"));
+    let mut module = WasmiModule::from_valid_module_with_ctx(valid_module, mock_context()).unwrap();
+
+    let token = Token::Uint(U256::from(1));
+    let token_ptr: AscPtr<AscEnum<EthereumValueKind>> = module.asc_new(&token);
+    let type_ptr = module.asc_new("uint256");
+    let data_ptr: AscPtr<Uint8Array> =
+        module.takes_ptr_ptr_returns_ptr("encode", token_ptr, type_ptr);
+    let data: Vec<u8> = module.asc_get(data_ptr);
+    assert_eq!(hex::encode(data), format!("{}01", "00".repeat(31)));
+
+    // Values that don't match the type and invalid types return `null`
+    let type_ptr = module.asc_new("address");
+    let data_ptr: AscPtr<Uint8Array> =
+        module.takes_ptr_ptr_returns_ptr("encode", token_ptr, type_ptr);
+    assert!(data_ptr.is_null());
+    let type_ptr = module.asc_new("uint257");
+    let data_ptr: AscPtr<Uint8Array> =
+        module.takes_ptr_ptr_returns_ptr("encode", token_ptr, type_ptr);
+    assert!(data_ptr.is_null());
+
+    // Numbers that don't fit into 256 bits return `null`, too
+    let n_ptr: AscPtr<AscBigInt> = module.asc_new(&scalar::BigInt::from(-1));
+    let asc_enum = AscEnum {
+        kind: EthereumValueKind::Uint,
+        _padding: 0,
+        payload: EnumPayload(n_ptr.to_payload()),
+    };
+    let token_ptr: AscPtr<AscEnum<EthereumValueKind>> = AscPtr::from(EnumPayload(
+        module.raw_new(&asc_enum.to_asc_bytes()).unwrap() as u64,
+    ));
+    let type_ptr = module.asc_new("uint256");
+    let data_ptr: AscPtr<Uint8Array> =
+        module.takes_ptr_ptr_returns_ptr("encode", token_ptr, type_ptr);
+    assert!(data_ptr.is_null());
+
+    let token = Token::Tuple(vec![
+        Token::Address(Address::from_str("7e5f4552091a69125d5dfcb7b8c2659029395bdf").unwrap()),
+        Token::Array(vec![
+            Token::Uint(U256::from(7)),
+            Token::Uint(U256::max_value()),
+        ]),
+        Token::Bytes(vec![0xde, 0xad, 0xbe, 0xef]),
+    ]);
+    let token_ptr: AscPtr<AscEnum<EthereumValueKind>> = module.asc_new(&token);
+    let type_ptr = module.asc_new("(address,uint256[],bytes)");
+    let data_ptr: AscPtr<Uint8Array> =
+        module.takes_ptr_ptr_returns_ptr("encode", token_ptr, type_ptr);
+    let data: Vec<u8> = module.asc_get(data_ptr);
+    assert_eq!(data, ethabi::encode(&[token.clone()]));
+
+    let types_ptr = module.asc_new("(address,uint256[],bytes)");
+    let data_ptr: AscPtr<Uint8Array> = module.asc_new(data.as_slice());
+    let decoded_ptr: AscPtr<AscEnum<EthereumValueKind>> =
+        module.takes_ptr_ptr_returns_ptr("decode", types_ptr, data_ptr);
+    let decoded: Token = module.asc_get(decoded_ptr);
+    assert_eq!(decoded, token);
+
+    // Invalid types and data return `null`
+    let types_ptr = module.asc_new("(address,uint256[]");
+    let decoded_ptr: AscPtr<AscEnum<EthereumValueKind>> =
+        module.takes_ptr_ptr_returns_ptr("decode", types_ptr, data_ptr);
+    assert!(decoded_ptr.is_null());
+
+    let types_ptr = module.asc_new("(address,uint256[],bytes)");
+    let data_ptr: AscPtr<Uint8Array> = module.asc_new(&data[..40]);
+    let decoded_ptr: AscPtr<AscEnum<EthereumValueKind>> =
+        module.takes_ptr_ptr_returns_ptr("decode", types_ptr, data_ptr);
+    assert!(decoded_ptr.is_null());
+}
+
 #[test]
 fn token_numeric_conversion() {
     let valid_module = test_valid_module(mock_data_source("wasm_test/token_to_numeric.wasm"));
@@ -775,6 +1078,93 @@ fn big_int_arithmetic() {
     assert_eq!(result, BigInt::from(1));
 }
 
+#[test]
+fn big_int_bitwise_sqrt_and_compare() {
+    let valid_module = test_valid_module(mock_data_source("wasm_test/big_int_arithmetic.wasm"));
+    let mut module = WasmiModule::from_valid_module_with_ctx(valid_module, mock_context()).unwrap();
+
+    let twelve: AscPtr<AscBigInt> = module.asc_new(&BigInt::from(12));
+    let ten: AscPtr<AscBigInt> = module.asc_new(&BigInt::from(10));
+    let minus_twelve: AscPtr<AscBigInt> = module.asc_new(&BigInt::from(-12));
+
+    // 12 | 10 = 14
+    let result_ptr: AscPtr<AscBigInt> = module.takes_ptr_ptr_returns_ptr("bitOr", twelve, ten);
+    let result: BigInt = module.asc_get(result_ptr);
+    assert_eq!(result, BigInt::from(14));
+
+    // 12 & 10 = 8
+    let result_ptr: AscPtr<AscBigInt> = module.takes_ptr_ptr_returns_ptr("bitAnd", twelve, ten);
+    let result: BigInt = module.asc_get(result_ptr);
+    assert_eq!(result, BigInt::from(8));
+
+    // -12 & 10 = 0, in two's complement
+    let result_ptr: AscPtr<AscBigInt> =
+        module.takes_ptr_ptr_returns_ptr("bitAnd", minus_twelve, ten);
+    let result: BigInt = module.asc_get(result_ptr);
+    assert_eq!(result, BigInt::from(0));
+
+    let mut shift = |fn_name: &str, x: AscPtr<AscBigInt>, bits: i32| -> BigInt {
+        let result_ptr: AscPtr<AscBigInt> = module
+            .module
+            .clone()
+            .invoke_export(
+                fn_name,
+                &[RuntimeValue::from(x), RuntimeValue::from(bits)],
+                &mut module,
+            )
+            .expect("call failed")
+            .expect("call returned nothing")
+            .try_into()
+            .expect("call did not return pointer");
+        module.asc_get(result_ptr)
+    };
+
+    // 12 << 96 = 12 * 2^96, as used for Q64.96 fixed point numbers
+    assert_eq!(
+        shift("leftShift", twelve, 96),
+        BigInt::from(12) * BigInt::from(2).pow(96)
+    );
+
+    // 12 >> 2 = 3
+    assert_eq!(shift("rightShift", twelve, 2), BigInt::from(3));
+
+    // -12 >> 3 = -2, rounding towards negative infinity
+    assert_eq!(shift("rightShift", minus_twelve, 3), BigInt::from(-2));
+
+    // sqrt(12) = 3
+    let result_ptr: AscPtr<AscBigInt> = module.takes_ptr_returns_ptr("sqrt", twelve);
+    let result: BigInt = module.asc_get(result_ptr);
+    assert_eq!(result, BigInt::from(3));
+
+    // The square root of a negative number fails
+    let err = module
+        .module
+        .clone()
+        .invoke_export("sqrt", &[RuntimeValue::from(minus_twelve)], &mut module)
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("attempted to take the square root of negative BigInt `-12`"));
+
+    let mut compare = |x: AscPtr<AscBigInt>, y: AscPtr<AscBigInt>| -> i32 {
+        module
+            .module
+            .clone()
+            .invoke_export(
+                "compare",
+                &[RuntimeValue::from(x), RuntimeValue::from(y)],
+                &mut module,
+            )
+            .expect("call failed")
+            .expect("call returned nothing")
+            .try_into()
+            .expect("call did not return i32")
+    };
+    assert_eq!(compare(ten, twelve), -1);
+    assert_eq!(compare(twelve, twelve), 0);
+    assert_eq!(compare(twelve, minus_twelve), 1);
+}
+
 #[test]
 fn abort() {
     let valid_module = test_valid_module(mock_data_source("wasm_test/abort.wasm"));
@@ -843,6 +1233,65 @@ fn data_source_create() {
     };
 }
 
+#[test]
+fn data_source_create_with_context() {
+    let valid_module = test_valid_module(mock_data_source("wasm_test/data_source_create.wasm"));
+    let mut module = WasmiModule::from_valid_module_with_ctx(valid_module, mock_context()).unwrap();
+
+    let mut context = HashMap::new();
+    context.insert("factory".to_owned(), Value::from("0xfac7"));
+    context.insert("fee".to_owned(), Value::from(BigInt::from(3000)));
+
+    let params = vec![String::from("0xc0a47dFe034B400B47bDaD5FecDa2621de6c4d95")];
+    let args = [
+        RuntimeValue::from(module.asc_new("example template")),
+        RuntimeValue::from(module.asc_new(&*params)),
+        RuntimeValue::from(module.asc_new(&context)),
+    ];
+    module
+        .module
+        .clone()
+        .invoke_export("dataSourceCreateWithContext", &args, &mut module)
+        .expect("call failed");
+
+    let result = &module.ctx.state.created_data_sources;
+    assert_eq!(result[0].template.name, "example template");
+    assert_eq!(result[0].params, params);
+    assert_eq!(result[0].context, Some(DataSourceContext::from(context)));
+}
+
+#[test]
+fn data_source_address_network_and_context() {
+    let mut data_source = mock_data_source("wasm_test/data_source_create.wasm");
+    data_source.context = Some(DataSourceContext::from(vec![(
+        "factory",
+        Value::from("0xfac7"),
+    )]));
+    let valid_module = test_valid_module(data_source);
+    let mut module = WasmiModule::from_valid_module_with_ctx(valid_module, mock_context()).unwrap();
+
+    let address: AscPtr<AscH160> = module.takes_no_args_returns_ptr("dataSourceAddress");
+    assert_eq!(
+        hex::encode(module.asc_get::<Vec<u8>, _>(address)),
+        "0123123123012312312301231231230123123123"
+    );
+
+    let network: AscPtr<AscString> = module.takes_no_args_returns_ptr("dataSourceNetwork");
+    assert_eq!(module.asc_get::<String, _>(network), "mainnet");
+
+    let context: AscPtr<AscEntity> = module.takes_no_args_returns_ptr("dataSourceContext");
+    let context: HashMap<String, Value> = module.asc_get(context);
+    assert_eq!(context.get("factory"), Some(&Value::from("0xfac7")));
+    assert_eq!(context.len(), 1);
+
+    // Data sources that were not created with a context get an empty one
+    let valid_module = test_valid_module(mock_data_source("wasm_test/data_source_create.wasm"));
+    let mut module = WasmiModule::from_valid_module_with_ctx(valid_module, mock_context()).unwrap();
+    let context: AscPtr<AscEntity> = module.takes_no_args_returns_ptr("dataSourceContext");
+    let context: HashMap<String, Value> = module.asc_get(context);
+    assert!(context.is_empty());
+}
+
 #[test]
 fn ens_name_by_hash() {
     let valid_module = test_valid_module(mock_data_source("wasm_test/ens_name_by_hash.wasm"));
@@ -18,6 +18,26 @@ fn unbounded_loop() {
     );
 }
 
+#[test]
+fn gas_limit() {
+    let valid_module = test_valid_module_and_store_with_gas_limit(
+        mock_data_source("wasm_test/non_terminating.wasm"),
+        Some(1_000_000),
+    )
+    .0;
+    let mut module = WasmiModule::from_valid_module_with_ctx(valid_module, mock_context()).unwrap();
+    module.start_time = Instant::now();
+    let err = module
+        .module
+        .clone()
+        .invoke_export("loop", &[], &mut module)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Trap: Trap { kind: Host(HostExportError(\"Mapping handler exceeded the gas limit of 1000000\")) }"
+    );
+}
+
 #[test]
 fn unbounded_recursion() {
     let valid_module = test_valid_module(mock_data_source("wasm_test/non_terminating.wasm"));
@@ -309,6 +329,80 @@ fn abi_store_value() {
     );
 }
 
+#[test]
+fn abi_ethereum_event_with_receipt() {
+    let valid_module = test_valid_module(mock_data_source("wasm_test/abi_classes.wasm"));
+    let mut module = WasmiModule::from_valid_module_with_ctx(valid_module, mock_context()).unwrap();
+
+    let receipt: web3::types::TransactionReceipt = serde_json::from_value(serde_json::json!({
+        "transactionHash": format!("0x{}", "11".repeat(32)),
+        "transactionIndex": "0x2",
+        "blockHash": format!("0x{}", "22".repeat(32)),
+        "blockNumber": "0x10",
+        "cumulativeGasUsed": "0x15f90",
+        "gasUsed": "0x5208",
+        "contractAddress": null,
+        "status": "0x1",
+        "logsBloom": format!("0x{}", "00".repeat(256)),
+        "logs": [{
+            "address": format!("0x{}", "33".repeat(20)),
+            "topics": [format!("0x{}", "44".repeat(32))],
+            "data": "0x0102",
+            "blockHash": format!("0x{}", "22".repeat(32)),
+            "blockNumber": "0x10",
+            "transactionHash": format!("0x{}", "11".repeat(32)),
+            "transactionIndex": "0x2",
+            "logIndex": "0x7",
+            "transactionLogIndex": "0x0",
+            "logType": null,
+            "removed": false
+        }]
+    }))
+    .unwrap();
+
+    let event = EthereumEventData {
+        address: H160::zero(),
+        log_index: U256::from(7),
+        transaction_log_index: U256::zero(),
+        log_type: None,
+        block: EthereumBlockData::default(),
+        transaction: EthereumTransactionData {
+            hash: H256::zero(),
+            index: web3::types::U128::zero(),
+            from: H160::zero(),
+            to: None,
+            value: U256::zero(),
+            gas_used: U256::zero(),
+            gas_price: U256::zero(),
+            input: web3::types::Bytes(vec![]),
+        },
+        params: vec![],
+        receipt: Some(EthereumTransactionReceiptData::from(&receipt)),
+    };
+    let event_ptr: AscPtr<AscEthereumEventWithReceipt<AscEthereumTransaction_0_0_2>> =
+        module.asc_new(&event);
+
+    // The receipt comes after all the fields of a plain event
+    let receipt = event_ptr.read_ptr(&module).receipt.read_ptr(&module);
+    let status: BigInt = module.asc_get(receipt.status);
+    let gas_used: BigInt = module.asc_get(receipt.gas_used);
+    let transaction_hash: H256 = module.asc_get(receipt.transaction_hash);
+    assert_eq!(status, BigInt::from(1));
+    assert_eq!(gas_used, BigInt::from(21000));
+    assert_eq!(transaction_hash, H256::from([0x11; 32]));
+    assert!(receipt.contract_address.is_null());
+
+    let logs = receipt.logs.read_ptr(&module).to_vec(&module);
+    assert_eq!(logs.len(), 1);
+    let log = logs[0].read_ptr(&module);
+    let address: H160 = module.asc_get(log.address);
+    let data: Vec<u8> = module.asc_get(log.data);
+    let log_index: BigInt = module.asc_get(log.log_index);
+    assert_eq!(address, H160::from([0x33; 20]));
+    assert_eq!(data, vec![1, 2]);
+    assert_eq!(log_index, BigInt::from(7));
+}
+
 #[test]
 fn abi_h160() {
     let valid_module = test_valid_module(mock_data_source("wasm_test/abi_classes.wasm"));
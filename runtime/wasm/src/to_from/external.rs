@@ -4,13 +4,14 @@ use std::collections::HashMap;
 use crate::web3::types as web3;
 use graph::components::ethereum::{
     EthereumBlockData, EthereumCallData, EthereumEventData, EthereumTransactionData,
+    EthereumTransactionReceiptData,
 };
 use graph::data::store;
 use graph::prelude::serde_json;
-use graph::prelude::{BigDecimal, BigInt};
+use graph::prelude::{format_err, BigDecimal, BigInt, Error};
 
 use crate::asc_abi::class::*;
-use crate::asc_abi::{AscHeap, AscPtr, AscType, FromAscObj, ToAscObj};
+use crate::asc_abi::{AscHeap, AscPtr, AscType, FromAscObj, ToAscObj, TryFromAscObj};
 
 use crate::UnresolvedContractCall;
 
@@ -160,6 +161,48 @@ impl FromAscObj<AscEnum<EthereumValueKind>> for ethabi::Token {
     }
 }
 
+/// Unlike `FromAscObj`, fails for integers that are out of range for their
+/// kind instead of truncating them
+impl TryFromAscObj<AscEnum<EthereumValueKind>> for ethabi::Token {
+    fn try_from_asc_obj<H: AscHeap>(
+        asc_enum: AscEnum<EthereumValueKind>,
+        heap: &H,
+    ) -> Result<Self, Error> {
+        use ethabi::Token;
+
+        let payload = asc_enum.payload;
+        Ok(match asc_enum.kind {
+            EthereumValueKind::Int => {
+                let ptr: AscPtr<AscBigInt> = AscPtr::from(payload);
+                let n: BigInt = heap.asc_get(ptr);
+                Token::Int(n.checked_to_signed_u256().ok_or_else(|| {
+                    format_err!("ethereum.Value `{}` does not fit into an int256", n)
+                })?)
+            }
+            EthereumValueKind::Uint => {
+                let ptr: AscPtr<AscBigInt> = AscPtr::from(payload);
+                let n: BigInt = heap.asc_get(ptr);
+                Token::Uint(n.checked_to_unsigned_u256().ok_or_else(|| {
+                    format_err!("ethereum.Value `{}` does not fit into a uint256", n)
+                })?)
+            }
+            EthereumValueKind::FixedArray => {
+                let ptr: AscEnumArray<EthereumValueKind> = AscPtr::from(payload);
+                Token::FixedArray(heap.try_asc_get(ptr)?)
+            }
+            EthereumValueKind::Array => {
+                let ptr: AscEnumArray<EthereumValueKind> = AscPtr::from(payload);
+                Token::Array(heap.try_asc_get(ptr)?)
+            }
+            EthereumValueKind::Tuple => {
+                let ptr: AscEnumArray<EthereumValueKind> = AscPtr::from(payload);
+                Token::Tuple(heap.try_asc_get(ptr)?)
+            }
+            _ => Token::from_asc_obj(asc_enum, heap),
+        })
+    }
+}
+
 impl FromAscObj<AscEnum<StoreValueKind>> for store::Value {
     fn from_asc_obj<H: AscHeap>(asc_enum: AscEnum<StoreValueKind>, heap: &H) -> Self {
         use self::store::Value;
@@ -359,6 +402,104 @@ where
     }
 }
 
+impl ToAscObj<AscEthereumLog> for web3::Log {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &mut H) -> AscEthereumLog {
+        AscEthereumLog {
+            address: heap.asc_new(&self.address),
+            topics: heap.asc_new(self.topics.as_slice()),
+            data: heap.asc_new(&*self.data.0),
+            block_hash: self
+                .block_hash
+                .map(|block_hash| heap.asc_new(&block_hash))
+                .unwrap_or_else(|| AscPtr::null()),
+            block_number: self
+                .block_number
+                .map(|number| heap.asc_new(&BigInt::from_unsigned_u256(&number)))
+                .unwrap_or_else(|| AscPtr::null()),
+            transaction_hash: self
+                .transaction_hash
+                .map(|transaction_hash| heap.asc_new(&transaction_hash))
+                .unwrap_or_else(|| AscPtr::null()),
+            transaction_index: self
+                .transaction_index
+                .map(|index| heap.asc_new(&BigInt::from(index)))
+                .unwrap_or_else(|| AscPtr::null()),
+            log_index: self
+                .log_index
+                .map(|index| heap.asc_new(&BigInt::from_unsigned_u256(&index)))
+                .unwrap_or_else(|| AscPtr::null()),
+            transaction_log_index: self
+                .transaction_log_index
+                .map(|index| heap.asc_new(&BigInt::from_unsigned_u256(&index)))
+                .unwrap_or_else(|| AscPtr::null()),
+            log_type: self
+                .log_type
+                .clone()
+                .map(|log_type| heap.asc_new(&log_type))
+                .unwrap_or_else(|| AscPtr::null()),
+        }
+    }
+}
+
+impl ToAscObj<AscEthereumTransactionReceipt> for EthereumTransactionReceiptData {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &mut H) -> AscEthereumTransactionReceipt {
+        AscEthereumTransactionReceipt {
+            transaction_hash: heap.asc_new(&self.transaction_hash),
+            transaction_index: heap.asc_new(&BigInt::from(self.transaction_index)),
+            block_hash: self
+                .block_hash
+                .map(|block_hash| heap.asc_new(&block_hash))
+                .unwrap_or_else(|| AscPtr::null()),
+            block_number: self
+                .block_number
+                .map(|number| heap.asc_new(&BigInt::from_unsigned_u256(&number)))
+                .unwrap_or_else(|| AscPtr::null()),
+            cumulative_gas_used: heap
+                .asc_new(&BigInt::from_unsigned_u256(&self.cumulative_gas_used)),
+            gas_used: self
+                .gas_used
+                .map(|gas_used| heap.asc_new(&BigInt::from_unsigned_u256(&gas_used)))
+                .unwrap_or_else(|| AscPtr::null()),
+            contract_address: self
+                .contract_address
+                .map(|address| heap.asc_new(&address))
+                .unwrap_or_else(|| AscPtr::null()),
+            status: self
+                .status
+                .map(|status| heap.asc_new(&BigInt::from(status)))
+                .unwrap_or_else(|| AscPtr::null()),
+            logs: heap.asc_new(self.logs.as_slice()),
+        }
+    }
+}
+
+impl<T: AscType> ToAscObj<AscEthereumEventWithReceipt<T>> for EthereumEventData
+where
+    EthereumTransactionData: ToAscObj<T>,
+{
+    fn to_asc_obj<H: AscHeap>(&self, heap: &mut H) -> AscEthereumEventWithReceipt<T> {
+        AscEthereumEventWithReceipt {
+            address: heap.asc_new(&self.address),
+            log_index: heap.asc_new(&BigInt::from_unsigned_u256(&self.log_index)),
+            transaction_log_index: heap
+                .asc_new(&BigInt::from_unsigned_u256(&self.transaction_log_index)),
+            log_type: self
+                .log_type
+                .clone()
+                .map(|log_type| heap.asc_new(&log_type))
+                .unwrap_or_else(|| AscPtr::null()),
+            block: heap.asc_new(&self.block),
+            transaction: heap.asc_new::<T, EthereumTransactionData>(&self.transaction),
+            params: heap.asc_new(self.params.as_slice()),
+            receipt: self
+                .receipt
+                .as_ref()
+                .map(|receipt| heap.asc_new(receipt))
+                .unwrap_or_else(|| AscPtr::null()),
+        }
+    }
+}
+
 impl ToAscObj<AscEthereumCall> for EthereumCallData {
     fn to_asc_obj<H: AscHeap>(&self, heap: &mut H) -> AscEthereumCall {
         AscEthereumCall {
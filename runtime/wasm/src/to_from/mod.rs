@@ -3,9 +3,11 @@ use std::hash::Hash;
 use std::iter::FromIterator;
 
 use crate::asc_abi::class::*;
-use crate::asc_abi::{AscHeap, AscPtr, AscType, AscValue, FromAscObj, ToAscObj};
+use graph::prelude::Error;
 
-///! Implementations of `ToAscObj` and `FromAscObj` for Rust types.
+use crate::asc_abi::{AscHeap, AscPtr, AscType, AscValue, FromAscObj, ToAscObj, TryFromAscObj};
+
+///! Implementations of `ToAscObj`, `FromAscObj` and `TryFromAscObj` for Rust types.
 ///! Standard Rust types go in `mod.rs` and external types in `external.rs`.
 mod external;
 
@@ -15,6 +17,12 @@ impl<T: AscValue> ToAscObj<TypedArray<T>> for [T] {
     }
 }
 
+impl<T: AscValue> ToAscObj<TypedArray<T>> for Vec<T> {
+    fn to_asc_obj<H: AscHeap>(&self, heap: &mut H) -> TypedArray<T> {
+        self.as_slice().to_asc_obj(heap)
+    }
+}
+
 impl<T: AscValue> FromAscObj<TypedArray<T>> for Vec<T> {
     fn from_asc_obj<H: AscHeap>(typed_array: TypedArray<T>, heap: &H) -> Self {
         typed_array.to_vec(heap)
@@ -88,6 +96,16 @@ impl<C: AscType, T: FromAscObj<C>> FromAscObj<Array<AscPtr<C>>> for Vec<T> {
     }
 }
 
+impl<C: AscType, T: TryFromAscObj<C>> TryFromAscObj<Array<AscPtr<C>>> for Vec<T> {
+    fn try_from_asc_obj<H: AscHeap>(array: Array<AscPtr<C>>, heap: &H) -> Result<Self, Error> {
+        array
+            .to_vec(heap)
+            .into_iter()
+            .map(|x| heap.try_asc_get(x))
+            .collect()
+    }
+}
+
 impl<K: AscType, V: AscType, T: FromAscObj<K>, U: FromAscObj<V>> FromAscObj<AscTypedMapEntry<K, V>>
     for (T, U)
 {
@@ -107,6 +125,58 @@ impl<'a, 'b, K: AscType, V: AscType, T: ToAscObj<K>, U: ToAscObj<V>>
     }
 }
 
+impl<V: AscValue> ToAscObj<AscWrapped<V>> for AscWrapped<V> {
+    fn to_asc_obj<H: AscHeap>(&self, _heap: &mut H) -> AscWrapped<V> {
+        *self
+    }
+}
+
+impl<V, VAsc, E, EAsc> ToAscObj<AscResult<AscPtr<VAsc>, AscPtr<EAsc>>> for Result<V, E>
+where
+    V: ToAscObj<VAsc>,
+    VAsc: AscType,
+    E: ToAscObj<EAsc>,
+    EAsc: AscType,
+{
+    fn to_asc_obj<H: AscHeap>(&self, heap: &mut H) -> AscResult<AscPtr<VAsc>, AscPtr<EAsc>> {
+        match self {
+            Ok(value) => {
+                let inner = heap.asc_new(value);
+                AscResult {
+                    value: heap.asc_new(&AscWrapped { inner }),
+                    error: AscPtr::null(),
+                }
+            }
+            Err(e) => {
+                let inner = heap.asc_new(e);
+                AscResult {
+                    value: AscPtr::null(),
+                    error: heap.asc_new(&AscWrapped { inner }),
+                }
+            }
+        }
+    }
+}
+
+impl<V, VAsc, E, EAsc> FromAscObj<AscResult<AscPtr<VAsc>, AscPtr<EAsc>>> for Result<V, E>
+where
+    V: FromAscObj<VAsc>,
+    VAsc: AscType,
+    E: FromAscObj<EAsc>,
+    EAsc: AscType,
+{
+    fn from_asc_obj<H: AscHeap>(
+        asc_result: AscResult<AscPtr<VAsc>, AscPtr<EAsc>>,
+        heap: &H,
+    ) -> Self {
+        if asc_result.value.is_null() {
+            Err(heap.asc_get(asc_result.error.read_ptr(heap).inner))
+        } else {
+            Ok(heap.asc_get(asc_result.value.read_ptr(heap).inner))
+        }
+    }
+}
+
 impl<K: AscType, V: AscType, T: FromAscObj<K> + Hash + Eq, U: FromAscObj<V>>
     FromAscObj<AscTypedMap<K, V>> for HashMap<T, U>
 {
@@ -10,6 +10,12 @@ declare namespace bigInt {
     function times(x: BigInt, y: BigInt): BigInt
     function dividedBy(x: BigInt, y: BigInt): BigInt
     function mod(x: BigInt, y: BigInt): BigInt
+    function bitOr(x: BigInt, y: BigInt): BigInt
+    function bitAnd(x: BigInt, y: BigInt): BigInt
+    function leftShift(x: BigInt, bits: u8): BigInt
+    function rightShift(x: BigInt, bits: u8): BigInt
+    function sqrt(x: BigInt): BigInt
+    function compare(x: BigInt, y: BigInt): i32
 }
 
 export function plus(x: BigInt, y: BigInt): BigInt {
@@ -31,3 +37,27 @@ export function dividedBy(x: BigInt, y: BigInt): BigInt {
 export function mod(x: BigInt, y: BigInt): BigInt {
     return bigInt.mod(x, y)
 }
+
+export function bitOr(x: BigInt, y: BigInt): BigInt {
+    return bigInt.bitOr(x, y)
+}
+
+export function bitAnd(x: BigInt, y: BigInt): BigInt {
+    return bigInt.bitAnd(x, y)
+}
+
+export function leftShift(x: BigInt, bits: u8): BigInt {
+    return bigInt.leftShift(x, bits)
+}
+
+export function rightShift(x: BigInt, bits: u8): BigInt {
+    return bigInt.rightShift(x, bits)
+}
+
+export function sqrt(x: BigInt): BigInt {
+    return bigInt.sqrt(x)
+}
+
+export function compare(x: BigInt, y: BigInt): i32 {
+    return bigInt.compare(x, y)
+}
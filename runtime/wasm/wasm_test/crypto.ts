@@ -4,8 +4,23 @@ export { memory };
 
 declare namespace crypto {
     function keccak256(input: Uint8Array): Uint8Array
+    function sha256(input: Uint8Array): Uint8Array
+    function ripemd160(input: Uint8Array): Uint8Array
+    function secp256k1Recover(hash: Uint8Array, v: i32, r: Uint8Array, s: Uint8Array): Uint8Array | null
 }
 
 export function hash(input: Uint8Array): Uint8Array {
     return crypto.keccak256(input)
 }
+
+export function sha256(input: Uint8Array): Uint8Array {
+    return crypto.sha256(input)
+}
+
+export function ripemd160(input: Uint8Array): Uint8Array {
+    return crypto.ripemd160(input)
+}
+
+export function secp256k1Recover(hash: Uint8Array, v: i32, r: Uint8Array, s: Uint8Array): Uint8Array | null {
+    return crypto.secp256k1Recover(hash, v, r, s)
+}
@@ -2,10 +2,39 @@ import "allocator/arena";
 
 export { memory };
 
+// Sequence of 20 `u8`s.
+type Address = Uint8Array;
+
+// A `TypedMap<string, Value>`, like an entity; its entries are only passed
+// through to the host.
+class DataSourceContext {
+    entries: Array<u32>
+}
+
 declare namespace dataSource {
     function create(name: string, params: Array<string>): void
+    function createWithContext(name: string, params: Array<string>, context: DataSourceContext): void
+    function address(): Address | null
+    function network(): string
+    function context(): DataSourceContext
 }
 
 export function dataSourceCreate(name: string, params: Array<string>): void {
     dataSource.create(name, params)
 }
+
+export function dataSourceCreateWithContext(name: string, params: Array<string>, context: DataSourceContext): void {
+    dataSource.createWithContext(name, params, context)
+}
+
+export function dataSourceAddress(): Address | null {
+    return dataSource.address()
+}
+
+export function dataSourceNetwork(): string {
+    return dataSource.network()
+}
+
+export function dataSourceContext(): DataSourceContext {
+    return dataSource.context()
+}
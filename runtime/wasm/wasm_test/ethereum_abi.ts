@@ -0,0 +1,40 @@
+import "allocator/arena";
+
+export { memory };
+
+// Sequences of `u8`s.
+type Bytes = Uint8Array;
+
+enum TokenKind {
+    ADDRESS = 0,
+    FIXED_BYTES = 1,
+    BYTES = 2,
+    INT = 3,
+    UINT = 4,
+    BOOL = 5,
+    STRING = 6,
+    FIXED_ARRAY = 7,
+    ARRAY = 8,
+    TUPLE = 9,
+}
+
+// Big enough to fit any pointer or native this.data.
+type Payload = u64
+
+export class Token {
+    kind: TokenKind
+    data: Payload
+}
+
+declare namespace ethereum {
+    function encode(token: Token, type: String): Bytes | null
+    function decode(types: String, data: Bytes): Token | null
+}
+
+export function encode(token: Token, type: String): Bytes | null {
+    return ethereum.encode(token, type)
+}
+
+export function decode(types: String, data: Bytes): Token | null {
+    return ethereum.decode(types, data)
+}
@@ -6,8 +6,28 @@ declare namespace typeConversion {
     function bytesToString(bytes: Uint8Array): string
 }
 
+class Wrapped<T> {
+    inner: T
+}
+
+class Result<V, E> {
+    _value: Wrapped<V> | null
+    _error: Wrapped<E> | null
+}
+
+// Stand-in for `JSONValue`; the test only looks at the pointer
+class JSONValue {
+    kind: u32
+    data: u64
+}
+
 declare namespace ipfs {
     function cat(hash: String): Uint8Array
+    function try_cat(hash: String): Result<Uint8Array, string>
+}
+
+declare namespace json {
+    function try_fromBytes(data: Uint8Array): Result<JSONValue, string>
 }
 
 export function ipfsCatString(hash: string): string {
@@ -17,3 +37,11 @@ export function ipfsCatString(hash: string): string {
 export function ipfsCat(hash: string): Uint8Array {
     return ipfs.cat(hash)
 }
+
+export function ipfsTryCat(hash: string): Result<Uint8Array, string> {
+    return ipfs.try_cat(hash)
+}
+
+export function jsonTryFromBytes(data: Uint8Array): Result<JSONValue, string> {
+    return json.try_fromBytes(data)
+}
@@ -472,6 +472,8 @@ mod tests {
             schema: schema.clone(),
             data_sources: vec![],
             templates: vec![],
+            pruning: None,
+            graft: None,
         };
 
         let graphql_runner = Arc::new(TestGraphQlRunner);
@@ -545,6 +547,8 @@ mod tests {
             schema: schema.clone(),
             data_sources: vec![],
             templates: vec![],
+            pruning: None,
+            graft: None,
         };
         let graphql_runner = Arc::new(TestGraphQlRunner);
         let store = Arc::new(MockStore::new(vec![(id.clone(), schema)]));
@@ -88,6 +88,8 @@ mod test {
             schema: schema.clone(),
             data_sources: vec![],
             templates: vec![],
+            pruning: None,
+            graft: None,
         };
 
         let store = Arc::new(MockStore::new(vec![(id, schema)]));
@@ -1,5 +1,5 @@
-use graphql_parser::{query as q, query::Name, schema as s, schema::ObjectType};
-use std::collections::{BTreeMap, HashMap};
+use graphql_parser::{query as q, schema as s};
+use std::collections::HashMap;
 use std::str::FromStr;
 
 use graph::data::graphql::{TryFromValue, ValueList, ValueMap};
@@ -300,7 +300,8 @@ where
         field_definition: &s::Field,
         object_type: ObjectOrInterface<'_>,
         arguments: &HashMap<&q::Name, q::Value>,
-        _types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        _schema: &Schema,
+        _block: BlockNumber,
         _max_first: u32,
     ) -> Result<q::Value, QueryExecutionError> {
         match (parent, object_type.name(), field.as_str()) {
@@ -341,7 +342,8 @@ where
         field_definition: &s::Field,
         object_type: ObjectOrInterface<'_>,
         _arguments: &HashMap<&q::Name, q::Value>,
-        _types_for_interface: &BTreeMap<Name, Vec<ObjectType>>,
+        _schema: &Schema,
+        _block: BlockNumber,
     ) -> Result<q::Value, QueryExecutionError> {
         match (parent, object_type.name(), field.name.as_str()) {
             (Some(status), "EthereumBlock", "chainHeadBlock") => Ok(status
@@ -15,6 +15,7 @@ use std::collections::BTreeMap;
 use std::env;
 use std::io;
 use std::net::{Ipv4Addr, SocketAddrV4};
+use std::path::PathBuf;
 
 lazy_static! {
     static ref EXTERNAL_HTTP_BASE_URL: Option<String> = env::var_os("EXTERNAL_HTTP_BASE_URL")
@@ -27,6 +28,9 @@ const JSON_RPC_DEPLOY_ERROR: i64 = 0;
 const JSON_RPC_REMOVE_ERROR: i64 = 1;
 const JSON_RPC_CREATE_ERROR: i64 = 2;
 const JSON_RPC_REASSIGN_ERROR: i64 = 3;
+const JSON_RPC_SET_PRUNING_ERROR: i64 = 4;
+const JSON_RPC_EXPORT_ERROR: i64 = 5;
+const JSON_RPC_IMPORT_ERROR: i64 = 6;
 
 #[derive(Debug, Deserialize)]
 struct SubgraphCreateParams {
@@ -49,6 +53,25 @@ struct SubgraphRemoveParams {
 struct SubgraphReassignParams {
     ipfs_hash: SubgraphDeploymentId,
     node_id: NodeId,
+    shard: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphSetPruningParams {
+    ipfs_hash: SubgraphDeploymentId,
+    enabled: bool,
+    retention_block: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphExportParams {
+    ipfs_hash: SubgraphDeploymentId,
+    directory: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphImportParams {
+    directory: PathBuf,
 }
 
 pub struct JsonRpcServer<R> {
@@ -152,7 +175,7 @@ where
 
         Box::new(
             self.registrar
-                .reassign_subgraph(params.ipfs_hash, params.node_id)
+                .reassign_subgraph(params.ipfs_hash, params.node_id, params.shard)
                 .map_err(move |e| {
                     if let SubgraphRegistrarError::Unknown(e) = e {
                         error!(logger, "subgraph_reassignment failed: {}", e);
@@ -165,6 +188,89 @@ where
                 .flatten(),
         )
     }
+
+    /// Handler for the `subgraph_set_pruning` endpoint.
+    fn set_pruning_handler(
+        &self,
+        params: SubgraphSetPruningParams,
+    ) -> Box<dyn Future<Item = Value, Error = jsonrpc_core::Error> + Send> {
+        let logger = self.logger.clone();
+
+        info!(logger, "Received subgraph_set_pruning request"; "params" => format!("{:?}", params));
+
+        let pruning = if params.enabled {
+            Some(Pruning {
+                retention_block: params.retention_block,
+            })
+        } else {
+            None
+        };
+
+        Box::new(
+            self.registrar
+                .set_subgraph_pruning(params.ipfs_hash, pruning)
+                .map_err(move |e| {
+                    if let SubgraphRegistrarError::Unknown(e) = e {
+                        error!(logger, "subgraph_set_pruning failed: {}", e);
+                        json_rpc_error(JSON_RPC_SET_PRUNING_ERROR, "internal error".to_owned())
+                    } else {
+                        json_rpc_error(JSON_RPC_SET_PRUNING_ERROR, e.to_string())
+                    }
+                })
+                .map(|_| Ok(Value::Null))
+                .flatten(),
+        )
+    }
+
+    /// Handler for the `subgraph_export` endpoint.
+    fn export_handler(
+        &self,
+        params: SubgraphExportParams,
+    ) -> Box<dyn Future<Item = Value, Error = jsonrpc_core::Error> + Send> {
+        let logger = self.logger.clone();
+
+        info!(logger, "Received subgraph_export request"; "params" => format!("{:?}", params));
+
+        Box::new(
+            self.registrar
+                .export_subgraph(params.ipfs_hash, params.directory)
+                .map_err(move |e| {
+                    if let SubgraphRegistrarError::Unknown(e) = e {
+                        error!(logger, "subgraph_export failed: {}", e);
+                        json_rpc_error(JSON_RPC_EXPORT_ERROR, "internal error".to_owned())
+                    } else {
+                        json_rpc_error(JSON_RPC_EXPORT_ERROR, e.to_string())
+                    }
+                })
+                .map(|_| Ok(Value::Null))
+                .flatten(),
+        )
+    }
+
+    /// Handler for the `subgraph_import` endpoint.
+    fn import_handler(
+        &self,
+        params: SubgraphImportParams,
+    ) -> Box<dyn Future<Item = Value, Error = jsonrpc_core::Error> + Send> {
+        let logger = self.logger.clone();
+
+        info!(logger, "Received subgraph_import request"; "params" => format!("{:?}", params));
+
+        Box::new(
+            self.registrar
+                .import_subgraph(params.directory)
+                .map_err(move |e| {
+                    if let SubgraphRegistrarError::Unknown(e) = e {
+                        error!(logger, "subgraph_import failed: {}", e);
+                        json_rpc_error(JSON_RPC_IMPORT_ERROR, "internal error".to_owned())
+                    } else {
+                        json_rpc_error(JSON_RPC_IMPORT_ERROR, e.to_string())
+                    }
+                })
+                .map(|id| Ok(serde_json::to_value(id).expect("invalid subgraph deployment ID")))
+                .flatten(),
+        )
+    }
 }
 
 impl<R> JsonRpcServerTrait<R> for JsonRpcServer<R>
@@ -236,6 +342,33 @@ where
                 .and_then(move |params| me.reassign_handler(params))
         });
 
+        let me = arc_self.clone();
+        handler.add_method("subgraph_set_pruning", move |params: Params| {
+            let me = me.clone();
+            params
+                .parse()
+                .into_future()
+                .and_then(move |params| me.set_pruning_handler(params))
+        });
+
+        let me = arc_self.clone();
+        handler.add_method("subgraph_export", move |params: Params| {
+            let me = me.clone();
+            params
+                .parse()
+                .into_future()
+                .and_then(move |params| me.export_handler(params))
+        });
+
+        let me = arc_self.clone();
+        handler.add_method("subgraph_import", move |params: Params| {
+            let me = me.clone();
+            params
+                .parse()
+                .into_future()
+                .and_then(move |params| me.import_handler(params))
+        });
+
         ServerBuilder::new(handler)
             // Enable REST API:
             // POST /<method>/<param1>/<param2>
@@ -0,0 +1,116 @@
+use hyper;
+use hyper::header::{ACCESS_CONTROL_ALLOW_ORIGIN, CONTENT_TYPE};
+use hyper::service::service_fn_ok;
+use hyper::{Body, Method, Response, Server, StatusCode};
+use std::error::Error;
+use std::fmt;
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+use graph::prelude::prometheus::{Encoder, TextEncoder};
+use graph::prelude::{MetricsServer as MetricsServerTrait, *};
+
+/// Errors that may occur when starting the server.
+#[derive(Debug)]
+pub enum PrometheusMetricsServeError {
+    BindError(hyper::Error),
+}
+
+impl Error for PrometheusMetricsServeError {
+    fn description(&self) -> &str {
+        "Failed to start the server"
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        None
+    }
+}
+
+impl fmt::Display for PrometheusMetricsServeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PrometheusMetricsServeError::BindError(e) => {
+                write!(f, "Failed to bind metrics server: {}", e)
+            }
+        }
+    }
+}
+
+impl From<hyper::Error> for PrometheusMetricsServeError {
+    fn from(err: hyper::Error) -> Self {
+        PrometheusMetricsServeError::BindError(err)
+    }
+}
+
+/// A server that exposes the metrics of a Prometheus registry at `/metrics`.
+pub struct PrometheusMetricsServer {
+    logger: Logger,
+    registry: Arc<Registry>,
+}
+
+impl PrometheusMetricsServer {
+    /// Creates a new metrics server.
+    pub fn new(logger_factory: &LoggerFactory, registry: Arc<Registry>) -> Self {
+        PrometheusMetricsServer {
+            logger: logger_factory.component_logger("MetricsServer", None),
+            registry,
+        }
+    }
+}
+
+impl MetricsServerTrait for PrometheusMetricsServer {
+    type ServeError = PrometheusMetricsServeError;
+
+    fn serve(
+        &mut self,
+        port: u16,
+    ) -> Result<Box<dyn Future<Item = (), Error = ()> + Send>, Self::ServeError> {
+        let logger = self.logger.clone();
+
+        info!(
+            logger,
+            "Starting metrics server at: http://localhost:{}/metrics", port
+        );
+
+        let addr = SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), port);
+
+        let registry = self.registry.clone();
+        let logger_for_service = self.logger.clone();
+        let new_service = move || {
+            let registry = registry.clone();
+            let logger = logger_for_service.clone();
+            service_fn_ok(move |req| match (req.method(), req.uri().path()) {
+                (&Method::GET, "/metrics") => {
+                    let metric_families = registry.gather();
+                    let encoder = TextEncoder::new();
+                    let mut buffer = vec![];
+                    match encoder.encode(&metric_families, &mut buffer) {
+                        Ok(()) => Response::builder()
+                            .status(StatusCode::OK)
+                            .header(CONTENT_TYPE, encoder.format_type())
+                            .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+                            .body(Body::from(buffer))
+                            .unwrap(),
+                        Err(e) => {
+                            error!(logger, "Failed to encode metrics"; "error" => e.to_string());
+                            Response::builder()
+                                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                                .body(Body::from("Failed to encode metrics"))
+                                .unwrap()
+                        }
+                    }
+                }
+                _ => Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(Body::from("Not found"))
+                    .unwrap(),
+            })
+        };
+
+        // Create a task to run the server and handle HTTP requests
+        let task = Server::try_bind(&addr.into())?
+            .serve(new_service)
+            .map_err(move |e| error!(logger, "Server error"; "error" => format!("{}", e)));
+
+        Ok(Box::new(task))
+    }
+}
@@ -0,0 +1,70 @@
+use hyper::{Body, Client, Request, StatusCode};
+use std::time::{Duration, Instant};
+
+use graph::prelude::*;
+use graph_server_metrics::PrometheusMetricsServer;
+
+use crate::tokio::timer::Delay;
+
+fn serve_metrics(port: u16) -> Arc<Registry> {
+    let logger = Logger::root(slog::Discard, o!());
+    let logger_factory = LoggerFactory::new(logger, None);
+    let registry = Arc::new(Registry::new());
+    let mut server = PrometheusMetricsServer::new(&logger_factory, registry.clone());
+    let metrics_server = server.serve(port).expect("Failed to start metrics server");
+    tokio::spawn(metrics_server.fuse());
+    registry
+}
+
+#[test]
+fn serves_registered_metrics() {
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime
+        .block_on(futures::lazy(|| {
+            let registry = serve_metrics(8040);
+            let counter = Counter::new("test_requests", "Number of test requests").unwrap();
+            registry.register(Box::new(counter.clone())).unwrap();
+            counter.inc_by(7.0);
+
+            // Give some time for the server to start.
+            Delay::new(Instant::now() + Duration::from_secs(2))
+                .map_err(|e| panic!("failed to start server: {:?}", e))
+                .and_then(|()| {
+                    let request = Request::get("http://localhost:8040/metrics")
+                        .body(Body::empty())
+                        .unwrap();
+                    Client::new().request(request)
+                })
+                .and_then(|response| {
+                    assert_eq!(response.status(), StatusCode::OK);
+                    response.into_body().concat2()
+                })
+                .map(|body| {
+                    let body = String::from_utf8(body.to_vec()).unwrap();
+                    assert!(body.contains("# TYPE test_requests counter"));
+                    assert!(body.contains("test_requests 7"));
+                })
+        }))
+        .unwrap()
+}
+
+#[test]
+fn rejects_other_paths() {
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime
+        .block_on(futures::lazy(|| {
+            serve_metrics(8041);
+
+            // Give some time for the server to start.
+            Delay::new(Instant::now() + Duration::from_secs(2))
+                .map_err(|e| panic!("failed to start server: {:?}", e))
+                .and_then(|()| {
+                    let request = Request::get("http://localhost:8041/")
+                        .body(Body::empty())
+                        .unwrap();
+                    Client::new().request(request)
+                })
+                .map(|response| assert_eq!(response.status(), StatusCode::NOT_FOUND))
+        }))
+        .unwrap()
+}
@@ -0,0 +1 @@
+drop table if exists eth_call_cache;
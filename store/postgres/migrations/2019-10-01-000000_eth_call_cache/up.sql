@@ -0,0 +1,9 @@
+-- Cache for the return values of eth_call. The id is the keccak256 hash
+-- of the contract address, the encoded call and the block hash, since the
+-- encoded call can be too large to be indexed directly
+create table if not exists eth_call_cache (
+    id               bytea primary key,
+    return_value     bytea not null,
+    contract_address bytea not null,
+    block_number     int not null
+);
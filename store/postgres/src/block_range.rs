@@ -11,12 +11,7 @@ use graph::prelude::{EventSource, HistoryEvent};
 
 use crate::relational::BLOCK_RANGE;
 
-/// The type we use for block numbers. This has to be a signed integer type
-/// since Postgres does not support unsigned integer types. But 2G ought to
-/// be enough for everybody
-pub type BlockNumber = i32;
-
-pub const BLOCK_NUMBER_MAX: BlockNumber = std::i32::MAX;
+pub use graph::prelude::{BlockNumber, BLOCK_NUMBER_MAX};
 
 /// The range of blocks for which an entity is valid. We need this struct
 /// to bind ranges into Diesel queries.
@@ -32,3 +32,12 @@ table! {
         name -> Varchar,
     }
 }
+
+table! {
+    eth_call_cache(id) {
+        id -> Binary,
+        return_value -> Binary,
+        contract_address -> Binary,
+        block_number -> Integer,
+    }
+}
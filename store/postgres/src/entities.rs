@@ -24,31 +24,35 @@ use diesel::debug_query;
 use diesel::deserialize::QueryableByName;
 use diesel::dsl::{any, sql};
 use diesel::pg::{Pg, PgConnection};
+use diesel::query_builder::{AstPass, QueryFragment, QueryId};
 use diesel::r2d2::{ConnectionManager, PooledConnection};
-use diesel::sql_types::{Integer, Jsonb, Nullable, Text};
+use diesel::sql_types::{Array, BigInt, Bool, Integer, Jsonb, Nullable, Text};
 use diesel::BoolExpressionMethods;
 use diesel::Connection as _;
 use diesel::ExpressionMethods;
+use diesel::{BoxableExpression, QueryResult};
 use diesel::{OptionalExtension, QueryDsl, RunQueryDsl};
+use graphql_parser::schema as s;
 use inflector::cases::snakecase::to_snake_case;
 use std::cell::RefCell;
 use std::collections::hash_map::DefaultHasher;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
 use std::hash::{Hash, Hasher};
+use std::str::FromStr;
 use std::time::Instant;
 
-use graph::data::schema::Schema as SubgraphSchema;
+use graph::data::schema::{Schema as SubgraphSchema, SCHEMA_TYPE_NAME};
 use graph::data::subgraph::schema::SUBGRAPHS_ID;
 use graph::prelude::{
-    debug, format_err, info, serde_json, warn, AttributeIndexDefinition, Entity, EntityChange,
-    EntityChangeOperation, EntityFilter, EntityKey, Error, EthereumBlockPointer, EventSource,
-    HistoryEvent, Logger, QueryExecutionError, StoreError, StoreEvent, SubgraphDeploymentId,
-    TransactionAbortError, ValueType,
+    debug, format_err, info, serde_json, warn, AttributeIndexDefinition, Entity, EntityAggregate,
+    EntityChange, EntityChangeOperation, EntityFilter, EntityKey, EntityLink, Error,
+    EthereumBlockPointer, EventSource, HistoryEvent, Logger, QueryExecutionError, StoreError,
+    StoreEvent, SubgraphDeploymentId, TransactionAbortError, Value, ValueType,
 };
 
-use crate::block_range::{block_number, BlockNumber};
-use crate::filter::build_filter;
+use crate::block_range::{block_number, BlockNumber, BLOCK_NUMBER_MAX};
+use crate::filter::{build_cursor_filter, build_filter};
 use crate::functions::set_config;
 use crate::jsonb::PgJsonbExpressionMethods as _;
 use crate::relational::{IdType, Layout};
@@ -211,6 +215,7 @@ pub(crate) type EntityColumn<ST> = diesel_dynamic_schema::Column<EntityTable, St
 impl EntitySource for EntityTable {}
 
 use public::deployment_schemas;
+pub(crate) use public::DeploymentSchemaVersion;
 
 /// Information about the database schema that stores the entities for a
 /// subgraph. The schemas are versioned by subgraph, which makes it possible
@@ -274,6 +279,25 @@ pub(crate) enum Storage {
     Relational(Layout),
 }
 
+/// Helper struct for reading the rows of an entity table as JSON
+#[derive(QueryableByName)]
+struct JsonRow {
+    #[sql_type = "Jsonb"]
+    data: serde_json::Value,
+}
+
+/// Helper struct for reading the children of a batch of parents from the
+/// entities table
+#[derive(QueryableByName)]
+struct JsonChildRow {
+    #[sql_type = "Text"]
+    parent_id: String,
+    #[sql_type = "Text"]
+    entity: String,
+    #[sql_type = "Jsonb"]
+    data: serde_json::Value,
+}
+
 /// Helper struct to support a custom query for entity history
 #[derive(Debug, Queryable)]
 struct RawHistory {
@@ -304,12 +328,25 @@ impl QueryableByName<Pg> for RawHistory {
 /// layout to actual database tables. Instances of this struct must not be
 /// cached across transactions as there is no mechanism in place to notify
 /// other index nodes that a subgraph has been migrated
+///
+/// The connection `conn` is to the primary database, which holds the
+/// metadata. The entities of a deployment can live in a different database
+/// shard; a connection that is used to work with such a deployment also has
+/// a connection to that shard, and all access to the entities of the
+/// deployment goes through it
 pub(crate) struct Connection<'a> {
     pub conn: PooledConnection<ConnectionManager<PgConnection>>,
+    shard: Option<ShardConnection>,
     cache: RefCell<HashMap<SubgraphDeploymentId, Storage>>,
     store: &'a Store,
 }
 
+/// A connection to the shard that holds the entities of `subgraph`
+struct ShardConnection {
+    subgraph: SubgraphDeploymentId,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+}
+
 impl<'a> Connection<'a> {
     pub(crate) fn new(
         conn: PooledConnection<ConnectionManager<PgConnection>>,
@@ -317,11 +354,100 @@ impl<'a> Connection<'a> {
     ) -> Connection<'a> {
         Connection {
             conn,
+            shard: None,
             cache: RefCell::new(HashMap::new()),
             store,
         }
     }
 
+    /// Use `conn` to access the entities of `subgraph`, which are stored in
+    /// the shard `conn` is connected to
+    pub(crate) fn with_shard(
+        self,
+        subgraph: SubgraphDeploymentId,
+        conn: PooledConnection<ConnectionManager<PgConnection>>,
+    ) -> Connection<'a> {
+        Connection {
+            shard: Some(ShardConnection { subgraph, conn }),
+            ..self
+        }
+    }
+
+    /// Return true if the entities of `subgraph` are not stored in the
+    /// primary database
+    pub(crate) fn is_sharded(&self, subgraph: &SubgraphDeploymentId) -> bool {
+        match &self.shard {
+            Some(shard) => &shard.subgraph == subgraph,
+            None => false,
+        }
+    }
+
+    /// Return the connection to the database that holds the entities of
+    /// `subgraph`
+    pub(crate) fn data_conn(&self, subgraph: &SubgraphDeploymentId) -> &PgConnection {
+        match &self.shard {
+            Some(shard) if &shard.subgraph == subgraph => &shard.conn,
+            _ => &self.conn,
+        }
+    }
+
+    /// Run `f` inside a transaction on the primary database and, if this
+    /// connection has one, inside a transaction on the shard. The shard
+    /// transaction is committed first; if committing the primary transaction
+    /// fails after that, the entities of a deployment can be ahead of its
+    /// block pointer, which `revert_to_block_ptr` fixes when the deployment
+    /// is started. Changes that remove entity versions must use
+    /// `removal_transaction` instead
+    pub(crate) fn transaction<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+        E: From<diesel::result::Error>,
+    {
+        match &self.shard {
+            Some(shard) => self.conn.transaction(|| shard.conn.transaction(f)),
+            None => self.conn.transaction(f),
+        }
+    }
+
+    /// Like `transaction`, but the primary transaction is committed first.
+    /// If committing the shard transaction fails after that, the shard still
+    /// has the entity versions that `f` removed, and the entities are again
+    /// only ever ahead of the block pointer. With the opposite order, a
+    /// reverted block could be gone from the shard while the block pointer
+    /// still includes it, and the block would never be processed again
+    pub(crate) fn removal_transaction<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+        E: From<diesel::result::Error>,
+    {
+        match &self.shard {
+            Some(shard) => shard.conn.transaction(|| self.conn.transaction(f)),
+            None => self.conn.transaction(f),
+        }
+    }
+
+    /// Like `transaction`, but the transactions are read-only and use
+    /// repeatable read isolation so that `f` sees a consistent snapshot of
+    /// each database
+    pub(crate) fn snapshot<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+        E: From<diesel::result::Error>,
+    {
+        let primary = self.conn.build_transaction().repeatable_read().read_only();
+        match &self.shard {
+            Some(shard) => primary.run(|| {
+                shard
+                    .conn
+                    .build_transaction()
+                    .repeatable_read()
+                    .read_only()
+                    .run(f)
+            }),
+            None => primary.run(f),
+        }
+    }
+
     /// Return the storage for the subgraph
     fn storage(&self, subgraph: &SubgraphDeploymentId) -> Result<Storage, StoreError> {
         let mut cache = self.cache.borrow_mut();
@@ -329,7 +455,7 @@ impl<'a> Connection<'a> {
         match cache.get(subgraph) {
             Some(storage) => Ok(storage.clone()),
             None => {
-                let storage = Storage::new(&self.conn, subgraph, self.store)?;
+                let storage = Storage::new(self.data_conn(subgraph), subgraph, self.store)?;
                 cache.insert(subgraph.clone(), storage.clone());
                 Ok(storage)
             }
@@ -349,7 +475,7 @@ impl<'a> Connection<'a> {
         Ok(
             diesel::update(dsl::table.filter(dsl::subgraph.eq(subgraph.to_string())))
                 .set(dsl::migrating.eq(false))
-                .execute(&self.conn)
+                .execute(self.data_conn(subgraph))
                 .map(|_| ())?,
         )
     }
@@ -361,9 +487,13 @@ impl<'a> Connection<'a> {
         id: &String,
         block: BlockNumber,
     ) -> Result<Option<Entity>, StoreError> {
+        let conn = self.data_conn(subgraph);
         match self.storage(subgraph)? {
-            Storage::Json(json) => json.find(&self.conn, entity, id),
-            Storage::Relational(layout) => layout.find(&self.conn, entity, id, block),
+            Storage::Json(json) => {
+                json_at_latest_block(subgraph, block)?;
+                json.find(conn, entity, id)
+            }
+            Storage::Relational(layout) => layout.find(conn, entity, id, block),
         }
     }
 
@@ -376,11 +506,83 @@ impl<'a> Connection<'a> {
         first: Option<u32>,
         skip: u32,
         block: BlockNumber,
+        after: Option<String>,
     ) -> Result<Vec<Entity>, QueryExecutionError> {
+        let conn = self.data_conn(subgraph);
+        match self.storage(subgraph)? {
+            Storage::Json(json) => {
+                json_at_latest_block(subgraph, block)?;
+                json.query(conn, entity_types, filter, order, first, skip, after)
+            }
+            Storage::Relational(layout) => {
+                layout.query(conn, entity_types, filter, order, first, skip, block, after)
+            }
+        }
+    }
+
+    /// Find the children of all `parent_ids` with a single query
+    pub(crate) fn query_children(
+        &self,
+        subgraph: &SubgraphDeploymentId,
+        entity_types: Vec<String>,
+        filter: Option<EntityFilter>,
+        order: Option<(String, ValueType, &str, &str)>,
+        first: Option<u32>,
+        skip: u32,
+        block: BlockNumber,
+        after: Option<String>,
+        parent_ids: Vec<String>,
+        link: EntityLink,
+    ) -> Result<HashMap<String, Vec<Entity>>, QueryExecutionError> {
+        let conn = self.data_conn(subgraph);
+        match self.storage(subgraph)? {
+            Storage::Json(json) => {
+                json_at_latest_block(subgraph, block)?;
+                json.query_children(
+                    conn,
+                    entity_types,
+                    filter,
+                    order,
+                    first,
+                    skip,
+                    after,
+                    parent_ids,
+                    link,
+                )
+            }
+            Storage::Relational(layout) => layout.query_children(
+                conn,
+                entity_types,
+                filter,
+                order,
+                first,
+                skip,
+                block,
+                after,
+                parent_ids,
+                link,
+            ),
+        }
+    }
+
+    /// Compute `aggregates` over the entities matching `filter` in the
+    /// database
+    pub(crate) fn aggregate(
+        &self,
+        subgraph: &SubgraphDeploymentId,
+        entity_types: Vec<String>,
+        filter: Option<EntityFilter>,
+        block: BlockNumber,
+        aggregates: Vec<EntityAggregate>,
+    ) -> Result<Vec<Value>, QueryExecutionError> {
+        let conn = self.data_conn(subgraph);
         match self.storage(subgraph)? {
-            Storage::Json(json) => json.query(&self.conn, entity_types, filter, order, first, skip),
+            Storage::Json(json) => {
+                json_at_latest_block(subgraph, block)?;
+                json.aggregate(conn, entity_types, filter, &aggregates)
+            }
             Storage::Relational(layout) => {
-                layout.query(&self.conn, entity_types, filter, order, first, skip, block)
+                layout.aggregate(conn, entity_types, filter, block, &aggregates)
             }
         }
     }
@@ -391,11 +593,10 @@ impl<'a> Connection<'a> {
         entity_id: &String,
         entities: Vec<&String>,
     ) -> Result<Option<String>, StoreError> {
+        let conn = self.data_conn(subgraph);
         match self.storage(subgraph)? {
-            Storage::Json(json) => json.conflicting_entity(&self.conn, entity_id, entities),
-            Storage::Relational(layout) => {
-                layout.conflicting_entity(&self.conn, entity_id, entities)
-            }
+            Storage::Json(json) => json.conflicting_entity(conn, entity_id, entities),
+            Storage::Relational(layout) => layout.conflicting_entity(conn, entity_id, entities),
         }
     }
 
@@ -405,12 +606,11 @@ impl<'a> Connection<'a> {
         entity: &Entity,
         history_event: Option<&HistoryEvent>,
     ) -> Result<(), StoreError> {
+        let conn = self.data_conn(&key.subgraph_id);
         match self.storage(&key.subgraph_id)? {
-            Storage::Json(json) => json
-                .insert(&self.conn, &key, entity, history_event)
-                .map(|_| ()),
+            Storage::Json(json) => json.insert(conn, &key, entity, history_event).map(|_| ()),
             Storage::Relational(layout) => {
-                layout.insert(&self.conn, key, entity, block_number(&history_event))
+                layout.insert(conn, key, entity, block_number(&history_event))
             }
         }
     }
@@ -421,12 +621,11 @@ impl<'a> Connection<'a> {
         entity: &Entity,
         history_event: Option<&HistoryEvent>,
     ) -> Result<(), StoreError> {
+        let conn = self.data_conn(&key.subgraph_id);
         match self.storage(&key.subgraph_id)? {
-            Storage::Json(json) => json
-                .update(&self.conn, key, entity, history_event)
-                .map(|_| ()),
+            Storage::Json(json) => json.update(conn, key, entity, history_event).map(|_| ()),
             Storage::Relational(layout) => {
-                layout.update(&self.conn, key, entity, block_number(&history_event))
+                layout.update(conn, key, entity, block_number(&history_event))
             }
         }
     }
@@ -450,11 +649,10 @@ impl<'a> Connection<'a> {
         key: &EntityKey,
         history_event: Option<&HistoryEvent>,
     ) -> Result<usize, StoreError> {
+        let conn = self.data_conn(&key.subgraph_id);
         match self.storage(&key.subgraph_id)? {
-            Storage::Json(json) => json.delete(&self.conn, key, history_event),
-            Storage::Relational(layout) => {
-                layout.delete(&self.conn, key, block_number(&history_event))
-            }
+            Storage::Json(json) => json.delete(conn, key, history_event),
+            Storage::Relational(layout) => layout.delete(conn, key, block_number(&history_event)),
         }
     }
 
@@ -462,8 +660,9 @@ impl<'a> Connection<'a> {
         &self,
         index: &AttributeIndexDefinition,
     ) -> Result<usize, StoreError> {
+        let conn = self.data_conn(&index.subgraph_id);
         match self.storage(&index.subgraph_id)? {
-            Storage::Json(json) => json.build_attribute_index(&self.conn, index),
+            Storage::Json(json) => json.build_attribute_index(conn, index),
             Storage::Relational(_) => Ok(1),
         }
     }
@@ -474,11 +673,12 @@ impl<'a> Connection<'a> {
         block_ptr: &EthereumBlockPointer,
     ) -> Result<(StoreEvent, i32), StoreError> {
         // Revert the block in the subgraph itself
+        let conn = self.data_conn(subgraph);
         let (event, count) = match self.storage(subgraph)? {
-            Storage::Json(json) => json.revert_block(&self.conn, block_ptr.hash_hex())?,
+            Storage::Json(json) => json.revert_block(conn, block_ptr.hash_hex())?,
             Storage::Relational(layout) => {
                 let block = block_ptr.number.try_into().unwrap();
-                layout.revert_block(&self.conn, block)?
+                layout.revert_block(conn, block)?
             }
         };
         // Revert the meta data changes that correspond to this subgraph.
@@ -498,6 +698,401 @@ impl<'a> Connection<'a> {
         }
     }
 
+    /// Undo all changes to the entities of `subgraph` that were made for
+    /// blocks after `block_ptr`. Entities in a shard are written in a
+    /// different transaction than the block pointer of their deployment,
+    /// and can therefore be ahead of it
+    pub(crate) fn revert_to_block_ptr(
+        &self,
+        subgraph: &SubgraphDeploymentId,
+        block_ptr: &EthereumBlockPointer,
+    ) -> Result<(StoreEvent, i32), StoreError> {
+        match self.storage(subgraph)? {
+            // JSONB storage is only used in the primary database, where
+            // entities and block pointer are written together
+            Storage::Json(_) => Ok((StoreEvent::new(vec![]), 0)),
+            Storage::Relational(layout) => {
+                let block: BlockNumber = block_ptr.number.try_into().unwrap();
+                layout.revert_block(self.data_conn(subgraph), block + 1)
+            }
+        }
+    }
+
+    /// Remove entity versions of `subgraph` that are not visible at `block`
+    /// or any later block. Return the number of versions removed
+    pub(crate) fn prune(
+        &self,
+        subgraph: &SubgraphDeploymentId,
+        block: BlockNumber,
+    ) -> Result<usize, StoreError> {
+        let conn = self.data_conn(subgraph);
+        match self.storage(subgraph)? {
+            Storage::Json(json) => json.prune(conn, block),
+            Storage::Relational(layout) => layout.prune(conn, block),
+        }
+    }
+
+    /// Reclaim the space freed up by pruning `subgraph`. This must not be
+    /// called inside a transaction
+    pub(crate) fn vacuum(&self, subgraph: &SubgraphDeploymentId) -> Result<(), StoreError> {
+        let conn = self.data_conn(subgraph);
+        match self.storage(subgraph)? {
+            Storage::Json(json) => json.vacuum(conn),
+            Storage::Relational(layout) => layout.vacuum(conn),
+        }
+    }
+
+    /// Copy all entities of `base` that are visible at `block` into the
+    /// subgraph of `history_event`, whose schema is `schema`. Only the entity
+    /// types and attributes that both schemas have are copied. Since JSONB
+    /// storage only keeps the current version of each entity, a `base` with
+    /// JSONB storage can only be copied as of its latest block `base_head`.
+    /// The entities of `base` are read through `base_conn`, since they can be
+    /// stored in a different shard. If both subgraphs use relational storage
+    /// and are stored in the `same_database`, the entities are copied with
+    /// one `insert .. select` per table; otherwise, they are read and
+    /// inserted page by page. Returns the number of entities that were
+    /// copied
+    pub(crate) fn copy_entities(
+        &self,
+        base_conn: &Connection,
+        base: &SubgraphDeploymentId,
+        base_head: BlockNumber,
+        schema: &SubgraphSchema,
+        block: BlockNumber,
+        history_event: &HistoryEvent,
+        same_database: bool,
+    ) -> Result<i32, StoreError> {
+        const PAGE_SIZE: u32 = 1000;
+
+        let base_storage = base_conn.storage(base)?;
+        // JSONB storage only has the latest version of each entity
+        let block = if let Storage::Json(_) = base_storage {
+            if block != base_head {
+                return Err(format_err!(
+                    "graft base `{}` uses JSONB storage and can only be grafted \
+                     at its latest block {}, not at block {}",
+                    base,
+                    base_head,
+                    block
+                )
+                .into());
+            }
+            BLOCK_NUMBER_MAX
+        } else {
+            block
+        };
+
+        let subgraph = &history_event.subgraph;
+        if let (true, Storage::Relational(base_layout), Storage::Relational(layout)) =
+            (same_database, &base_storage, self.storage(subgraph)?)
+        {
+            let count = layout.copy_from(self.data_conn(subgraph), base_layout, block)?;
+            return Ok(count as i32);
+        }
+
+        let object_types = |document: &s::Document| -> HashMap<String, HashSet<String>> {
+            document
+                .definitions
+                .iter()
+                .filter_map(|definition| match definition {
+                    s::Definition::TypeDefinition(s::TypeDefinition::Object(object_type))
+                        if object_type.name != SCHEMA_TYPE_NAME =>
+                    {
+                        Some(object_type)
+                    }
+                    _ => None,
+                })
+                .map(|object_type| {
+                    let attributes = object_type
+                        .fields
+                        .iter()
+                        .filter(|field| {
+                            !field
+                                .directives
+                                .iter()
+                                .any(|directive| directive.name == "derivedFrom")
+                        })
+                        .map(|field| field.name.clone())
+                        .collect();
+                    (object_type.name.clone(), attributes)
+                })
+                .collect()
+        };
+        let base_types = object_types(&self.store.raw_subgraph_schema(base)?.document);
+
+        let mut count = 0;
+        for (entity_type, attributes) in object_types(&schema.document) {
+            if !base_types.contains_key(&entity_type) {
+                continue;
+            }
+
+            // Page through the entities by id so that we never have to
+            // hold all entities of a type in memory
+            let mut last_id: Option<String> = None;
+            loop {
+                let filter = last_id
+                    .take()
+                    .map(|id| EntityFilter::GreaterThan("id".to_owned(), Value::String(id)));
+                let page = base_conn.query(
+                    base,
+                    vec![entity_type.clone()],
+                    filter,
+                    Some(("id".to_owned(), ValueType::String, "", "ASC")),
+                    Some(PAGE_SIZE),
+                    0,
+                    block,
+                    None,
+                )?;
+                let done = page.len() < PAGE_SIZE as usize;
+
+                for mut entity in page {
+                    entity.retain(|attribute, _| attributes.contains(attribute));
+                    let id = entity.id()?;
+                    let key = EntityKey {
+                        subgraph_id: history_event.subgraph.clone(),
+                        entity_type: entity_type.clone(),
+                        entity_id: id.clone(),
+                    };
+                    self.insert(&key, &entity, Some(history_event))?;
+                    last_id = Some(id);
+                    count += 1;
+                }
+                if done {
+                    break;
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// Return true if the entities of `subgraph` are stored in a relational
+    /// schema, and false if they are stored as JSONB. This only looks at
+    /// `deployment_schemas` and does not need the schema of the subgraph
+    pub(crate) fn uses_relational_schema(
+        &self,
+        subgraph: &SubgraphDeploymentId,
+    ) -> Result<bool, StoreError> {
+        use public::DeploymentSchemaVersion as V;
+
+        let schema = find_schema(self.data_conn(subgraph), subgraph)?
+            .ok_or_else(|| StoreError::Unknown(format_err!("unknown subgraph {}", subgraph)))?;
+        Ok(match schema.version {
+            V::Split => false,
+            V::Relational => true,
+        })
+    }
+
+    /// Return the storage scheme of `subgraph` and the names of the tables
+    /// that hold its entities. For JSONB storage, these include the table
+    /// with the history of the entities
+    pub(crate) fn entity_tables(
+        &self,
+        subgraph: &SubgraphDeploymentId,
+    ) -> Result<(DeploymentSchemaVersion, Vec<String>), StoreError> {
+        Ok(match self.storage(subgraph)? {
+            Storage::Json(_) => (
+                DeploymentSchemaVersion::Split,
+                vec!["entities".to_owned(), "entity_history".to_owned()],
+            ),
+            Storage::Relational(layout) => {
+                let mut tables: Vec<_> = layout
+                    .tables
+                    .values()
+                    .map(|table| table.name.to_string())
+                    .collect();
+                tables.sort();
+                (DeploymentSchemaVersion::Relational, tables)
+            }
+        })
+    }
+
+    /// Lock the entity tables of `subgraph` against writes until the end of
+    /// the current transaction
+    pub(crate) fn lock_entity_tables(
+        &self,
+        subgraph: &SubgraphDeploymentId,
+    ) -> Result<(), StoreError> {
+        let conn = self.data_conn(subgraph);
+        let (_, tables) = self.entity_tables(subgraph)?;
+        for table in tables {
+            let schema = self.entity_table_schema(subgraph, &table)?;
+            conn.batch_execute(&format!(
+                "lock table \"{}\".\"{}\" in share mode",
+                schema, table
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Call `f` with every row of the entity table `table` of `subgraph`,
+    /// converted to JSON. For relational storage, the rows contain all
+    /// versions of each entity together with their block ranges. The rows
+    /// of the `entity_history` of JSONB storage contain the hash of the
+    /// block that made the change instead of the id of its event, since
+    /// events are local to a database. The rows are read through a cursor,
+    /// and this function must therefore be called inside a transaction.
+    /// Returns the number of rows
+    pub(crate) fn export_table<F>(
+        &self,
+        subgraph: &SubgraphDeploymentId,
+        table: &str,
+        mut f: F,
+    ) -> Result<usize, StoreError>
+    where
+        F: FnMut(serde_json::Value) -> Result<(), StoreError>,
+    {
+        const PAGE_SIZE: usize = 10000;
+
+        let conn = self.data_conn(subgraph);
+        let schema = self.entity_table_schema(subgraph, table)?;
+        let query = match (self.storage(subgraph)?, table) {
+            (Storage::Json(_), "entity_history") => format!(
+                "select (to_jsonb(h.*) - 'id' - 'event_id') \
+                        || jsonb_build_object('source', m.source) as data \
+                   from \"{}\".entity_history h, event_meta_data m \
+                  where m.id = h.event_id \
+                  order by h.id",
+                schema
+            ),
+            _ => format!(
+                "select to_jsonb(t.*) as data from \"{}\".\"{}\" t",
+                schema, table
+            ),
+        };
+        conn.batch_execute(&format!(
+            "declare export_rows no scroll cursor for {}",
+            query
+        ))?;
+
+        let mut count = 0;
+        loop {
+            let rows = diesel::sql_query(format!("fetch forward {} from export_rows", PAGE_SIZE))
+                .load::<JsonRow>(conn)?;
+            if rows.is_empty() {
+                break;
+            }
+            count += rows.len();
+            for row in rows {
+                f(row.data)?;
+            }
+        }
+        conn.batch_execute("close export_rows")?;
+        Ok(count)
+    }
+
+    /// Insert `rows` as produced by `export_table` into the entity table
+    /// `table` of `subgraph`. For JSONB storage, the rows of the entity
+    /// history are attached to new events for the blocks that made the
+    /// changes. Returns the number of rows inserted
+    pub(crate) fn import_table<I>(
+        &self,
+        subgraph: &SubgraphDeploymentId,
+        table: &str,
+        rows: I,
+    ) -> Result<usize, StoreError>
+    where
+        I: IntoIterator<Item = Result<serde_json::Value, StoreError>>,
+    {
+        const PAGE_SIZE: usize = 1000;
+
+        let conn = self.data_conn(subgraph);
+        let schema = self.entity_table_schema(subgraph, table)?;
+        let json = match self.storage(subgraph)? {
+            Storage::Json(_) => true,
+            Storage::Relational(_) => false,
+        };
+        let query = if json && table == "entity_history" {
+            // `db_transaction_id` must be unique; negated values from the
+            // sequence for event ids can not clash with real transactions
+            format!(
+                "with rows as ( \
+                   select r.value as data, r.ord \
+                     from jsonb_array_elements($1) with ordinality as r(value, ord)), \
+                 events as ( \
+                   insert into event_meta_data(db_transaction_id, db_transaction_time, source) \
+                   select -nextval(pg_get_serial_sequence('event_meta_data', 'id')), \
+                          statement_timestamp(), s.source \
+                     from (select distinct data->>'source' as source from rows) s \
+                   returning id, source) \
+                 insert into \"{schema}\".entity_history \
+                        (event_id, entity, entity_id, data_before, reversion, op_id) \
+                 select e.id, h.entity, h.entity_id, h.data_before, h.reversion, h.op_id \
+                   from rows r \
+                        cross join lateral \
+                          jsonb_populate_record(null::\"{schema}\".entity_history, r.data) h \
+                        join events e on e.source is not distinct from r.data->>'source' \
+                  order by r.ord",
+                schema = schema
+            )
+        } else {
+            format!(
+                "insert into \"{schema}\".\"{table}\" \
+                 select * from jsonb_populate_recordset(null::\"{schema}\".\"{table}\", $1)",
+                schema = schema,
+                table = table
+            )
+        };
+        let insert = |page: Vec<serde_json::Value>| {
+            diesel::sql_query(query.as_str())
+                .bind::<Jsonb, _>(serde_json::Value::Array(page))
+                .execute(conn)
+        };
+
+        // The triggers of JSONB storage would record the rows in the entity
+        // history as if a block had inserted them; the history is imported
+        // separately
+        let triggers = json && table == "entities";
+        if triggers {
+            conn.batch_execute(&format!(
+                "alter table \"{}\".entities disable trigger user",
+                schema
+            ))?;
+        }
+
+        let mut count = 0;
+        let mut page = Vec::with_capacity(PAGE_SIZE);
+        for row in rows {
+            page.push(row?);
+            if page.len() == PAGE_SIZE {
+                count += insert(std::mem::replace(&mut page, Vec::with_capacity(PAGE_SIZE)))?;
+            }
+        }
+        if !page.is_empty() {
+            count += insert(page)?;
+        }
+
+        if triggers {
+            conn.batch_execute(&format!(
+                "alter table \"{}\".entities enable trigger user",
+                schema
+            ))?;
+        }
+        Ok(count)
+    }
+
+    /// Return the database schema of `subgraph` after checking that `table`
+    /// is one of the tables that hold its entities
+    fn entity_table_schema(
+        &self,
+        subgraph: &SubgraphDeploymentId,
+        table: &str,
+    ) -> Result<String, StoreError> {
+        let (_, tables) = self.entity_tables(subgraph)?;
+        if !tables.iter().any(|name| name == table) {
+            return Err(format_err!(
+                "`{}` is not an entity table of subgraph {}",
+                table,
+                subgraph
+            )
+            .into());
+        }
+        Ok(match self.storage(subgraph)? {
+            Storage::Json(json) => json.schema,
+            Storage::Relational(layout) => layout.schema,
+        })
+    }
+
     pub(crate) fn update_entity_count(
         &self,
         subgraph: &SubgraphDeploymentId,
@@ -508,7 +1103,11 @@ impl<'a> Connection<'a> {
         }
 
         let storage = self.storage(&subgraph)?;
-        storage.update_entity_count(&self.conn, subgraph, count)
+        let data_conn = match &self.shard {
+            Some(shard) if &shard.subgraph == subgraph => Some(&*shard.conn),
+            _ => None,
+        };
+        storage.update_entity_count(&self.conn, data_conn, subgraph, count)
     }
 
     pub(crate) fn create_history_event(
@@ -577,10 +1176,11 @@ impl<'a> Connection<'a> {
             return Ok(false);
         }
 
-        let do_migrate = self.conn.transaction(|| -> Result<bool, Error> {
+        let conn = self.data_conn(subgraph);
+        let do_migrate = conn.transaction(|| -> Result<bool, Error> {
             let lock =
                 diesel::sql_query("lock table public.deployment_schemas in exclusive mode nowait")
-                    .execute(&self.conn);
+                    .execute(conn);
             if lock.is_err() {
                 return Ok(false);
             }
@@ -593,7 +1193,7 @@ impl<'a> Connection<'a> {
             let query = diesel::sql_query(query)
                 .bind::<Text, _>(subgraph.to_string())
                 .bind::<Integer, _>(MIGRATION_LIMIT);
-            Ok(query.execute(&self.conn)? > 0)
+            Ok(query.execute(conn)? > 0)
         })?;
 
         if do_migrate {
@@ -618,7 +1218,7 @@ impl<'a> Connection<'a> {
             // the migration
             diesel::update(dsl::table.filter(dsl::subgraph.eq(subgraph.to_string())))
                 .set(dsl::migrating.eq(false))
-                .execute(&self.conn)?;
+                .execute(conn)?;
             result
         } else {
             Ok(false)
@@ -633,13 +1233,14 @@ impl<'a> Connection<'a> {
         logger: &Logger,
         subgraph: &SubgraphDeploymentId,
     ) -> Result<bool, Error> {
-        self.conn.transaction(|| -> Result<bool, Error> {
+        let conn = self.data_conn(subgraph);
+        conn.transaction(|| -> Result<bool, Error> {
             let storage = self.storage(subgraph)?;
             let errmsg = format_err!(
                 "subgraph {} has no entry in deployment_schemas and can not be migrated",
                 subgraph.to_string()
             );
-            let schema = find_schema(&self.conn, &subgraph)?.ok_or(errmsg)?;
+            let schema = find_schema(conn, &subgraph)?.ok_or(errmsg)?;
 
             debug!(
                 logger,
@@ -649,7 +1250,7 @@ impl<'a> Connection<'a> {
                 "state" => format!("{:?}", schema.state)
             );
             let start = Instant::now();
-            let storage = storage.migrate(&self.conn, logger, &schema)?;
+            let storage = storage.migrate(conn, logger, &schema)?;
             let needs_migrating = storage.needs_migrating();
             self.cache.borrow_mut().insert(subgraph.clone(), storage);
             info!(
@@ -665,6 +1266,143 @@ impl<'a> Connection<'a> {
     }
 }
 
+/// How the parents of a `JsonChildrenQuery` are linked to their children;
+/// see `EntityLink`. For `Direct` links, the children of
+/// `parent_ids[i]` include the entity with id `child_ids[i]`
+enum JsonParents {
+    Derived {
+        parent_ids: Vec<String>,
+        attribute: String,
+        list: bool,
+    },
+    Direct {
+        parent_ids: Vec<String>,
+        child_ids: Vec<String>,
+    },
+}
+
+/// A query for the children of a batch of parents in the `entities` table
+/// of a subgraph with JSONB storage. The children of each parent are
+/// numbered in the order of the query, which makes it possible to apply
+/// `skip` and `first` to each parent separately
+struct JsonChildrenQuery<'a> {
+    schema: &'a str,
+    entity_types: Vec<String>,
+    filters: Vec<Box<dyn BoxableExpression<EntityTable, Pg, SqlType = Bool>>>,
+    /// A tuple (attribute, value_type, cast, direction)
+    order: Option<(String, ValueType, &'a str, &'a str)>,
+    skip: i64,
+    /// The position of the last child of each parent to return, i.e.,
+    /// `skip + first`
+    last: Option<i64>,
+    parents: JsonParents,
+}
+
+impl<'a> JsonChildrenQuery<'a> {
+    /// Generate the same sort key that `JsonStorage::query` orders by
+    fn sort_key(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        if let Some((attribute, value_type, cast, direction)) = &self.order {
+            if value_type == &ValueType::String {
+                out.push_sql("left(entities.data -> ");
+                out.push_bind_param::<Text, _>(attribute)?;
+                out.push_sql(" ->> 'data', ");
+                out.push_sql(&STRING_PREFIX_SIZE.to_string());
+                out.push_sql(")");
+            } else {
+                out.push_sql("(entities.data -> ");
+                out.push_bind_param::<Text, _>(attribute)?;
+                out.push_sql(" ->> 'data')");
+                out.push_sql(cast);
+            }
+            out.push_sql(" ");
+            out.push_sql(direction);
+            out.push_sql(" NULLS LAST, ");
+        }
+        out.push_sql("entities.id");
+        Ok(())
+    }
+}
+
+impl<'a> QueryFragment<Pg> for JsonChildrenQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        // Generate
+        //   select c.parent_id, c.entity, c.data
+        //     from (select p.parent_id, entities.entity, entities.data,
+        //                  row_number() over (partition by p.parent_id
+        //                                     order by sort_key, entities.id) as pos
+        //             from unnest($parent_ids) as p(parent_id)
+        //                  join schema.entities on link
+        //            where entities.entity = any($entity_types)
+        //              and filter) c
+        //    where c.pos > $skip and c.pos <= $last
+        //    order by c.parent_id, c.pos
+        // where `link` checks that the child's `attribute` holds the id of
+        // the parent, or, for `Direct` links, also unnests the `child_ids`
+        // and checks that the child has that id
+        out.push_sql("select c.parent_id, c.entity, c.data\n  from (");
+        out.push_sql("select p.parent_id, entities.entity, entities.data,");
+        out.push_sql(" row_number() over (partition by p.parent_id order by ");
+        self.sort_key(out.reborrow())?;
+        out.push_sql(") as pos\n          from ");
+        match &self.parents {
+            JsonParents::Derived {
+                parent_ids,
+                attribute,
+                list,
+            } => {
+                out.push_sql("unnest(");
+                out.push_bind_param::<Array<Text>, _>(parent_ids)?;
+                out.push_sql(") as p(parent_id) join ");
+                out.push_identifier(self.schema)?;
+                out.push_sql(".entities on entities.data -> ");
+                out.push_bind_param::<Text, _>(attribute)?;
+                if *list {
+                    out.push_sql(
+                        " -> 'data' @> jsonb_build_array(jsonb_build_object('data', p.parent_id))",
+                    );
+                } else {
+                    out.push_sql(" ->> 'data' = p.parent_id");
+                }
+            }
+            JsonParents::Direct {
+                parent_ids,
+                child_ids,
+            } => {
+                out.push_sql("unnest(");
+                out.push_bind_param::<Array<Text>, _>(parent_ids)?;
+                out.push_sql(", ");
+                out.push_bind_param::<Array<Text>, _>(child_ids)?;
+                out.push_sql(") as p(parent_id, child_id) join ");
+                out.push_identifier(self.schema)?;
+                out.push_sql(".entities on entities.id = p.child_id");
+            }
+        }
+        out.push_sql("\n         where entities.entity = any(");
+        out.push_bind_param::<Array<Text>, _>(&self.entity_types)?;
+        out.push_sql(")");
+        for filter in &self.filters {
+            out.push_sql(" and ");
+            filter.walk_ast(out.reborrow())?;
+        }
+        out.push_sql(") c\n where c.pos > ");
+        out.push_bind_param::<BigInt, _>(&self.skip)?;
+        if let Some(last) = &self.last {
+            out.push_sql(" and c.pos <= ");
+            out.push_bind_param::<BigInt, _>(last)?;
+        }
+        out.push_sql("\n order by c.parent_id, c.pos");
+        Ok(())
+    }
+}
+
+impl<'a> QueryId for JsonChildrenQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
 // Find the database schema for `subgraph`. If no explicit schema exists,
 // return `None`.
 fn find_schema(
@@ -677,6 +1415,22 @@ fn find_schema(
         .optional()?)
 }
 
+// JSONB storage only keeps the latest version of each entity, and can
+// therefore not answer queries for any other block
+fn json_at_latest_block(
+    subgraph: &SubgraphDeploymentId,
+    block: BlockNumber,
+) -> Result<(), QueryExecutionError> {
+    if block == BLOCK_NUMBER_MAX {
+        Ok(())
+    } else {
+        Err(QueryExecutionError::NotSupported(format!(
+            "subgraph `{}` uses JSONB storage and can not be queried at block {}",
+            subgraph, block
+        )))
+    }
+}
+
 fn entity_to_json(key: &EntityKey, entity: &Entity) -> Result<serde_json::Value, Error> {
     serde_json::to_value(entity).map_err(|e| {
         format_err!(
@@ -722,21 +1476,31 @@ impl JsonStorage {
         order: Option<(String, ValueType, &str, &str)>,
         first: Option<u32>,
         skip: u32,
+        after: Option<String>,
     ) -> Result<Vec<Entity>, QueryExecutionError> {
         let entities = self.clone();
         let mut query = entities
             .table
-            .filter((&self.entity).eq(any(entity_types)))
+            .filter((&self.entity).eq(any(entity_types.clone())))
             .select((&self.data, &self.entity))
             .into_boxed::<Pg>();
 
         if let Some(filter) = filter {
-            let filter = build_filter(filter).map_err(|e| {
+            let filter = build_filter(filter, &self.schema).map_err(|e| {
                 QueryExecutionError::FilterNotSupportedError(format!("{}", e.value), e.filter)
             })?;
             query = query.filter(filter);
         }
 
+        if let Some(after) = after {
+            query = query.filter(build_cursor_filter(
+                &self.schema,
+                entity_types,
+                order.clone(),
+                after,
+            ));
+        }
+
         if let Some((attribute, value_type, cast, direction)) = order {
             query = match value_type {
                 ValueType::String => query.order(
@@ -786,6 +1550,169 @@ impl JsonStorage {
             .collect()
     }
 
+    /// Find the children of all `parent_ids`; the arguments other than
+    /// `parent_ids` and `link` apply to the children of each parent
+    /// separately, exactly as they do for `query`
+    fn query_children(
+        &self,
+        conn: &PgConnection,
+        entity_types: Vec<String>,
+        filter: Option<EntityFilter>,
+        order: Option<(String, ValueType, &str, &str)>,
+        first: Option<u32>,
+        skip: u32,
+        after: Option<String>,
+        parent_ids: Vec<String>,
+        link: EntityLink,
+    ) -> Result<HashMap<String, Vec<Entity>>, QueryExecutionError> {
+        let mut filters = vec![];
+        if let Some(filter) = filter {
+            filters.push(build_filter(filter, &self.schema).map_err(|e| {
+                QueryExecutionError::FilterNotSupportedError(format!("{}", e.value), e.filter)
+            })?);
+        }
+        if let Some(after) = after {
+            filters.push(build_cursor_filter(
+                &self.schema,
+                entity_types.clone(),
+                order.clone(),
+                after,
+            ));
+        }
+
+        let parents = match link {
+            EntityLink::Derived { attribute, list } => JsonParents::Derived {
+                parent_ids,
+                attribute,
+                list,
+            },
+            EntityLink::Direct { child_ids } => {
+                let mut pairs = (vec![], vec![]);
+                for (parent_id, child_ids) in parent_ids.into_iter().zip(child_ids) {
+                    for child_id in child_ids {
+                        pairs.0.push(parent_id.clone());
+                        pairs.1.push(child_id);
+                    }
+                }
+                JsonParents::Direct {
+                    parent_ids: pairs.0,
+                    child_ids: pairs.1,
+                }
+            }
+        };
+
+        let query = JsonChildrenQuery {
+            schema: &self.schema,
+            entity_types,
+            filters,
+            order,
+            skip: skip as i64,
+            last: first.map(|first| skip as i64 + first as i64),
+            parents,
+        };
+        let query_debug_info = debug_query(&query).to_string();
+
+        let rows = conn.query_by_name::<_, JsonChildRow>(&query).map_err(|e| {
+            QueryExecutionError::ResolveEntitiesError(format!(
+                "{}, query = {:?}",
+                e, query_debug_info
+            ))
+        })?;
+
+        // The query returns the children of each parent in order
+        let mut children = HashMap::new();
+        for row in rows {
+            let entity = entity_from_json(row.data, &row.entity)?;
+            children
+                .entry(row.parent_id)
+                .or_insert_with(Vec::new)
+                .push(entity);
+        }
+        Ok(children)
+    }
+
+    fn aggregate(
+        &self,
+        conn: &PgConnection,
+        entity_types: Vec<String>,
+        filter: Option<EntityFilter>,
+        aggregates: &[EntityAggregate],
+    ) -> Result<Vec<Value>, QueryExecutionError> {
+        // Compute
+        //   array[count(*)::text,
+        //         sum((data->'a1'->>'data')::numeric)::text,
+        //         min(data->'a1'->>'type'), ...]
+        // i.e., the text of each aggregate followed by the type of the
+        // aggregated attribute, which is the same for all entities
+        let mut select = String::from("array[");
+        for (i, aggregate) in aggregates.iter().enumerate() {
+            if i > 0 {
+                select.push_str(", ");
+            }
+            let function = match aggregate {
+                EntityAggregate::Count => {
+                    select.push_str("count(*)::text");
+                    continue;
+                }
+                EntityAggregate::Sum(_) => "sum",
+                EntityAggregate::Min(_) => "min",
+                EntityAggregate::Max(_) => "max",
+                EntityAggregate::Avg(_) => "avg",
+            };
+            let attribute = aggregate
+                .attribute()
+                .expect("aggregates other than count have an attribute")
+                .replace('\'', "''");
+            select.push_str(&format!(
+                "{}((data->'{}'->>'data')::numeric)::text, min(data->'{}'->>'type')",
+                function, attribute, attribute
+            ));
+        }
+        select.push_str("]::text[]");
+
+        let entities = self.clone();
+        let mut query = entities
+            .table
+            .filter((&self.entity).eq(any(entity_types)))
+            .select(sql::<Array<Nullable<Text>>>(&select))
+            .into_boxed::<Pg>();
+
+        if let Some(filter) = filter {
+            let filter = build_filter(filter, &self.schema).map_err(|e| {
+                QueryExecutionError::FilterNotSupportedError(format!("{}", e.value), e.filter)
+            })?;
+            query = query.filter(filter);
+        }
+
+        let query_debug_info = debug_query(&query).to_string();
+
+        let texts = query.get_result::<Vec<Option<String>>>(conn).map_err(|e| {
+            QueryExecutionError::ResolveEntitiesError(format!(
+                "{}, query = {:?}",
+                e, query_debug_info
+            ))
+        })?;
+        let mut texts = texts.into_iter();
+        aggregates
+            .iter()
+            .map(|aggregate| {
+                let text = texts.next().and_then(|text| text);
+                let value_type = match aggregate {
+                    EntityAggregate::Count => None,
+                    _ => texts.next().and_then(|value_type| value_type),
+                };
+                match text {
+                    Some(text) => value_type
+                        .map(|value_type| ValueType::from_str(&value_type))
+                        .transpose()
+                        .and_then(|value_type| aggregate.value_from_text(&text, value_type))
+                        .map_err(|e| QueryExecutionError::ResolveEntitiesError(e.to_string())),
+                    None => Ok(Value::Null),
+                }
+            })
+            .collect()
+    }
+
     fn insert(
         &self,
         conn: &PgConnection,
@@ -898,7 +1825,7 @@ impl JsonStorage {
 
         match guard {
             Some(filter) => {
-                let filter = build_filter(filter).map_err(|e| {
+                let filter = build_filter(filter, &self.schema).map_err(|e| {
                     TransactionAbortError::Other(format!(
                         "invalid filter '{}' for value '{}'",
                         e.filter, e.value
@@ -1085,6 +2012,32 @@ impl JsonStorage {
             .map(|(changes, count)| (StoreEvent::new(changes), count))
     }
 
+    /// Remove the history records for all blocks before `block`; those
+    /// blocks can not be reverted anymore. We can only tell the number of
+    /// blocks that are in `ethereum_blocks`, which the block stream makes
+    /// sure of for all blocks it processes
+    fn prune(&self, conn: &PgConnection, block: BlockNumber) -> Result<usize, StoreError> {
+        let query = format!(
+            "delete from {}.entity_history h
+              using event_meta_data m, ethereum_blocks b
+              where m.id = h.event_id
+                and b.hash = m.source
+                and b.number < $1",
+            self.schema
+        );
+        Ok(diesel::sql_query(query)
+            .bind::<BigInt, _>(block as i64)
+            .execute(conn)?)
+    }
+
+    fn vacuum(&self, conn: &PgConnection) -> Result<(), StoreError> {
+        conn.batch_execute(&format!(
+            "vacuum analyze \"{}\".entity_history",
+            self.schema
+        ))?;
+        Ok(())
+    }
+
     fn build_attribute_index(
         &self,
         conn: &PgConnection,
@@ -1293,13 +2246,28 @@ impl Storage {
 
     /// Adjust the `entityCount` property of the `SubgraphDeployment` for
     /// `subgraph` by `count`. This needs to be performed after the changes
-    /// underlying `count` have been written to the store.
+    /// underlying `count` have been written to the store. If the entities
+    /// of `subgraph` are stored in a shard, `data_conn` is the connection
+    /// to that shard
     pub(crate) fn update_entity_count(
         &self,
         conn: &PgConnection,
+        data_conn: Option<&PgConnection>,
         subgraph: &SubgraphDeploymentId,
         count: i32,
     ) -> Result<(), StoreError> {
+        #[derive(QueryableByName)]
+        struct Recount {
+            #[sql_type = "Bool"]
+            recount: bool,
+        }
+
+        #[derive(QueryableByName)]
+        struct Count {
+            #[sql_type = "BigInt"]
+            count: i64,
+        }
+
         let count_query = match self {
             Storage::Json(json) => json.count_query.as_str(),
             Storage::Relational(layout) => layout.count_query.as_str(),
@@ -1319,6 +2287,35 @@ impl Storage {
         // argument, the query to count entities. In all other cases,
         // `coalesce` does not evaluate its second argument
         let current_count = "(nullif(data->'entityCount'->>'data', '00'))::numeric";
+
+        // The entities in a shard can not be counted in the same query that
+        // updates the metadata. We therefore check first whether we need to
+        // recount, and if so count them in the shard
+        let count_query = match data_conn {
+            None => format!("({})", count_query),
+            Some(data_conn) => {
+                let recount = diesel::sql_query(format!(
+                    "select {} is null as recount from subgraphs.entities \
+                     where entity='SubgraphDeployment' and id = $1",
+                    current_count
+                ))
+                .bind::<Text, _>(subgraph.to_string())
+                .get_result::<Recount>(conn)?
+                .recount;
+                if recount {
+                    diesel::sql_query(format!(
+                        "select coalesce(({}), 0)::bigint as count",
+                        count_query
+                    ))
+                    .get_result::<Count>(data_conn)?
+                    .count
+                    .to_string()
+                } else {
+                    // Not evaluated by `coalesce` below
+                    "null".to_owned()
+                }
+            }
+        };
         let query = format!(
             "
             update subgraphs.entities
@@ -1326,7 +2323,7 @@ impl Storage {
                                   {{ \"data\": \"%s\",
                                     \"type\": \"BigInt\"}}}}',
                                   coalesce({current_count} + $1,
-                                           ({count_query}))))::jsonb
+                                           {count_query})))::jsonb
             where entity='SubgraphDeployment'
               and id = $2
             ",
@@ -1400,12 +2397,21 @@ pub(crate) fn create_schema(
         Some(_) => self::public::DeploymentSchemaVersion::Relational,
         None => self::public::DeploymentSchemaVersion::Split,
     };
+    create_schema_with_version(conn, schema, scheme)
+}
 
+/// Create the database schema for `schema` using the storage scheme
+/// `scheme`. There must not be an entry for the subgraph yet
+pub(crate) fn create_schema_with_version(
+    conn: &PgConnection,
+    schema: &SubgraphSchema,
+    scheme: DeploymentSchemaVersion,
+) -> Result<(), StoreError> {
     // Create a schema for the deployment.
     let schemas: Vec<String> = diesel::insert_into(deployment_schemas::table)
         .values((
             deployment_schemas::subgraph.eq(schema.id.to_string()),
-            deployment_schemas::version.eq(scheme),
+            deployment_schemas::version.eq(scheme.clone()),
         ))
         .returning(deployment_schemas::name)
         .get_results(conn)?;
@@ -1416,15 +2422,15 @@ pub(crate) fn create_schema(
     let query = format!("create schema {}", schema_name);
     conn.batch_execute(&*query)?;
 
-    match std::env::var_os("RELATIONAL_SCHEMA") {
-        Some(_) => Layout::create_relational_schema(
+    match scheme {
+        DeploymentSchemaVersion::Relational => Layout::create_relational_schema(
             conn,
             &schema_name,
             schema.id.clone(),
             &schema.document,
         )
         .map(|_| ()),
-        None => create_split_schema(conn, &schema_name),
+        DeploymentSchemaVersion::Split => create_split_schema(conn, &schema_name),
     }
 }
 
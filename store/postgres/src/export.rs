@@ -0,0 +1,359 @@
+//! Export a deployment into a directory and import it from there, e.g., to
+//! move a deployment between databases. The directory is self-describing
+//! and contains
+//!
+//! - `deployment.json`: the format version, the deployment id, its storage
+//!   scheme, the block it has been indexed up to, and its entity tables
+//! - `schema.graphql`: the GraphQL schema of the deployment
+//! - `metadata.jsonl`: the entities in the subgraph of subgraphs that
+//!   describe the deployment, i.e., the deployment itself, its manifest and
+//!   everything the manifest refers to, and its dynamic data sources
+//! - `entities/<table>.jsonl`: one file per entity table with one row of
+//!   the table per line. For relational storage, these are all versions of
+//!   each entity together with their block ranges; for JSONB storage, the
+//!   current entities and their history, with the hash of the block that
+//!   made each change
+//!
+//! Names of subgraphs, their versions, and assignments of the deployment to
+//! nodes are not part of the export
+use graphql_parser::schema as s;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use graph::data::subgraph::schema::*;
+use graph::prelude::{
+    format_err, info, serde_json, Deserialize, Entity, EntityFilter, Error, EthereumBlockPointer,
+    Logger, MetadataOperation, Serialize, StoreError, SubgraphDeploymentId, Value,
+};
+
+use crate::entities::{self as e, DeploymentSchemaVersion};
+use crate::store::Store;
+
+/// The version of the export format; change this whenever the format
+/// changes in a way that older versions can not import
+const FORMAT_VERSION: u32 = 1;
+
+const DEPLOYMENT_FILE: &str = "deployment.json";
+const SCHEMA_FILE: &str = "schema.graphql";
+const METADATA_FILE: &str = "metadata.jsonl";
+const ENTITIES_DIR: &str = "entities";
+
+/// The contents of `deployment.json`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeploymentInfo {
+    format_version: u32,
+    id: SubgraphDeploymentId,
+    /// Either `relational` or `jsonb`
+    storage: String,
+    block_ptr: EthereumBlockPointer,
+    tables: Vec<String>,
+}
+
+/// One line of `metadata.jsonl`
+#[derive(Debug, Serialize, Deserialize)]
+struct MetadataEntity {
+    entity: String,
+    id: String,
+    data: Entity,
+}
+
+fn io_error(path: &Path, e: impl std::fmt::Display) -> StoreError {
+    format_err!("{}: {}", path.display(), e).into()
+}
+
+fn write_jsonl<T: Serialize>(
+    out: &mut impl Write,
+    path: &Path,
+    value: &T,
+) -> Result<(), StoreError> {
+    serde_json::to_writer(&mut *out, value).map_err(|e| io_error(path, e))?;
+    out.write_all(b"\n").map_err(|e| io_error(path, e))
+}
+
+/// Read a file with one JSON value per line
+fn read_jsonl<T>(path: &Path) -> Result<impl Iterator<Item = Result<T, StoreError>>, StoreError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let file = File::open(path).map_err(|e| io_error(path, e))?;
+    let path = path.to_owned();
+    Ok(BufReader::new(file)
+        .lines()
+        .filter(|line| line.as_ref().map_or(true, |line| !line.is_empty()))
+        .map(move |line| {
+            let line = line.map_err(|e| io_error(&path, e))?;
+            serde_json::from_str(&line).map_err(|e| io_error(&path, e))
+        }))
+}
+
+fn block_ptr(deployment: &Entity) -> Result<EthereumBlockPointer, Error> {
+    let hash = deployment
+        .get("latestEthereumBlockHash")
+        .and_then(|hash| hash.clone().as_string())
+        .ok_or_else(|| format_err!("SubgraphDeployment is missing latestEthereumBlockHash"))?
+        .parse()
+        .map_err(|e| format_err!("latestEthereumBlockHash: {}", e))?;
+    let number = deployment
+        .get("latestEthereumBlockNumber")
+        .and_then(|number| number.clone().as_bigint())
+        .ok_or_else(|| format_err!("SubgraphDeployment is missing latestEthereumBlockNumber"))?
+        .to_u64();
+    Ok(EthereumBlockPointer { hash, number })
+}
+
+/// Find the fields of each type in the schema of the subgraph of subgraphs
+/// that refer to other entities, and the types of the entities they refer to
+fn references(document: &s::Document) -> HashMap<String, Vec<(String, String)>> {
+    fn named_type(field_type: &s::Type) -> &str {
+        match field_type {
+            s::Type::NamedType(name) => name.as_str(),
+            s::Type::ListType(inner) | s::Type::NonNullType(inner) => named_type(inner),
+        }
+    }
+
+    let object_types: Vec<_> = document
+        .definitions
+        .iter()
+        .filter_map(|definition| match definition {
+            s::Definition::TypeDefinition(s::TypeDefinition::Object(object_type)) => {
+                Some(object_type)
+            }
+            _ => None,
+        })
+        .collect();
+    let names: HashSet<_> = object_types
+        .iter()
+        .map(|object_type| object_type.name.as_str())
+        .collect();
+
+    object_types
+        .iter()
+        .map(|object_type| {
+            let fields = object_type
+                .fields
+                .iter()
+                .filter(|field| {
+                    !field
+                        .directives
+                        .iter()
+                        .any(|directive| directive.name == "derivedFrom")
+                })
+                .filter(|field| names.contains(named_type(&field.field_type)))
+                .map(|field| (field.name.clone(), named_type(&field.field_type).to_owned()))
+                .collect();
+            (object_type.name.clone(), fields)
+        })
+        .collect()
+}
+
+/// Collect the deployment entity for `subgraph`, its dynamic data sources,
+/// and all entities they refer to, directly or indirectly
+fn metadata_entities(
+    store: &Store,
+    econn: &e::Connection,
+    subgraph: &SubgraphDeploymentId,
+) -> Result<Vec<MetadataEntity>, StoreError> {
+    let references = references(&store.raw_subgraph_schema(&*SUBGRAPHS_ID)?.document);
+
+    let mut queue = vec![(
+        SubgraphDeploymentEntity::TYPENAME.to_owned(),
+        subgraph.to_string(),
+    )];
+    let query = DynamicEthereumContractDataSourceEntity::query()
+        .filter(EntityFilter::new_equal("deployment", subgraph.to_string()));
+    for data_source in store.execute_query(econn, query)? {
+        queue.push((
+            DynamicEthereumContractDataSourceEntity::TYPENAME.to_owned(),
+            data_source.id()?,
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    let mut entities = vec![];
+    while let Some((entity_type, id)) = queue.pop() {
+        if !seen.insert((entity_type.clone(), id.clone())) {
+            continue;
+        }
+        let mut data = store
+            .get_entity(econn, &*SUBGRAPHS_ID, &entity_type, &id)?
+            .ok_or_else(|| format_err!("metadata entity {}[{}] does not exist", entity_type, id))?;
+        data.remove("__typename");
+
+        for (field, target_type) in references.get(&entity_type).into_iter().flatten() {
+            match data.get(field) {
+                Some(Value::String(target)) => queue.push((target_type.clone(), target.clone())),
+                Some(Value::List(targets)) => {
+                    for target in targets {
+                        if let Value::String(target) = target {
+                            queue.push((target_type.clone(), target.clone()));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        entities.push(MetadataEntity {
+            entity: entity_type,
+            id,
+            data,
+        });
+    }
+    entities.sort_by(|a, b| (&a.entity, &a.id).cmp(&(&b.entity, &b.id)));
+    Ok(entities)
+}
+
+/// Write the deployment `subgraph` into the directory `dir`, which must not
+/// exist yet. All data is read from one snapshot of each database so that
+/// the deployment can keep indexing during the export
+pub(crate) fn export(
+    store: &Store,
+    logger: &Logger,
+    subgraph: &SubgraphDeploymentId,
+    dir: &Path,
+) -> Result<(), StoreError> {
+    let econn = store.get_deployment_conn(subgraph)?;
+
+    econn.snapshot(|| -> Result<(), StoreError> {
+        let metadata = metadata_entities(store, &econn, subgraph)?;
+        let deployment = metadata
+            .iter()
+            .find(|entity| entity.entity == SubgraphDeploymentEntity::TYPENAME)
+            .ok_or_else(|| format_err!("deployment {} does not exist", subgraph))?;
+        let manifest = metadata
+            .iter()
+            .find(|entity| entity.entity == SubgraphManifestEntity::TYPENAME)
+            .ok_or_else(|| format_err!("deployment {} has no manifest", subgraph))?;
+        let raw_schema = match manifest.data.get("schema") {
+            Some(Value::String(raw_schema)) => raw_schema.clone(),
+            _ => return Err(format_err!("deployment {} has no schema", subgraph).into()),
+        };
+
+        let (version, tables) = econn.entity_tables(subgraph)?;
+        let storage = match version {
+            DeploymentSchemaVersion::Split => "jsonb",
+            DeploymentSchemaVersion::Relational => "relational",
+        };
+        let info = DeploymentInfo {
+            format_version: FORMAT_VERSION,
+            id: subgraph.clone(),
+            storage: storage.to_owned(),
+            block_ptr: block_ptr(&deployment.data)?,
+            tables,
+        };
+
+        let entities_dir = dir.join(ENTITIES_DIR);
+        fs::create_dir(dir).map_err(|e| io_error(dir, e))?;
+        fs::create_dir(&entities_dir).map_err(|e| io_error(&entities_dir, e))?;
+
+        let path = dir.join(DEPLOYMENT_FILE);
+        let file = File::create(&path).map_err(|e| io_error(&path, e))?;
+        serde_json::to_writer_pretty(file, &info).map_err(|e| io_error(&path, e))?;
+
+        let path = dir.join(SCHEMA_FILE);
+        fs::write(&path, raw_schema).map_err(|e| io_error(&path, e))?;
+
+        let path = dir.join(METADATA_FILE);
+        let mut out = BufWriter::new(File::create(&path).map_err(|e| io_error(&path, e))?);
+        for entity in &metadata {
+            write_jsonl(&mut out, &path, entity)?;
+        }
+        out.flush().map_err(|e| io_error(&path, e))?;
+
+        for table in &info.tables {
+            let path = entities_dir.join(format!("{}.jsonl", table));
+            let mut out = BufWriter::new(File::create(&path).map_err(|e| io_error(&path, e))?);
+            let count =
+                econn.export_table(subgraph, table, |row| write_jsonl(&mut out, &path, &row))?;
+            out.flush().map_err(|e| io_error(&path, e))?;
+            info!(logger, "Exported entity table";
+                              "subgraph" => subgraph.to_string(),
+                              "table" => table,
+                              "rows" => count);
+        }
+        Ok(())
+    })
+}
+
+/// Recreate the deployment that `export` wrote into `dir`. The deployment
+/// must not exist yet. Returns the id of the deployment
+pub(crate) fn import(
+    store: &Store,
+    logger: &Logger,
+    dir: &Path,
+) -> Result<SubgraphDeploymentId, StoreError> {
+    let path = dir.join(DEPLOYMENT_FILE);
+    let file = File::open(&path).map_err(|e| io_error(&path, e))?;
+    let info: DeploymentInfo =
+        serde_json::from_reader(BufReader::new(file)).map_err(|e| io_error(&path, e))?;
+    if info.format_version != FORMAT_VERSION {
+        return Err(format_err!(
+            "{} has format version {} but only version {} is supported",
+            dir.display(),
+            info.format_version,
+            FORMAT_VERSION
+        )
+        .into());
+    }
+    let version = match info.storage.as_str() {
+        "jsonb" => DeploymentSchemaVersion::Split,
+        "relational" => DeploymentSchemaVersion::Relational,
+        _ => {
+            return Err(format_err!(
+                "{} uses unknown storage scheme `{}`",
+                dir.display(),
+                info.storage
+            )
+            .into())
+        }
+    };
+
+    // The storage of the deployment needs its schema before the metadata
+    // that contains it is committed
+    let path = dir.join(SCHEMA_FILE);
+    let raw_schema = fs::read_to_string(&path).map_err(|e| io_error(&path, e))?;
+    store.cache_schema(&info.id, &raw_schema, true)?;
+    let schema = store.raw_subgraph_schema(&info.id)?;
+
+    let mut ops = vec![MetadataOperation::AbortUnless {
+        description: "Subgraph deployment entity must not exist yet to be imported".to_owned(),
+        query: SubgraphDeploymentEntity::query()
+            .filter(EntityFilter::new_equal("id", info.id.to_string())),
+        entity_ids: vec![],
+    }];
+    for entity in read_jsonl::<MetadataEntity>(&dir.join(METADATA_FILE))? {
+        let mut entity = entity?;
+        if entity.entity == SubgraphDeploymentEntity::TYPENAME {
+            // Imported deployments are always stored in the primary shard
+            entity.data.set("shard", Value::Null);
+        }
+        ops.push(MetadataOperation::Set {
+            entity: entity.entity,
+            id: entity.id,
+            data: entity.data,
+        });
+    }
+
+    let econn = store.get_entity_conn()?;
+    econn.conn.transaction(|| -> Result<(), StoreError> {
+        store.apply_metadata_operations_with_conn(&econn, ops)?;
+        e::create_schema_with_version(&econn.conn, &schema, version)?;
+        for table in &info.tables {
+            let path = dir.join(ENTITIES_DIR).join(format!("{}.jsonl", table));
+            let count = econn.import_table(&info.id, table, read_jsonl(&path)?)?;
+            info!(logger, "Imported entity table";
+                          "subgraph" => info.id.to_string(),
+                          "table" => table,
+                          "rows" => count);
+        }
+        // An export from a shard can contain changes for blocks after the
+        // block pointer of the deployment. Nothing can have subscribed to
+        // the deployment yet, and its entity count is already the one for
+        // the block pointer
+        econn.revert_to_block_ptr(&info.id, &info.block_ptr)?;
+        Ok(())
+    })?;
+    Ok(info.id)
+}
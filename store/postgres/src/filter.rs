@@ -1,10 +1,13 @@
 use diesel::dsl::{self, sql};
+use diesel::expression::{AppearsOnTable, Expression, NonAggregate, SelectableExpression};
 use diesel::pg::Pg;
 use diesel::prelude::*;
+use diesel::query_builder::{AstPass, QueryFragment};
 use diesel::serialize::ToSql;
 use diesel::sql_types::{Array, Bool, Double, HasSqlType, Integer, Numeric, Text};
 use std::error::Error as StdError;
 use std::fmt::{self, Display};
+use std::marker::PhantomData;
 use std::str::FromStr;
 
 use graph::components::store::EntityFilter;
@@ -176,8 +179,226 @@ impl<QS> IntoArrayFilter<QS, SqlValue> for Vec<SqlValue> {
     }
 }
 
+/// The name under which the `entities` table for a query with nesting
+/// `depth` is known; the outermost query uses the table name itself, and
+/// the `exists` subqueries for nested filters the aliases `c1`, `c2`, etc.
+fn table_alias(depth: usize) -> String {
+    if depth == 0 {
+        "entities".to_owned()
+    } else {
+        format!("c{}", depth)
+    }
+}
+
+/// An `exists` subquery that checks whether any of the entities of one of
+/// `entity_types` that are linked to an entity through `attribute` match
+/// `filter`. See `EntityFilter::Child` for the meaning of `derived`
+struct ChildFilter<QS> {
+    schema: String,
+    depth: usize,
+    attribute: String,
+    entity_types: Vec<String>,
+    derived: bool,
+    filter: FilterExpression<QS>,
+}
+
+impl<QS> Expression for ChildFilter<QS> {
+    type SqlType = Bool;
+}
+
+impl<QS> NonAggregate for ChildFilter<QS> {}
+
+impl<QS> AppearsOnTable<QS> for ChildFilter<QS> {}
+
+impl<QS> SelectableExpression<QS> for ChildFilter<QS> {}
+
+impl<QS> QueryFragment<Pg> for ChildFilter<QS> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        let parent = table_alias(self.depth);
+        let child = table_alias(self.depth + 1);
+        let (referencing, referenced) = if self.derived {
+            (&child, &parent)
+        } else {
+            (&parent, &child)
+        };
+
+        // Generate
+        //   exists (select 1 from schema.entities c
+        //            where c.entity = any($entity_types)
+        //              and (r.data -> $attribute @> jsonb_build_object('data', d.id)
+        //                   or r.data -> $attribute -> 'data'
+        //                        @> jsonb_build_array(jsonb_build_object('data', d.id)))
+        //              and filter)
+        // where `r` is the side of the link that holds the reference and
+        // `d` the side that is referenced. The attribute holds either a
+        // single id or a list of ids
+        out.push_sql("exists (select 1 from ");
+        out.push_identifier(&self.schema)?;
+        out.push_sql(".entities ");
+        out.push_sql(&child);
+        out.push_sql(" where ");
+        out.push_sql(&child);
+        out.push_sql(".entity = any(");
+        out.push_bind_param::<Array<Text>, _>(&self.entity_types)?;
+        out.push_sql(") and (");
+        out.push_sql(referencing);
+        out.push_sql(".data -> ");
+        out.push_bind_param::<Text, _>(&self.attribute)?;
+        out.push_sql(" @> jsonb_build_object('data', ");
+        out.push_sql(referenced);
+        out.push_sql(".id) or ");
+        out.push_sql(referencing);
+        out.push_sql(".data -> ");
+        out.push_bind_param::<Text, _>(&self.attribute)?;
+        out.push_sql(" -> 'data' @> jsonb_build_array(jsonb_build_object('data', ");
+        out.push_sql(referenced);
+        out.push_sql(".id))) and ");
+        self.filter.walk_ast(out.reborrow())?;
+        out.push_sql(")");
+        Ok(())
+    }
+}
+
+/// A condition that only lets entities through that come after the entity
+/// with id `after` in the order given by `order`, a tuple (attribute,
+/// value_type, cast, descending), and then by `id`. Entities are sorted
+/// with `NULLS LAST` in both directions
+struct CursorFilter<QS> {
+    schema: String,
+    entity_types: Vec<String>,
+    order: Option<(String, ValueType, String, bool)>,
+    after: String,
+    phantom: PhantomData<QS>,
+}
+
+impl<QS> CursorFilter<QS> {
+    /// Generate the expression for the sort key of the entity with alias
+    /// `table`, exactly like it is used for ordering results
+    fn sort_key(&self, table: &str, mut out: AstPass<Pg>) -> QueryResult<()> {
+        let (attribute, value_type, cast, _) = self.order.as_ref().unwrap();
+        if value_type == &ValueType::String {
+            out.push_sql("left(");
+            out.push_sql(table);
+            out.push_sql(".data -> ");
+            out.push_bind_param::<Text, _>(attribute)?;
+            out.push_sql(" ->> 'data', ");
+            out.push_sql(&STRING_PREFIX_SIZE.to_string());
+            out.push_sql(")");
+        } else {
+            out.push_sql("(");
+            out.push_sql(table);
+            out.push_sql(".data -> ");
+            out.push_bind_param::<Text, _>(attribute)?;
+            out.push_sql(" ->> 'data')");
+            out.push_sql(cast);
+        }
+        Ok(())
+    }
+}
+
+impl<QS> Expression for CursorFilter<QS> {
+    type SqlType = Bool;
+}
+
+impl<QS> NonAggregate for CursorFilter<QS> {}
+
+impl<QS> AppearsOnTable<QS> for CursorFilter<QS> {}
+
+impl<QS> SelectableExpression<QS> for CursorFilter<QS> {}
+
+impl<QS> QueryFragment<Pg> for CursorFilter<QS> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        let entity = table_alias(0);
+
+        let op = match &self.order {
+            // Without an order, entities are sorted by id
+            None => {
+                out.push_sql(&entity);
+                out.push_sql(".id > ");
+                out.push_bind_param::<Text, _>(&self.after)?;
+                return Ok(());
+            }
+            Some((_, _, _, true)) => " < ",
+            Some((_, _, _, false)) => " > ",
+        };
+
+        // Generate
+        //   exists (select 1 from schema.entities cursor
+        //            where cursor.entity = any($entity_types)
+        //              and cursor.id = $after
+        //              and case when sort(cursor) is null
+        //                       then sort(e) is null and e.id > cursor.id
+        //                       else sort(e) op sort(cursor) or sort(e) is null
+        //                            or (sort(e) = sort(cursor) and e.id > cursor.id)
+        //                   end)
+        out.push_sql("exists (select 1 from ");
+        out.push_identifier(&self.schema)?;
+        out.push_sql(".entities cursor where cursor.entity = any(");
+        out.push_bind_param::<Array<Text>, _>(&self.entity_types)?;
+        out.push_sql(") and cursor.id = ");
+        out.push_bind_param::<Text, _>(&self.after)?;
+        out.push_sql(" and case when ");
+        self.sort_key("cursor", out.reborrow())?;
+        out.push_sql(" is null then ");
+        self.sort_key(&entity, out.reborrow())?;
+        out.push_sql(" is null and ");
+        out.push_sql(&entity);
+        out.push_sql(".id > cursor.id else ");
+        self.sort_key(&entity, out.reborrow())?;
+        out.push_sql(op);
+        self.sort_key("cursor", out.reborrow())?;
+        out.push_sql(" or ");
+        self.sort_key(&entity, out.reborrow())?;
+        out.push_sql(" is null or (");
+        self.sort_key(&entity, out.reborrow())?;
+        out.push_sql(" = ");
+        self.sort_key("cursor", out.reborrow())?;
+        out.push_sql(" and ");
+        out.push_sql(&entity);
+        out.push_sql(".id > cursor.id) end)");
+        Ok(())
+    }
+}
+
+/// Build the condition for entities of one of `entity_types` in the
+/// database schema `schema` that come after the entity with id `after` in
+/// the given `order`, a tuple (attribute, value_type, cast, direction)
+pub(crate) fn build_cursor_filter<QS>(
+    schema: &str,
+    entity_types: Vec<String>,
+    order: Option<(String, ValueType, &str, &str)>,
+    after: String,
+) -> FilterExpression<QS>
+where
+    QS: EntitySource + 'static,
+{
+    Box::new(CursorFilter {
+        schema: schema.to_owned(),
+        entity_types,
+        order: order.map(|(attribute, value_type, cast, direction)| {
+            (attribute, value_type, cast.to_owned(), direction == "DESC")
+        }),
+        after,
+        phantom: PhantomData,
+    })
+}
+
+/// Build the filter expression for `filter` for entities stored in the
+/// `entities` table in the database schema `schema`
 pub(crate) fn build_filter<QS>(
     filter: EntityFilter,
+    schema: &str,
+) -> Result<FilterExpression<QS>, UnsupportedFilter>
+where
+    QS: EntitySource + 'static,
+{
+    build_nested_filter(filter, schema, 0)
+}
+
+fn build_nested_filter<QS>(
+    filter: EntityFilter,
+    schema: &str,
+    depth: usize,
 ) -> Result<FilterExpression<QS>, UnsupportedFilter>
 where
     QS: EntitySource + 'static,
@@ -189,12 +410,12 @@ where
 
     match filter {
         And(filters) => filters.into_iter().try_fold(true_expr, |p, filter| {
-            build_filter(filter)
+            build_nested_filter(filter, schema, depth)
                 .map(|filter_expr| Box::new(p.and(filter_expr)) as FilterExpression<QS>)
         }),
 
         Or(filters) => filters.into_iter().try_fold(false_expr, |p, filter| {
-            build_filter(filter)
+            build_nested_filter(filter, schema, depth)
                 .map(|filter_expr| Box::new(p.or(filter_expr)) as FilterExpression<QS>)
         }),
 
@@ -357,10 +578,14 @@ where
                 return Ok(true_expr);
             }
 
-            build_filter(And(values
-                .into_iter()
-                .map(|value| Not(attribute.clone(), value))
-                .collect()))
+            build_nested_filter(
+                And(values
+                    .into_iter()
+                    .map(|value| Not(attribute.clone(), value))
+                    .collect()),
+                schema,
+                depth,
+            )
         }
 
         StartsWith(..) | NotStartsWith(..) => {
@@ -420,5 +645,25 @@ where
                 }
             }
         }
+
+        // Full-text search is only supported for relational storage
+        Fulltext(_, value) => Err(UnsupportedFilter {
+            filter: "fulltext".to_owned(),
+            value,
+        }),
+
+        Child {
+            attribute,
+            entity_types,
+            filter,
+            derived,
+        } => Ok(Box::new(ChildFilter {
+            schema: schema.to_owned(),
+            depth,
+            attribute,
+            entity_types,
+            derived,
+            filter: build_nested_filter(*filter, schema, depth + 1)?,
+        })),
     }
 }
@@ -24,6 +24,7 @@ mod block_range;
 mod chain_head_listener;
 mod db_schema;
 mod entities;
+mod export;
 mod filter;
 mod functions;
 mod jsonb;
@@ -36,6 +37,7 @@ mod store_events;
 
 #[cfg(debug_assertions)]
 pub mod db_schema_for_tests {
+    pub use crate::db_schema::eth_call_cache;
     pub use crate::db_schema::ethereum_blocks;
     pub use crate::db_schema::ethereum_networks;
 }
@@ -47,4 +49,4 @@ pub mod layout_for_tests {
 }
 
 pub use self::chain_head_listener::ChainHeadUpdateListener;
-pub use self::store::{Store, StoreConfig};
+pub use self::store::{PlacementRule, Store, StoreConfig};
@@ -17,12 +17,15 @@ use std::rc::Rc;
 use std::str::FromStr;
 
 use crate::relational_queries::{
-    ClampRangeQuery, ConflictingEntityQuery, EntityData, FilterQuery, FindQuery, InsertQuery,
+    AggregateData, AggregateQuery, ChildEntityData, ClampRangeQuery, ConflictingEntityQuery,
+    CopyEntityQuery, EntityData, FilterQuery, FindQuery, InsertQuery, ParentLink, PruneQuery,
     RevertClampQuery, RevertRemoveQuery,
 };
+use graph::data::schema::{FulltextAlgorithm, FulltextDefinition, SCHEMA_TYPE_NAME};
 use graph::prelude::{
-    format_err, Entity, EntityChange, EntityChangeOperation, EntityFilter, EntityKey,
-    QueryExecutionError, StoreError, StoreEvent, SubgraphDeploymentId, ValueType,
+    format_err, Entity, EntityAggregate, EntityChange, EntityChangeOperation, EntityFilter,
+    EntityKey, EntityLink, QueryExecutionError, StoreError, StoreEvent, SubgraphDeploymentId,
+    Value, ValueType,
 };
 
 use crate::block_range::BlockNumber;
@@ -167,6 +170,8 @@ impl Layout {
 
         for defn in &document.definitions {
             match defn {
+                // `_Schema_` only holds schema-level directives
+                TypeDefinition(Object(obj_type)) if obj_type.name == SCHEMA_TYPE_NAME => (),
                 TypeDefinition(Object(obj_type)) => {
                     let table =
                         Table::new(obj_type, &mut interfaces, id_type, tables.len() as u32)?;
@@ -185,6 +190,20 @@ impl Layout {
             }
         }
 
+        // Add the columns for full-text searches to the tables whose
+        // entities they search
+        let fulltext_definitions = FulltextDefinition::from_document(document)
+            .map_err(|e| StoreError::Unknown(e.into()))?;
+        for definition in &fulltext_definitions {
+            for (entity, fields) in &definition.included_fields {
+                let table = tables
+                    .iter_mut()
+                    .find(|table| &table.object == entity)
+                    .ok_or_else(|| StoreError::UnknownTable(entity.clone()))?;
+                table.add_fulltext_column(definition, fields)?;
+            }
+        }
+
         let tables: Vec<_> = tables.into_iter().map(|table| Rc::new(table)).collect();
         let interfaces = interfaces
             .into_iter()
@@ -334,6 +353,7 @@ impl Layout {
         first: Option<u32>,
         skip: u32,
         block: BlockNumber,
+        after: Option<String>,
     ) -> Result<Vec<Entity>, QueryExecutionError> {
         let tables = entity_types
             .into_iter()
@@ -346,7 +366,7 @@ impl Layout {
             Some(skip.to_string())
         };
 
-        let query = FilterQuery::new(&self.schema, tables, filter, order, first, skip, block);
+        let query = FilterQuery::new(self, tables, filter, order, first, skip, block, after, None);
         let query_debug_info = query.clone();
 
         let values = query.load::<EntityData>(conn).map_err(|e| {
@@ -363,6 +383,123 @@ impl Layout {
             .collect()
     }
 
+    /// Find the children of all `parent_ids` with a single query; the
+    /// arguments other than `parent_ids` and `link` apply to the children
+    /// of each parent separately. See `Store::find_children`
+    pub fn query_children(
+        &self,
+        conn: &PgConnection,
+        entity_types: Vec<String>,
+        filter: Option<EntityFilter>,
+        order: Option<(String, ValueType, &str, &str)>,
+        first: Option<u32>,
+        skip: u32,
+        block: BlockNumber,
+        after: Option<String>,
+        parent_ids: Vec<String>,
+        link: EntityLink,
+    ) -> Result<HashMap<String, Vec<Entity>>, QueryExecutionError> {
+        let tables = entity_types
+            .into_iter()
+            .map(|entity| self.table_for_entity(&entity).map(|rc| rc.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let first = first.map(|first| first.to_string());
+        let skip = if skip == 0 {
+            None
+        } else {
+            Some(skip.to_string())
+        };
+        let parents = match &link {
+            EntityLink::Derived { attribute, .. } => ParentLink::Derived {
+                parent_ids: parent_ids.iter().map(String::as_str).collect(),
+                attribute,
+            },
+            EntityLink::Direct { child_ids } => {
+                let mut pairs = (vec![], vec![]);
+                for (parent_id, child_ids) in parent_ids.iter().zip(child_ids) {
+                    for child_id in child_ids {
+                        pairs.0.push(parent_id.as_str());
+                        pairs.1.push(child_id.as_str());
+                    }
+                }
+                ParentLink::Direct {
+                    parent_ids: pairs.0,
+                    child_ids: pairs.1,
+                }
+            }
+        };
+
+        let query = FilterQuery::new(
+            self,
+            tables,
+            filter,
+            order,
+            first,
+            skip,
+            block,
+            after,
+            Some(parents),
+        );
+        let query_debug_info = query.clone();
+
+        let values = query.load::<ChildEntityData>(conn).map_err(|e| {
+            QueryExecutionError::ResolveEntitiesError(format!(
+                "{}, query = {:?}",
+                e,
+                debug_query(&query_debug_info).to_string()
+            ))
+        })?;
+
+        // The query returns the children of each parent in order
+        let mut children = HashMap::new();
+        for child_data in values {
+            let (parent_id, entity) = child_data.to_entity(self)?;
+            children
+                .entry(parent_id)
+                .or_insert_with(Vec::new)
+                .push(entity);
+        }
+        Ok(children)
+    }
+
+    /// Compute `aggregates` over all entities of `entity_types` that match
+    /// `filter` in the database. See `Store::aggregate`
+    pub fn aggregate(
+        &self,
+        conn: &PgConnection,
+        entity_types: Vec<String>,
+        filter: Option<EntityFilter>,
+        block: BlockNumber,
+        aggregates: &[EntityAggregate],
+    ) -> Result<Vec<Value>, QueryExecutionError> {
+        let tables = entity_types
+            .into_iter()
+            .map(|entity| self.table_for_entity(&entity).map(|rc| rc.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let table = match tables.first() {
+            Some(table) => *table,
+            // There are no entities to aggregate over
+            None => {
+                return aggregates
+                    .iter()
+                    .map(|aggregate| aggregate.compute(&[]))
+                    .collect()
+            }
+        };
+
+        let query = AggregateQuery::new(self, tables, filter, block, aggregates);
+        let query_debug_info = query.clone();
+
+        let data = query.get_result::<AggregateData>(conn).map_err(|e| {
+            QueryExecutionError::ResolveEntitiesError(format!(
+                "{}, query = {:?}",
+                e,
+                debug_query(&query_debug_info).to_string()
+            ))
+        })?;
+        data.to_values(aggregates, table).map_err(|e| e.into())
+    }
+
     pub fn update(
         &self,
         conn: &PgConnection,
@@ -441,6 +578,48 @@ impl Layout {
         }
         Ok((StoreEvent::new(changes), count))
     }
+
+    /// Remove all versions that are not visible at `block` or any later
+    /// block. Return the number of versions that were removed
+    pub fn prune(&self, conn: &PgConnection, block: BlockNumber) -> Result<usize, StoreError> {
+        let mut count = 0;
+        for table in self.tables.values() {
+            count += PruneQuery::new(&self.schema, table, block).execute(conn)?;
+        }
+        Ok(count)
+    }
+
+    /// Copy the entities of `base` that are visible at `block` into this
+    /// layout, where they become current as of `block`. Both layouts must
+    /// be in the database that `conn` connects to. Return the number of
+    /// entities that were copied
+    pub fn copy_from(
+        &self,
+        conn: &PgConnection,
+        base: &Layout,
+        block: BlockNumber,
+    ) -> Result<usize, StoreError> {
+        let mut count = 0;
+        for (object, table) in self.tables.iter() {
+            if let Some(src) = base.tables.get(object) {
+                count += CopyEntityQuery::new(&self.schema, table, &base.schema, src, block)
+                    .execute(conn)?;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Make the space taken up by pruned versions available for reuse. This
+    /// can not be run inside a transaction
+    pub fn vacuum(&self, conn: &PgConnection) -> Result<(), StoreError> {
+        for table in self.tables.values() {
+            conn.batch_execute(&format!(
+                "vacuum analyze \"{}\".\"{}\"",
+                self.schema, table.name
+            ))?;
+        }
+        Ok(())
+    }
 }
 
 /// This is almost the same as graph::data::store::ValueType, but without
@@ -564,6 +743,20 @@ pub(crate) const PRIMARY_KEY_COLUMN: &str = "id";
 
 pub(crate) const BLOCK_RANGE: &str = "block_range";
 
+/// A `tsvector` column that holds the terms of a full-text search over
+/// some of the `String` columns of a table
+#[derive(Clone, Debug)]
+pub struct FulltextColumn {
+    pub name: SqlName,
+    /// The name of the search in the GraphQL schema
+    pub search: String,
+    /// The Postgres text search configuration, e.g. 'english'
+    pub language: String,
+    pub algorithm: FulltextAlgorithm,
+    /// The GraphQL names of the fields whose text is searched
+    pub fields: Vec<String>,
+}
+
 #[derive(Clone, Debug)]
 pub struct Table {
     /// The name of the GraphQL object type ('Thing')
@@ -573,6 +766,8 @@ pub struct Table {
     pub name: SqlName,
 
     pub columns: Vec<Column>,
+    /// The columns for full-text searches over this table
+    pub fulltext_columns: Vec<FulltextColumn>,
     /// The position of this table in all the tables for this layout; this
     /// is really only needed for the tests to make the names of indexes
     /// predictable
@@ -599,6 +794,7 @@ impl Table {
             object: defn.name.clone(),
             name: table_name.clone(),
             columns,
+            fulltext_columns: vec![],
             position,
         };
         for interface_name in &defn.implements_interfaces {
@@ -614,6 +810,33 @@ impl Table {
         }
         Ok(table)
     }
+
+    fn add_fulltext_column(
+        &mut self,
+        definition: &FulltextDefinition,
+        fields: &Vec<String>,
+    ) -> Result<(), StoreError> {
+        SqlName::check_valid_identifier(&definition.name, "full-text search")?;
+
+        let name = SqlName::from(definition.name.as_str());
+        if self.column(&name).is_ok() {
+            return Err(StoreError::Unknown(format_err!(
+                "full-text search {} conflicts with attribute {}.{}",
+                definition.name,
+                self.object,
+                name
+            )));
+        }
+        self.fulltext_columns.push(FulltextColumn {
+            name,
+            search: definition.name.clone(),
+            language: definition.language.clone(),
+            algorithm: definition.algorithm,
+            fields: fields.clone(),
+        });
+        Ok(())
+    }
+
     /// Find the column `name` in this table. The name must be in snake case,
     /// i.e., use SQL conventions
     pub fn column(&self, name: &SqlName) -> Result<&Column, StoreError> {
@@ -632,6 +855,14 @@ impl Table {
             .ok_or_else(|| StoreError::UnknownField(field.to_string()))
     }
 
+    /// Find the column for the full-text search `search` in this table, if
+    /// the search includes the table's entity type
+    pub fn fulltext_column(&self, search: &str) -> Option<&FulltextColumn> {
+        self.fulltext_columns
+            .iter()
+            .find(|column| column.search == search)
+    }
+
     /// Generate the DDL for one table, i.e. one `create table` statement
     /// and all `create index` statements for the table's columns
     ///
@@ -644,6 +875,9 @@ impl Table {
             column.as_ddl(out)?;
             write!(out, ",\n")?;
         }
+        for column in self.fulltext_columns.iter() {
+            write!(out, "        {:20} tsvector,\n", column.name)?;
+        }
         // Add block_range column and constraint
         write!(
             out,
@@ -677,6 +911,17 @@ impl Table {
                 index_expr = index_expr,
             )?;
         }
+        for (i, column) in self.fulltext_columns.iter().enumerate() {
+            write!(
+                out,
+                "create index fulltext_{table_index}_{column_index}_{table_name}_{column_name}\n    on {schema_name}.{table_name} using gin({column_name});\n",
+                table_index = self.position,
+                table_name = self.name,
+                column_index = i,
+                column_name = column.name,
+                schema_name = layout.schema,
+            )?;
+        }
         write!(out, "\n")
     }
 }
@@ -759,6 +1004,10 @@ mod tests {
         let layout = test_layout(FOREST_GQL);
         let sql = layout.as_ddl().expect("Failed to generate DDL");
         assert_eq!(FOREST_DDL, sql);
+
+        let layout = test_layout(FULLTEXT_GQL);
+        let sql = layout.as_ddl().expect("Failed to generate DDL");
+        assert_eq!(FULLTEXT_DDL, sql);
     }
 
     const THING_GQL: &str = "
@@ -964,6 +1213,39 @@ create index attr_2_1_habitat_most_common
 create index attr_2_2_habitat_dwellers
     on rel.habitat using gin(dwellers);
 
+";
+
+    const FULLTEXT_GQL: &str = "
+type _Schema_ @fulltext(
+    name: \"bandSearch\",
+    language: en,
+    algorithm: rank,
+    include: [{ entity: \"Band\", fields: [{ name: \"name\" }, { name: \"bio\" }] }]
+)
+type Band @entity {
+    id: ID!,
+    name: String!,
+    bio: String
+}";
+
+    const FULLTEXT_DDL: &str = "create table rel.band (
+        id                   text not null,
+        name                 text not null,
+        bio                  text,
+        band_search          tsvector,
+
+        block_range          int4range not null,
+        exclude using gist   (id with =, block_range with &&)
+);
+create index attr_0_0_band_id
+    on rel.band using btree(id);
+create index attr_0_1_band_name
+    on rel.band using btree(left(name, 2048));
+create index attr_0_2_band_bio
+    on rel.band using btree(left(bio, 2048));
+create index fulltext_0_0_band_band_search
+    on rel.band using gin(band_search);
+
 ";
 
 }
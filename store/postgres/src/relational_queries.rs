@@ -9,22 +9,23 @@ use diesel::pg::{Pg, PgConnection};
 use diesel::query_builder::{AstPass, QueryFragment, QueryId};
 use diesel::query_dsl::{LoadQuery, RunQueryDsl};
 use diesel::result::QueryResult;
-use diesel::sql_types::{Array, Binary, Bool, Integer, Jsonb, Numeric, Range, Text};
+use diesel::sql_types::{Array, Binary, Bool, Integer, Jsonb, Nullable, Numeric, Range, Text};
 use diesel::Connection;
 use failure::Fail;
 use std::convert::TryFrom;
 use std::str::FromStr;
 
+use graph::data::schema::FulltextAlgorithm;
 use graph::data::store::scalar;
 use graph::prelude::{
-    format_err, serde_json, Attribute, Entity, EntityFilter, EntityKey, StoreError, Value,
-    ValueType,
+    format_err, serde_json, Attribute, Entity, EntityAggregate, EntityFilter, EntityKey,
+    StoreError, Value, ValueType,
 };
 
 use crate::block_range::{BlockNumber, BlockRange, BlockRangeContainsClause};
 use crate::filter::UnsupportedFilter;
 use crate::relational::{
-    Column, ColumnType, Layout, SqlName, Table, BLOCK_RANGE, PRIMARY_KEY_COLUMN,
+    Column, ColumnType, FulltextColumn, Layout, SqlName, Table, BLOCK_RANGE, PRIMARY_KEY_COLUMN,
 };
 use crate::sql_value::SqlValue;
 
@@ -156,6 +157,30 @@ fn query_builder_error(msg: String) -> diesel::result::Error {
     ))
 }
 
+/// Helper struct for retrieving the children of a batch of parents with
+/// a `FilterQuery`; like `EntityData`, but also returns the id of the parent
+/// each entity belongs to
+#[derive(QueryableByName)]
+pub struct ChildEntityData {
+    #[sql_type = "Text"]
+    parent_id: String,
+    #[sql_type = "Text"]
+    entity: String,
+    #[sql_type = "Jsonb"]
+    data: serde_json::Value,
+}
+
+impl ChildEntityData {
+    pub fn to_entity(self, layout: &Layout) -> Result<(String, Entity), StoreError> {
+        let entity = EntityData {
+            entity: self.entity,
+            data: self.data,
+        }
+        .to_entity(layout)?;
+        Ok((self.parent_id, entity))
+    }
+}
+
 /// A `QueryValue` makes it possible to bind a `Value` into a SQL query
 /// where the needed SQL type is `ColumnType`
 struct QueryValue<'a>(&'a Value, ColumnType);
@@ -193,15 +218,105 @@ impl<'a> QueryFragment<Pg> for QueryValue<'a> {
     }
 }
 
+/// A `FulltextQuery` generates the `tsquery` for the text of a full-text
+/// search, using the search's text search configuration. The text is plain
+/// text that must match all its words; it is not parsed as `tsquery`
+/// syntax, which would make queries with stray operators fail
+struct FulltextQuery<'a> {
+    column: &'a FulltextColumn,
+    text: &'a str,
+}
+
+impl<'a> QueryFragment<Pg> for FulltextQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        // Generate
+        //   plainto_tsquery($language::regconfig, $text)
+        out.push_sql("plainto_tsquery(");
+        out.push_bind_param::<Text, _>(&self.column.language)?;
+        out.push_sql("::regconfig, ");
+        out.push_bind_param::<Text, _>(&self.text)?;
+        out.push_sql(")");
+        Ok(())
+    }
+}
+
+/// A `FulltextRank` computes how well an entity matches the text of a
+/// full-text search. Tables that are not part of the search rank all
+/// entities as 0
+struct FulltextRank<'a> {
+    column: Option<&'a FulltextColumn>,
+    text: &'a str,
+}
+
+impl<'a> QueryFragment<Pg> for FulltextRank<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        let column = match self.column {
+            Some(column) => column,
+            None => {
+                out.push_sql("0::real");
+                return Ok(());
+            }
+        };
+
+        // Generate
+        //   ts_rank(e.column, plainto_tsquery(...))
+        match column.algorithm {
+            FulltextAlgorithm::Rank => out.push_sql("ts_rank(e."),
+            FulltextAlgorithm::ProximityRank => out.push_sql("ts_rank_cd(e."),
+        }
+        out.push_identifier(column.name.as_str())?;
+        out.push_sql(", ");
+        FulltextQuery {
+            column,
+            text: self.text,
+        }
+        .walk_ast(out.reborrow())?;
+        out.push_sql(")");
+        Ok(())
+    }
+}
+
+/// Find the full-text search in the top-level `And` of `filter`, if there
+/// is one, and return the name of the search and its text
+fn fulltext_search(filter: &EntityFilter) -> Option<(&str, &str)> {
+    match filter {
+        EntityFilter::Fulltext(search, Value::String(text)) => {
+            Some((search.as_str(), text.as_str()))
+        }
+        EntityFilter::And(filters) => filters.iter().find_map(fulltext_search),
+        _ => None,
+    }
+}
+
+/// The alias under which the table for a query with nesting `depth` is
+/// known; the outermost query uses `e`, and the `exists` subqueries for
+/// nested filters `c1`, `c2`, etc.
+fn table_alias(depth: usize) -> String {
+    if depth == 0 {
+        "e".to_owned()
+    } else {
+        format!("c{}", depth)
+    }
+}
+
 /// A `QueryFilter` adds the conditions represented by the `filter` to
 /// the `where` clause of a SQL query. The attributes mentioned in
 /// the `filter` must all come from the given `table`, which is used to
 /// map GraphQL names to column names, and to determine the type of the
-/// column an attribute refers to
+/// column an attribute refers to. Nested filters on linked entities are
+/// turned into `exists` subqueries against the tables in `layout`, and
+/// `depth` counts how deeply we are nested in them
 #[derive(Constructor)]
 struct QueryFilter<'a> {
     filter: &'a EntityFilter,
     table: &'a Table,
+    layout: &'a Layout,
+    block: BlockNumber,
+    depth: usize,
 }
 
 impl<'a> QueryFilter<'a> {
@@ -209,6 +324,9 @@ impl<'a> QueryFilter<'a> {
         QueryFilter {
             filter,
             table: self.table,
+            layout: self.layout,
+            block: self.block,
+            depth: self.depth,
         }
     }
 
@@ -424,6 +542,110 @@ impl<'a> QueryFilter<'a> {
         Ok(())
     }
 
+    fn fulltext(&self, search: &str, value: &Value, mut out: AstPass<Pg>) -> QueryResult<()> {
+        let text = match value {
+            Value::String(text) => text,
+            _ => {
+                return Err(UnsupportedFilter {
+                    filter: "fulltext".to_owned(),
+                    value: value.clone(),
+                }
+                .into())
+            }
+        };
+
+        match self.table.fulltext_column(search) {
+            Some(column) => {
+                out.push_identifier(column.name.as_str())?;
+                out.push_sql(" @@ ");
+                FulltextQuery { column, text }.walk_ast(out)?;
+            }
+            // Entities whose type the search does not include never match
+            None => out.push_sql("false"),
+        }
+        Ok(())
+    }
+
+    /// Generate
+    ///   (exists (select 1 from schema.child c
+    ///             where c.block_range @> $block
+    ///               and <link between e and c>
+    ///               and <filter on c>)
+    ///    or exists (...))
+    /// with one `exists` clause for each table of the `entity_types`. The
+    /// link compares the `attribute` of the entity, or, for `derived`
+    /// links, of the child, with the id of the other side
+    fn child(
+        &self,
+        attribute: &Attribute,
+        entity_types: &Vec<String>,
+        filter: &EntityFilter,
+        derived: bool,
+        mut out: AstPass<Pg>,
+    ) -> QueryResult<()> {
+        let parent = table_alias(self.depth);
+        let child = table_alias(self.depth + 1);
+
+        if entity_types.is_empty() {
+            out.push_sql("false");
+            return Ok(());
+        }
+
+        out.push_sql("(");
+        for (i, entity_type) in entity_types.iter().enumerate() {
+            if i > 0 {
+                out.push_sql(" or ");
+            }
+            let table = self
+                .layout
+                .table_for_entity(entity_type)
+                .map_err(|e| query_builder_error(e.to_string()))?;
+
+            out.push_sql("exists (select 1 from ");
+            out.push_identifier(&self.layout.schema)?;
+            out.push_sql(".");
+            out.push_identifier(table.name.as_str())?;
+            out.push_sql(" ");
+            out.push_sql(&child);
+            out.push_sql(" where ");
+            BlockRangeContainsClause::new(self.block).walk_ast(out.reborrow())?;
+            out.push_sql(" and ");
+
+            // The side of the link that holds the reference, its column,
+            // and the side whose id is referenced
+            let (referencing, column, referenced) = if derived {
+                let column = table
+                    .column_for_field(attribute)
+                    .map_err(|e| query_builder_error(e.to_string()))?;
+                (&child, column, &parent)
+            } else {
+                (&parent, self.column(attribute)?, &child)
+            };
+            out.push_sql(referenced);
+            out.push_sql(".");
+            out.push_identifier(PRIMARY_KEY_COLUMN)?;
+            if column.is_list() {
+                out.push_sql(" = any(");
+                out.push_sql(referencing);
+                out.push_sql(".");
+                out.push_identifier(column.name.as_str())?;
+                out.push_sql(")");
+            } else {
+                out.push_sql(" = ");
+                out.push_sql(referencing);
+                out.push_sql(".");
+                out.push_identifier(column.name.as_str())?;
+            }
+
+            out.push_sql(" and ");
+            QueryFilter::new(filter, table, self.layout, self.block, self.depth + 1)
+                .walk_ast(out.reborrow())?;
+            out.push_sql(")");
+        }
+        out.push_sql(")");
+        Ok(())
+    }
+
     fn starts_or_ends_with(
         &self,
         attribute: &Attribute,
@@ -496,6 +718,15 @@ impl<'a> QueryFragment<Pg> for QueryFilter<'a> {
             NotEndsWith(attr, value) => {
                 self.starts_or_ends_with(attr, value, " not like ", false, out)?
             }
+
+            Fulltext(search, value) => self.fulltext(search, value, out)?,
+
+            Child {
+                attribute,
+                entity_types,
+                filter,
+                derived,
+            } => self.child(attribute, entity_types, filter, *derived, out)?,
         }
         Ok(())
     }
@@ -622,6 +853,10 @@ impl<'a> QueryFragment<Pg> for InsertQuery<'a> {
                 out.push_sql(", ");
             }
         }
+        for column in self.table.fulltext_columns.iter() {
+            out.push_identifier(column.name.as_str())?;
+            out.push_sql(", ");
+        }
         out.push_identifier(BLOCK_RANGE)?;
 
         out.push_sql(")\nvalues(");
@@ -631,6 +866,24 @@ impl<'a> QueryFragment<Pg> for InsertQuery<'a> {
                 out.push_sql(", ");
             }
         }
+        for column in self.table.fulltext_columns.iter() {
+            // The document for a full-text search is the text of all the
+            // searched fields
+            let text = column
+                .fields
+                .iter()
+                .filter_map(|field| match self.entity.get(field) {
+                    Some(Value::String(s)) => Some(s.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            out.push_sql("to_tsvector(");
+            out.push_bind_param::<Text, _>(&column.language)?;
+            out.push_sql("::regconfig, ");
+            out.push_bind_param::<Text, _>(&text)?;
+            out.push_sql("), ");
+        }
         let block_range: BlockRange = (self.block..).into();
         out.push_bind_param::<Range<Integer>, _>(&block_range)?;
         out.push_sql(")");
@@ -704,25 +957,275 @@ impl<'a> LoadQuery<PgConnection, ConflictingEntityData> for ConflictingEntityQue
 
 impl<'a, Conn> RunQueryDsl<Conn> for ConflictingEntityQuery<'a> {}
 
+/// The columns under which the ids of parents, and of their children, are
+/// known in a `FilterQuery` for the children of a batch of parents. The `$`
+/// makes sure they can not clash with the columns of the entity tables
+const PARENT_ID: &str = "g$parent_id";
+const CHILD_ID: &str = "g$child_id";
+
+/// Restricts a `FilterQuery` to the children of a batch of parents. The
+/// query then applies `first` and `skip` to the children of each parent
+/// separately, and returns the id of the parent with each child
+#[derive(Debug, Clone)]
+pub enum ParentLink<'a> {
+    /// The children hold the id of their parent in `attribute`, either
+    /// directly or as an element of a list
+    Derived {
+        parent_ids: Vec<&'a str>,
+        attribute: &'a str,
+    },
+    /// Each parent id is paired with the id of one of the parent's children
+    Direct {
+        parent_ids: Vec<&'a str>,
+        child_ids: Vec<&'a str>,
+    },
+}
+
+impl<'a> ParentLink<'a> {
+    /// Generate
+    ///   unnest($parent_ids) as p(parent_id)
+    /// or
+    ///   unnest($parent_ids, $child_ids) as p(parent_id, child_id)
+    fn from_clause(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.push_sql("unnest(");
+        match self {
+            ParentLink::Derived { parent_ids, .. } => {
+                out.push_bind_param::<Array<Text>, _>(parent_ids)?;
+                out.push_sql(") as p(");
+                out.push_identifier(PARENT_ID)?;
+            }
+            ParentLink::Direct {
+                parent_ids,
+                child_ids,
+            } => {
+                out.push_bind_param::<Array<Text>, _>(parent_ids)?;
+                out.push_sql(", ");
+                out.push_bind_param::<Array<Text>, _>(child_ids)?;
+                out.push_sql(") as p(");
+                out.push_identifier(PARENT_ID)?;
+                out.push_sql(", ");
+                out.push_identifier(CHILD_ID)?;
+            }
+        }
+        out.push_sql(")");
+        Ok(())
+    }
+
+    /// Generate the condition that links an entity `e` from `table` to
+    /// its parent `p`
+    fn link(&self, table: &Table, mut out: AstPass<Pg>) -> QueryResult<()> {
+        match self {
+            ParentLink::Derived { attribute, .. } => {
+                let column = table
+                    .column_for_field(attribute)
+                    .map_err(|e| query_builder_error(e.to_string()))?;
+                if column.is_list() {
+                    out.push_sql("p.");
+                    out.push_identifier(PARENT_ID)?;
+                    out.push_sql(" = any(e.");
+                    out.push_identifier(column.name.as_str())?;
+                    out.push_sql(")");
+                } else {
+                    out.push_sql("e.");
+                    out.push_identifier(column.name.as_str())?;
+                    out.push_sql(" = p.");
+                    out.push_identifier(PARENT_ID)?;
+                }
+            }
+            ParentLink::Direct { .. } => {
+                out.push_sql("e.");
+                out.push_identifier(PRIMARY_KEY_COLUMN)?;
+                out.push_sql(" = p.");
+                out.push_identifier(CHILD_ID)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Constructor)]
 pub struct FilterQuery<'a> {
-    schema: &'a str,
+    layout: &'a Layout,
     tables: Vec<&'a Table>,
     filter: Option<EntityFilter>,
     order: Option<(String, ValueType, &'a str, &'a str)>,
     first: Option<String>,
     skip: Option<String>,
     block: BlockNumber,
+    after: Option<String>,
+    parents: Option<ParentLink<'a>>,
 }
 
 impl<'a> FilterQuery<'a> {
+    /// Generate the condition that only lets entities from `table` through
+    /// that come after the entity with id `after` in the order of the query,
+    /// i.e., by `sort_key` and then by `id`. Postgres sorts `null` values
+    /// last when sorting in ascending order, and first when sorting in
+    /// descending order
+    fn cursor(&self, table: &Table, after: &str, mut out: AstPass<Pg>) -> QueryResult<()> {
+        let (attribute, descending) = match &self.order {
+            Some((attribute, _, _, direction)) => (attribute, *direction == "DESC"),
+            None => {
+                if self.filter.as_ref().and_then(fulltext_search).is_some() {
+                    return Err(query_builder_error(
+                        "`after` can not be used with a full-text search".to_owned(),
+                    ));
+                }
+                // Without an order, entities are sorted by id
+                out.push_sql("e.");
+                out.push_identifier(PRIMARY_KEY_COLUMN)?;
+                out.push_sql(" > ");
+                return out.push_bind_param::<Text, _>(&after);
+            }
+        };
+        // Generate
+        //   exists (select 1
+        //             from (select c.col as sort_key, c.id
+        //                     from schema.table1 c
+        //                    where c.id = $after and c.block_range @> $block
+        //                   union all ...) cursor
+        //            where case when cursor.sort_key is null
+        //                       then <comparison for null sort key>
+        //                       else <comparison for non-null sort key>
+        //                  end)
+        out.push_sql("exists (select 1 from (");
+        for (i, cursor_table) in self.tables.iter().enumerate() {
+            if i > 0 {
+                out.push_sql(" union all ");
+            }
+            let cursor_column = cursor_table
+                .column_for_field(attribute)
+                .map_err(|e| query_builder_error(e.to_string()))?;
+            out.push_sql("select c.");
+            out.push_identifier(cursor_column.name.as_str())?;
+            out.push_sql(" as sort_key, c.");
+            out.push_identifier(PRIMARY_KEY_COLUMN)?;
+            out.push_sql(" from ");
+            out.push_identifier(&self.layout.schema)?;
+            out.push_sql(".");
+            out.push_identifier(cursor_table.name.as_str())?;
+            out.push_sql(" c where c.");
+            out.push_identifier(PRIMARY_KEY_COLUMN)?;
+            out.push_sql(" = ");
+            out.push_bind_param::<Text, _>(&after)?;
+            out.push_sql(" and ");
+            BlockRangeContainsClause::new(self.block).walk_ast(out.reborrow())?;
+        }
+        out.push_sql(") cursor where case when cursor.sort_key is null then ");
+
+        let column = table
+            .column_for_field(attribute)
+            .map_err(|e| query_builder_error(e.to_string()))?;
+        let push_column = |out: &mut AstPass<Pg>| -> QueryResult<()> {
+            out.push_sql("e.");
+            out.push_identifier(column.name.as_str())
+        };
+        let push_id_after_cursor = |out: &mut AstPass<Pg>| -> QueryResult<()> {
+            out.push_sql("e.");
+            out.push_identifier(PRIMARY_KEY_COLUMN)?;
+            out.push_sql(" > cursor.");
+            out.push_identifier(PRIMARY_KEY_COLUMN)
+        };
+
+        if descending {
+            // Nulls come first, followed by values in descending order
+            push_column(&mut out)?;
+            out.push_sql(" is not null or ");
+            push_id_after_cursor(&mut out)?;
+            out.push_sql(" else ");
+            push_column(&mut out)?;
+            out.push_sql(" < cursor.sort_key or (");
+        } else {
+            // Values in ascending order come first, followed by nulls
+            push_column(&mut out)?;
+            out.push_sql(" is null and ");
+            push_id_after_cursor(&mut out)?;
+            out.push_sql(" else ");
+            push_column(&mut out)?;
+            out.push_sql(" > cursor.sort_key or ");
+            push_column(&mut out)?;
+            out.push_sql(" is null or (");
+        }
+        push_column(&mut out)?;
+        out.push_sql(" = cursor.sort_key and ");
+        push_id_after_cursor(&mut out)?;
+        out.push_sql(") end)");
+        Ok(())
+    }
+
+    /// Generate the queries for all tables, joined with `union all`
+    fn union(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        for (i, table) in self.tables.iter().enumerate() {
+            if i > 0 {
+                out.push_sql("\nunion all\n");
+            }
+            self.object_query(table, out.reborrow())?;
+        }
+        Ok(())
+    }
+
+    /// Generate the expressions by which the entities are sorted
+    fn order_by(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        if let Some((_, _, _, direction)) = &self.order {
+            out.push_sql("sort_key ");
+            out.push_sql(direction);
+            out.push_sql(", ");
+        } else if self.filter.as_ref().and_then(fulltext_search).is_some() {
+            // Best matches first
+            out.push_sql("sort_key desc, ");
+        }
+        out.push_identifier(PRIMARY_KEY_COLUMN)
+    }
+
+    /// Generate the query for the children of a batch of parents
+    ///   select parent_id, entity, data
+    ///     from (select *, row_number() over (partition by parent_id
+    ///                                        order by sort_key, id) as pos
+    ///             from (<union of the queries for each table>) u) w
+    ///    where pos > $skip and pos <= $skip + $first
+    ///    order by parent_id, pos
+    /// which numbers the children of each parent in the order of the query
+    /// and only keeps the ones in the range of the query
+    fn children_query(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        let skip = self.skip.as_ref().map(String::as_str).unwrap_or("0");
+
+        out.push_sql("select ");
+        out.push_identifier(PARENT_ID)?;
+        out.push_sql(" as parent_id, entity, data");
+        out.push_sql("\n  from (select *, row_number() over (partition by ");
+        out.push_identifier(PARENT_ID)?;
+        out.push_sql(" order by ");
+        self.order_by(out.reborrow())?;
+        out.push_sql(") as pos\n  from (");
+        self.union(out.reborrow())?;
+        out.push_sql(") u) w\n where pos > ");
+        out.push_sql(skip);
+        if let Some(first) = &self.first {
+            out.push_sql(" and pos <= ");
+            out.push_sql(skip);
+            out.push_sql(" + ");
+            out.push_sql(first);
+        }
+        out.push_sql("\n order by ");
+        out.push_identifier(PARENT_ID)?;
+        out.push_sql(", pos");
+        Ok(())
+    }
+
     fn object_query(&self, table: &Table, mut out: AstPass<Pg>) -> QueryResult<()> {
         // Generate
         //   select 'entity_type' as entity, to_jsonb(e.*) as data, e.col as sort_key, e.id
         //     from schema.table
         //    where block_range @> $block
         //      and query_filter
+        // When querying for the children of parents, also select the id of
+        // the parent, and join with the parents
         out.push_sql("select ");
+        if self.parents.is_some() {
+            out.push_sql("p.");
+            out.push_identifier(PARENT_ID)?;
+            out.push_sql(", ");
+        }
         out.push_bind_param::<Text, _>(&table.object)?;
         out.push_sql(" as entity, to_jsonb(e.*) as data");
         if let Some((attribute, _, _, _)) = &self.order {
@@ -734,17 +1237,42 @@ impl<'a> FilterQuery<'a> {
             out.push_identifier(column.name.as_str())?;
             out.push_sql(" as sort_key, e.");
             out.push_identifier(PRIMARY_KEY_COLUMN)?;
+        } else if let Some((search, text)) = self.filter.as_ref().and_then(fulltext_search) {
+            // Rank the matches of a full-text search
+            out.push_sql(", ");
+            FulltextRank {
+                column: table.fulltext_column(search),
+                text,
+            }
+            .walk_ast(out.reborrow())?;
+            out.push_sql(" as sort_key, e.");
+            out.push_identifier(PRIMARY_KEY_COLUMN)?;
+        } else if self.parents.is_some() {
+            out.push_sql(", e.");
+            out.push_identifier(PRIMARY_KEY_COLUMN)?;
         }
         out.push_sql("\n  from ");
-        out.push_identifier(&self.schema)?;
+        if let Some(parents) = &self.parents {
+            parents.from_clause(out.reborrow())?;
+            out.push_sql(", ");
+        }
+        out.push_identifier(&self.layout.schema)?;
         out.push_sql(".");
         out.push_identifier(table.name.as_str())?;
         out.push_sql(" e");
         out.push_sql("\n where ");
         BlockRangeContainsClause::new(self.block).walk_ast(out.reborrow())?;
+        if let Some(parents) = &self.parents {
+            out.push_sql(" and ");
+            parents.link(table, out.reborrow())?;
+        }
         if let Some(filter) = &self.filter {
             out.push_sql(" and ");
-            QueryFilter::new(filter, table).walk_ast(out)?;
+            QueryFilter::new(filter, table, self.layout, self.block, 0).walk_ast(out.reborrow())?;
+        }
+        if let Some(after) = &self.after {
+            out.push_sql(" and ");
+            self.cursor(table, after, out)?;
         }
         Ok(())
     }
@@ -757,6 +1285,10 @@ impl<'a> QueryFragment<Pg> for FilterQuery<'a> {
             return Ok(());
         }
 
+        if self.parents.is_some() {
+            return self.children_query(out);
+        }
+
         // For each table, construct a query
         //   select '...' as entity, to_jsonb(e.*) as data
         //     from schema.table
@@ -765,19 +1297,9 @@ impl<'a> QueryFragment<Pg> for FilterQuery<'a> {
         // Optionally select the column to order by as sort key
         // and sort the whole thing by it. Also add limit
         // and offset
-        for (i, table) in self.tables.iter().enumerate() {
-            if i > 0 {
-                out.push_sql("\nunion all\n");
-            }
-            self.object_query(table, out.reborrow())?;
-        }
+        self.union(out.reborrow())?;
         out.push_sql("\n order by ");
-        if let Some((_, _, _, direction)) = &self.order {
-            out.push_sql("sort_key ");
-            out.push_sql(direction);
-            out.push_sql(", ");
-        }
-        out.push_identifier(PRIMARY_KEY_COLUMN)?;
+        self.order_by(out.reborrow())?;
 
         if let Some(first) = &self.first {
             out.push_sql("\n limit ");
@@ -803,8 +1325,158 @@ impl<'a> LoadQuery<PgConnection, EntityData> for FilterQuery<'a> {
     }
 }
 
+impl<'a> LoadQuery<PgConnection, ChildEntityData> for FilterQuery<'a> {
+    fn internal_load(self, conn: &PgConnection) -> QueryResult<Vec<ChildEntityData>> {
+        conn.query_by_name(&self)
+    }
+}
+
 impl<'a, Conn> RunQueryDsl<Conn> for FilterQuery<'a> {}
 
+/// Compute `aggregates` over all entities in `tables` that match `filter`
+/// with a single row of SQL aggregates
+#[derive(Debug, Clone, Constructor)]
+pub struct AggregateQuery<'a> {
+    layout: &'a Layout,
+    tables: Vec<&'a Table>,
+    filter: Option<EntityFilter>,
+    block: BlockNumber,
+    aggregates: &'a [EntityAggregate],
+}
+
+impl<'a> AggregateQuery<'a> {
+    fn object_query(&self, table: &Table, mut out: AstPass<Pg>) -> QueryResult<()> {
+        // Generate
+        //   select e.id, e.col1 as a1, e.col2 as a2, ...
+        //     from schema.table e
+        //    where block_range @> $block
+        //      and query_filter
+        // where `a<i>` is the column for the `i`-th aggregate
+        out.push_sql("select e.");
+        out.push_identifier(PRIMARY_KEY_COLUMN)?;
+        for (i, aggregate) in self.aggregates.iter().enumerate() {
+            if let Some(attribute) = aggregate.attribute() {
+                let column = table
+                    .column_for_field(attribute)
+                    .map_err(|e| query_builder_error(e.to_string()))?;
+                out.push_sql(", e.");
+                out.push_identifier(column.name.as_str())?;
+                out.push_sql(&format!(" as a{}", i));
+            }
+        }
+        out.push_sql("\n  from ");
+        out.push_identifier(&self.layout.schema)?;
+        out.push_sql(".");
+        out.push_identifier(table.name.as_str())?;
+        out.push_sql(" e");
+        out.push_sql("\n where ");
+        BlockRangeContainsClause::new(self.block).walk_ast(out.reborrow())?;
+        if let Some(filter) = &self.filter {
+            out.push_sql(" and ");
+            QueryFilter::new(filter, table, self.layout, self.block, 0).walk_ast(out.reborrow())?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> QueryFragment<Pg> for AggregateQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        // Construct a query
+        //   select array[count(*)::text, sum(a1)::text, min(a2)::text, ...]
+        //            as values
+        //     from (<query for table1> union all <query for table2> ...) u
+        // We return all aggregates as text since their number and types are
+        // only known at runtime
+        out.push_sql("select array[");
+        for (i, aggregate) in self.aggregates.iter().enumerate() {
+            if i > 0 {
+                out.push_sql(", ");
+            }
+            let function = match aggregate {
+                EntityAggregate::Count => {
+                    out.push_sql("count(*)::text");
+                    continue;
+                }
+                EntityAggregate::Sum(_) => "sum",
+                EntityAggregate::Min(_) => "min",
+                EntityAggregate::Max(_) => "max",
+                EntityAggregate::Avg(_) => "avg",
+            };
+            out.push_sql(function);
+            out.push_sql(&format!("(u.a{})::text", i));
+        }
+        out.push_sql("]::text[] as values\n  from (");
+        for (i, table) in self.tables.iter().enumerate() {
+            if i > 0 {
+                out.push_sql("\nunion all\n");
+            }
+            self.object_query(table, out.reborrow())?;
+        }
+        out.push_sql(") u");
+        Ok(())
+    }
+}
+
+impl<'a> QueryId for AggregateQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+/// The result of an `AggregateQuery`: the value of each aggregate in text
+/// form, or `None` if the aggregate is `null`
+#[derive(QueryableByName)]
+pub struct AggregateData {
+    #[sql_type = "Array<Nullable<Text>>"]
+    values: Vec<Option<String>>,
+}
+
+impl AggregateData {
+    /// Convert the text of each aggregate to a `Value` of the type that
+    /// `EntityAggregate` promises, using `table` to look up the type of
+    /// the aggregated attributes
+    pub fn to_values(
+        self,
+        aggregates: &[EntityAggregate],
+        table: &Table,
+    ) -> Result<Vec<Value>, StoreError> {
+        aggregates
+            .iter()
+            .zip(self.values)
+            .map(|(aggregate, text)| {
+                let text = match text {
+                    Some(text) => text,
+                    None => return Ok(Value::Null),
+                };
+                let value_type = match aggregate.attribute() {
+                    Some(attribute) => Some(match table.column_for_field(attribute)?.column_type {
+                        ColumnType::Boolean => ValueType::Boolean,
+                        ColumnType::BigDecimal => ValueType::BigDecimal,
+                        ColumnType::BigInt => ValueType::BigInt,
+                        ColumnType::Bytes => ValueType::Bytes,
+                        ColumnType::Int => ValueType::Int,
+                        ColumnType::String => ValueType::String,
+                    }),
+                    None => None,
+                };
+                aggregate
+                    .value_from_text(&text, value_type)
+                    .map_err(StoreError::Unknown)
+            })
+            .collect()
+    }
+}
+
+impl<'a> LoadQuery<PgConnection, AggregateData> for AggregateQuery<'a> {
+    fn internal_load(self, conn: &PgConnection) -> QueryResult<Vec<AggregateData>> {
+        conn.query_by_name(&self)
+    }
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for AggregateQuery<'a> {}
+
 /// Reduce the upper bound of the current entry's block range to `block` as
 /// long as that does not result in an empty block range
 #[derive(Debug, Clone, Constructor)]
@@ -954,3 +1626,138 @@ impl<'a> LoadQuery<PgConnection, RevertEntityData> for RevertClampQuery<'a> {
 }
 
 impl<'a, Conn> RunQueryDsl<Conn> for RevertClampQuery<'a> {}
+
+/// A query that removes all versions whose block range ends before
+/// `block`, i.e., all versions that are not visible at `block` or any
+/// later block
+#[derive(Debug, Clone, Constructor)]
+pub struct PruneQuery<'a> {
+    schema: &'a str,
+    table: &'a Table,
+    block: BlockNumber,
+}
+
+impl<'a> QueryFragment<Pg> for PruneQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        // Construct a query
+        //   delete from table
+        //    where upper(block_range) <= $block
+        //
+        // The upper bound of the block range of current versions is
+        // infinite, and `upper` returns null for them
+        out.push_sql("delete from ");
+        out.push_identifier(&self.schema)?;
+        out.push_sql(".");
+        out.push_identifier(self.table.name.as_str())?;
+        out.push_sql("\n where upper(");
+        out.push_identifier(BLOCK_RANGE)?;
+        out.push_sql(") <= ");
+        out.push_bind_param::<Integer, _>(&self.block)
+    }
+}
+
+impl<'a> QueryId for PruneQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for PruneQuery<'a> {}
+
+/// A query that copies the versions of the entities in `src` that are
+/// visible at `block` into `dst`, where they become current as of `block`.
+/// Only the attributes that `dst` has are copied, and its full-text search
+/// documents are computed from the copied attributes
+#[derive(Debug, Clone, Constructor)]
+pub struct CopyEntityQuery<'a> {
+    dst_schema: &'a str,
+    dst: &'a Table,
+    src_schema: &'a str,
+    src: &'a Table,
+    block: BlockNumber,
+}
+
+impl<'a> CopyEntityQuery<'a> {
+    /// The columns of `dst` that can be copied from `src`
+    fn columns(&self) -> Vec<&Column> {
+        self.dst
+            .columns
+            .iter()
+            .filter(|column| {
+                self.src.columns.iter().any(|src| {
+                    src.name == column.name
+                        && src.column_type == column.column_type
+                        && src.is_list() == column.is_list()
+                })
+            })
+            .collect()
+    }
+}
+
+impl<'a> QueryFragment<Pg> for CopyEntityQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        // Construct a query
+        //   insert into dst_schema.dst(column, ..., fulltext, ..., block_range)
+        //   select column, ...,
+        //          to_tsvector($language::regconfig, concat_ws(' ', field, ...)), ...,
+        //          $block_range
+        //     from src_schema.src
+        //    where block_range @> $block
+        let columns = self.columns();
+
+        out.push_sql("insert into ");
+        out.push_identifier(self.dst_schema)?;
+        out.push_sql(".");
+        out.push_identifier(self.dst.name.as_str())?;
+        out.push_sql("(");
+        for column in columns.iter() {
+            out.push_identifier(column.name.as_str())?;
+            out.push_sql(", ");
+        }
+        for column in self.dst.fulltext_columns.iter() {
+            out.push_identifier(column.name.as_str())?;
+            out.push_sql(", ");
+        }
+        out.push_identifier(BLOCK_RANGE)?;
+
+        out.push_sql(")\nselect ");
+        for column in columns.iter() {
+            out.push_identifier(column.name.as_str())?;
+            out.push_sql(", ");
+        }
+        for fulltext in self.dst.fulltext_columns.iter() {
+            out.push_sql("to_tsvector(");
+            out.push_bind_param::<Text, _>(&fulltext.language)?;
+            out.push_sql("::regconfig, concat_ws(' '");
+            for column in columns
+                .iter()
+                .filter(|column| fulltext.fields.contains(&column.field))
+            {
+                out.push_sql(", ");
+                out.push_identifier(column.name.as_str())?;
+            }
+            out.push_sql(")), ");
+        }
+        let block_range: BlockRange = (self.block..).into();
+        out.push_bind_param::<Range<Integer>, _>(&block_range)?;
+
+        out.push_sql("\n  from ");
+        out.push_identifier(self.src_schema)?;
+        out.push_sql(".");
+        out.push_identifier(self.src.name.as_str())?;
+        out.push_sql("\n where ");
+        BlockRangeContainsClause::new(self.block).walk_ast(out)
+    }
+}
+
+impl<'a> QueryId for CopyEntityQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for CopyEntityQuery<'a> {}
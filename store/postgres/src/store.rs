@@ -5,7 +5,8 @@ use diesel::r2d2::{self, ConnectionManager, Pool, PooledConnection};
 use diesel::{insert_into, select, update};
 use futures::sync::mpsc::{channel, Sender};
 use lru_time_cache::LruCache;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
 use std::sync::{Mutex, RwLock};
 use std::time::{Duration, Instant};
 use uuid::Uuid;
@@ -17,6 +18,7 @@ use graph::prelude::serde_json;
 use graph::prelude::{ChainHeadUpdateListener as _, *};
 use graph::util::security::SafeDisplay;
 use graph_graphql::prelude::api_schema;
+use tiny_keccak::Keccak;
 use tokio::timer::Interval;
 use web3::types::H256;
 
@@ -104,6 +106,52 @@ pub struct StoreConfig {
     pub network_name: String,
     pub start_block: u64,
     pub conn_pool_size: u32,
+    /// The URLs of the databases, keyed by shard name, that can hold the
+    /// entities of deployments in addition to the primary database at
+    /// `postgres_url`
+    pub shards: BTreeMap<String, String>,
+    /// The rules for placing new deployments in shards. The first rule that
+    /// matches the name of the subgraph wins; deployments that match no
+    /// rule are placed in the primary database
+    pub placement: Vec<PlacementRule>,
+}
+
+/// Deployments of subgraphs whose name matches `pattern` are placed in
+/// `shard`. The pattern matches names literally, except that a `*` matches
+/// any sequence of characters
+#[derive(Clone, Debug)]
+pub struct PlacementRule {
+    pub shard: String,
+    pub pattern: String,
+}
+
+impl PlacementRule {
+    fn matches(&self, name: &SubgraphName) -> bool {
+        let name = name.to_string();
+        let mut parts = self.pattern.split('*');
+
+        // The part before the first `*` has to match the start of the name
+        let first = parts.next().unwrap_or("");
+        if !name.starts_with(first) {
+            return false;
+        }
+        let mut rest = &name[first.len()..];
+
+        let parts: Vec<_> = parts.collect();
+        match parts.split_last() {
+            // The pattern does not contain a `*`
+            None => rest.is_empty(),
+            Some((last, middle)) => {
+                for part in middle {
+                    match rest.find(part) {
+                        Some(pos) => rest = &rest[pos + part.len()..],
+                        None => return false,
+                    }
+                }
+                rest.ends_with(last)
+            }
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -122,6 +170,10 @@ pub struct Store {
     network_name: String,
     genesis_block_ptr: EthereumBlockPointer,
     conn: Pool<ConnectionManager<PgConnection>>,
+    /// The connection pools for all shards other than the primary database
+    shards: HashMap<String, Pool<ConnectionManager<PgConnection>>>,
+    placement: Vec<PlacementRule>,
+    conn_wait_time: Box<Histogram>,
     schema_cache: Mutex<LruCache<SubgraphDeploymentId, SchemaPair>>,
 }
 
@@ -130,6 +182,7 @@ impl Store {
         config: StoreConfig,
         logger: &Logger,
         net_identifiers: EthereumNetworkIdentifier,
+        registry: Arc<dyn MetricsRegistry>,
     ) -> Self {
         // Create a store-specific logger
         let logger = logger.new(o!("component" => "Store"));
@@ -141,31 +194,70 @@ impl Store {
                 error!(self.0, "Postgres connection error"; "error" => error.to_string())
             }
         }
-        let error_handler = Box::new(ErrorHandler(logger.clone()));
 
         // Connect to Postgres
-        let conn_manager = ConnectionManager::new(config.postgres_url.as_str());
-        let pool = Pool::builder()
-            .error_handler(error_handler)
-            // Set the time we wait for a connection to 6h. The default is 30s
-            // which can be too little if database connections are highly
-            // contended; if we don't get a connection within the timeout,
-            // ultimately subgraphs get marked as failed. This effectively
-            // turns off this timeout and makes it possible that work needing
-            // a database connection blocks for a very long time
-            .connection_timeout(Duration::from_secs(6 * 60 * 60))
-            .max_size(config.conn_pool_size)
-            .build(conn_manager)
-            .unwrap();
-        info!(
-            logger,
-            "Connected to Postgres";
-            "url" => SafeDisplay(config.postgres_url.as_str())
-        );
+        let connect = |shard: &str, url: &str| {
+            let error_handler = Box::new(ErrorHandler(logger.clone()));
+            let conn_manager = ConnectionManager::new(url);
+            let pool = Pool::builder()
+                .error_handler(error_handler)
+                // Set the time we wait for a connection to 6h. The default is 30s
+                // which can be too little if database connections are highly
+                // contended; if we don't get a connection within the timeout,
+                // ultimately subgraphs get marked as failed. This effectively
+                // turns off this timeout and makes it possible that work needing
+                // a database connection blocks for a very long time
+                .connection_timeout(Duration::from_secs(6 * 60 * 60))
+                .max_size(config.conn_pool_size)
+                .build(conn_manager)
+                .unwrap();
+            info!(
+                logger,
+                "Connected to Postgres";
+                "shard" => shard,
+                "url" => SafeDisplay(url)
+            );
+            pool
+        };
+        let pool = connect(PRIMARY_SHARD, config.postgres_url.as_str());
+
+        let mut const_labels = HashMap::new();
+        const_labels.insert(String::from("network"), config.network_name.clone());
+        let conn_wait_time = registry
+            .new_histogram(
+                "store_connection_wait_time_ms",
+                "Time spent waiting for a connection from the Postgres connection pool",
+                const_labels,
+                vec![1.0, 5.0, 10.0, 50.0, 100.0, 500.0, 1000.0, 5000.0, 30000.0],
+            )
+            .expect("failed to create `store_connection_wait_time_ms` histogram");
 
         // Create the entities table (if necessary)
         initiate_schema(&logger, &pool.get().unwrap(), &pool.get().unwrap());
 
+        // Shards hold the entities of deployments, together with the
+        // tables that track their schemas, which the migrations set up
+        let shards: HashMap<_, _> = config
+            .shards
+            .iter()
+            .map(|(shard, url)| {
+                if shard == PRIMARY_SHARD {
+                    panic!("the name `{}` is reserved for the primary shard", shard);
+                }
+                let pool = connect(shard, url);
+                initiate_schema(&logger, &pool.get().unwrap(), &pool.get().unwrap());
+                (shard.clone(), pool)
+            })
+            .collect();
+        for rule in &config.placement {
+            if rule.shard != PRIMARY_SHARD && !shards.contains_key(&rule.shard) {
+                panic!(
+                    "placement rule for `{}` uses unknown shard `{}`",
+                    rule.pattern, rule.shard
+                );
+            }
+        }
+
         // Listen to entity changes in Postgres
         let mut listener = StoreEventListener::new(&logger, config.postgres_url.clone());
         let store_events = listener
@@ -185,6 +277,9 @@ impl Store {
             network_name: config.network_name.clone(),
             genesis_block_ptr: (net_identifiers.genesis_block_hash, config.start_block).into(),
             conn: pool,
+            shards,
+            placement: config.placement,
+            conn_wait_time,
             schema_cache: Mutex::new(LruCache::with_capacity(100)),
         };
 
@@ -339,7 +434,7 @@ impl Store {
     }
 
     /// Gets an entity from Postgres.
-    fn get_entity(
+    pub(crate) fn get_entity(
         &self,
         conn: &e::Connection,
         op_subgraph: &SubgraphDeploymentId,
@@ -361,16 +456,15 @@ impl Store {
             })
     }
 
-    fn execute_query(
-        &self,
-        conn: &e::Connection,
-        query: EntityQuery,
-    ) -> Result<Vec<Entity>, QueryExecutionError> {
-        // Add order by filters to query
-        let order = match query.order_by {
+    /// Translate the order of a query into a tuple
+    /// (attribute, value_type, cast, direction)
+    fn query_order(
+        order_by: Option<(String, ValueType)>,
+        order_direction: Option<EntityOrder>,
+    ) -> Result<Option<(String, ValueType, &'static str, &'static str)>, QueryExecutionError> {
+        Ok(match order_by {
             Some((attribute, value_type)) => {
-                let direction = query
-                    .order_direction
+                let direction = order_direction
                     .map(|direction| match direction {
                         EntityOrder::Ascending => "ASC",
                         EntityOrder::Descending => "DESC",
@@ -392,7 +486,16 @@ impl Store {
                 Some((attribute, value_type, cast_type, direction))
             }
             None => None,
-        };
+        })
+    }
+
+    pub(crate) fn execute_query(
+        &self,
+        conn: &e::Connection,
+        query: EntityQuery,
+    ) -> Result<Vec<Entity>, QueryExecutionError> {
+        // Add order by filters to query
+        let order = Self::query_order(query.order_by, query.order_direction)?;
 
         // Process results; deserialize JSON data
         conn.query(
@@ -402,7 +505,8 @@ impl Store {
             order,
             query.range.first,
             query.range.skip,
-            BLOCK_NUMBER_MAX,
+            query.block,
+            query.after,
         )
     }
 
@@ -652,7 +756,7 @@ impl Store {
         }
     }
 
-    fn apply_metadata_operations_with_conn(
+    pub(crate) fn apply_metadata_operations_with_conn(
         &self,
         econn: &e::Connection,
         operations: Vec<MetadataOperation>,
@@ -700,9 +804,17 @@ impl Store {
     }
 
     fn get_conn(&self) -> Result<PooledConnection<ConnectionManager<PgConnection>>, Error> {
+        self.get_conn_from(&self.conn)
+    }
+
+    fn get_conn_from(
+        &self,
+        pool: &Pool<ConnectionManager<PgConnection>>,
+    ) -> Result<PooledConnection<ConnectionManager<PgConnection>>, Error> {
         let start_time = Instant::now();
-        let conn = self.conn.get();
+        let conn = pool.get();
         let wait = start_time.elapsed();
+        self.conn_wait_time.observe(wait.as_millis() as f64);
         if wait > Duration::from_millis(10) {
             warn!(self.logger, "Possible contention in DB connection pool";
                                "wait_ms" => wait.as_millis())
@@ -710,11 +822,75 @@ impl Store {
         conn.map_err(Error::from)
     }
 
-    fn get_entity_conn(&self) -> Result<e::Connection, Error> {
+    /// Get a connection to the primary database. It can only be used for
+    /// the metadata and for deployments whose entities are stored there
+    pub(crate) fn get_entity_conn(&self) -> Result<e::Connection, Error> {
         let conn = self.get_conn()?;
         Ok(e::Connection::new(conn, self))
     }
 
+    /// Get a connection for working with `subgraph` that is also connected
+    /// to the shard that holds its entities
+    pub(crate) fn get_deployment_conn(
+        &self,
+        subgraph: &SubgraphDeploymentId,
+    ) -> Result<e::Connection, StoreError> {
+        let econn = self.get_entity_conn()?;
+        let shard = self.shard(&econn, subgraph)?;
+        self.connect_shard(econn, subgraph, &shard)
+    }
+
+    /// Add a connection to `shard` for the entities of `subgraph` to `econn`
+    fn connect_shard<'a>(
+        &'a self,
+        econn: e::Connection<'a>,
+        subgraph: &SubgraphDeploymentId,
+        shard: &str,
+    ) -> Result<e::Connection<'a>, StoreError> {
+        if shard == PRIMARY_SHARD {
+            return Ok(econn);
+        }
+        let pool = self.shards.get(shard).ok_or_else(|| {
+            format_err!(
+                "the entities of {} are stored in shard `{}`, which is not configured",
+                subgraph,
+                shard
+            )
+        })?;
+        let conn = self.get_conn_from(pool)?;
+        Ok(econn.with_shard(subgraph.clone(), conn))
+    }
+
+    /// Return the name of the shard that holds the entities of `subgraph`.
+    /// The shard is looked up every time since other nodes can move the
+    /// deployment to a different shard
+    fn shard(
+        &self,
+        econn: &e::Connection,
+        subgraph: &SubgraphDeploymentId,
+    ) -> Result<String, StoreError> {
+        if self.shards.is_empty() || subgraph.is_meta() {
+            return Ok(PRIMARY_SHARD.to_owned());
+        }
+        Ok(self
+            .get_entity(
+                econn,
+                &*SUBGRAPHS_ID,
+                &SubgraphDeploymentEntity::TYPENAME.to_owned(),
+                &subgraph.to_string(),
+            )?
+            .map(|deployment| SubgraphDeploymentEntity::shard(&deployment))
+            .unwrap_or_else(|| PRIMARY_SHARD.to_owned()))
+    }
+
+    /// Pick the shard for a new deployment of the subgraph `name`
+    fn place(&self, name: &SubgraphName) -> &str {
+        self.placement
+            .iter()
+            .find(|rule| rule.matches(name))
+            .map_or(PRIMARY_SHARD, |rule| rule.shard.as_str())
+    }
+
     /// Creates a history event to use when applying entity operations.
     pub fn create_history_event(
         &self,
@@ -727,6 +903,168 @@ impl Store {
         conn.create_history_event(subgraph, event_source)
     }
 
+    fn prune(
+        &self,
+        logger: &Logger,
+        subgraph_id: &SubgraphDeploymentId,
+        reorg_block: BlockNumber,
+    ) -> Result<(), StoreError> {
+        let pruning = self
+            .get(SubgraphDeploymentEntity::key(subgraph_id.clone()))?
+            .and_then(|entity| SubgraphDeploymentEntity::pruning(&entity));
+        let pruning = match pruning {
+            Some(pruning) => pruning,
+            None => return Ok(()),
+        };
+
+        // Never remove history that we might still need to revert blocks
+        let cutoff = pruning.retention_block.map_or(reorg_block, |block| {
+            reorg_block.min(block.min(BLOCK_NUMBER_MAX as u64) as BlockNumber)
+        });
+        if cutoff <= self.earliest_block(subgraph_id)? {
+            return Ok(());
+        }
+
+        let start = Instant::now();
+        let econn = self.get_deployment_conn(subgraph_id)?;
+        let count = econn.removal_transaction(|| -> Result<usize, StoreError> {
+            let count = econn.prune(subgraph_id, cutoff)?;
+            // Record the cutoff last so that the deployment entity, which
+            // is updated for every block, is only locked briefly
+            let ops = SubgraphDeploymentEntity::update_pruned_block_operations(subgraph_id, cutoff);
+            self.apply_metadata_operations_with_conn(&econn, ops)?;
+            Ok(count)
+        })?;
+        econn.vacuum(subgraph_id)?;
+
+        info!(logger, "Pruned entity history";
+                      "subgraph" => subgraph_id.to_string(),
+                      "block" => cutoff,
+                      "versions" => count,
+                      "time_ms" => start.elapsed().as_millis());
+        Ok(())
+    }
+
+    fn copy_graft_base_with_conn(
+        &self,
+        logger: &Logger,
+        econn: &e::Connection,
+        subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<(), StoreError> {
+        let deployment = self.get_entity(
+            econn,
+            &*SUBGRAPHS_ID,
+            &SubgraphDeploymentEntity::TYPENAME.to_owned(),
+            &subgraph_id.to_string(),
+        )?;
+        let (base, graft_block) = match deployment
+            .as_ref()
+            .map(SubgraphDeploymentEntity::graft)
+            .transpose()?
+            .and_then(|graft| graft)
+        {
+            Some(graft) => graft,
+            None => return Ok(()),
+        };
+        let block_ptr = self.block_ptr(subgraph_id.clone())?;
+        if block_ptr.number >= graft_block.number {
+            return Ok(());
+        }
+
+        let block = graft_block.number as BlockNumber;
+        if self.earliest_block(&base)? > block {
+            return Err(format_err!(
+                "graft base `{}` has pruned the history for block {}",
+                base,
+                block
+            )
+            .into());
+        }
+
+        let start = Instant::now();
+        let base_head = self.block_ptr(base.clone())?.number as BlockNumber;
+        let schema = self.raw_subgraph_schema(subgraph_id)?;
+        let history_event = econn
+            .create_history_event(subgraph_id.clone(), EventSource::EthereumBlock(graft_block))?;
+        let base_conn = self.get_deployment_conn(&base)?;
+        let same_database = self.shard(econn, subgraph_id)? == self.shard(econn, &base)?;
+        let count = econn.copy_entities(
+            &base_conn,
+            &base,
+            base_head,
+            &schema,
+            block,
+            &history_event,
+            same_database,
+        )?;
+        econn.update_entity_count(subgraph_id, count)?;
+
+        let mut ops = vec![];
+        let query = DynamicEthereumContractDataSourceEntity::query_up_to(&base, graft_block.number);
+        for data_source in self.execute_query(econn, query)? {
+            ops.extend(DynamicEthereumContractDataSourceEntity::copy_operations(
+                data_source,
+                subgraph_id,
+            )?);
+        }
+        ops.extend(
+            SubgraphDeploymentEntity::update_ethereum_block_pointer_operations(
+                subgraph_id,
+                block_ptr,
+                graft_block,
+            ),
+        );
+        // The graft has no data for blocks before the graft block
+        ops.extend(SubgraphDeploymentEntity::update_pruned_block_operations(
+            subgraph_id,
+            block,
+        ));
+        self.apply_metadata_operations_with_conn(econn, ops)?;
+
+        info!(logger, "Copied graft base";
+                           "subgraph" => subgraph_id.to_string(),
+                           "base" => base.to_string(),
+                           "block" => block,
+                           "entities" => count,
+                           "time_ms" => start.elapsed().as_millis());
+        Ok(())
+    }
+
+    /// Copy the entity tables `tables` of `subgraph_id` from `source` to
+    /// `target` and record that the deployment is now stored in `shard`.
+    /// Returns the number of rows that were copied
+    fn copy_deployment(
+        &self,
+        source: &e::Connection,
+        target: &e::Connection,
+        subgraph_id: &SubgraphDeploymentId,
+        shard: &str,
+        version: e::DeploymentSchemaVersion,
+        tables: &[String],
+    ) -> Result<usize, StoreError> {
+        const PAGE_SIZE: usize = 1000;
+
+        let schema = self.raw_subgraph_schema(subgraph_id)?;
+        e::create_schema_with_version(target.data_conn(subgraph_id), &schema, version)?;
+
+        let mut count = 0;
+        for table in tables {
+            let mut page = Vec::with_capacity(PAGE_SIZE);
+            source.export_table(subgraph_id, table, |row| {
+                page.push(row);
+                if page.len() == PAGE_SIZE {
+                    count += target.import_table(subgraph_id, table, page.drain(..).map(Ok))?;
+                }
+                Ok(())
+            })?;
+            count += target.import_table(subgraph_id, table, page.drain(..).map(Ok))?;
+        }
+
+        let ops = SubgraphDeploymentEntity::update_shard_operations(subgraph_id, shard);
+        self.apply_metadata_operations_with_conn(target, ops)?;
+        Ok(count)
+    }
+
     fn cached_schema(&self, subgraph_id: &SubgraphDeploymentId) -> Result<SchemaPair, Error> {
         if let Some(pair) = self.schema_cache.lock().unwrap().get(&subgraph_id) {
             trace!(self.logger, "schema cache hit"; "id" => subgraph_id.to_string());
@@ -757,9 +1095,35 @@ impl Store {
             }
         };
 
+        let relational =
+            *subgraph_id != *SUBGRAPHS_ID && self.uses_relational_schema(subgraph_id)?;
+        self.cache_schema(subgraph_id, &raw_schema, relational)?;
+        Ok(self
+            .schema_cache
+            .lock()
+            .unwrap()
+            .get(&subgraph_id)
+            .unwrap()
+            .clone())
+    }
+
+    /// Parse `raw_schema` as the schema of `subgraph_id` and put it into the
+    /// schema cache. This makes the schema of a deployment available before
+    /// its metadata has been committed. Full-text search is only supported
+    /// for deployments with `relational` storage; the API schema of other
+    /// deployments does not have search fields
+    pub(crate) fn cache_schema(
+        &self,
+        subgraph_id: &SubgraphDeploymentId,
+        raw_schema: &str,
+        relational: bool,
+    ) -> Result<(), Error> {
         // Parse the schema and add @subgraphId directives
-        let raw_schema = Schema::parse(&raw_schema, subgraph_id.clone())?;
+        let raw_schema = Schema::parse(raw_schema, subgraph_id.clone())?;
         let mut schema = raw_schema.clone();
+        if !relational {
+            schema.remove_fulltext_directives();
+        }
 
         // Generate an API schema for the subgraph and make sure all types in the
         // API schema have a @subgraphId directive as well
@@ -774,8 +1138,7 @@ impl Store {
         // Insert the schema into the cache.
         let mut cache = self.schema_cache.lock().unwrap();
         cache.insert(subgraph_id.clone(), pair);
-
-        Ok(cache.get(&subgraph_id).unwrap().clone())
+        Ok(())
     }
 
     pub(crate) fn raw_subgraph_schema(
@@ -823,25 +1186,19 @@ impl StoreTrait for Store {
     }
 
     fn get(&self, key: EntityKey) -> Result<Option<Entity>, QueryExecutionError> {
-        let conn = self
-            .get_entity_conn()
-            .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
+        let conn = self.get_deployment_conn(&key.subgraph_id)?;
         self.get_entity(&conn, &key.subgraph_id, &key.entity_type, &key.entity_id)
     }
 
     fn find(&self, query: EntityQuery) -> Result<Vec<Entity>, QueryExecutionError> {
-        let conn = self
-            .get_entity_conn()
-            .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
+        let conn = self.get_deployment_conn(&query.subgraph_id)?;
         self.execute_query(&conn, query)
     }
 
     fn find_one(&self, mut query: EntityQuery) -> Result<Option<Entity>, QueryExecutionError> {
         query.range = EntityRange::first(1);
 
-        let conn = self
-            .get_entity_conn()
-            .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
+        let conn = self.get_deployment_conn(&query.subgraph_id)?;
 
         let mut results = self.execute_query(&conn, query)?;
         match results.len() {
@@ -850,6 +1207,45 @@ impl StoreTrait for Store {
         }
     }
 
+    fn find_children(
+        &self,
+        query: EntityQuery,
+        parent_ids: Vec<String>,
+        link: EntityLink,
+    ) -> Result<HashMap<String, Vec<Entity>>, QueryExecutionError> {
+        let conn = self.get_deployment_conn(&query.subgraph_id)?;
+        let order = Self::query_order(query.order_by, query.order_direction)?;
+
+        conn.query_children(
+            &query.subgraph_id,
+            query.entity_types,
+            query.filter,
+            order,
+            query.range.first,
+            query.range.skip,
+            query.block,
+            query.after,
+            parent_ids,
+            link,
+        )
+    }
+
+    fn aggregate(
+        &self,
+        query: EntityQuery,
+        aggregates: Vec<EntityAggregate>,
+    ) -> Result<Vec<Value>, QueryExecutionError> {
+        let conn = self.get_deployment_conn(&query.subgraph_id)?;
+
+        conn.aggregate(
+            &query.subgraph_id,
+            query.entity_types,
+            query.filter,
+            query.block,
+            aggregates,
+        )
+    }
+
     fn find_ens_name(&self, hash: &str) -> Result<Option<String>, QueryExecutionError> {
         use crate::db_schema::ens_names as dsl;
 
@@ -871,6 +1267,48 @@ impl StoreTrait for Store {
             })
     }
 
+    fn block_number(
+        &self,
+        _subgraph_id: &SubgraphDeploymentId,
+        block_hash: H256,
+    ) -> Result<Option<BlockNumber>, StoreError> {
+        // All subgraphs in this store index the same network, and we
+        // therefore check the block against the head of that chain
+        let head = match self.chain_head_ptr()? {
+            Some(head) => head,
+            None => return Ok(None),
+        };
+        let block = match self.block(block_hash)? {
+            Some(block) => EthereumBlockPointer::from(block),
+            None => return Ok(None),
+        };
+        if block.number > head.number {
+            return Ok(None);
+        }
+
+        // The block is on the main chain if it is the ancestor of the
+        // chain head at its height
+        match self.ancestor_block(head, head.number - block.number)? {
+            Some(ancestor) => Ok(Some(EthereumBlockPointer::from(ancestor))
+                .filter(|ancestor| ancestor.hash == block.hash)
+                .map(|ancestor| ancestor.number as BlockNumber)),
+            None => {
+                // The chain store does not have all blocks between the
+                // chain head and the block, which is common for older
+                // blocks. Accept the block if it is the only one at its
+                // height in the chain store
+                use crate::db_schema::ethereum_blocks::dsl::*;
+
+                let blocks_at_height = ethereum_blocks
+                    .filter(network_name.eq(&self.network_name))
+                    .filter(number.eq(block.number as i64))
+                    .count()
+                    .get_result::<i64>(&*self.get_conn()?)?;
+                Ok(Some(block.number as BlockNumber).filter(|_| blocks_at_height == 1))
+            }
+        }
+    }
+
     fn set_block_ptr_with_no_changes(
         &self,
         subgraph_id: SubgraphDeploymentId,
@@ -882,9 +1320,8 @@ impl StoreTrait for Store {
             block_ptr_from,
             block_ptr_to,
         );
-        let conn = self.get_entity_conn().map_err(Error::from)?;
-        conn.conn
-            .transaction(|| self.apply_metadata_operations_with_conn(&conn, ops))?;
+        let conn = self.get_deployment_conn(&subgraph_id)?;
+        conn.transaction(|| self.apply_metadata_operations_with_conn(&conn, ops))?;
 
         conn.should_migrate(&subgraph_id, &block_ptr_to)
     }
@@ -914,9 +1351,9 @@ impl StoreTrait for Store {
             );
         }
 
-        let econn = self.get_entity_conn()?;
+        let econn = self.get_deployment_conn(&subgraph_id)?;
 
-        econn.conn.transaction(|| {
+        econn.transaction(|| {
             // Ensure the history event exists in the database
             let event_source = EventSource::EthereumBlock(block_ptr_to);
             let history_event = econn.create_history_event(subgraph_id.clone(), event_source)?;
@@ -954,10 +1391,15 @@ impl StoreTrait for Store {
         &self,
         indexes: Vec<AttributeIndexDefinition>,
     ) -> Result<(), SubgraphAssignmentProviderError> {
-        let econn = self.get_entity_conn()?;
-        econn
-            .conn
-            .transaction(|| self.build_entity_attribute_indexes_with_conn(&econn, indexes))
+        // All indexes are for the same subgraph
+        let subgraph_id = match indexes.first() {
+            Some(index) => index.subgraph_id.clone(),
+            None => return Ok(()),
+        };
+        let econn = self
+            .get_deployment_conn(&subgraph_id)
+            .map_err(|e| SubgraphAssignmentProviderError::Unknown(e.into()))?;
+        econn.transaction(|| self.build_entity_attribute_indexes_with_conn(&econn, indexes))
     }
 
     fn revert_block_operations(
@@ -971,8 +1413,8 @@ impl StoreTrait for Store {
             panic!("revert_block_operations must revert a single block only");
         }
 
-        let econn = self.get_entity_conn()?;
-        econn.conn.transaction(|| {
+        let econn = self.get_deployment_conn(&subgraph_id)?;
+        econn.removal_transaction(|| {
             let ops = SubgraphDeploymentEntity::update_ethereum_block_pointer_operations(
                 &subgraph_id,
                 block_ptr_from,
@@ -1012,18 +1454,29 @@ impl StoreTrait for Store {
         StoreEventStream::new(Box::new(receiver)).filter_by_entities(entities)
     }
 
+    fn uses_relational_schema(&self, id: &SubgraphDeploymentId) -> Result<bool, StoreError> {
+        self.get_deployment_conn(id)?.uses_relational_schema(id)
+    }
+
     fn create_subgraph_deployment(
         &self,
         subgraph_logger: &Logger,
+        name: &SubgraphName,
         schema: &Schema,
-        ops: Vec<MetadataOperation>,
+        mut ops: Vec<MetadataOperation>,
     ) -> Result<(), StoreError> {
         // Various timing parameters, all in seconds
         const INITIAL_DELAY: u64 = 2;
         const MAX_DELAY: u64 = 64;
         const LOCK_TIMEOUT: u64 = 2;
 
-        let econn = self.get_entity_conn()?;
+        let shard = self.place(name);
+        if shard != PRIMARY_SHARD {
+            ops.extend(SubgraphDeploymentEntity::update_shard_operations(
+                &schema.id, shard,
+            ));
+        }
+        let econn = self.connect_shard(self.get_entity_conn()?, &schema.id, shard)?;
         let mut delay = Duration::from_secs(INITIAL_DELAY);
 
         // Creating a subgraph creates a table that references
@@ -1052,12 +1505,21 @@ impl StoreTrait for Store {
         // and then retry the subgraph creation.
         loop {
             let start = Instant::now();
-            let result = econn.conn.transaction(|| -> Result<(), StoreError> {
+            let result = econn.transaction(|| -> Result<(), StoreError> {
                 self.apply_metadata_operations_with_conn(&econn, ops.clone())?;
-                econn
-                    .conn
-                    .batch_execute(&format!("set local lock_timeout to '{}s'", LOCK_TIMEOUT))?;
-                crate::entities::create_schema(&econn.conn, schema)
+                let conn = econn.data_conn(&schema.id);
+                conn.batch_execute(&format!("set local lock_timeout to '{}s'", LOCK_TIMEOUT))?;
+                if shard == PRIMARY_SHARD {
+                    e::create_schema(conn, schema)
+                } else {
+                    // Only relational storage is supported outside of the
+                    // primary shard
+                    e::create_schema_with_version(
+                        conn,
+                        schema,
+                        e::DeploymentSchemaVersion::Relational,
+                    )
+                }
             });
             if let Err(StoreError::Unknown(_)) = &result {
                 // There is no robust way to actually find out that we timed
@@ -1088,10 +1550,19 @@ impl StoreTrait for Store {
         subgraph_id: &SubgraphDeploymentId,
         ops: Vec<MetadataOperation>,
     ) -> Result<(), StoreError> {
-        let econn = self.get_entity_conn()?;
+        let econn = self.get_deployment_conn(subgraph_id)?;
 
-        econn.conn.transaction(|| {
+        econn.transaction(|| {
             self.apply_metadata_operations_with_conn(&econn, ops)?;
+            if econn.is_sharded(subgraph_id) {
+                // Writing the entities may have succeeded when updating
+                // the block pointer did not
+                let block_ptr = self.block_ptr(subgraph_id.clone())?;
+                let (event, count) = econn.revert_to_block_ptr(subgraph_id, &block_ptr)?;
+                econn.update_entity_count(subgraph_id, count)?;
+                let v = serde_json::to_value(event)?;
+                JsonNotification::send("store_events", &v, &*econn.conn)?;
+            }
             econn.start_subgraph(subgraph_id)
         })
     }
@@ -1102,7 +1573,7 @@ impl StoreTrait for Store {
         subgraph_id: &SubgraphDeploymentId,
         block_ptr: &EthereumBlockPointer,
     ) {
-        let econn = match self.get_entity_conn() {
+        let econn = match self.get_deployment_conn(subgraph_id) {
             Ok(econn) => econn,
             Err(e) => {
                 warn!(logger, "failed to get connection to start migrating";
@@ -1122,6 +1593,91 @@ impl StoreTrait for Store {
             );
         }
     }
+
+    fn copy_graft_base(
+        &self,
+        logger: &Logger,
+        subgraph_id: &SubgraphDeploymentId,
+    ) -> Result<(), StoreError> {
+        let econn = self.get_deployment_conn(subgraph_id)?;
+        econn.transaction(|| self.copy_graft_base_with_conn(logger, &econn, subgraph_id))
+    }
+
+    fn prune_subgraph_deployment(
+        &self,
+        logger: &Logger,
+        subgraph_id: &SubgraphDeploymentId,
+        reorg_block: BlockNumber,
+    ) {
+        if let Err(e) = self.prune(logger, subgraph_id, reorg_block) {
+            warn!(logger, "aborted pruning";
+                            "subgraph" => subgraph_id.to_string(),
+                            "error" => e.to_string(),
+            );
+        }
+    }
+
+    fn export_subgraph_deployment(
+        &self,
+        logger: &Logger,
+        subgraph_id: &SubgraphDeploymentId,
+        dir: &Path,
+    ) -> Result<(), StoreError> {
+        crate::export::export(self, logger, subgraph_id, dir)
+    }
+
+    fn import_subgraph_deployment(
+        &self,
+        logger: &Logger,
+        dir: &Path,
+    ) -> Result<SubgraphDeploymentId, StoreError> {
+        crate::export::import(self, logger, dir)
+    }
+
+    fn move_subgraph_deployment(
+        &self,
+        logger: &Logger,
+        subgraph_id: &SubgraphDeploymentId,
+        shard: &str,
+    ) -> Result<(), StoreError> {
+        if shard != PRIMARY_SHARD && !self.shards.contains_key(shard) {
+            return Err(format_err!("unknown shard `{}`", shard).into());
+        }
+        let source = self.get_deployment_conn(subgraph_id)?;
+        if self.shard(&source, subgraph_id)? == shard {
+            return Ok(());
+        }
+        let (version, tables) = source.entity_tables(subgraph_id)?;
+        if let e::DeploymentSchemaVersion::Split = version {
+            if shard != PRIMARY_SHARD {
+                return Err(format_err!(
+                    "subgraph {} uses JSONB storage and can not be moved out of the primary shard",
+                    subgraph_id
+                )
+                .into());
+            }
+        }
+        let target = self.connect_shard(self.get_entity_conn()?, subgraph_id, shard)?;
+
+        let start = Instant::now();
+        let conn = source.data_conn(subgraph_id);
+        let count = conn.transaction(|| -> Result<usize, StoreError> {
+            // Keep the deployment from changing while we copy it
+            source.lock_entity_tables(subgraph_id)?;
+            let count = target.transaction(|| {
+                self.copy_deployment(&source, &target, subgraph_id, shard, version, &tables)
+            })?;
+            e::drop_schema(conn, subgraph_id)?;
+            Ok(count)
+        })?;
+
+        info!(logger, "Moved subgraph deployment";
+                      "subgraph" => subgraph_id.to_string(),
+                      "shard" => shard,
+                      "rows" => count,
+                      "time_ms" => start.elapsed().as_millis());
+        Ok(())
+    }
 }
 
 impl SubgraphDeploymentStore for Store {
@@ -1260,7 +1816,95 @@ impl ChainStore for Store {
     }
 }
 
+/// The key under which the return value of a call is stored in the
+/// `eth_call_cache` table
+fn contract_call_id(
+    contract_address: &ethabi::Address,
+    encoded_call: &[u8],
+    block: &EthereumBlockPointer,
+) -> [u8; 32] {
+    let mut id = [0u8; 32];
+    let mut hash = Keccak::new_keccak256();
+    hash.update(&contract_address[..]);
+    hash.update(encoded_call);
+    hash.update(&block.hash[..]);
+    hash.finalize(&mut id);
+    id
+}
+
+impl EthereumCallCache for Store {
+    fn get_call(
+        &self,
+        contract_address: ethabi::Address,
+        encoded_call: &[u8],
+        block: EthereumBlockPointer,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        use crate::db_schema::eth_call_cache::dsl::*;
+
+        let call_id = contract_call_id(&contract_address, encoded_call, &block);
+        eth_call_cache
+            .select(return_value)
+            .filter(id.eq(&call_id[..]))
+            .first::<Vec<u8>>(&*self.get_conn()?)
+            .optional()
+            .map_err(Error::from)
+    }
+
+    fn set_call(
+        &self,
+        contract_address: ethabi::Address,
+        encoded_call: &[u8],
+        block: EthereumBlockPointer,
+        return_value: &[u8],
+    ) -> Result<(), Error> {
+        use crate::db_schema::eth_call_cache::dsl as cache;
+
+        let call_id = contract_call_id(&contract_address, encoded_call, &block);
+
+        // Calls are deterministic for a given block hash, so if another
+        // node already cached this call, the value is the same
+        insert_into(cache::eth_call_cache)
+            .values((
+                cache::id.eq(&call_id[..]),
+                cache::return_value.eq(return_value),
+                cache::contract_address.eq(&contract_address[..]),
+                cache::block_number.eq(block.number as i32),
+            ))
+            .on_conflict_do_nothing()
+            .execute(&*self.get_conn()?)
+            .map(|_| ())
+            .map_err(Error::from)
+    }
+}
+
 /// Delete all entities. This function exists solely for integration tests
 /// and should never be called from any other code. Unfortunately, Rust makes
 /// it very hard to export items just for testing
 pub use crate::entities::delete_all_entities_for_test_use_only;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        let rule = PlacementRule {
+            shard: "shard".to_owned(),
+            pattern: pattern.to_owned(),
+        };
+        rule.matches(&SubgraphName::new(name).unwrap())
+    }
+
+    #[test]
+    fn placement_rule_matches() {
+        assert!(matches("user/subgraph", "user/subgraph"));
+        assert!(!matches("user/subgraph", "user/subgraph2"));
+        assert!(matches("*", "user/subgraph"));
+        assert!(matches("user/*", "user/subgraph"));
+        assert!(!matches("user/*", "other/subgraph"));
+        assert!(matches("*/subgraph", "user/subgraph"));
+        assert!(matches("user*graph", "user/subgraph"));
+        assert!(matches("u*/*b*h", "user/subgraph"));
+        assert!(!matches("u*/*x*h", "user/subgraph"));
+        assert!(!matches("user*user", "user"));
+    }
+}
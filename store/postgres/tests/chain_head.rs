@@ -8,8 +8,8 @@ use lazy_static::lazy_static;
 use std::fmt::Debug;
 use std::sync::Arc;
 
-use graph::components::store::ChainStore;
-use graph::prelude::serde_json;
+use graph::components::store::{ChainStore, EthereumCallCache};
+use graph::prelude::{serde_json, EthereumBlockPointer};
 use graph_store_postgres::db_schema_for_tests as db_schema;
 use graph_store_postgres::Store as DieselStore;
 
@@ -109,6 +109,9 @@ fn remove_test_data() {
     diesel::delete(db_schema::ethereum_networks::table)
         .execute(&conn)
         .expect("Failed to delete ethereum_networks");
+    diesel::delete(db_schema::eth_call_cache::table)
+        .execute(&conn)
+        .expect("Failed to delete eth_call_cache");
 }
 
 fn insert_test_data(_store: Arc<DieselStore>, chain: Chain) {
@@ -263,3 +266,38 @@ fn long_chain_with_uncles() {
     ];
     check_chain_head_update(chain, Some(&*BLOCK_FOUR), None);
 }
+
+#[test]
+fn eth_call_cache() {
+    let chain = vec![&*BLOCK_ONE, &*BLOCK_TWO];
+
+    run_test(chain, |store| -> Result<(), ()> {
+        let address = "0123123123012312312301231231230123123123".parse().unwrap();
+        let call: [u8; 6] = [1, 2, 3, 4, 5, 6];
+        let return_value: [u8; 3] = [7, 8, 9];
+        let block_one = EthereumBlockPointer::from((BLOCK_ONE.hash.parse().unwrap(), 1u64));
+        let block_two = EthereumBlockPointer::from((BLOCK_TWO.hash.parse().unwrap(), 2u64));
+
+        store
+            .set_call(address, &call, block_one, &return_value)
+            .expect("set_call failed");
+
+        // The call is cached for the block it was made against
+        let cached = store
+            .get_call(address, &call, block_one)
+            .expect("get_call failed");
+        assert_eq!(Some(return_value.to_vec()), cached);
+
+        // The same call against another block is not cached
+        let cached = store
+            .get_call(address, &call, block_two)
+            .expect("get_call failed");
+        assert_eq!(None, cached);
+
+        // Caching the same call again is not an error
+        store
+            .set_call(address, &call, block_one, &return_value)
+            .expect("set_call failed");
+        Ok(())
+    })
+}
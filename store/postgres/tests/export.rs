@@ -0,0 +1,257 @@
+//! Test exporting a deployment into a directory and importing it again
+use diesel::dsl::sql;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use futures::future;
+use hex_literal::hex;
+use lazy_static::lazy_static;
+use std::path::PathBuf;
+
+use graph::data::subgraph::schema::*;
+use graph::data::subgraph::*;
+use graph::prelude::*;
+use graph_store_postgres::Store as DieselStore;
+use web3::types::H256;
+
+use test_store::*;
+
+const THING_GQL: &str = "
+    type Thing @entity {
+        id: ID!,
+        name: String!
+    }
+";
+
+lazy_static! {
+    static ref THING_SUBGRAPH_ID: SubgraphDeploymentId =
+        SubgraphDeploymentId::new("exportedThings").unwrap();
+    static ref THING_SCHEMA: Schema =
+        Schema::parse(THING_GQL, THING_SUBGRAPH_ID.clone()).expect("failed to parse schema");
+    static ref BLOCK_TWO: EthereumBlockPointer = (
+        H256::from(hex!(
+            "b98fb783b49de5652097a989414c767824dff7e7fd765a63b493772511db81c1"
+        )),
+        2u64
+    )
+        .into();
+}
+
+fn remove_test_data() {
+    let conn =
+        PgConnection::establish(&postgres_test_url()).expect("failed to connect to Postgres");
+    graph_store_postgres::store::delete_all_entities_for_test_use_only(&conn)
+        .expect("failed to remove entity test data");
+}
+
+/// The directory that the test deployment is exported into; it does not
+/// exist when the test starts
+fn export_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("graph-export-test-{}", std::process::id()));
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).expect("failed to remove export directory");
+    }
+    dir
+}
+
+fn run_test<F>(test: F)
+where
+    F: FnOnce(Arc<DieselStore>, PathBuf) + Send + 'static,
+{
+    let store = STORE.clone();
+
+    // Lock regardless of poisoning. This also forces sequential test execution.
+    let mut runtime = match STORE_RUNTIME.lock() {
+        Ok(guard) => guard,
+        Err(err) => err.into_inner(),
+    };
+
+    runtime
+        .block_on(future::lazy(move || -> Result<(), ()> {
+            remove_test_data();
+            let dir = export_dir();
+            test(store, dir.clone());
+            std::fs::remove_dir_all(&dir).ok();
+            Ok(())
+        }))
+        .expect("failed to run export test");
+}
+
+/// Create the test deployment with relational or JSONB storage, and change
+/// its entities in blocks 1 and 2
+fn create_deployment(store: &Arc<DieselStore>, relational: bool) {
+    let relational_before = std::env::var_os("RELATIONAL_SCHEMA");
+    if relational {
+        std::env::set_var("RELATIONAL_SCHEMA", "on");
+    } else {
+        std::env::remove_var("RELATIONAL_SCHEMA");
+    }
+
+    let manifest = SubgraphManifest {
+        id: THING_SUBGRAPH_ID.clone(),
+        location: "/ipfs/test".to_owned(),
+        spec_version: "1".to_owned(),
+        description: None,
+        repository: None,
+        schema: THING_SCHEMA.clone(),
+        data_sources: vec![],
+        templates: vec![],
+        pruning: None,
+        graft: None,
+    };
+    let ops = SubgraphDeploymentEntity::new(&manifest, false, false, *GENESIS_PTR, None)
+        .create_operations(&*THING_SUBGRAPH_ID);
+    store
+        .create_subgraph_deployment(
+            &*LOGGER,
+            &SubgraphName::new("export/things").unwrap(),
+            &*THING_SCHEMA,
+            ops,
+        )
+        .unwrap();
+
+    match relational_before {
+        Some(value) => std::env::set_var("RELATIONAL_SCHEMA", value),
+        None => std::env::remove_var("RELATIONAL_SCHEMA"),
+    }
+
+    transact_entity_operations(
+        store,
+        THING_SUBGRAPH_ID.clone(),
+        *GENESIS_PTR,
+        *BLOCK_ONE,
+        vec![set_thing("1", "one")],
+    )
+    .unwrap();
+    transact_entity_operations(
+        store,
+        THING_SUBGRAPH_ID.clone(),
+        *BLOCK_ONE,
+        *BLOCK_TWO,
+        vec![set_thing("1", "uno"), set_thing("2", "two")],
+    )
+    .unwrap();
+}
+
+fn set_thing(id: &str, name: &str) -> EntityOperation {
+    let mut data = Entity::new();
+    data.set("id", id);
+    data.set("name", name);
+    EntityOperation::Set {
+        key: thing_key(id),
+        data,
+    }
+}
+
+fn thing_key(id: &str) -> EntityKey {
+    EntityKey {
+        subgraph_id: THING_SUBGRAPH_ID.clone(),
+        entity_type: "Thing".to_owned(),
+        entity_id: id.to_owned(),
+    }
+}
+
+fn thing_name(store: &DieselStore, id: &str) -> Option<String> {
+    store
+        .get(thing_key(id))
+        .unwrap()
+        .map(|thing| thing.get("name").unwrap().clone().as_string().unwrap())
+}
+
+/// All versions of the things in the test deployment together with their
+/// block ranges
+fn thing_versions() -> String {
+    let conn =
+        PgConnection::establish(&postgres_test_url()).expect("failed to connect to Postgres");
+    let schema = diesel::select(sql::<Text>(&format!(
+        "(select name from deployment_schemas where subgraph = '{}')",
+        THING_SUBGRAPH_ID.as_str()
+    )))
+    .get_result::<String>(&conn)
+    .expect("failed to look up the schema of the deployment");
+    diesel::select(sql::<Text>(&format!(
+        "(select string_agg(id || ':' || name || ':' || block_range::text, ', ' \
+                            order by id, lower(block_range)) \
+            from \"{}\".thing)",
+        schema
+    )))
+    .get_result::<String>(&conn)
+    .expect("failed to read the versions of things")
+}
+
+/// The metadata that describes the test deployment; the shard is not part
+/// of an export
+fn deployment_metadata(store: &DieselStore) -> Vec<Entity> {
+    let mut deployment = store
+        .get(SubgraphDeploymentEntity::key(THING_SUBGRAPH_ID.clone()))
+        .unwrap()
+        .expect("deployment exists");
+    deployment.remove("shard");
+    let manifest = store
+        .get(SubgraphManifestEntity::key(SubgraphManifestEntity::id(
+            &*THING_SUBGRAPH_ID,
+        )))
+        .unwrap()
+        .expect("manifest exists");
+    vec![deployment, manifest]
+}
+
+#[test]
+fn export_and_import_relational() {
+    run_test(|store, dir| {
+        create_deployment(&store, true);
+        let versions = thing_versions();
+        let metadata = deployment_metadata(&store);
+        assert_eq!("1:one:[1,2), 1:uno:[2,), 2:two:[2,)", versions);
+
+        store
+            .export_subgraph_deployment(&*LOGGER, &*THING_SUBGRAPH_ID, &dir)
+            .unwrap();
+        remove_test_data();
+        let id = store.import_subgraph_deployment(&*LOGGER, &dir).unwrap();
+
+        assert_eq!(*THING_SUBGRAPH_ID, id);
+        assert_eq!(*BLOCK_TWO, store.block_ptr(id.clone()).unwrap());
+        assert_eq!(metadata, deployment_metadata(&store));
+        assert_eq!(versions, thing_versions());
+
+        // The history of the entities was imported, too
+        store
+            .revert_block_operations(id.clone(), *BLOCK_TWO, *BLOCK_ONE)
+            .unwrap();
+        assert_eq!(Some("one".to_owned()), thing_name(&store, "1"));
+        assert_eq!(None, thing_name(&store, "2"));
+    })
+}
+
+#[test]
+fn export_and_import_json() {
+    run_test(|store, dir| {
+        create_deployment(&store, false);
+        let metadata = deployment_metadata(&store);
+
+        store
+            .export_subgraph_deployment(&*LOGGER, &*THING_SUBGRAPH_ID, &dir)
+            .unwrap();
+        remove_test_data();
+        let id = store.import_subgraph_deployment(&*LOGGER, &dir).unwrap();
+
+        assert_eq!(*THING_SUBGRAPH_ID, id);
+        assert_eq!(*BLOCK_TWO, store.block_ptr(id.clone()).unwrap());
+        assert_eq!(metadata, deployment_metadata(&store));
+        assert_eq!(Some("uno".to_owned()), thing_name(&store, "1"));
+        assert_eq!(Some("two".to_owned()), thing_name(&store, "2"));
+
+        // The history of the entities was imported, and importing the
+        // entities did not add to it
+        store
+            .revert_block_operations(id.clone(), *BLOCK_TWO, *BLOCK_ONE)
+            .unwrap();
+        assert_eq!(Some("one".to_owned()), thing_name(&store, "1"));
+        assert_eq!(None, thing_name(&store, "2"));
+        store
+            .revert_block_operations(id.clone(), *BLOCK_ONE, *GENESIS_PTR)
+            .unwrap();
+        assert_eq!(None, thing_name(&store, "1"));
+    })
+}
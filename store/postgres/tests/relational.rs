@@ -10,8 +10,9 @@ use std::str::FromStr;
 
 use graph::data::store::scalar::{BigDecimal, BigInt, Bytes};
 use graph::prelude::{
-    bigdecimal::One, web3::types::H256, Entity, EntityFilter, EntityKey, EntityOrder, EntityQuery,
-    EntityRange, Schema, SubgraphDeploymentId, Value, ValueType,
+    bigdecimal::One, web3::types::H256, Entity, EntityAggregate, EntityFilter, EntityKey,
+    EntityLink, EntityOrder, EntityQuery, EntityRange, Schema, SubgraphDeploymentId, Value,
+    ValueType,
 };
 use graph_store_postgres::layout_for_tests::{Layout, BLOCK_NUMBER_MAX};
 
@@ -67,6 +68,13 @@ const THINGS_GQL: &str = "
         favorite_color: String,
         drinks: [String!]
     }
+
+    type _Schema_ @fulltext(
+        name: \"userSearch\",
+        language: simple,
+        algorithm: rank,
+        include: [{ entity: \"User\", fields: [{ name: \"name\" }, { name: \"email\" }] }]
+    )
 ";
 
 const SCHEMA_NAME: &str = "layout";
@@ -371,6 +379,88 @@ fn update() {
     });
 }
 
+#[test]
+fn find_at_block() {
+    run_test(|conn, layout| -> Result<(), ()> {
+        insert_entity(&conn, &layout, "Scalar", SCALAR_ENTITY.clone());
+
+        let mut entity = SCALAR_ENTITY.clone();
+        entity.set("string", "updated");
+        let key = EntityKey {
+            subgraph_id: THINGS_SUBGRAPH_ID.clone(),
+            entity_type: "Scalar".to_owned(),
+            entity_id: entity.id().unwrap().clone(),
+        };
+        layout
+            .update(&conn, &key, &entity, 3)
+            .expect("Failed to update");
+
+        // Before the update, we see the original version
+        let actual = layout
+            .find(conn, "Scalar", "one", 2)
+            .expect("Failed to read Scalar[one]")
+            .unwrap();
+        assert_entity_eq!(scrub(&*SCALAR_ENTITY), actual);
+
+        // From the block of the update on, we see the updated version
+        let actual = layout
+            .find(conn, "Scalar", "one", 3)
+            .expect("Failed to read Scalar[one]")
+            .unwrap();
+        assert_entity_eq!(scrub(&entity), actual);
+        Ok(())
+    });
+}
+
+#[test]
+fn prune() {
+    run_test(|conn, layout| -> Result<(), ()> {
+        insert_entity(&conn, &layout, "Scalar", SCALAR_ENTITY.clone());
+        let mut two = SCALAR_ENTITY.clone();
+        two.set("id", "two");
+        insert_entity(&conn, &layout, "Scalar", two);
+
+        let mut entity = SCALAR_ENTITY.clone();
+        let mut key = EntityKey {
+            subgraph_id: THINGS_SUBGRAPH_ID.clone(),
+            entity_type: "Scalar".to_owned(),
+            entity_id: entity.id().unwrap().clone(),
+        };
+        entity.set("string", "updated at 3");
+        layout
+            .update(&conn, &key, &entity, 3)
+            .expect("Failed to update");
+        entity.set("string", "updated at 5");
+        layout
+            .update(&conn, &key, &entity, 5)
+            .expect("Failed to update");
+        key.entity_id = "two".to_owned();
+        layout.delete(&conn, &key, 4).expect("Failed to delete");
+
+        // Removes the original version of `one`, and `two`, which was
+        // deleted at block 4
+        let count = layout.prune(&conn, 4).expect("Failed to prune");
+        assert_eq!(2, count);
+
+        // Versions that are visible at block 4 or later are still there
+        let actual = layout
+            .find(conn, "Scalar", "one", 4)
+            .expect("Failed to read Scalar[one]")
+            .unwrap();
+        assert_eq!(Some(&Value::from("updated at 3")), actual.get("string"));
+        let actual = layout
+            .find(conn, "Scalar", "one", BLOCK_NUMBER_MAX)
+            .expect("Failed to read Scalar[one]")
+            .unwrap();
+        assert_entity_eq!(scrub(&entity), actual);
+
+        // Pruning again does not remove anything
+        let count = layout.prune(&conn, 4).expect("Failed to prune");
+        assert_eq!(0, count);
+        Ok(())
+    });
+}
+
 fn count_scalar_entities(conn: &PgConnection, layout: &Layout) -> usize {
     let filter = EntityFilter::Or(vec![
         EntityFilter::Equal("bool".into(), true.into()),
@@ -385,6 +475,7 @@ fn count_scalar_entities(conn: &PgConnection, layout: &Layout) -> usize {
             None,
             0,
             BLOCK_NUMBER_MAX,
+            None,
         )
         .expect("Count query failed")
         .len()
@@ -483,7 +574,8 @@ fn test_find(expected_entity_ids: Vec<&str>, query: EntityQuery) {
                 order,
                 query.range.first,
                 query.range.skip,
-                BLOCK_NUMBER_MAX,
+                query.block,
+                query.after,
             )
             .expect("layout.query failed to execute query");
 
@@ -516,6 +608,265 @@ fn find_string_contains() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
+        },
+    )
+}
+
+#[test]
+fn find_after() {
+    test_find(
+        vec!["2", "3"],
+        EntityQuery::new(
+            THINGS_SUBGRAPH_ID.clone(),
+            vec!["User".to_owned()],
+            EntityRange::first(100),
+        )
+        .order_by(("id".to_owned(), ValueType::String), EntityOrder::Ascending)
+        .after("1".to_owned()),
+    );
+    test_find(
+        vec!["1", "2"],
+        EntityQuery::new(
+            THINGS_SUBGRAPH_ID.clone(),
+            vec!["User".to_owned()],
+            EntityRange::first(100),
+        )
+        .order_by(
+            ("name".to_owned(), ValueType::String),
+            EntityOrder::Descending,
+        )
+        .after("3".to_owned()),
+    );
+    test_find(
+        vec![],
+        EntityQuery::new(
+            THINGS_SUBGRAPH_ID.clone(),
+            vec!["User".to_owned()],
+            EntityRange::first(100),
+        )
+        .order_by(
+            ("name".to_owned(), ValueType::String),
+            EntityOrder::Ascending,
+        )
+        .after("3".to_owned()),
+    )
+}
+
+#[test]
+fn find_children() {
+    fn children_ids(
+        conn: &PgConnection,
+        layout: &Layout,
+        parent_ids: Vec<&str>,
+        link: EntityLink,
+    ) -> Vec<(String, Vec<String>)> {
+        let children = layout
+            .query_children(
+                conn,
+                vec!["User".to_owned()],
+                None,
+                Some(("name".to_owned(), ValueType::String, "", "ASC")),
+                Some(2),
+                0,
+                BLOCK_NUMBER_MAX,
+                None,
+                parent_ids.iter().map(|id| id.to_string()).collect(),
+                link,
+            )
+            .expect("layout.query_children failed to execute query");
+
+        let mut children: Vec<_> = children
+            .into_iter()
+            .map(|(parent_id, entities)| {
+                let ids = entities
+                    .into_iter()
+                    .map(|entity| match entity.get("id") {
+                        Some(Value::String(id)) => id.to_owned(),
+                        _ => panic!("layout.query_children returned entity without ID"),
+                    })
+                    .collect();
+                (parent_id, ids)
+            })
+            .collect();
+        children.sort();
+        children
+    }
+
+    let expected = |children: Vec<(&str, Vec<&str>)>| -> Vec<(String, Vec<String>)> {
+        children
+            .into_iter()
+            .map(|(parent_id, ids)| {
+                (
+                    parent_id.to_owned(),
+                    ids.into_iter().map(str::to_owned).collect(),
+                )
+            })
+            .collect()
+    };
+
+    run_test(move |conn, layout| -> Result<(), ()> {
+        insert_users(conn, layout);
+
+        // Parents that reference their children; `first` applies to each
+        // parent separately
+        let link = EntityLink::Direct {
+            child_ids: vec![
+                vec!["1".to_owned(), "2".to_owned(), "3".to_owned()],
+                vec!["3".to_owned()],
+                vec![],
+            ],
+        };
+        assert_eq!(
+            expected(vec![("p1", vec!["2", "1"]), ("p2", vec!["3"])]),
+            children_ids(conn, layout, vec!["p1", "p2", "p3"], link)
+        );
+
+        // Children that hold the id of their parent
+        let link = EntityLink::Derived {
+            attribute: "favorite_color".to_owned(),
+            list: false,
+        };
+        assert_eq!(
+            expected(vec![("red", vec!["2"])]),
+            children_ids(conn, layout, vec!["red", "blue"], link)
+        );
+
+        // Children that hold a list of parent ids
+        let link = EntityLink::Derived {
+            attribute: "drinks".to_owned(),
+            list: true,
+        };
+        assert_eq!(
+            expected(vec![
+                ("beer", vec!["2"]),
+                ("coffee", vec!["3"]),
+                ("tea", vec!["3"])
+            ]),
+            children_ids(conn, layout, vec!["beer", "tea", "coffee", "milk"], link)
+        );
+        Ok(())
+    })
+}
+
+#[test]
+fn aggregate() {
+    run_test(|conn, layout| -> Result<(), ()> {
+        insert_users(conn, layout);
+
+        let aggregates = vec![
+            EntityAggregate::Count,
+            EntityAggregate::Sum("age".to_owned()),
+            EntityAggregate::Min("age".to_owned()),
+            EntityAggregate::Max("age".to_owned()),
+            EntityAggregate::Avg("age".to_owned()),
+            EntityAggregate::Sum("seconds_age".to_owned()),
+        ];
+        let aggregate = |filter: Option<EntityFilter>| {
+            layout
+                .aggregate(
+                    conn,
+                    vec!["User".to_owned()],
+                    filter,
+                    BLOCK_NUMBER_MAX,
+                    &aggregates,
+                )
+                .expect("layout.aggregate failed to execute query")
+        };
+
+        assert_eq!(
+            vec![
+                Value::BigInt(BigInt::from(3)),
+                Value::BigInt(BigInt::from(138)),
+                Value::Int(28),
+                Value::Int(67),
+                Value::BigDecimal(BigDecimal::from_str("46").unwrap()),
+                Value::BigInt(BigInt::from(138) * 31557600.into()),
+            ],
+            aggregate(None)
+        );
+
+        let filter = EntityFilter::GreaterThan("age".to_owned(), Value::Int(30));
+        assert_eq!(
+            vec![
+                Value::BigInt(BigInt::from(2)),
+                Value::BigInt(BigInt::from(110)),
+                Value::Int(43),
+                Value::Int(67),
+                Value::BigDecimal(BigDecimal::from_str("55").unwrap()),
+                Value::BigInt(BigInt::from(110) * 31557600.into()),
+            ],
+            aggregate(Some(filter))
+        );
+
+        // Aggregates other than `count` are null if no entity matches
+        let filter = EntityFilter::GreaterThan("age".to_owned(), Value::Int(100));
+        assert_eq!(
+            vec![
+                Value::BigInt(BigInt::from(0)),
+                Value::Null,
+                Value::Null,
+                Value::Null,
+                Value::Null,
+                Value::Null,
+            ],
+            aggregate(Some(filter))
+        );
+        Ok(())
+    })
+}
+
+#[test]
+fn find_fulltext() {
+    test_find(
+        vec!["2"],
+        EntityQuery {
+            subgraph_id: THINGS_SUBGRAPH_ID.clone(),
+            entity_types: vec!["user".to_owned()],
+            filter: Some(EntityFilter::And(vec![EntityFilter::Fulltext(
+                "userSearch".into(),
+                "cindini".into(),
+            )])),
+            order_by: None,
+            order_direction: None,
+            range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
+        },
+    );
+    // The text is plain text, not `tsquery` syntax: punctuation is
+    // ignored, and all words have to match
+    test_find(
+        vec!["1"],
+        EntityQuery {
+            subgraph_id: THINGS_SUBGRAPH_ID.clone(),
+            entity_types: vec!["user".to_owned()],
+            filter: Some(EntityFilter::And(vec![EntityFilter::Fulltext(
+                "userSearch".into(),
+                "johnton ) (".into(),
+            )])),
+            order_by: None,
+            order_direction: None,
+            range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
+        },
+    );
+    test_find(
+        vec![],
+        EntityQuery {
+            subgraph_id: THINGS_SUBGRAPH_ID.clone(),
+            entity_types: vec!["user".to_owned()],
+            filter: Some(EntityFilter::And(vec![EntityFilter::Fulltext(
+                "userSearch".into(),
+                "johnton | cindini".into(),
+            )])),
+            order_by: None,
+            order_direction: None,
+            range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -537,6 +888,8 @@ fn find_list_contains() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         }
     }
 
@@ -562,6 +915,8 @@ fn find_string_equal() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -580,6 +935,8 @@ fn find_string_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -598,6 +955,8 @@ fn find_string_greater_than() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -616,6 +975,8 @@ fn find_string_less_than_order_by_asc() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -634,6 +995,8 @@ fn find_string_less_than_order_by_desc() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -655,6 +1018,8 @@ fn find_string_less_than_range() {
                 first: Some(1),
                 skip: 1,
             },
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -673,6 +1038,8 @@ fn find_string_multiple_and() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -691,6 +1058,8 @@ fn find_string_ends_with() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -709,6 +1078,8 @@ fn find_string_not_ends_with() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -727,6 +1098,8 @@ fn find_string_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -745,6 +1118,8 @@ fn find_string_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -763,6 +1138,8 @@ fn find_float_equal() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -781,6 +1158,8 @@ fn find_float_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -799,6 +1178,8 @@ fn find_float_greater_than() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -817,6 +1198,8 @@ fn find_float_less_than() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -835,6 +1218,8 @@ fn find_float_less_than_order_by_desc() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -856,6 +1241,8 @@ fn find_float_less_than_range() {
                 first: Some(1),
                 skip: 1,
             },
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -877,6 +1264,8 @@ fn find_float_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -898,6 +1287,8 @@ fn find_float_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -916,6 +1307,8 @@ fn find_int_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -934,6 +1327,8 @@ fn find_int_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -952,6 +1347,8 @@ fn find_int_greater_than() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -970,6 +1367,8 @@ fn find_int_greater_or_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -988,6 +1387,8 @@ fn find_int_less_than() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1006,6 +1407,8 @@ fn find_int_less_or_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1024,6 +1427,8 @@ fn find_int_less_than_order_by_desc() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1045,6 +1450,8 @@ fn find_int_less_than_range() {
                 first: Some(1),
                 skip: 1,
             },
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1063,6 +1470,8 @@ fn find_int_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1081,6 +1490,8 @@ fn find_int_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1099,6 +1510,8 @@ fn find_bool_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1117,6 +1530,8 @@ fn find_bool_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1135,6 +1550,8 @@ fn find_bool_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1153,6 +1570,8 @@ fn find_bool_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1171,6 +1590,8 @@ fn find_bytes_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1189,6 +1610,8 @@ fn find_null_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1204,6 +1627,8 @@ fn find_null_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1222,6 +1647,8 @@ fn find_null_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     );
 
@@ -1237,6 +1664,8 @@ fn find_null_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     );
 }
@@ -1252,6 +1681,8 @@ fn find_order_by_float() {
             order_by: Some(("weight".to_owned(), ValueType::BigDecimal)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     );
     test_find(
@@ -1263,6 +1694,8 @@ fn find_order_by_float() {
             order_by: Some(("weight".to_owned(), ValueType::BigDecimal)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     );
 }
@@ -1278,6 +1711,8 @@ fn find_order_by_id() {
             order_by: Some(("id".to_owned(), ValueType::ID)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     );
     test_find(
@@ -1289,6 +1724,8 @@ fn find_order_by_id() {
             order_by: Some(("id".to_owned(), ValueType::ID)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     );
 }
@@ -1304,6 +1741,8 @@ fn find_order_by_int() {
             order_by: Some(("age".to_owned(), ValueType::Int)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     );
     test_find(
@@ -1315,6 +1754,8 @@ fn find_order_by_int() {
             order_by: Some(("age".to_owned(), ValueType::Int)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     );
 }
@@ -1330,6 +1771,8 @@ fn find_order_by_string() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     );
     test_find(
@@ -1341,6 +1784,8 @@ fn find_order_by_string() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     );
 }
@@ -1359,6 +1804,8 @@ fn find_where_nested_and_or() {
             order_by: Some(("id".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -0,0 +1,297 @@
+//! Test storing deployments in a shard, i.e., a database other than the
+//! primary database
+use diesel::connection::SimpleConnection as _;
+use diesel::dsl::sql;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_types::BigInt as SqlBigInt;
+use futures::future;
+use hex_literal::hex;
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+
+use graph::data::subgraph::schema::*;
+use graph::data::subgraph::*;
+use graph::prelude::*;
+use graph_store_postgres::{PlacementRule, Store as DieselStore};
+use web3::types::H256;
+
+use test_store::*;
+
+const SHARD: &str = "shard";
+
+const THING_GQL: &str = "
+    type Thing @entity {
+        id: ID!,
+        name: String!
+    }
+";
+
+lazy_static! {
+    static ref THING_SUBGRAPH_ID: SubgraphDeploymentId =
+        SubgraphDeploymentId::new("shardedThings").unwrap();
+    static ref THING_SCHEMA: Schema =
+        Schema::parse(THING_GQL, THING_SUBGRAPH_ID.clone()).expect("failed to parse schema");
+    static ref BLOCK_TWO: EthereumBlockPointer = (
+        H256::from(hex!(
+            "b98fb783b49de5652097a989414c767824dff7e7fd765a63b493772511db81c1"
+        )),
+        2u64
+    )
+        .into();
+    static ref SHARD_URL: String = create_shard_database();
+
+    // A store that places all subgraphs whose name starts with `shard/`
+    // into the shard
+    static ref SHARDED_STORE: Arc<DieselStore> = {
+        STORE_RUNTIME.lock().unwrap().block_on(future::lazy(|| -> Result<_, ()> {
+            let mut shards = BTreeMap::new();
+            shards.insert(SHARD.to_owned(), SHARD_URL.clone());
+            let placement = vec![PlacementRule {
+                shard: SHARD.to_owned(),
+                pattern: "shard/*".to_owned(),
+            }];
+            Ok(Arc::new(create_store(shards, placement)))
+        })).expect("could not create sharded store")
+    };
+}
+
+/// Create a second database next to the test database and return its URL
+fn create_shard_database() -> String {
+    let url = postgres_test_url();
+    let (base, params) = match url.find('?') {
+        Some(pos) => url.split_at(pos),
+        None => (url.as_str(), ""),
+    };
+    let pos = base
+        .rfind('/')
+        .expect("test database URL has no database name");
+    let name = format!("{}_shard", &base[pos + 1..]);
+
+    let conn = PgConnection::establish(&url).expect("failed to connect to Postgres");
+    // The database most likely exists already if this fails
+    conn.batch_execute(&format!("create database \"{}\"", name))
+        .ok();
+    format!("{}/{}{}", &base[..pos], name, params)
+}
+
+fn remove_test_data() {
+    for url in &[postgres_test_url(), SHARD_URL.clone()] {
+        let conn = PgConnection::establish(url).expect("failed to connect to Postgres");
+        graph_store_postgres::store::delete_all_entities_for_test_use_only(&conn)
+            .expect("failed to remove entity test data");
+    }
+}
+
+/// Whether the database at `url` holds the entities of the test subgraph
+fn holds_entities(url: &str) -> bool {
+    let conn = PgConnection::establish(url).expect("failed to connect to Postgres");
+    let count = diesel::select(sql::<SqlBigInt>(&format!(
+        "(select count(*) from deployment_schemas where subgraph = '{}')",
+        THING_SUBGRAPH_ID.as_str()
+    )))
+    .get_result::<i64>(&conn)
+    .expect("failed to query deployment_schemas");
+    count > 0
+}
+
+fn run_test<F>(test: F)
+where
+    F: FnOnce(Arc<DieselStore>) + Send + 'static,
+{
+    let store = SHARDED_STORE.clone();
+
+    // Lock regardless of poisoning. This also forces sequential test execution.
+    let mut runtime = match STORE_RUNTIME.lock() {
+        Ok(guard) => guard,
+        Err(err) => err.into_inner(),
+    };
+
+    runtime
+        .block_on(future::lazy(move || -> Result<(), ()> {
+            remove_test_data();
+            test(store);
+            Ok(())
+        }))
+        .expect("failed to run shard test");
+}
+
+fn create_deployment(store: &DieselStore, name: &str) {
+    let manifest = SubgraphManifest {
+        id: THING_SUBGRAPH_ID.clone(),
+        location: "/ipfs/test".to_owned(),
+        spec_version: "1".to_owned(),
+        description: None,
+        repository: None,
+        schema: THING_SCHEMA.clone(),
+        data_sources: vec![],
+        templates: vec![],
+        pruning: None,
+        graft: None,
+    };
+    let ops = SubgraphDeploymentEntity::new(&manifest, false, false, *GENESIS_PTR, None)
+        .create_operations(&*THING_SUBGRAPH_ID);
+    store
+        .create_subgraph_deployment(
+            &*LOGGER,
+            &SubgraphName::new(name).unwrap(),
+            &*THING_SCHEMA,
+            ops,
+        )
+        .unwrap();
+}
+
+fn set_thing(id: &str, name: &str) -> EntityOperation {
+    let mut data = Entity::new();
+    data.set("id", id);
+    data.set("name", name);
+    EntityOperation::Set {
+        key: thing_key(id),
+        data,
+    }
+}
+
+fn thing_key(id: &str) -> EntityKey {
+    EntityKey {
+        subgraph_id: THING_SUBGRAPH_ID.clone(),
+        entity_type: "Thing".to_owned(),
+        entity_id: id.to_owned(),
+    }
+}
+
+fn thing_name(store: &DieselStore, id: &str) -> Option<String> {
+    store
+        .get(thing_key(id))
+        .unwrap()
+        .map(|thing| thing.get("name").unwrap().clone().as_string().unwrap())
+}
+
+fn shard_of(store: &DieselStore) -> String {
+    let deployment = store
+        .get(SubgraphDeploymentEntity::key(THING_SUBGRAPH_ID.clone()))
+        .unwrap()
+        .expect("deployment exists");
+    SubgraphDeploymentEntity::shard(&deployment)
+}
+
+#[test]
+fn place_write_and_revert_in_shard() {
+    run_test(|store| {
+        create_deployment(&store, "shard/things");
+        assert_eq!(SHARD, shard_of(&store));
+        assert!(holds_entities(&SHARD_URL));
+        assert!(!holds_entities(&postgres_test_url()));
+
+        let store = &store;
+        transact_entity_operations(
+            store,
+            THING_SUBGRAPH_ID.clone(),
+            *GENESIS_PTR,
+            *BLOCK_ONE,
+            vec![set_thing("1", "one")],
+        )
+        .unwrap();
+        transact_entity_operations(
+            store,
+            THING_SUBGRAPH_ID.clone(),
+            *BLOCK_ONE,
+            *BLOCK_TWO,
+            vec![set_thing("1", "uno"), set_thing("2", "two")],
+        )
+        .unwrap();
+        assert_eq!(Some("uno".to_owned()), thing_name(store, "1"));
+        assert_eq!(Some("two".to_owned()), thing_name(store, "2"));
+
+        store
+            .revert_block_operations(THING_SUBGRAPH_ID.clone(), *BLOCK_TWO, *BLOCK_ONE)
+            .unwrap();
+        assert_eq!(
+            *BLOCK_ONE,
+            store.block_ptr(THING_SUBGRAPH_ID.clone()).unwrap()
+        );
+        assert_eq!(Some("one".to_owned()), thing_name(store, "1"));
+        assert_eq!(None, thing_name(store, "2"));
+    })
+}
+
+#[test]
+fn place_in_primary() {
+    run_test(|store| {
+        create_deployment(&store, "other/things");
+        assert_eq!(PRIMARY_SHARD, shard_of(&store));
+        assert!(!holds_entities(&SHARD_URL));
+        assert!(holds_entities(&postgres_test_url()));
+    })
+}
+
+#[test]
+fn move_between_shards() {
+    run_test(|store| {
+        // Only relational deployments can be moved into a shard
+        std::env::set_var("RELATIONAL_SCHEMA", "on");
+        create_deployment(&store, "other/things");
+        std::env::remove_var("RELATIONAL_SCHEMA");
+
+        let store = &store;
+        transact_entity_operations(
+            store,
+            THING_SUBGRAPH_ID.clone(),
+            *GENESIS_PTR,
+            *BLOCK_ONE,
+            vec![set_thing("1", "one")],
+        )
+        .unwrap();
+        transact_entity_operations(
+            store,
+            THING_SUBGRAPH_ID.clone(),
+            *BLOCK_ONE,
+            *BLOCK_TWO,
+            vec![set_thing("1", "uno"), set_thing("2", "two")],
+        )
+        .unwrap();
+
+        store
+            .move_subgraph_deployment(&*LOGGER, &*THING_SUBGRAPH_ID, SHARD)
+            .unwrap();
+        assert_eq!(SHARD, shard_of(store));
+        assert!(holds_entities(&SHARD_URL));
+        assert!(!holds_entities(&postgres_test_url()));
+        assert_eq!(Some("uno".to_owned()), thing_name(store, "1"));
+        assert_eq!(Some("two".to_owned()), thing_name(store, "2"));
+
+        // The history of the entities was moved, too
+        store
+            .revert_block_operations(THING_SUBGRAPH_ID.clone(), *BLOCK_TWO, *BLOCK_ONE)
+            .unwrap();
+        assert_eq!(Some("one".to_owned()), thing_name(store, "1"));
+        assert_eq!(None, thing_name(store, "2"));
+
+        store
+            .move_subgraph_deployment(&*LOGGER, &*THING_SUBGRAPH_ID, PRIMARY_SHARD)
+            .unwrap();
+        assert_eq!(PRIMARY_SHARD, shard_of(store));
+        assert!(!holds_entities(&SHARD_URL));
+        assert!(holds_entities(&postgres_test_url()));
+        assert_eq!(Some("one".to_owned()), thing_name(store, "1"));
+
+        let err = store
+            .move_subgraph_deployment(&*LOGGER, &*THING_SUBGRAPH_ID, "nowhere")
+            .unwrap_err();
+        assert!(err.to_string().contains("unknown shard"));
+    })
+}
+
+#[test]
+fn refuse_moving_json_to_shard() {
+    run_test(|store| {
+        std::env::remove_var("RELATIONAL_SCHEMA");
+        create_deployment(&store, "other/things");
+
+        let err = store
+            .move_subgraph_deployment(&*LOGGER, &*THING_SUBGRAPH_ID, SHARD)
+            .unwrap_err();
+        assert!(err.to_string().contains("JSONB storage"));
+        assert_eq!(PRIMARY_SHARD, shard_of(&store));
+        assert!(holds_entities(&postgres_test_url()));
+    })
+}
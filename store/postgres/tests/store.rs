@@ -41,6 +41,7 @@ lazy_static! {
     static ref TEST_SUBGRAPH_ID_STRING: String = String::from("testsubgraph");
     static ref TEST_SUBGRAPH_ID: SubgraphDeploymentId =
         SubgraphDeploymentId::new(TEST_SUBGRAPH_ID_STRING.as_str()).unwrap();
+    static ref TEST_SUBGRAPH_NAME: SubgraphName = SubgraphName::new("test/store").unwrap();
     static ref TEST_SUBGRAPH_SCHEMA: Schema =
         Schema::parse(USER_GQL, TEST_SUBGRAPH_ID.clone()).expect("Failed to parse user schema");
     static ref TEST_BLOCK_0_PTR: EthereumBlockPointer = (
@@ -145,6 +146,8 @@ fn insert_test_data(store: Arc<DieselStore>) {
         schema: TEST_SUBGRAPH_SCHEMA.clone(),
         data_sources: vec![],
         templates: vec![],
+        pruning: None,
+        graft: None,
     };
 
     // Create SubgraphDeploymentEntity
@@ -157,7 +160,7 @@ fn insert_test_data(store: Arc<DieselStore>) {
     )
     .create_operations(&*TEST_SUBGRAPH_ID);
     store
-        .create_subgraph_deployment(&*LOGGER, &TEST_SUBGRAPH_SCHEMA, ops)
+        .create_subgraph_deployment(&*LOGGER, &TEST_SUBGRAPH_NAME, &TEST_SUBGRAPH_SCHEMA, ops)
         .unwrap();
 
     let test_entity_1 = create_test_entity(
@@ -583,6 +586,8 @@ fn find_string_contains() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -601,6 +606,8 @@ fn find_string_equal() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -619,6 +626,8 @@ fn find_string_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -637,6 +646,8 @@ fn find_string_greater_than() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -655,6 +666,8 @@ fn find_string_less_than_order_by_asc() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -673,6 +686,8 @@ fn find_string_less_than_order_by_desc() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -694,6 +709,8 @@ fn find_string_less_than_range() {
                 first: Some(1),
                 skip: 1,
             },
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -712,6 +729,8 @@ fn find_string_multiple_and() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -730,6 +749,8 @@ fn find_string_ends_with() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -748,6 +769,8 @@ fn find_string_not_ends_with() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -766,6 +789,8 @@ fn find_string_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -784,6 +809,8 @@ fn find_string_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -802,6 +829,8 @@ fn find_float_equal() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -820,6 +849,8 @@ fn find_float_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -838,6 +869,8 @@ fn find_float_greater_than() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -856,6 +889,8 @@ fn find_float_less_than() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -874,6 +909,8 @@ fn find_float_less_than_order_by_desc() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -895,6 +932,8 @@ fn find_float_less_than_range() {
                 first: Some(1),
                 skip: 1,
             },
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -916,6 +955,8 @@ fn find_float_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -937,6 +978,8 @@ fn find_float_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -955,6 +998,8 @@ fn find_int_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -973,6 +1018,8 @@ fn find_int_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -991,6 +1038,8 @@ fn find_int_greater_than() {
             order_by: None,
             order_direction: None,
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1009,6 +1058,8 @@ fn find_int_greater_or_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1027,6 +1078,8 @@ fn find_int_less_than() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1045,6 +1098,8 @@ fn find_int_less_or_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1063,6 +1118,8 @@ fn find_int_less_than_order_by_desc() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1084,6 +1141,8 @@ fn find_int_less_than_range() {
                 first: Some(1),
                 skip: 1,
             },
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1102,6 +1161,8 @@ fn find_int_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1120,6 +1181,8 @@ fn find_int_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1138,6 +1201,8 @@ fn find_bool_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1156,6 +1221,8 @@ fn find_bool_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1174,6 +1241,8 @@ fn find_bool_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1192,6 +1261,8 @@ fn find_bool_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(5),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1210,6 +1281,8 @@ fn find_bytes_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1228,6 +1301,8 @@ fn find_null_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1243,6 +1318,8 @@ fn find_null_not_equal() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1261,6 +1338,8 @@ fn find_null_not_in() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
@@ -1276,6 +1355,8 @@ fn find_order_by_float() {
             order_by: Some(("weight".to_owned(), ValueType::BigDecimal)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     );
     test_find(
@@ -1287,6 +1368,8 @@ fn find_order_by_float() {
             order_by: Some(("weight".to_owned(), ValueType::BigDecimal)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     );
 }
@@ -1302,6 +1385,8 @@ fn find_order_by_id() {
             order_by: Some(("id".to_owned(), ValueType::ID)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     );
     test_find(
@@ -1313,6 +1398,8 @@ fn find_order_by_id() {
             order_by: Some(("id".to_owned(), ValueType::ID)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     );
 }
@@ -1328,6 +1415,8 @@ fn find_order_by_int() {
             order_by: Some(("age".to_owned(), ValueType::Int)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     );
     test_find(
@@ -1339,6 +1428,8 @@ fn find_order_by_int() {
             order_by: Some(("age".to_owned(), ValueType::Int)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     );
 }
@@ -1354,6 +1445,8 @@ fn find_order_by_string() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     );
     test_find(
@@ -1365,6 +1458,8 @@ fn find_order_by_string() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     );
 }
@@ -1383,10 +1478,93 @@ fn find_where_nested_and_or() {
             order_by: Some(("id".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Ascending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         },
     )
 }
 
+#[test]
+fn find_children() {
+    fn children_ids(
+        store: &DieselStore,
+        parent_ids: Vec<&str>,
+        link: EntityLink,
+    ) -> Vec<(String, Vec<String>)> {
+        let query = EntityQuery::new(
+            TEST_SUBGRAPH_ID.clone(),
+            vec![USER.to_owned()],
+            EntityRange::first(2),
+        )
+        .order_by(
+            ("name".to_owned(), ValueType::String),
+            EntityOrder::Ascending,
+        );
+        let children = store
+            .find_children(
+                query,
+                parent_ids.into_iter().map(str::to_owned).collect(),
+                link,
+            )
+            .expect("store.find_children failed to execute query");
+
+        let mut children: Vec<_> = children
+            .into_iter()
+            .map(|(parent_id, entities)| {
+                let ids = entities
+                    .into_iter()
+                    .map(|entity| match entity.get("id") {
+                        Some(Value::String(id)) => id.to_owned(),
+                        _ => panic!("store.find_children returned entity without ID"),
+                    })
+                    .collect();
+                (parent_id, ids)
+            })
+            .collect();
+        children.sort();
+        children
+    }
+
+    fn expected(children: Vec<(&str, Vec<&str>)>) -> Vec<(String, Vec<String>)> {
+        children
+            .into_iter()
+            .map(|(parent_id, ids)| {
+                (
+                    parent_id.to_owned(),
+                    ids.into_iter().map(str::to_owned).collect(),
+                )
+            })
+            .collect()
+    }
+
+    run_test(|store| -> Result<(), ()> {
+        // Parents that reference their children; `first` applies to each
+        // parent separately
+        let link = EntityLink::Direct {
+            child_ids: vec![
+                vec!["1".to_owned(), "2".to_owned(), "3".to_owned()],
+                vec!["3".to_owned()],
+                vec![],
+            ],
+        };
+        assert_eq!(
+            expected(vec![("a", vec!["2", "1"]), ("b", vec!["3"])]),
+            children_ids(&store, vec!["a", "b", "c"], link)
+        );
+
+        // Children that reference their parent
+        let link = EntityLink::Derived {
+            attribute: "favorite_color".to_owned(),
+            list: false,
+        };
+        assert_eq!(
+            expected(vec![("red", vec!["2"])]),
+            children_ids(&store, vec!["red", "blue"], link)
+        );
+        Ok(())
+    })
+}
+
 fn make_entity_change(
     entity_type: &str,
     entity_id: &str,
@@ -1508,6 +1686,8 @@ fn check_basic_revert(
         order_by: Some(("name".to_owned(), ValueType::String)),
         order_direction: Some(EntityOrder::Descending),
         range: EntityRange::first(100),
+        block: BLOCK_NUMBER_MAX,
+        after: None,
     };
 
     let subscription = subscribe_and_consume(store.clone(), subgraph_id, entity_type);
@@ -1580,6 +1760,8 @@ fn revert_block_with_delete() {
             order_by: Some(("name".to_owned(), ValueType::String)),
             order_direction: Some(EntityOrder::Descending),
             range: EntityRange::first(100),
+            block: BLOCK_NUMBER_MAX,
+            after: None,
         };
 
         // Delete entity with id=2
@@ -1717,6 +1899,7 @@ fn mock_data_source(path: &str) -> DataSource {
         source: Source {
             address: Some(Address::from_str("0123123123012312312301231231230123123123").unwrap()),
             abi: String::from("123123"),
+            start_block: 0,
         },
         mapping: Mapping {
             kind: String::from("ethereum/events"),
@@ -1754,6 +1937,7 @@ fn mock_data_source(path: &str) -> DataSource {
                 runtime: Arc::new(runtime),
             },
         }],
+        context: None,
     }
 }
 
@@ -1915,6 +2099,8 @@ fn entity_changes_are_fired_and_forwarded_to_subscriptions() {
             schema: schema.clone(),
             data_sources: vec![],
             templates: vec![],
+            pruning: None,
+            graft: None,
         };
 
         // Create SubgraphDeploymentEntity
@@ -1927,7 +2113,7 @@ fn entity_changes_are_fired_and_forwarded_to_subscriptions() {
         )
         .create_operations(&subgraph_id);
         store
-            .create_subgraph_deployment(&*LOGGER, &schema, ops)
+            .create_subgraph_deployment(&*LOGGER, &TEST_SUBGRAPH_NAME, &schema, ops)
             .unwrap();
 
         // Create store subscriptions
@@ -2251,7 +2437,7 @@ fn create_subgraph_deployment_tolerates_locks() {
         barrier.wait();
         let start = std::time::Instant::now();
         store
-            .create_subgraph_deployment(&*LOGGER, &schema, vec![])
+            .create_subgraph_deployment(&*LOGGER, &TEST_SUBGRAPH_NAME, &schema, vec![])
             .expect("Subgraph creation failed");
         if std::env::var_os("RELATIONAL_SCHEMA").is_none() {
             // This test makes no sense for relational schemas as we do
@@ -2334,3 +2520,257 @@ fn handle_large_string_with_index() {
         Ok(())
     })
 }
+
+#[test]
+fn find_at_block_requires_relational_storage() {
+    run_test(|store| -> Result<(), ()> {
+        let query = EntityQuery::new(
+            TEST_SUBGRAPH_ID.clone(),
+            vec![USER.to_owned()],
+            EntityRange::first(5),
+        )
+        .at_block(1);
+
+        let result = store.find(query);
+        if std::env::var_os("RELATIONAL_SCHEMA").is_none() {
+            // JSONB storage only has the latest version of each entity
+            match result {
+                Err(QueryExecutionError::NotSupported(msg)) => assert!(msg.contains("JSONB")),
+                Err(e) => panic!("unexpected error: {}", e),
+                Ok(_) => panic!("JSONB storage must not answer queries at a block"),
+            }
+        } else {
+            assert!(result.is_ok());
+        }
+        Ok(())
+    })
+}
+
+/// Create a deployment with the user schema that is grafted onto the test
+/// subgraph at `block`, and copy the data of the test subgraph into it
+fn create_graft(store: &DieselStore, id: &SubgraphDeploymentId, block: EthereumBlockPointer) {
+    let schema = Schema::parse(USER_GQL, id.clone()).expect("Failed to parse user schema");
+    let manifest = SubgraphManifest {
+        id: id.clone(),
+        location: "/ipfs/test".to_owned(),
+        spec_version: "1".to_owned(),
+        description: None,
+        repository: None,
+        schema: schema.clone(),
+        data_sources: vec![],
+        templates: vec![],
+        pruning: None,
+        graft: Some(Graft {
+            base: TEST_SUBGRAPH_ID.clone(),
+            block: block.number,
+        }),
+    };
+    let ops = SubgraphDeploymentEntity::new(&manifest, false, false, *TEST_BLOCK_0_PTR, None)
+        .with_graft(TEST_SUBGRAPH_ID.clone(), block)
+        .create_operations(id);
+    store
+        .create_subgraph_deployment(&*LOGGER, &TEST_SUBGRAPH_NAME, &schema, ops)
+        .unwrap();
+    store.copy_graft_base(&*LOGGER, id).unwrap();
+}
+
+fn get_user(store: &DieselStore, subgraph_id: &SubgraphDeploymentId, id: &str) -> Option<Entity> {
+    store
+        .get(EntityKey {
+            subgraph_id: subgraph_id.clone(),
+            entity_type: USER.to_owned(),
+            entity_id: id.to_owned(),
+        })
+        .unwrap()
+}
+
+fn dynamic_data_sources(store: &DieselStore, subgraph_id: &SubgraphDeploymentId) -> Vec<Entity> {
+    store
+        .find(
+            DynamicEthereumContractDataSourceEntity::query().filter(EntityFilter::new_equal(
+                "deployment",
+                subgraph_id.to_string(),
+            )),
+        )
+        .unwrap()
+}
+
+#[test]
+fn copy_graft_base() {
+    run_test(|store| -> Result<(), ()> {
+        // Add a dynamic data source to the base in block 4
+        let data_source = mock_data_source("../../runtime/wasm/wasm_test/abort.wasm");
+        let dynamic_ds = DynamicEthereumContractDataSourceEntity::from((
+            &TEST_SUBGRAPH_ID.clone(),
+            &data_source,
+            &TEST_BLOCK_4_PTR.clone(),
+        ));
+        transact_entity_operations(
+            &store,
+            TEST_SUBGRAPH_ID.clone(),
+            *TEST_BLOCK_3_PTR,
+            *TEST_BLOCK_4_PTR,
+            dynamic_ds.write_entity_operations("dynamic-data-source"),
+        )
+        .unwrap();
+
+        let graft_id = SubgraphDeploymentId::new("graftAtBlockFour").unwrap();
+        create_graft(&store, &graft_id, *TEST_BLOCK_4_PTR);
+
+        assert_eq!(
+            *TEST_BLOCK_4_PTR,
+            store.block_ptr(graft_id.clone()).unwrap()
+        );
+        for id in &["1", "2", "3"] {
+            let user = get_user(&store, &graft_id, id).expect("user was copied");
+            assert_eq!(get_user(&store, &TEST_SUBGRAPH_ID, id), Some(user));
+        }
+        let copies = dynamic_data_sources(&store, &graft_id);
+        assert_eq!(1, copies.len());
+        assert_eq!(
+            Some(&Value::from(format!("{}-dynamic-data-source", graft_id))),
+            copies[0].get("id")
+        );
+
+        // Only relational storage has the history needed to graft onto an
+        // earlier block
+        if std::env::var_os("RELATIONAL_SCHEMA").is_some() {
+            let graft_id = SubgraphDeploymentId::new("graftAtBlockTwo").unwrap();
+            create_graft(&store, &graft_id, *TEST_BLOCK_2_PTR);
+
+            assert_eq!(
+                *TEST_BLOCK_2_PTR,
+                store.block_ptr(graft_id.clone()).unwrap()
+            );
+            let user = get_user(&store, &graft_id, "3").expect("user was copied");
+            assert_eq!(Some(&Value::from("queensha@email.com")), user.get("email"));
+            assert!(dynamic_data_sources(&store, &graft_id).is_empty());
+        }
+        Ok(())
+    })
+}
+
+/// Put the test blocks into the block cache. Pruning JSONB storage looks
+/// up the number of the block of each entry in the entity history there
+fn insert_test_blocks(conn: &PgConnection) {
+    use graph_store_postgres::db_schema_for_tests::ethereum_blocks as b;
+    use graph_store_postgres::db_schema_for_tests::ethereum_networks as n;
+
+    diesel::insert_into(n::table)
+        .values((
+            n::name.eq(NETWORK_NAME),
+            n::genesis_block_hash.eq(TEST_BLOCK_0_PTR.hash_hex()),
+            n::net_version.eq(NETWORK_VERSION),
+        ))
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .expect("Failed to insert test network");
+
+    let blocks = vec![
+        *TEST_BLOCK_0_PTR,
+        *TEST_BLOCK_1_PTR,
+        *TEST_BLOCK_2_PTR,
+        *TEST_BLOCK_3_PTR,
+    ];
+    for block in blocks {
+        diesel::insert_into(b::table)
+            .values((
+                b::hash.eq(block.hash_hex()),
+                b::number.eq(block.number as i64),
+                b::network_name.eq(NETWORK_NAME),
+                b::data.eq(serde_json::json!({ "number": block.number })),
+            ))
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .expect("Failed to insert test block");
+    }
+}
+
+/// Count the versions of entities of `subgraph` that are kept for
+/// reverting blocks and querying earlier blocks: the entries in the entity
+/// history for JSONB storage, and the rows in the `user` table for
+/// relational storage
+fn version_count(conn: &PgConnection, subgraph: &SubgraphDeploymentId) -> i64 {
+    let schema = diesel::select(dsl::sql::<sql_types::Text>(&format!(
+        "(select name from deployment_schemas where subgraph = '{}')",
+        subgraph
+    )))
+    .get_result::<String>(conn)
+    .expect("Failed to look up the schema of the subgraph");
+    let table = match std::env::var_os("RELATIONAL_SCHEMA") {
+        Some(_) => "user",
+        None => "entity_history",
+    };
+    diesel::select(dsl::sql::<sql_types::BigInt>(&format!(
+        "(select count(*) from \"{}\".\"{}\")",
+        schema, table
+    )))
+    .get_result::<i64>(conn)
+    .expect("Failed to count entity versions")
+}
+
+#[test]
+fn prune_entity_history() {
+    run_test(|store| -> Result<(), ()> {
+        let conn = PgConnection::establish(&postgres_test_url()).unwrap();
+        insert_test_blocks(&conn);
+
+        let subgraph_id = SubgraphDeploymentId::new("pruneSubgraph").unwrap();
+        let schema =
+            Schema::parse(USER_GQL, subgraph_id.clone()).expect("Failed to parse user schema");
+        let manifest = SubgraphManifest {
+            id: subgraph_id.clone(),
+            location: "/ipfs/test".to_owned(),
+            spec_version: "1".to_owned(),
+            description: None,
+            repository: None,
+            schema: schema.clone(),
+            data_sources: vec![],
+            templates: vec![],
+            pruning: Some(Pruning {
+                retention_block: None,
+            }),
+            graft: None,
+        };
+        let ops = SubgraphDeploymentEntity::new(&manifest, false, false, *TEST_BLOCK_0_PTR, None)
+            .create_operations(&subgraph_id);
+        store
+            .create_subgraph_deployment(&*LOGGER, &TEST_SUBGRAPH_NAME, &schema, ops)
+            .unwrap();
+
+        // Change the user in each of the blocks 1, 2 and 3
+        let blocks = vec![
+            *TEST_BLOCK_0_PTR,
+            *TEST_BLOCK_1_PTR,
+            *TEST_BLOCK_2_PTR,
+            *TEST_BLOCK_3_PTR,
+        ];
+        for (window, name) in blocks.windows(2).zip(vec!["one", "two", "three"]) {
+            let data = Entity::from(vec![("id", Value::from("1")), ("name", Value::from(name))]);
+            let op = EntityOperation::Set {
+                key: EntityKey {
+                    subgraph_id: subgraph_id.clone(),
+                    entity_type: USER.to_owned(),
+                    entity_id: "1".to_owned(),
+                },
+                data,
+            };
+            transact_entity_operations(&store, subgraph_id.clone(), window[0], window[1], vec![op])
+                .unwrap();
+        }
+        assert_eq!(3, version_count(&conn, &subgraph_id));
+
+        // Blocks before block 2 can not be reverted anymore
+        store.prune_subgraph_deployment(&*LOGGER, &subgraph_id, 2);
+        assert_eq!(2, store.earliest_block(&subgraph_id).unwrap());
+        assert_eq!(2, version_count(&conn, &subgraph_id));
+
+        // The history that is still needed to revert block 3 was kept
+        store
+            .revert_block_operations(subgraph_id.clone(), *TEST_BLOCK_3_PTR, *TEST_BLOCK_2_PTR)
+            .unwrap();
+        let user = get_user(&store, &subgraph_id, "1").expect("user exists");
+        assert_eq!(Some(&Value::from("two")), user.get("name"));
+        Ok(())
+    })
+}
@@ -2,9 +2,11 @@ use crate::tokio::runtime::Runtime;
 use graph::log;
 #[allow(unused_imports)]
 use graph::prelude::{Store as _, *};
-use graph_store_postgres::{Store, StoreConfig};
+use graph_mock::MockMetricsRegistry;
+use graph_store_postgres::{PlacementRule, Store, StoreConfig};
 use hex_literal::hex;
 use lazy_static::lazy_static;
+use std::collections::BTreeMap;
 use std::env;
 use std::sync::Mutex;
 use web3::types::H256;
@@ -30,24 +32,7 @@ lazy_static! {
     // Create Store instance once for use with each of the tests.
     pub static ref STORE: Arc<Store> = {
         STORE_RUNTIME.lock().unwrap().block_on(future::lazy(|| -> Result<_, ()> {
-            // Set up Store
-            let logger = &*LOGGER;
-            let postgres_url = postgres_test_url();
-            let net_identifiers = EthereumNetworkIdentifier {
-                net_version: NETWORK_VERSION.to_owned(),
-                genesis_block_hash: GENESIS_PTR.hash,
-            };
-
-            Ok(Arc::new(Store::new(
-                StoreConfig {
-                    postgres_url,
-                    network_name: NETWORK_NAME.to_owned(),
-                    start_block: 0u64,
-                    conn_pool_size: 10,
-                },
-                &logger,
-                net_identifiers,
-            )))
+            Ok(Arc::new(create_store(BTreeMap::new(), vec![])))
         })).expect("could not create Diesel Store instance for test suite")
     };
 
@@ -64,6 +49,29 @@ lazy_static! {
     ).into();
 }
 
+/// Create a store for the test database with the given shards and
+/// placement rules. This must be called from within a tokio runtime
+pub fn create_store(shards: BTreeMap<String, String>, placement: Vec<PlacementRule>) -> Store {
+    let net_identifiers = EthereumNetworkIdentifier {
+        net_version: NETWORK_VERSION.to_owned(),
+        genesis_block_hash: GENESIS_PTR.hash,
+    };
+
+    Store::new(
+        StoreConfig {
+            postgres_url: postgres_test_url(),
+            network_name: NETWORK_NAME.to_owned(),
+            start_block: 0u64,
+            conn_pool_size: 10,
+            shards,
+            placement,
+        },
+        &*LOGGER,
+        net_identifiers,
+        Arc::new(MockMetricsRegistry::new()),
+    )
+}
+
 pub fn make_history_event(
     block_ptr: &EthereumBlockPointer,
     subgraph: &SubgraphDeploymentId,